@@ -1,12 +1,21 @@
 // Background service worker - Manages WebSocket connection to MCP server
 
+import { encode, decode } from './msgpack.js';
+
+// Must be one of the server's SUPPORTED_PROTOCOLS, or it closes the connection
+const PROTOCOL_VERSION = 'browser-mcp.v1';
+
 class MCPBridge {
   constructor() {
     this.ws = null;
+    this.wireFormat = 'json'; // Switched to 'msgpack' if the server accepts it
     this.wsUrl = 'ws://localhost:6009/ws'; // Default fallback
     this.reconnectInterval = 5000;
     this.activeTab = null;
     this.debuggerAttached = new Set();
+    this.cancelledRequests = new Set(); // Requests the server stopped waiting for
+    this.jsContexts = new Map(); // contextId -> { tabId, executionContextId }
+    this.interceptRules = new Map(); // tabId -> [{ urlPattern, regex, action, ... }]
     this.isReconnecting = false;
     this.popupPorts = new Set();
     this.reconnectTimer = null;
@@ -22,6 +31,7 @@ class MCPBridge {
     
     // Network request tracking
     this.networkRequests = new Map(); // tabId -> array of requests
+    this.pendingNetworkRequests = new Map(); // requestId -> in-flight request
     this.maxRequestsPerTab = 200; // Keep last 200 requests per tab
     
     // Debug version identifier to verify extension reload
@@ -35,15 +45,22 @@ class MCPBridge {
     this.setupMessageHandlers();
     this.setupDebugger();
     this.setupNetworkMonitoring();
+    this.setupTabListeners();
   }
 
   async loadConfiguration() {
     return new Promise((resolve) => {
-      chrome.storage.sync.get(['serverUrl'], (result) => {
+      chrome.storage.sync.get(['serverUrl', 'connectionLabel'], (result) => {
         if (result.serverUrl) {
           this.wsUrl = result.serverUrl;
         }
-        resolve();
+        // Stable name tools can pass as tabLabel instead of a tab ID
+        this.connectionLabel = result.connectionLabel || null;
+        // Kept across reloads so the server can restore this connection's tab on reconnect
+        chrome.storage.local.get(['resumeToken'], (local) => {
+          this.resumeToken = local.resumeToken || null;
+          resolve();
+        });
       });
     });
   }
@@ -66,10 +83,12 @@ class MCPBridge {
         this.broadcastStatus();
       }
       
-      this.ws = new WebSocket(this.wsUrl);
+      this.ws = new WebSocket(this.wsUrl, PROTOCOL_VERSION);
+      this.ws.binaryType = 'arraybuffer';
       
       this.ws.onopen = () => {
         console.log('Connected to MCP server');
+        this.wireFormat = 'json';
         this.isReconnecting = false;
         this.consecutiveFailures = 0; // Reset failure counter on successful connection
         this.lastPongReceived = Date.now();
@@ -79,6 +98,9 @@ class MCPBridge {
           event: {
             type: 'connection_established',
             status: 'connected',
+            wireFormats: ['msgpack', 'json'],
+            ...(this.connectionLabel && { label: this.connectionLabel }),
+            ...(this.resumeToken && { resumeToken: this.resumeToken }),
             timestamp: Date.now()
           }
         });
@@ -86,7 +108,23 @@ class MCPBridge {
 
       this.ws.onmessage = (event) => {
         try {
-          const data = JSON.parse(event.data);
+          const data = event.data instanceof ArrayBuffer
+            ? decode(event.data)
+            : JSON.parse(event.data);
+          
+          // Server's answer to the wireFormats we offered on connect
+          if (data.type === 'wire_format') {
+            this.wireFormat = data.format === 'msgpack' ? 'msgpack' : 'json';
+            console.log('[DEBUG] Using wire format:', this.wireFormat);
+            return;
+          }
+          
+          // Presented on the next connection_established to skip re-announcing tabs
+          if (data.type === 'resume_token') {
+            this.resumeToken = data.token;
+            chrome.storage.local.set({ resumeToken: data.token });
+            return;
+          }
           
           // Handle pong responses for health checking
           if (data.type === 'pong') {
@@ -120,16 +158,50 @@ class MCPBridge {
     }
   }
 
+  // Binary frame: u16 big-endian header length, JSON header, then the raw payload
+  sendBinaryToMCP(header, bytes) {
+    if (header.requestId && this.cancelledRequests.delete(header.requestId)) {
+      return true;
+    }
+    if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
+      return false;
+    }
+    
+    const headerBytes = new TextEncoder().encode(JSON.stringify(header));
+    const frame = new Uint8Array(2 + headerBytes.length + bytes.length);
+    new DataView(frame.buffer).setUint16(0, headerBytes.length);
+    frame.set(headerBytes, 2);
+    frame.set(bytes, 2 + headerBytes.length);
+    
+    try {
+      this.ws.send(frame);
+      return true;
+    } catch (error) {
+      console.error('[DEBUG] Error sending binary frame to MCP server:', error);
+      return false;
+    }
+  }
+
   sendToMCP(data) {
+    // Drop late results for requests the server has cancelled
+    if (data.requestId && this.cancelledRequests.delete(data.requestId)) {
+      console.log('[DEBUG] Dropping result for cancelled request:', data.requestId);
+      return false;
+    }
+    
     console.log('[DEBUG] sendToMCP called with data:', data);
     console.log('[DEBUG] WebSocket state:', this.ws ? this.ws.readyState : 'no websocket');
     console.log('[DEBUG] WebSocket OPEN constant:', WebSocket.OPEN);
     
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
       try {
-        const jsonString = JSON.stringify(data);
-        console.log('[DEBUG] Sending to MCP server:', jsonString.substring(0, 200) + '...');
-        this.ws.send(jsonString);
+        if (this.wireFormat === 'msgpack') {
+          this.ws.send(encode(data));
+        } else {
+          const jsonString = JSON.stringify(data);
+          console.log('[DEBUG] Sending to MCP server:', jsonString.substring(0, 200) + '...');
+          this.ws.send(jsonString);
+        }
         console.log('[DEBUG] WebSocket send completed successfully');
         return true;
       } catch (error) {
@@ -278,9 +350,30 @@ class MCPBridge {
     });
   }
 
+  setupTabListeners() {
+    // Let the server drop cached responses when a tab navigates or changes
+    chrome.tabs.onUpdated.addListener((tabId, changeInfo, tab) => {
+      if (changeInfo.status === 'complete') {
+        this.sendToMCP({
+          type: 'notification',
+          event: { type: 'page_loaded', tabId, url: tab.url }
+        });
+      } else if (changeInfo.url || changeInfo.title) {
+        this.sendToMCP({
+          type: 'notification',
+          event: { type: 'tab_updated', tabId, url: tab.url, title: tab.title }
+        });
+      }
+    });
+  }
+
   setupDebugger() {
     // Listen for debugger events
     chrome.debugger.onEvent.addListener((source, method, params) => {
+      if (method === 'Fetch.requestPaused') {
+        this.handlePausedRequest(source.tabId, params);
+        return;
+      }
       this.sendToMCP({
         type: 'notification',
         event: {
@@ -295,7 +388,19 @@ class MCPBridge {
 
     chrome.debugger.onDetach.addListener((source, reason) => {
       this.debuggerAttached.delete(source.tabId);
+      this.interceptRules.delete(source.tabId);
+      for (const [contextId, context] of this.jsContexts) {
+        if (context.tabId === source.tabId) {
+          this.jsContexts.delete(contextId);
+        }
+      }
       console.log(`Debugger detached from tab ${source.tabId}: ${reason}`);
+      // Closing the infobar or opening DevTools detaches without the server asking,
+      // so tell it the debugger is gone and paused scripts are running again
+      this.sendToMCP({
+        type: 'notification',
+        event: { type: 'debugger_detached', tabId: source.tabId, reason }
+      });
     });
   }
 
@@ -303,21 +408,43 @@ class MCPBridge {
     console.log('Received MCP message:', message);
     
     switch (message.action) {
+      case 'cancelRequest':
+        this.cancelledRequests.add(message.requestId);
+        break;
+      
       case 'getPageContent':
         await this.getPageContent(message.tabId, message.requestId);
         break;
       
       case 'getDOMSnapshot':
-        await this.getDOMSnapshot(message.tabId, message.requestId);
+        await this.getDOMSnapshot(message.tabId, message.requestId, message.pierce);
         break;
       
       case 'executeScript':
-        await this.executeScript(message.tabId, message.script, message.requestId);
+        if (message.contextId) {
+          await this.executeInJsContext(message.tabId, message.contextId, message.script, message.requestId);
+        } else if (message.awaitPromise) {
+          await this.evaluateAwaitingPromise(message.tabId, message.script, message.requestId);
+        } else {
+          await this.executeScript(message.tabId, message.script, message.requestId);
+        }
+        break;
+      
+      case 'createJsContext':
+        await this.createJsContext(message.tabId, message.requestId);
+        break;
+      
+      case 'disposeJsContext':
+        await this.disposeJsContext(message.contextId, message.requestId);
         break;
       
       case 'getNetworkData':
         await this.getNetworkData(message.tabId, message.requestId);
         break;
+
+      case 'getNetworkRequestBody':
+        await this.getNetworkRequestBody(message.tabId, message.networkRequestId, message.requestId);
+        break;
       
       case 'getConsoleMessages':
         await this.getConsoleMessages(message.tabId, message.requestId);
@@ -331,17 +458,121 @@ class MCPBridge {
         await this.detachDebugger(message.tabId, message.requestId);
         break;
       
+      case 'pauseExecution':
+        await this.setExecutionPaused(message.tabId, true, message.requestId);
+        break;
+      
+      case 'resumeExecution':
+        await this.setExecutionPaused(message.tabId, false, message.requestId);
+        break;
+      
       case 'captureScreenshot':
-        await this.captureScreenshot(message.tabId, message.requestId);
+        await this.captureScreenshot(message.tabId, message.requestId, message.clip, message.captureSelector);
         break;
       
       case 'getPerformanceMetrics':
         await this.getPerformanceMetrics(message.tabId, message.requestId);
         break;
       
+      case 'getResourceUsage':
+        await this.getResourceUsage(message.tabId, message.requestId);
+        break;
+      
+      case 'waitForNetworkIdle':
+        await this.waitForNetworkIdle(message.tabId, message.idleMs, message.timeoutMs, message.maxInflight, message.requestId);
+        break;
+      
+      case 'waitForConsoleError':
+        await this.waitForConsoleError(message.tabId, message.timeoutMs, message.pattern, message.requestId);
+        break;
+
+      case 'getComputedStyle':
+        await this.getComputedStyle(message.tabId, message.selector, message.properties, message.requestId);
+        break;
+
+      case 'getLinks':
+        await this.getLinks(message.tabId, message.sameOriginOnly, message.includeText, message.requestId);
+        break;
+
+      case 'dispatchKey':
+        await this.dispatchKey(message.tabId, message.selector, message.key, message.modifiers || [], message.requestId);
+        break;
+
+      case 'fillForm':
+        await this.fillForm(message.tabId, message.fields || {}, message.requestId);
+        break;
+
+      case 'evaluateXPath':
+        await this.evaluateXPath(message.tabId, message.expression, message.requestId);
+        break;
+
+      case 'getElementRect':
+        await this.getElementRect(message.tabId, message.selector, message.requestId);
+        break;
+
+      case 'observeMutations':
+        await this.observeMutations(message, message.requestId);
+        break;
+
+      case 'downloadResource':
+        await this.downloadResource(message.tabId, message.url, message.maxBytes, message.requestId);
+        break;
+
+      case 'replayRequest':
+        await this.replayRequest(message, message.requestId);
+        break;
+
+      case 'getNavigationHistory':
+        await this.getNavigationHistory(message.tabId, message.requestId);
+        break;
+
+      case 'navigateHistory':
+        await this.navigateHistory(message.tabId, message.delta, message.requestId);
+        break;
+
+      case 'setPageContent':
+        await this.setPageContent(message.tabId, message.html, message.requestId);
+        break;
+
+      case 'clearBrowsingData':
+        await this.clearBrowsingData(message.tabId, message, message.requestId);
+        break;
+
+      case 'captureMhtml':
+        await this.captureMhtml(message.tabId, message.requestId);
+        break;
+
+      case 'getLayoutMetrics':
+        await this.getLayoutMetrics(message.tabId, message.requestId);
+        break;
+
+      case 'getCapabilities':
+        await this.getCapabilities(message.tabId, message.requestId);
+        break;
+
+      case 'setExtraHeaders':
+        await this.setExtraHeaders(message.tabId, message.headers || {}, message.requestId);
+        break;
+
+      case 'addInitScript':
+        await this.addInitScript(message.tabId, message.source, message.requestId);
+        break;
+
+      case 'removeInitScript':
+        await this.removeInitScript(message.tabId, message.scriptId, message.requestId);
+        break;
+
+      case 'setInterceptRules':
+        await this.setInterceptRules(message.tabId, message.rules, message.requestId);
+        break;
+      
       case 'getAccessibilityTree':
         await this.getAccessibilityTree(message.tabId, message.timeout, message.requestId);
         break;
+
+      case 'getAccessibilityNode':
+        await this.getAccessibilityNode(message.tabId, message.selector, message.requestId);
+        break;
       
       case 'getCookies':
         await this.getCookies(message.url, message.requestId);
@@ -355,8 +586,20 @@ class MCPBridge {
         await this.emulateDevice(message.tabId, message.device, message.requestId);
         break;
       
+      case 'setEmulatedMedia':
+        await this.setEmulatedMedia(message.tabId, message.media, message.features, message.requestId);
+        break;
+      
+      case 'terminateExecution':
+        await this.terminateExecution(message.tabId, message.requestId);
+        break;
+      
+      case 'getUserAgent':
+        await this.getUserAgent(message.tabId, message.requestId);
+        break;
+      
       case 'setUserAgent':
-        await this.setUserAgent(message.tabId, message.userAgent, message.requestId);
+        await this.setUserAgent(message.tabId, message.userAgent, message.acceptLanguage, message.requestId);
         break;
       
       case 'getAllTabs':
@@ -509,7 +752,7 @@ class MCPBridge {
     }
   }
 
-  async getDOMSnapshot(tabId, requestId) {
+  async getDOMSnapshot(tabId, requestId, pierce = false) {
     try {
       // Get active tab if no tabId provided
       if (!tabId || tabId === null || tabId === undefined) {
@@ -525,7 +768,8 @@ class MCPBridge {
       }
       
       const response = await chrome.tabs.sendMessage(tabId, {
-        action: 'getDOMSnapshot'
+        action: 'getDOMSnapshot',
+        pierce
       });
       
       this.sendToMCP({
@@ -576,6 +820,111 @@ class MCPBridge {
     }
   }
 
+  // Evaluates through the debugger so a returned Promise can be awaited; values
+  // that aren't thenables come back as they are
+  async evaluateAwaitingPromise(tabId, script, requestId) {
+    try {
+      if (!tabId) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+      
+      const { result, exceptionDetails } = await chrome.debugger.sendCommand({ tabId }, 'Runtime.evaluate', {
+        expression: script,
+        returnByValue: true,
+        awaitPromise: true
+      });
+      if (exceptionDetails) {
+        throw new Error(exceptionDetails.exception?.description || exceptionDetails.text);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: result.value
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async createJsContext(tabId, requestId) {
+    try {
+      // Persistent contexts are isolated worlds, which need the debugger; the server attaches it first
+      const { frameTree } = await chrome.debugger.sendCommand({ tabId }, 'Page.getFrameTree');
+      const contextId = `ctx-${crypto.randomUUID()}`;
+      const { executionContextId } = await chrome.debugger.sendCommand({ tabId }, 'Page.createIsolatedWorld', {
+        frameId: frameTree.frame.id,
+        worldName: contextId
+      });
+      this.jsContexts.set(contextId, { tabId, executionContextId });
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { contextId }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async executeInJsContext(tabId, contextId, script, requestId) {
+    try {
+      const context = this.jsContexts.get(contextId);
+      if (!context || context.tabId !== tabId) {
+        throw new Error(`Unknown JavaScript context ${contextId} for tab ${tabId}`);
+      }
+      
+      const { result, exceptionDetails } = await chrome.debugger.sendCommand({ tabId }, 'Runtime.evaluate', {
+        expression: script,
+        contextId: context.executionContextId,
+        returnByValue: true,
+        awaitPromise: true
+      });
+      if (exceptionDetails) {
+        throw new Error(exceptionDetails.exception?.description || exceptionDetails.text);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: result.value
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async disposeJsContext(contextId, requestId) {
+    // Isolated worlds can't be destroyed explicitly; forgetting the id releases it for GC on navigation
+    this.jsContexts.delete(contextId);
+    this.sendToMCP({
+      type: 'response',
+      requestId,
+      data: { status: 'disposed', contextId }
+    });
+  }
+
   async attachDebugger(tabId, requestId) {
     try {
       if (!this.debuggerAttached.has(tabId)) {
@@ -589,13 +938,14 @@ class MCPBridge {
         await chrome.debugger.sendCommand({ tabId }, 'DOM.enable');
         await chrome.debugger.sendCommand({ tabId }, 'CSS.enable');
         await chrome.debugger.sendCommand({ tabId }, 'Overlay.enable');
-        
-        this.sendToMCP({
-          type: 'response',
-          requestId,
-          data: { status: 'attached' }
-        });
       }
+      
+      // Answer even when already attached; the server attaches on demand and waits for this
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'attached' }
+      });
     } catch (error) {
       this.sendToMCP({
         type: 'error',
@@ -626,6 +976,31 @@ class MCPBridge {
     }
   }
 
+  async setExecutionPaused(tabId, paused, requestId) {
+    try {
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+
+      // Debugger.pause needs the domain enabled; enabling twice is harmless
+      await chrome.debugger.sendCommand({ tabId }, 'Debugger.enable');
+      await chrome.debugger.sendCommand({ tabId }, paused ? 'Debugger.pause' : 'Debugger.resume');
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { state: paused ? 'paused' : 'running' }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
   async getNetworkData(tabId, requestId) {
     try {
       // Get active tab if no tabId provided
@@ -668,6 +1043,53 @@ class MCPBridge {
     }
   }
   
+  async getNetworkRequestBody(tabId, networkRequestId, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+
+      const requests = this.networkRequests.get(tabId) || [];
+      const request = requests.find(req => req.requestId === networkRequestId);
+      if (!request) {
+        throw new Error(`Network request ${networkRequestId} not found`);
+      }
+
+      // webRequest never sees response bodies, so safe requests are fetched again
+      let responseBody = null;
+      let replayed = false;
+      if (request.method === 'GET' || request.method === 'HEAD') {
+        const response = await fetch(request.url, { method: request.method, credentials: 'include' });
+        responseBody = request.method === 'HEAD' ? '' : await response.text();
+        replayed = true;
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: {
+          requestId: networkRequestId,
+          url: request.url,
+          method: request.method,
+          requestBody: request.requestBody ? request.requestBody.data : null,
+          responseBody,
+          replayed
+        }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+  
   summarizeRequestTypes(requests) {
     const types = {};
     requests.forEach(req => {
@@ -738,18 +1160,72 @@ class MCPBridge {
     }
   }
 
-  async captureScreenshot(tabId, requestId) {
+  async captureScreenshot(tabId, requestId, clip, captureSelector) {
     try {
-      const dataUrl = await chrome.tabs.captureVisibleTab(null, {
-        format: 'png',
-        quality: 100
-      });
+      if ((clip || captureSelector) && !tabId) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+
+      // An element's box is resolved in the page, then captured like an explicit clip
+      if (captureSelector) {
+        const rect = await chrome.tabs.sendMessage(tabId, {
+          action: 'getElementRect',
+          selector: captureSelector
+        });
+        if (rect?.error) {
+          throw new Error(rect.error);
+        }
+        const box = rect.boundingBox;
+        if (!box || box.width <= 0 || box.height <= 0) {
+          throw new Error(`Element ${captureSelector} has no visible area`);
+        }
+        // getBoundingClientRect is viewport-relative but the debugger clips in page
+        // coordinates, so shift by how far the page is scrolled
+        const { cssVisualViewport, visualViewport } = await chrome.debugger.sendCommand({ tabId }, 'Page.getLayoutMetrics');
+        const viewport = cssVisualViewport || visualViewport;
+        clip = { x: box.x + viewport.pageX, y: box.y + viewport.pageY, width: box.width, height: box.height };
+      }
+
+      let dataUrl;
+      if (clip) {
+        // captureVisibleTab can't crop, so clipped captures go through the debugger
+        const { data } = await chrome.debugger.sendCommand({ tabId }, 'Page.captureScreenshot', {
+          format: 'png',
+          clip: { x: clip.x, y: clip.y, width: clip.width, height: clip.height, scale: 1 }
+        });
+        dataUrl = `data:image/png;base64,${data}`;
+      } else {
+        dataUrl = await chrome.tabs.captureVisibleTab(null, {
+          format: 'png',
+          quality: 100
+        });
+      }
       
-      this.sendToMCP({
-        type: 'response',
+      // Send raw image bytes in a binary frame, falling back to the data URL
+      const blob = await (await fetch(dataUrl)).blob();
+      const bitmap = await createImageBitmap(blob);
+      const header = {
         requestId,
-        data: dataUrl
-      });
+        type: 'screenshot',
+        format: 'png',
+        width: bitmap.width,
+        height: bitmap.height
+      };
+      if (clip) {
+        header.clip = { x: clip.x, y: clip.y, width: clip.width, height: clip.height };
+      }
+      bitmap.close();
+      if (!this.sendBinaryToMCP(header, new Uint8Array(await blob.arrayBuffer()))) {
+        this.sendToMCP({
+          type: 'response',
+          requestId,
+          data: clip ? { dataUrl, clip: header.clip } : dataUrl
+        });
+      }
     } catch (error) {
       this.sendToMCP({
         type: 'error',
@@ -792,12 +1268,899 @@ class MCPBridge {
     }
   }
 
-  async getAccessibilityTree(tabId, timeout, requestId) {
+  async getResourceUsage(tabId, requestId) {
     try {
       // Get active tab if no tabId provided
       if (!tabId || tabId === null || tabId === undefined) {
-        try {
-          const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const usage = await chrome.tabs.sendMessage(tabId, {
+        action: 'getResourceUsage'
+      });
+      
+      // CPU time is only available through the debugger
+      if (this.debuggerAttached.has(tabId)) {
+        try {
+          await chrome.debugger.sendCommand({ tabId }, 'Performance.enable');
+          const { metrics } = await chrome.debugger.sendCommand({ tabId }, 'Performance.getMetrics');
+          const taskDuration = metrics.find(m => m.name === 'TaskDuration');
+          if (taskDuration) {
+            usage.cpuTimeMs = taskDuration.value * 1000;
+          }
+        } catch (error) {
+          console.warn('Failed to read CPU metrics:', error.message);
+        }
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: usage
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  countInflightRequests(tabId) {
+    let count = 0;
+    for (const request of this.pendingNetworkRequests.values()) {
+      if (request.tabId === tabId) count++;
+    }
+    return count;
+  }
+
+  async waitForNetworkIdle(tabId, idleMs = 500, timeoutMs = 10000, maxInflight = 1, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      // Idle once fewer than maxInflight requests have been active for idleMs
+      const startedAt = Date.now();
+      let idleSince = null;
+      const result = await new Promise((resolve, reject) => {
+        const poll = setInterval(() => {
+          const now = Date.now();
+          const inflight = this.countInflightRequests(tabId);
+          if (inflight < maxInflight) {
+            idleSince = idleSince ?? now;
+            if (now - idleSince >= idleMs) {
+              clearInterval(poll);
+              resolve({ idle: true, waitedMs: now - startedAt, inflight });
+              return;
+            }
+          } else {
+            idleSince = null;
+          }
+          if (now - startedAt >= timeoutMs) {
+            clearInterval(poll);
+            reject(new Error(`Network not idle after ${timeoutMs}ms (${inflight} requests in flight)`));
+          }
+        }, 50);
+      });
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: result
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async waitForConsoleError(tabId, timeoutMs = 10000, pattern, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+      await chrome.debugger.sendCommand({ tabId }, 'Runtime.enable');
+      
+      const matcher = pattern ? new RegExp(pattern) : null;
+      const startedAt = Date.now();
+      const result = await new Promise((resolve, reject) => {
+        const toError = (method, params) => {
+          if (method === 'Runtime.consoleAPICalled' && params.type === 'error') {
+            const text = params.args
+              .map(arg => arg.value ?? arg.description ?? '')
+              .join(' ');
+            const frame = params.stackTrace?.callFrames?.[0];
+            return { level: 'error', text, source: 'console', url: frame?.url, line: frame?.lineNumber };
+          }
+          if (method === 'Runtime.exceptionThrown') {
+            const details = params.exceptionDetails;
+            const text = details.exception?.description || details.text;
+            return { level: 'error', text, source: 'exception', url: details.url, line: details.lineNumber };
+          }
+          return null;
+        };
+        const listener = (source, method, params) => {
+          if (source.tabId !== tabId) return;
+          const error = toError(method, params);
+          if (!error || (matcher && !matcher.test(error.text))) return;
+          cleanup();
+          resolve({ message: { ...error, timestamp: Date.now() }, waitedMs: Date.now() - startedAt });
+        };
+        const timer = setTimeout(() => {
+          cleanup();
+          reject(new Error(`No console error${pattern ? ` matching /${pattern}/` : ''} within ${timeoutMs}ms`));
+        }, timeoutMs);
+        const cleanup = () => {
+          clearTimeout(timer);
+          chrome.debugger.onEvent.removeListener(listener);
+        };
+        chrome.debugger.onEvent.addListener(listener);
+      });
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: result
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getComputedStyle(tabId, selector, properties, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'getComputedStyle',
+        selector,
+        properties
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response.styles
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getLinks(tabId, sameOriginOnly, includeText, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'getLinks',
+        sameOriginOnly,
+        includeText
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { links: response.links }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async dispatchKey(tabId, selector, key, modifiers, requestId) {
+    // Keys whose events need a code and key code for pages to react to them
+    const NAMED_KEYS = {
+      Enter: { code: 'Enter', keyCode: 13, text: '\r' },
+      Tab: { code: 'Tab', keyCode: 9 },
+      Escape: { code: 'Escape', keyCode: 27 },
+      Backspace: { code: 'Backspace', keyCode: 8 },
+      Delete: { code: 'Delete', keyCode: 46 },
+      ArrowLeft: { code: 'ArrowLeft', keyCode: 37 },
+      ArrowUp: { code: 'ArrowUp', keyCode: 38 },
+      ArrowRight: { code: 'ArrowRight', keyCode: 39 },
+      ArrowDown: { code: 'ArrowDown', keyCode: 40 },
+      Home: { code: 'Home', keyCode: 36 },
+      End: { code: 'End', keyCode: 35 },
+      PageUp: { code: 'PageUp', keyCode: 33 },
+      PageDown: { code: 'PageDown', keyCode: 34 },
+      ' ': { code: 'Space', keyCode: 32, text: ' ' }
+    };
+    const MODIFIER_BITS = { Alt: 1, Control: 2, Meta: 4, Shift: 8 };
+
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+
+      // The server attaches the debugger first; the active tab fallback must already have it
+      if (!this.debuggerAttached.has(tabId)) {
+        throw new Error(`The debugger is not attached to tab ${tabId}; call attach_debugger first`);
+      }
+
+      if (selector) {
+        const { result } = await chrome.debugger.sendCommand({ tabId }, 'Runtime.evaluate', {
+          expression: `(() => {
+            const el = document.querySelector(${JSON.stringify(selector)});
+            if (!el) return false;
+            el.focus();
+            return true;
+          })()`,
+          returnByValue: true
+        });
+        if (!result.value) {
+          throw new Error(`No element matches selector ${selector}`);
+        }
+      }
+
+      const named = NAMED_KEYS[key];
+      const modifierMask = modifiers.reduce((mask, name) => mask | (MODIFIER_BITS[name] || 0), 0);
+      const single = key.length === 1 ? key : null;
+      const event = {
+        key,
+        code: named?.code || (single && /[a-z]/i.test(single) ? `Key${single.toUpperCase()}` : undefined),
+        windowsVirtualKeyCode: named?.keyCode || (single ? single.toUpperCase().charCodeAt(0) : 0),
+        modifiers: modifierMask
+      };
+      // Shortcuts with Control/Meta shouldn't type their character
+      const text = named?.text ?? (single && !(modifierMask & (MODIFIER_BITS.Control | MODIFIER_BITS.Meta)) ? single : undefined);
+
+      await chrome.debugger.sendCommand({ tabId }, 'Input.dispatchKeyEvent', {
+        ...event,
+        type: text ? 'keyDown' : 'rawKeyDown',
+        text
+      });
+      await chrome.debugger.sendCommand({ tabId }, 'Input.dispatchKeyEvent', { ...event, type: 'keyUp' });
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success' }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async observeMutations(message, requestId) {
+    try {
+      let tabId = message.tabId;
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'observeMutations',
+        selector: message.selector,
+        durationMs: message.durationMs,
+        attributes: message.attributes,
+        childList: message.childList,
+        subtree: message.subtree
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { records: response.records, truncated: response.truncated }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async downloadResource(tabId, url, maxBytes, requestId) {
+    try {
+      // The content script fetches with the page's origin, cookies and auth
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'downloadResource',
+        url,
+        maxBytes
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async replayRequest({ tabId, method, url, headers, body, maxBytes }, requestId) {
+    try {
+      // Sent from the content script so the page's cookies and origin apply
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'replayRequest',
+        method,
+        url,
+        headers,
+        body,
+        maxBytes
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getNavigationHistory(tabId, requestId) {
+    try {
+      // The server attaches the debugger first, so Page is used on its session
+      const { currentIndex, entries } = await chrome.debugger.sendCommand({ tabId }, 'Page.getNavigationHistory');
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: {
+          current_index: currentIndex,
+          entries: entries.map(entry => ({ url: entry.url, title: entry.title }))
+        }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async navigateHistory(tabId, delta, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+
+      // The server attaches the debugger first; the active tab fallback must already have it
+      if (!this.debuggerAttached.has(tabId)) {
+        throw new Error(`The debugger is not attached to tab ${tabId}; call attach_debugger first`);
+      }
+
+      const { currentIndex, entries } = await chrome.debugger.sendCommand({ tabId }, 'Page.getNavigationHistory');
+      const target = currentIndex + delta;
+      if (target < 0 || target >= entries.length) {
+        throw new Error(`delta ${delta} is out of range: at entry ${currentIndex} of ${entries.length}`);
+      }
+
+      const entry = entries[target];
+      await chrome.debugger.sendCommand({ tabId }, 'Page.navigateToHistoryEntry', { entryId: entry.id });
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { url: entry.url, title: entry.title, current_index: target }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async setPageContent(tabId, html, requestId) {
+    try {
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'setPageContent',
+        html
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async clearBrowsingData(tabId, { cache, cookies, localStorage }, requestId) {
+    try {
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+
+      const tab = await chrome.tabs.get(tabId);
+      const origin = tab.url ? new URL(tab.url).origin : null;
+      if (cache) {
+        await chrome.debugger.sendCommand({ tabId }, 'Network.clearBrowserCache');
+      }
+      if (cookies) {
+        await chrome.debugger.sendCommand({ tabId }, 'Network.clearBrowserCookies');
+      }
+      if (localStorage) {
+        if (!origin || origin === 'null') {
+          throw new Error('The tab has no origin whose local storage could be cleared');
+        }
+        await chrome.debugger.sendCommand({ tabId }, 'Storage.clearDataForOrigin', {
+          origin,
+          storageTypes: 'local_storage'
+        });
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success', origin }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async captureMhtml(tabId, requestId) {
+    try {
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+
+      const { data } = await chrome.debugger.sendCommand({ tabId }, 'Page.captureSnapshot', {
+        format: 'mhtml'
+      });
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { data }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getLayoutMetrics(tabId, requestId) {
+    try {
+      if (!this.debuggerAttached.has(tabId)) {
+        await chrome.debugger.attach({ tabId }, '1.3');
+        this.debuggerAttached.add(tabId);
+      }
+
+      const metrics = await chrome.debugger.sendCommand({ tabId }, 'Page.getLayoutMetrics');
+      // The css* fields are in CSS pixels; the legacy ones are in device pixels
+      const layout = metrics.cssLayoutViewport || metrics.layoutViewport;
+      const visual = metrics.cssVisualViewport || metrics.visualViewport;
+      const content = metrics.cssContentSize || metrics.contentSize;
+      const devicePixelRatio = metrics.cssLayoutViewport && metrics.layoutViewport
+        ? metrics.layoutViewport.clientWidth / metrics.cssLayoutViewport.clientWidth
+        : 1;
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: {
+          scrollX: visual.pageX,
+          scrollY: visual.pageY,
+          scrollWidth: content.width,
+          scrollHeight: content.height,
+          viewportWidth: visual.clientWidth,
+          viewportHeight: visual.clientHeight,
+          devicePixelRatio: devicePixelRatio || 1
+        }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getCapabilities(tabId, requestId) {
+    try {
+      const tab = await chrome.tabs.get(tabId);
+      const restricted = /^(chrome|chrome-extension|edge|about|moz-extension):/.test(tab.url || '');
+      const unavailable = (reason) => ({ available: false, reason });
+
+      let debuggerCapability = { available: true };
+      if (!chrome.debugger) {
+        debuggerCapability = unavailable('The extension lacks the debugger permission');
+      } else if (restricted) {
+        debuggerCapability = unavailable(`The debugger cannot attach to ${tab.url}`);
+      } else if (!this.debuggerAttached.has(tabId)) {
+        const targets = await chrome.debugger.getTargets();
+        const target = targets.find(t => t.tabId === tabId);
+        if (target && target.attached) {
+          debuggerCapability = unavailable('Another debugger (such as DevTools) is attached to this tab');
+        }
+      }
+
+      let screenshots = { available: true };
+      if (restricted) {
+        screenshots = unavailable(`Screenshots are not allowed on ${tab.url}`);
+      } else if (!tab.active) {
+        screenshots = unavailable('Only the visible tab in a window can be captured');
+      }
+
+      let cookies = { available: true };
+      if (!chrome.cookies) {
+        cookies = unavailable('The extension lacks the cookies permission');
+      } else if (restricted || !(await chrome.permissions.contains({ origins: [new URL(tab.url).origin + '/*'] }))) {
+        cookies = unavailable(`The extension has no host permission for ${tab.url}`);
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { debugger: debuggerCapability, screenshots, cookies }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async setExtraHeaders(tabId, headers, requestId) {
+    try {
+      // The server attaches the debugger first. An empty object removes previously set
+      // headers, which a detached debugger has already dropped
+      if (this.debuggerAttached.has(tabId) || Object.keys(headers).length > 0) {
+        await chrome.debugger.sendCommand({ tabId }, 'Network.enable');
+        await chrome.debugger.sendCommand({ tabId }, 'Network.setExtraHTTPHeaders', { headers });
+      }
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success', count: Object.keys(headers).length }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async addInitScript(tabId, source, requestId) {
+    try {
+      // The server attaches the debugger first, so the script is tracked with its session
+      await chrome.debugger.sendCommand({ tabId }, 'Page.enable');
+      const { identifier } = await chrome.debugger.sendCommand({ tabId }, 'Page.addScriptToEvaluateOnNewDocument', {
+        source
+      });
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { identifier }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async removeInitScript(tabId, identifier, requestId) {
+    try {
+      await chrome.debugger.sendCommand({ tabId }, 'Page.removeScriptToEvaluateOnNewDocument', {
+        identifier
+      });
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success' }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async fillForm(tabId, fields, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'fillForm',
+        fields
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { fields: response.fields }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getElementRect(tabId, selector, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'getElementRect',
+        selector
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async evaluateXPath(tabId, expression, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'evaluateXPath',
+        expression
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { result: response.result }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async setInterceptRules(tabId, rules, requestId) {
+    try {
+      if (!tabId) {
+        throw new Error('tabId is required for intercept rules');
+      }
+
+      if (!rules || rules.length === 0) {
+        if (this.interceptRules.delete(tabId) && this.debuggerAttached.has(tabId)) {
+          await chrome.debugger.sendCommand({ tabId }, 'Fetch.disable');
+        }
+      } else {
+        // The server attaches the debugger first, so Fetch is enabled on its session
+        this.interceptRules.set(tabId, rules.map(rule => ({
+          ...rule,
+          regex: this.fetchPatternToRegex(rule.urlPattern)
+        })));
+        await chrome.debugger.sendCommand({ tabId }, 'Fetch.enable', {
+          patterns: rules.map(rule => ({ urlPattern: rule.urlPattern, requestStage: 'Request' }))
+        });
+      }
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success', count: rules ? rules.length : 0 }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  // Fetch patterns: '*' is any run of characters, '?' one character, a backslash escapes
+  fetchPatternToRegex(pattern) {
+    let source = '';
+    for (let i = 0; i < pattern.length; i++) {
+      const char = pattern[i];
+      if (char === '\\' && i + 1 < pattern.length) {
+        source += pattern[++i].replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+      } else if (char === '*') {
+        source += '.*';
+      } else if (char === '?') {
+        source += '.';
+      } else {
+        source += char.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+      }
+    }
+    return new RegExp(`^${source}$`);
+  }
+
+  async handlePausedRequest(tabId, params) {
+    const target = { tabId };
+    const { requestId } = params;
+    const rule = (this.interceptRules.get(tabId) || []).find(r => r.regex.test(params.request.url));
+
+    try {
+      if (!rule) {
+        await chrome.debugger.sendCommand(target, 'Fetch.continueRequest', { requestId });
+      } else if (rule.action === 'block') {
+        await chrome.debugger.sendCommand(target, 'Fetch.failRequest', { requestId, errorReason: 'BlockedByClient' });
+      } else if (rule.action === 'delay') {
+        await new Promise(resolve => setTimeout(resolve, rule.delayMs));
+        await chrome.debugger.sendCommand(target, 'Fetch.continueRequest', { requestId });
+      } else if (rule.action === 'respond_with') {
+        const bytes = new TextEncoder().encode(rule.body || '');
+        let binary = '';
+        for (const byte of bytes) binary += String.fromCharCode(byte);
+
+        await chrome.debugger.sendCommand(target, 'Fetch.fulfillRequest', {
+          requestId,
+          responseCode: rule.status || 200,
+          responseHeaders: Object.entries(rule.headers || {}).map(([name, value]) => ({ name, value: String(value) })),
+          body: btoa(binary)
+        });
+      }
+    } catch (error) {
+      // The request may already be gone if the tab navigated away
+      console.warn(`Failed to apply intercept rule to ${params.request.url}:`, error.message);
+    }
+  }
+
+  async getAccessibilityTree(tabId, timeout, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        try {
+          const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
           if (!activeTab || !activeTab.id) {
             throw new Error('No active tab found');
           }
@@ -826,6 +2189,39 @@ class MCPBridge {
     }
   }
 
+  async getAccessibilityNode(tabId, selector, requestId) {
+    try {
+      // Get active tab if no tabId provided
+      if (!tabId || tabId === null || tabId === undefined) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      
+      const response = await chrome.tabs.sendMessage(tabId, {
+        action: 'getAccessibilityNode',
+        selector
+      });
+      if (response?.error) {
+        throw new Error(response.error);
+      }
+      
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: response
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
   async getCookies(url, requestId) {
     try {
       const cookies = await chrome.cookies.getAll({ url });
@@ -918,17 +2314,87 @@ class MCPBridge {
     }
   }
 
-  async setUserAgent(tabId, userAgent, requestId) {
+  async setEmulatedMedia(tabId, media, features, requestId) {
+    try {
+      // The server attaches the debugger first, so the emulation is tracked with its session.
+      // Each call replaces the previous emulation; empty media and no features reset it
+      await chrome.debugger.sendCommand({ tabId }, 'Emulation.setEmulatedMedia', {
+        media: media || '',
+        features: features || []
+      });
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success' }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  // Interrupts the script the tab is running; its own request then fails
+  async terminateExecution(tabId, requestId) {
     try {
+      if (!tabId) {
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        if (!activeTab || !activeTab.id) {
+          throw new Error('No active tab found');
+        }
+        tabId = activeTab.id;
+      }
+      // The server attaches the debugger first; the active tab fallback must already have it
       if (!this.debuggerAttached.has(tabId)) {
-        await this.attachDebugger(tabId, requestId);
+        throw new Error(`The debugger is not attached to tab ${tabId}; call attach_debugger first`);
       }
 
-      await chrome.debugger.sendCommand(
-        { tabId },
-        'Emulation.setUserAgentOverride',
-        { userAgent }
-      );
+      await chrome.debugger.sendCommand({ tabId }, 'Runtime.terminateExecution');
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: { status: 'success' }
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async getUserAgent(tabId, requestId) {
+    try {
+      const [{ result }] = await chrome.scripting.executeScript({
+        target: { tabId },
+        func: () => ({ userAgent: navigator.userAgent, language: navigator.language })
+      });
+
+      this.sendToMCP({
+        type: 'response',
+        requestId,
+        data: result
+      });
+    } catch (error) {
+      this.sendToMCP({
+        type: 'error',
+        requestId,
+        error: error.message
+      });
+    }
+  }
+
+  async setUserAgent(tabId, userAgent, acceptLanguage, requestId) {
+    try {
+      // The server attaches the debugger first, so the override is tracked with its session.
+      // Covers both request headers and navigator.userAgent
+      const override = { userAgent };
+      if (acceptLanguage) override.acceptLanguage = acceptLanguage;
+      await chrome.debugger.sendCommand({ tabId }, 'Network.setUserAgentOverride', override);
 
       this.sendToMCP({
         type: 'response',
@@ -947,6 +2413,7 @@ class MCPBridge {
   async getAllTabs(requestId) {
     try {
       const tabs = await chrome.tabs.query({});
+      const focusedWindow = await chrome.windows.getLastFocused().catch(() => null);
       
       this.sendToMCP({
         type: 'response',
@@ -957,6 +2424,7 @@ class MCPBridge {
           title: tab.title,
           active: tab.active,
           windowId: tab.windowId,
+          windowFocused: tab.windowId === focusedWindow?.id,
           index: tab.index
         }))
       });
@@ -1078,7 +2546,7 @@ class MCPBridge {
     console.log('[NETWORK] Setting up network request monitoring');
     
     // Track request start times and details
-    const pendingRequests = new Map();
+    const pendingRequests = this.pendingNetworkRequests;
     
     // Listen for request start
     chrome.webRequest.onBeforeRequest.addListener(
@@ -1168,9 +2636,13 @@ class MCPBridge {
       { urls: ['<all_urls>'] }
     );
     
-    // Clean up network data when tabs are closed
+    // Clean up network data when tabs are closed, and let the server drop its state
     chrome.tabs.onRemoved.addListener((tabId) => {
       this.networkRequests.delete(tabId);
+      this.sendToMCP({
+        type: 'notification',
+        event: { type: 'tab_removed', tabId }
+      });
     });
   }
   
@@ -2,8 +2,32 @@
 
 class PageDataExtractor {
   constructor() {
+    this.webVitals = { largestContentfulPaint: null, firstInputDelay: null, cumulativeLayoutShift: null };
     this.setupMessageListener();
     this.injectPageScript();
+    this.observeWebVitals();
+  }
+
+  // LCP, FID and CLS are only reported to observers; buffered picks up entries from before we ran
+  observeWebVitals() {
+    const observe = (type, onEntry) => {
+      try {
+        new PerformanceObserver(list => list.getEntries().forEach(onEntry)).observe({ type, buffered: true });
+      } catch (error) {
+        // Entry type not supported by this browser
+      }
+    };
+    observe('largest-contentful-paint', entry => {
+      this.webVitals.largestContentfulPaint = entry.startTime;
+    });
+    observe('first-input', entry => {
+      this.webVitals.firstInputDelay = entry.processingStart - entry.startTime;
+    });
+    observe('layout-shift', entry => {
+      if (!entry.hadRecentInput) {
+        this.webVitals.cumulativeLayoutShift = (this.webVitals.cumulativeLayoutShift || 0) + entry.value;
+      }
+    });
   }
 
   setupMessageListener() {
@@ -13,20 +37,53 @@ class PageDataExtractor {
           sendResponse(this.getPageContent());
           break;
         case 'getDOMSnapshot':
-          sendResponse(this.getDOMSnapshot());
+          sendResponse(this.getDOMSnapshot(request.pierce === true));
           break;
         case 'getComputedStyles':
           sendResponse(this.getComputedStyles(request.selector));
           break;
+        case 'getComputedStyle':
+          sendResponse(this.getComputedStyle(request.selector, request.properties));
+          break;
+        case 'getLinks':
+          sendResponse(this.getLinks(request.sameOriginOnly, request.includeText !== false));
+          break;
+        case 'fillForm':
+          sendResponse(this.fillForm(request.fields || {}));
+          break;
+        case 'evaluateXPath':
+          sendResponse(this.evaluateXPath(request.expression));
+          break;
+        case 'getElementRect':
+          sendResponse(this.getElementRect(request.selector));
+          break;
+        case 'observeMutations':
+          this.observeMutations(request).then(sendResponse);
+          return true; // Responds once the observation window closes
+        case 'downloadResource':
+          this.downloadResource(request.url, request.maxBytes).then(sendResponse);
+          return true; // Responds once the body has been read
+        case 'replayRequest':
+          this.replayRequest(request).then(sendResponse);
+          return true; // Responds once the replayed response has been read
+        case 'setPageContent':
+          sendResponse(this.setPageContent(request.html));
+          break;
         case 'executeScript':
           this.executeInPageContext(request.script, sendResponse);
           return true; // Will respond asynchronously
         case 'getPerformanceMetrics':
           sendResponse(this.getPerformanceMetrics());
           break;
+        case 'getResourceUsage':
+          sendResponse(this.getResourceUsage());
+          break;
         case 'getAccessibilityTree':
           sendResponse(this.getAccessibilityTree(request.timeout));
           break;
+        case 'getAccessibilityNode':
+          sendResponse(this.getAccessibilityNode(request.selector));
+          break;
         case 'captureConsoleLog':
           this.captureConsoleLog(sendResponse);
           return true;
@@ -60,10 +117,10 @@ class PageDataExtractor {
     };
   }
 
-  getDOMSnapshot() {
+  getDOMSnapshot(pierce = false) {
     const snapshot = {
       nodeCount: document.querySelectorAll('*').length,
-      structure: this.serializeDOM(document.documentElement),
+      structure: this.serializeDOM(document.documentElement, 0, 10, pierce),
       forms: this.extractForms(),
       links: this.extractLinks(),
       images: this.extractImages(),
@@ -73,7 +130,7 @@ class PageDataExtractor {
     return snapshot;
   }
 
-  serializeDOM(element, depth = 0, maxDepth = 10) {
+  serializeDOM(element, depth = 0, maxDepth = 10, pierce = false) {
     if (depth > maxDepth) return null;
     
     const node = {
@@ -88,8 +145,20 @@ class PageDataExtractor {
       node.attributes[attr.name] = attr.value;
     }
 
+    // Closed shadow roots are not reachable from script, so only open ones show up
+    if (pierce && element.shadowRoot && depth < maxDepth) {
+      const shadowNode = { nodeType: 'shadow-root', shadowRoot: true, attributes: {}, children: [] };
+      for (const child of element.shadowRoot.children) {
+        const serializedChild = this.serializeDOM(child, depth + 2, maxDepth, pierce);
+        if (serializedChild) {
+          shadowNode.children.push(serializedChild);
+        }
+      }
+      node.children.push(shadowNode);
+    }
+
     for (const child of element.children) {
-      const serializedChild = this.serializeDOM(child, depth + 1, maxDepth);
+      const serializedChild = this.serializeDOM(child, depth + 1, maxDepth, pierce);
       if (serializedChild) {
         node.children.push(serializedChild);
       }
@@ -185,6 +254,289 @@ class PageDataExtractor {
     }
   }
 
+  getComputedStyle(selector, properties) {
+    try {
+      const element = document.querySelector(selector);
+      if (!element) {
+        return { error: `No element matches selector: ${selector}` };
+      }
+      const styles = window.getComputedStyle(element);
+      const names = properties && properties.length ? properties : Array.from(styles);
+      const result = {};
+      for (const prop of names) {
+        result[prop] = styles.getPropertyValue(prop);
+      }
+      return { styles: result };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
+  fillForm(fields) {
+    const results = {};
+    for (const [selector, value] of Object.entries(fields)) {
+      try {
+        const element = document.querySelector(selector);
+        if (!element) {
+          results[selector] = { status: 'not_found' };
+          continue;
+        }
+        if (element.disabled || element.readOnly) {
+          results[selector] = { status: 'error', error: 'Element is disabled or read-only' };
+          continue;
+        }
+        this.setFieldValue(element, value);
+        results[selector] = { status: 'filled' };
+      } catch (e) {
+        results[selector] = { status: 'error', error: e.message };
+      }
+    }
+    return { fields: results };
+  }
+
+  setFieldValue(element, value) {
+    element.focus();
+    if (element.type === 'checkbox' || element.type === 'radio') {
+      element.checked = value === true || value === 'true' || value === 'on';
+    } else if (element.isContentEditable) {
+      element.textContent = String(value);
+    } else {
+      // Go through the prototype's setter so frameworks tracking the value see the change
+      const setter = Object.getOwnPropertyDescriptor(Object.getPrototypeOf(element), 'value')?.set;
+      if (setter) {
+        setter.call(element, String(value));
+      } else {
+        element.value = String(value);
+      }
+    }
+    element.dispatchEvent(new Event('input', { bubbles: true }));
+    element.dispatchEvent(new Event('change', { bubbles: true }));
+  }
+
+  getLinks(sameOriginOnly, includeText) {
+    try {
+      const links = [];
+      for (const anchor of document.querySelectorAll('a[href]')) {
+        // anchor.href is already resolved against the document base URL
+        const href = anchor.href;
+        if (!href || !/^https?:/.test(anchor.protocol)) continue;
+        if (sameOriginOnly && anchor.origin !== location.origin) continue;
+
+        const link = { href };
+        if (includeText) {
+          const text = (anchor.innerText || anchor.textContent || '').trim();
+          if (text) link.text = text.substring(0, 200);
+        }
+        if (anchor.rel) link.rel = anchor.rel;
+        links.push(link);
+      }
+      return { links };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
+  getElementRect(selector) {
+    try {
+      const element = document.querySelector(selector);
+      if (!element) {
+        return { error: `No element matches ${selector}` };
+      }
+
+      const rect = element.getBoundingClientRect();
+      const style = window.getComputedStyle(element);
+      const isVisible = rect.width > 0 && rect.height > 0 &&
+        style.visibility !== 'hidden' && style.display !== 'none' && parseFloat(style.opacity) > 0;
+      const isInViewport = rect.bottom > 0 && rect.right > 0 &&
+        rect.top < window.innerHeight && rect.left < window.innerWidth;
+
+      return {
+        boundingBox: { x: rect.x, y: rect.y, width: rect.width, height: rect.height },
+        isVisible,
+        isInViewport
+      };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
+  evaluateXPath(expression) {
+    const MAX_NODES = 500;
+
+    try {
+      const result = document.evaluate(expression, document, null, XPathResult.ANY_TYPE, null);
+      switch (result.resultType) {
+        case XPathResult.NUMBER_TYPE:
+          return { result: { type: 'number', value: result.numberValue } };
+        case XPathResult.STRING_TYPE:
+          return { result: { type: 'string', value: result.stringValue } };
+        case XPathResult.BOOLEAN_TYPE:
+          return { result: { type: 'boolean', value: result.booleanValue } };
+      }
+
+      // Re-evaluate node sets as a snapshot so matches come back in document order
+      const snapshot = document.evaluate(expression, document, null, XPathResult.ORDERED_NODE_SNAPSHOT_TYPE, null);
+      const nodes = [];
+      for (let i = 0; i < Math.min(snapshot.snapshotLength, MAX_NODES); i++) {
+        nodes.push(this.xpathMatchToNode(snapshot.snapshotItem(i)));
+      }
+      return { result: { type: 'nodes', value: nodes } };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
+  async downloadResource(url, maxBytes) {
+    try {
+      const response = await fetch(url, { credentials: 'include' });
+      if (!response.ok) {
+        return { error: `Fetching ${url} failed with HTTP ${response.status}` };
+      }
+
+      const tooLarge = (size) => ({ error: `${url} is ${size} bytes, over the ${maxBytes}-byte download limit` });
+      const declaredLength = Number(response.headers.get('content-length'));
+      if (declaredLength > maxBytes) {
+        return tooLarge(declaredLength);
+      }
+      const bytes = new Uint8Array(await response.arrayBuffer());
+      if (bytes.length > maxBytes) {
+        return tooLarge(bytes.length);
+      }
+
+      // btoa takes a binary string; build it in chunks to stay under argument limits
+      let binary = '';
+      for (let i = 0; i < bytes.length; i += 0x8000) {
+        binary += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+      }
+      return {
+        url: response.url,
+        status: response.status,
+        contentType: response.headers.get('content-type'),
+        data: btoa(binary)
+      };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
+  async replayRequest({ method, url, headers, body, maxBytes }) {
+    const started = performance.now();
+    try {
+      const response = await fetch(url, {
+        method,
+        headers,
+        // fetch rejects a body on GET/HEAD even when it is empty
+        body: ['GET', 'HEAD'].includes(method.toUpperCase()) ? undefined : body ?? undefined,
+        credentials: 'include'
+      });
+      const text = await response.text();
+      return {
+        url: response.url,
+        status: response.status,
+        statusText: response.statusText,
+        headers: Object.fromEntries(response.headers),
+        body: text.slice(0, maxBytes),
+        bodyTruncated: text.length > maxBytes,
+        durationMs: Math.round(performance.now() - started)
+      };
+    } catch (e) {
+      return { error: `Replaying ${method} ${url} failed: ${e.message}` };
+    }
+  }
+
+  observeMutations({ selector, durationMs, attributes, childList, subtree }) {
+    const MAX_RECORDS = 1000;
+
+    const target = selector ? document.querySelector(selector) : document.documentElement;
+    if (!target) {
+      return Promise.resolve({ error: `No element matches selector ${selector}` });
+    }
+
+    const describe = (node) => {
+      if (node.nodeType !== Node.ELEMENT_NODE) return node.nodeName;
+      const id = node.id ? `#${node.id}` : '';
+      const classes = [...node.classList].map(name => `.${name}`).join('');
+      return `${node.tagName.toLowerCase()}${id}${classes}`;
+    };
+
+    const records = [];
+    let truncated = false;
+    const start = performance.now();
+    const collect = (mutations) => {
+      const offset = performance.now() - start;
+      for (const mutation of mutations) {
+        if (records.length >= MAX_RECORDS) {
+          truncated = true;
+          break;
+        }
+        records.push({
+          type: mutation.type,
+          target: this.xpathFor(mutation.target),
+          attribute_name: mutation.attributeName || undefined,
+          old_value: mutation.oldValue ?? undefined,
+          added_nodes: [...mutation.addedNodes].map(describe),
+          removed_nodes: [...mutation.removedNodes].map(describe),
+          offset_ms: offset
+        });
+      }
+    };
+    const observer = new MutationObserver(collect);
+    observer.observe(target, {
+      attributes,
+      attributeOldValue: attributes,
+      childList,
+      subtree
+    });
+
+    return new Promise((resolve) => {
+      setTimeout(() => {
+        // Pick up anything queued but not yet delivered to the callback
+        collect(observer.takeRecords());
+        observer.disconnect();
+        resolve({ records, truncated });
+      }, durationMs);
+    });
+  }
+
+  // Shape a matched node like the server's DomNode, without children
+  xpathMatchToNode(node) {
+    const isElement = node.nodeType === Node.ELEMENT_NODE;
+    const attributes = {};
+    if (isElement) {
+      for (const attr of node.attributes) {
+        attributes[attr.name] = attr.value;
+      }
+    }
+    const nodeTypes = { [Node.ELEMENT_NODE]: 'element', [Node.ATTRIBUTE_NODE]: 'attribute', [Node.TEXT_NODE]: 'text', [Node.COMMENT_NODE]: 'comment' };
+
+    return {
+      node_type: nodeTypes[node.nodeType] || 'node',
+      tag_name: isElement ? node.tagName.toLowerCase() : null,
+      text_content: (node.textContent || '').trim().substring(0, 500) || null,
+      attributes,
+      computed_styles: null,
+      children: [],
+      xpath: this.xpathFor(node),
+      selector: null
+    };
+  }
+
+  xpathFor(node) {
+    if (node.nodeType === Node.ATTRIBUTE_NODE) {
+      return `${this.xpathFor(node.ownerElement)}/@${node.name}`;
+    }
+    const parts = [];
+    for (let current = node; current && current.nodeType !== Node.DOCUMENT_NODE; current = current.parentNode) {
+      const name = current.nodeType === Node.ELEMENT_NODE ? current.tagName.toLowerCase() : 'text()';
+      let index = 1;
+      for (let sibling = current.previousSibling; sibling; sibling = sibling.previousSibling) {
+        if (sibling.nodeType === current.nodeType && sibling.nodeName === current.nodeName) index++;
+      }
+      parts.unshift(`${name}[${index}]`);
+    }
+    return '/' + parts.join('/');
+  }
+
   executeInPageContext(script, callback) {
     window.addEventListener('message', function responseHandler(event) {
       if (event.data.type === 'SCRIPT_RESULT') {
@@ -199,6 +551,18 @@ class PageDataExtractor {
     }, '*');
   }
 
+  setPageContent(html) {
+    try {
+      // open/write/close replaces the document and runs its scripts, like a fresh load
+      document.open();
+      document.write(html);
+      document.close();
+      return { title: document.title };
+    } catch (error) {
+      return { error: error.message };
+    }
+  }
+
   getPerformanceMetrics() {
     const perf = window.performance;
     const timing = perf.timing;
@@ -210,8 +574,15 @@ class PageDataExtractor {
         domContentLoaded: timing.domContentLoadedEventEnd - timing.fetchStart,
         domInteractive: timing.domInteractive - timing.fetchStart,
         firstPaint: perf.getEntriesByType('paint')[0]?.startTime,
-        firstContentfulPaint: perf.getEntriesByType('paint')[1]?.startTime
+        firstContentfulPaint: perf.getEntriesByType('paint')[1]?.startTime,
+        dnsLookup: timing.domainLookupEnd - timing.domainLookupStart,
+        tcpConnect: timing.connectEnd - timing.connectStart,
+        sslHandshake: timing.secureConnectionStart ? timing.connectEnd - timing.secureConnectionStart : 0,
+        request: timing.responseStart - timing.requestStart,
+        response: timing.responseEnd - timing.responseStart,
+        domProcessing: timing.domComplete - timing.domLoading
       },
+      webVitals: { ...this.webVitals },
       navigation: {
         type: navigation.type,
         redirectCount: navigation.redirectCount
@@ -219,8 +590,11 @@ class PageDataExtractor {
       resources: perf.getEntriesByType('resource').map(r => ({
         name: r.name,
         type: r.initiatorType,
+        startTime: r.startTime,
         duration: r.duration,
-        size: r.transferSize
+        size: r.transferSize,
+        encodedBodySize: r.encodedBodySize,
+        decodedBodySize: r.decodedBodySize
       })),
       memory: performance.memory ? {
         usedJSHeapSize: performance.memory.usedJSHeapSize,
@@ -230,6 +604,74 @@ class PageDataExtractor {
     };
   }
 
+  getResourceUsage() {
+    const memory = performance.memory || {};
+    return {
+      usedJSHeapSize: memory.usedJSHeapSize || 0,
+      totalJSHeapSize: memory.totalJSHeapSize || 0,
+      jsHeapSizeLimit: memory.jsHeapSizeLimit || 0,
+      domNodeCount: document.getElementsByTagName('*').length
+    };
+  }
+
+  accessibleName(element) {
+    return element.getAttribute('aria-label') || 
+           element.getAttribute('alt') || 
+           (element.innerText ? element.innerText.substring(0, 100) : '') || // Limit text length
+           element.getAttribute('title') || 
+           '';
+  }
+
+  accessibleRole(element) {
+    return element.getAttribute('role') || 
+           element.tagName.toLowerCase();
+  }
+
+  getAccessibilityNode(selector) {
+    try {
+      const element = document.querySelector(selector);
+      if (!element) {
+        return { node: null };
+      }
+
+      const properties = {
+        focusable: element.tabIndex >= 0,
+        tagName: element.tagName.toLowerCase()
+      };
+      for (const attr of element.getAttributeNames()) {
+        if (attr.startsWith('aria-')) {
+          properties[attr] = element.getAttribute(attr);
+        }
+      }
+      if ('disabled' in element) properties.disabled = element.disabled;
+      if ('required' in element) properties.required = element.required;
+
+      const describedBy = element.getAttribute('aria-describedby');
+      const description = describedBy
+        ? describedBy.split(/\s+/)
+            .map(id => document.getElementById(id)?.textContent?.trim())
+            .filter(Boolean)
+            .join(' ')
+        : element.getAttribute('title');
+      const rect = element.getBoundingClientRect();
+
+      return {
+        node: {
+          role: this.accessibleRole(element),
+          name: this.accessibleName(element) || null,
+          description: description || null,
+          value: typeof element.value !== 'string' ? null
+            : element.type === 'password' ? '[REDACTED]' : element.value,
+          properties,
+          children: [],
+          bounds: { x: rect.x, y: rect.y, width: rect.width, height: rect.height }
+        }
+      };
+    } catch (e) {
+      return { error: e.message };
+    }
+  }
+
   getAccessibilityTree(customTimeout) {
     const startTime = Date.now();
     // Use custom timeout if provided, otherwise default to 28 seconds
@@ -246,17 +688,10 @@ class PageDataExtractor {
         throw new Error(`Accessibility tree processing timeout after ${MAX_PROCESSING_TIME/1000}s`);
       }
       
-      return element.getAttribute('aria-label') || 
-             element.getAttribute('alt') || 
-             (element.innerText ? element.innerText.substring(0, 100) : '') || // Limit text length
-             element.getAttribute('title') || 
-             '';
+      return this.accessibleName(element);
     };
 
-    const getRole = (element) => {
-      return element.getAttribute('role') || 
-             element.tagName.toLowerCase();
-    };
+    const getRole = (element) => this.accessibleRole(element);
 
     const isAccessibilityRelevant = (element) => {
       // Check for any accessibility-relevant attributes or semantic elements
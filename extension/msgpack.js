@@ -0,0 +1,164 @@
+// Minimal MessagePack codec for the MCP server wire format.
+// Covers the types JSON messages use, plus Uint8Array as binary.
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder();
+
+export function encode(value) {
+  const bytes = [];
+  write(bytes, value);
+  return new Uint8Array(bytes);
+}
+
+function pushUint(bytes, value, size) {
+  for (let shift = (size - 1) * 8; shift >= 0; shift -= 8) {
+    bytes.push(Math.floor(value / 2 ** shift) & 0xff);
+  }
+}
+
+function writeFloat(bytes, value) {
+  const view = new DataView(new ArrayBuffer(8));
+  view.setFloat64(0, value);
+  bytes.push(0xcb, ...new Uint8Array(view.buffer));
+}
+
+function writeInt(bytes, value) {
+  if (value >= 0) {
+    if (value < 0x80) bytes.push(value);
+    else if (value < 0x100) bytes.push(0xcc, value);
+    else if (value < 0x10000) { bytes.push(0xcd); pushUint(bytes, value, 2); }
+    else if (value < 0x100000000) { bytes.push(0xce); pushUint(bytes, value, 4); }
+    else { bytes.push(0xcf); pushUint(bytes, value, 8); }
+  } else if (value >= -32) {
+    bytes.push(value & 0xff);
+  } else if (value >= -0x80) {
+    bytes.push(0xd0, value & 0xff);
+  } else if (value >= -0x8000) {
+    bytes.push(0xd1); pushUint(bytes, value & 0xffff, 2);
+  } else if (value >= -0x80000000) {
+    bytes.push(0xd2); pushUint(bytes, value >>> 0, 4);
+  } else {
+    // Beyond 32 bits, fall back to a float like JSON would
+    writeFloat(bytes, value);
+  }
+}
+
+function writeLength(bytes, length, fix, fixMax, codes) {
+  if (fix !== null && length <= fixMax) bytes.push(fix | length);
+  else if (codes[0] !== null && length < 0x100) bytes.push(codes[0], length);
+  else if (length < 0x10000) { bytes.push(codes[1]); pushUint(bytes, length, 2); }
+  else { bytes.push(codes[2]); pushUint(bytes, length, 4); }
+}
+
+function write(bytes, value) {
+  if (value === null || value === undefined) {
+    bytes.push(0xc0);
+  } else if (typeof value === 'boolean') {
+    bytes.push(value ? 0xc3 : 0xc2);
+  } else if (typeof value === 'number') {
+    if (Number.isSafeInteger(value)) writeInt(bytes, value);
+    else writeFloat(bytes, value);
+  } else if (typeof value === 'string') {
+    const utf8 = textEncoder.encode(value);
+    writeLength(bytes, utf8.length, 0xa0, 31, [0xd9, 0xda, 0xdb]);
+    for (const byte of utf8) bytes.push(byte);
+  } else if (value instanceof Uint8Array) {
+    writeLength(bytes, value.length, null, 0, [0xc4, 0xc5, 0xc6]);
+    for (const byte of value) bytes.push(byte);
+  } else if (Array.isArray(value)) {
+    writeLength(bytes, value.length, 0x90, 15, [null, 0xdc, 0xdd]);
+    for (const item of value) write(bytes, item);
+  } else if (typeof value.toJSON === 'function') {
+    write(bytes, value.toJSON());
+  } else {
+    // Skip undefined members, as JSON.stringify does
+    const entries = Object.entries(value).filter(([, item]) => item !== undefined);
+    writeLength(bytes, entries.length, 0x80, 15, [null, 0xde, 0xdf]);
+    for (const [key, item] of entries) {
+      write(bytes, key);
+      write(bytes, item);
+    }
+  }
+}
+
+export function decode(buffer) {
+  const bytes = buffer instanceof Uint8Array ? buffer : new Uint8Array(buffer);
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  let offset = 0;
+
+  const uint = (size) => {
+    let value = 0;
+    for (let i = 0; i < size; i++) value = value * 256 + bytes[offset++];
+    return value;
+  };
+  const str = (length) => {
+    const value = textDecoder.decode(bytes.subarray(offset, offset + length));
+    offset += length;
+    return value;
+  };
+  const bin = (length) => {
+    const value = bytes.slice(offset, offset + length);
+    offset += length;
+    return value;
+  };
+  const array = (length) => {
+    const value = [];
+    for (let i = 0; i < length; i++) value.push(read());
+    return value;
+  };
+  const map = (length) => {
+    const value = {};
+    for (let i = 0; i < length; i++) {
+      const key = read();
+      value[key] = read();
+    }
+    return value;
+  };
+  const signed = (size) => {
+    const value = size === 1 ? view.getInt8(offset)
+      : size === 2 ? view.getInt16(offset)
+      : size === 4 ? view.getInt32(offset)
+      : Number(view.getBigInt64(offset));
+    offset += size;
+    return value;
+  };
+
+  function read() {
+    const byte = bytes[offset++];
+    if (byte < 0x80) return byte;
+    if (byte < 0x90) return map(byte & 0x0f);
+    if (byte < 0xa0) return array(byte & 0x0f);
+    if (byte < 0xc0) return str(byte & 0x1f);
+    if (byte >= 0xe0) return byte - 0x100;
+
+    switch (byte) {
+      case 0xc0: return null;
+      case 0xc2: return false;
+      case 0xc3: return true;
+      case 0xc4: return bin(uint(1));
+      case 0xc5: return bin(uint(2));
+      case 0xc6: return bin(uint(4));
+      case 0xca: { const value = view.getFloat32(offset); offset += 4; return value; }
+      case 0xcb: { const value = view.getFloat64(offset); offset += 8; return value; }
+      case 0xcc: return uint(1);
+      case 0xcd: return uint(2);
+      case 0xce: return uint(4);
+      case 0xcf: return uint(8);
+      case 0xd0: return signed(1);
+      case 0xd1: return signed(2);
+      case 0xd2: return signed(4);
+      case 0xd3: return signed(8);
+      case 0xd9: return str(uint(1));
+      case 0xda: return str(uint(2));
+      case 0xdb: return str(uint(4));
+      case 0xdc: return array(uint(2));
+      case 0xdd: return array(uint(4));
+      case 0xde: return map(uint(2));
+      case 0xdf: return map(uint(4));
+      default:
+        throw new Error(`Unsupported MessagePack type 0x${byte.toString(16)}`);
+    }
+  }
+
+  return read();
+}
@@ -0,0 +1,114 @@
+use browser_mcp_rust_server::cache::BrowserDataCache;
+use browser_mcp_rust_server::types::browser::{DomNode, DomSnapshot};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+
+const TAGS: &[&str] = &["div", "span", "a", "li", "p", "button"];
+const ATTRIBUTES: &[&str] = &["class", "id", "href", "data-testid", "aria-label", "role"];
+const PROPERTIES: &[&str] = &["display", "color", "margin-top", "font-size", "position", "z-index"];
+
+// A wide, shallow tree like a typical list-heavy page
+fn build_node(depth: usize, width: usize, seq: &mut usize) -> DomNode {
+    *seq += 1;
+    let n = *seq;
+    let children = if depth == 0 {
+        Vec::new()
+    } else {
+        (0..width).map(|_| build_node(depth - 1, width, seq)).collect()
+    };
+
+    DomNode {
+        node_type: Arc::from("element"),
+        tag_name: Some(Arc::from(TAGS[n % TAGS.len()])),
+        text_content: None,
+        attributes: ATTRIBUTES
+            .iter()
+            .map(|name| (Arc::from(*name), format!("value-{}", n)))
+            .collect(),
+        computed_styles: Some(
+            PROPERTIES
+                .iter()
+                .map(|name| (Arc::from(*name), "auto".to_string()))
+                .collect::<HashMap<_, _>>(),
+        ),
+        children,
+        xpath: None,
+        selector: None,
+        shadow_root: false,
+    }
+}
+
+fn build_snapshot() -> DomSnapshot {
+    let mut seq = 0;
+    let root = build_node(3, 12, &mut seq);
+    DomSnapshot {
+        root,
+        node_count: seq,
+        max_depth: 3,
+        include_styles: true,
+        timestamp: std::time::SystemTime::now(),
+    }
+}
+
+/// Bytes held by node type, tag, attribute and property strings when every node owns its own copy.
+fn owned_key_bytes(node: &DomNode) -> usize {
+    node.node_type.len()
+        + node.tag_name.as_ref().map_or(0, |tag| tag.len())
+        + node.attributes.keys().map(|key| key.len()).sum::<usize>()
+        + node
+            .computed_styles
+            .as_ref()
+            .map_or(0, |styles| styles.keys().map(|key| key.len()).sum::<usize>())
+        + node.children.iter().map(owned_key_bytes).sum::<usize>()
+}
+
+fn collect_keys<'a>(node: &'a DomNode, unique: &mut HashSet<&'a str>) {
+    unique.insert(&node.node_type);
+    unique.extend(node.tag_name.as_deref());
+    unique.extend(node.attributes.keys().map(|key| &**key));
+    if let Some(styles) = &node.computed_styles {
+        unique.extend(styles.keys().map(|key| &**key));
+    }
+    for child in &node.children {
+        collect_keys(child, unique);
+    }
+}
+
+fn report_memory_reduction() {
+    let snapshot = build_snapshot();
+    let mut unique = HashSet::new();
+    collect_keys(&snapshot.root, &mut unique);
+
+    let owned = owned_key_bytes(&snapshot.root);
+    let interned: usize = unique.iter().map(|key| key.len()).sum();
+    println!(
+        "dom snapshot of {} nodes: {} bytes of owned key strings, {} bytes interned ({} unique, {:.1}% saved)",
+        snapshot.node_count,
+        owned,
+        interned,
+        unique.len(),
+        100.0 * (owned - interned) as f64 / owned as f64
+    );
+}
+
+fn benchmark_dom_interning(c: &mut Criterion) {
+    report_memory_reduction();
+
+    let rt = Runtime::new().unwrap();
+    let cache = BrowserDataCache::new(100, Duration::from_secs(300));
+    let snapshot = build_snapshot();
+
+    c.bench_function("update_dom_snapshot_interned", |b| {
+        b.iter(|| {
+            rt.block_on(cache.update_dom_snapshot(1, black_box(snapshot.clone())));
+        });
+    });
+}
+
+criterion_group!(benches, benchmark_dom_interning);
+criterion_main!(benches);
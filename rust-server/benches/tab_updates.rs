@@ -0,0 +1,137 @@
+use browser_mcp_rust_server::cache::BrowserDataCache;
+use browser_mcp_rust_server::types::browser::{
+    ConsoleMessage, DomSnapshot, NetworkRequest, PageContent, ScreenshotData,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::broadcast;
+
+const THREADS: usize = 4;
+
+/// The tab layout before fields got their own slots: every update cloned the whole
+/// struct under the shard's write lock, inserted a fresh Arc and broadcast the change.
+#[derive(Clone)]
+#[allow(dead_code)] // Only cloned, to reproduce the old cost
+struct CloneOnWriteTab {
+    page_content: Option<Arc<PageContent>>,
+    dom_snapshot: Option<Arc<DomSnapshot>>,
+    console_logs: Option<Arc<RwLock<VecDeque<ConsoleMessage>>>>,
+    network_data: Option<Arc<RwLock<VecDeque<NetworkRequest>>>>,
+    screenshot_data: Option<Arc<ScreenshotData>>,
+    debugger_attached: bool,
+    last_updated: SystemTime,
+}
+
+struct CloneOnWriteCache {
+    tabs: DashMap<u32, Arc<CloneOnWriteTab>>,
+    events: broadcast::Sender<u32>,
+}
+
+fn update_clone_on_write(cache: &CloneOnWriteCache, tab_id: u32, screenshot: ScreenshotData) {
+    let tabs = &cache.tabs;
+    let new_screenshot = Arc::new(screenshot);
+    let updated = if let Some(existing) = tabs.get_mut(&tab_id) {
+        let mut data = (**existing).clone();
+        data.screenshot_data = Some(new_screenshot);
+        data.last_updated = SystemTime::now();
+        Arc::new(data)
+    } else {
+        Arc::new(CloneOnWriteTab {
+            page_content: None,
+            dom_snapshot: None,
+            console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
+            network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
+            screenshot_data: Some(new_screenshot),
+            debugger_attached: false,
+            last_updated: SystemTime::now(),
+        })
+    };
+    tabs.insert(tab_id, updated);
+    let _ = cache.events.send(tab_id);
+}
+
+fn screenshot() -> ScreenshotData {
+    ScreenshotData {
+        data: Vec::new(),
+        format: "png".to_string(),
+        width: 1280,
+        height: 720,
+        clip: None,
+        timestamp: SystemTime::now(),
+    }
+}
+
+fn page_content() -> PageContent {
+    PageContent {
+        url: "https://example.com/".to_string(),
+        title: "Example".to_string(),
+        text: "lorem ipsum ".repeat(10_000),
+        html: "<p>lorem ipsum</p>".repeat(10_000),
+        metadata: HashMap::new(),
+        last_updated: SystemTime::now(),
+    }
+}
+
+/// The cache's update methods never await, so a single poll completes them.
+fn run_now<F: std::future::Future<Output = ()>>(future: F) {
+    let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(future);
+    assert!(future.as_mut().poll(&mut context).is_ready());
+}
+
+/// Run `iters` updates split across threads that all hit the same tab.
+fn contended(iters: u64, update: impl Fn() + Sync) -> Duration {
+    let per_thread = iters / THREADS as u64 + 1;
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..per_thread {
+                    update();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn benchmark_tab_updates(c: &mut Criterion) {
+    let legacy = CloneOnWriteCache {
+        tabs: DashMap::new(),
+        events: broadcast::channel(1000).0,
+    };
+    update_clone_on_write(&legacy, 1, screenshot());
+    legacy.tabs.alter(&1, |_, tab| {
+        let mut data = (*tab).clone();
+        data.page_content = Some(Arc::new(page_content()));
+        Arc::new(data)
+    });
+
+    let cache = BrowserDataCache::new(100, Duration::from_secs(300));
+    run_now(cache.update_page_content(1, page_content()));
+
+    let mut group = c.benchmark_group("hot_tab_update");
+    group.bench_function("clone_whole_tab", |b| {
+        b.iter(|| update_clone_on_write(&legacy, 1, black_box(screenshot())));
+    });
+    group.bench_function("swap_field_slot", |b| {
+        b.iter(|| run_now(cache.update_screenshot(1, black_box(screenshot()))));
+    });
+    group.bench_function("clone_whole_tab_contended", |b| {
+        b.iter_custom(|iters| contended(iters, || update_clone_on_write(&legacy, 1, screenshot())));
+    });
+    group.bench_function("swap_field_slot_contended", |b| {
+        b.iter_custom(|iters| contended(iters, || run_now(cache.update_screenshot(1, screenshot()))));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_tab_updates);
+criterion_main!(benches);
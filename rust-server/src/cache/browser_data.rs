@@ -1,9 +1,10 @@
+use super::memory::StringInterner;
+use super::response::{ResponseCache, ResponseCacheKey};
 use crate::types::{browser::*, errors::*, messages::*};
 use dashmap::DashMap;
-use parking_lot::RwLock;
 use std::{
-    collections::{HashSet, VecDeque},
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    sync::{atomic::Ordering, Arc},
     time::{Duration, SystemTime},
 };
 use tokio::sync::broadcast;
@@ -18,6 +19,30 @@ pub struct BrowserDataCache {
     connection_tabs: Arc<DashMap<Uuid, u32>>,
     tab_connections: Arc<DashMap<u32, HashSet<Uuid>>>,
 
+    // Live persistent JavaScript contexts per tab
+    js_contexts: Arc<DashMap<u32, HashSet<String>>>,
+
+    // Last URL each tab was seen at, for URL access control
+    tab_urls: Arc<DashMap<u32, String>>,
+
+    // Request interception rules the extension is applying per tab
+    intercept_rules: Arc<DashMap<u32, Vec<InterceptRule>>>,
+
+    // Extra request headers the extension is adding per tab
+    extra_headers: Arc<DashMap<u32, HashMap<String, String>>>,
+
+    // Scripts registered to run on each navigation, per tab
+    init_scripts: Arc<DashMap<u32, Vec<InitScript>>>,
+
+    // User agent overrides the debugger is applying per tab
+    user_agents: Arc<DashMap<u32, UserAgentOverride>>,
+
+    // CSS media emulation the debugger is applying per tab
+    emulated_media: Arc<DashMap<u32, EmulatedMedia>>,
+
+    // Reference screenshots compare_screenshot diffs against per tab
+    baseline_screenshots: Arc<DashMap<u32, Arc<ScreenshotData>>>,
+
     // Event broadcasting for real-time updates
     update_sender: broadcast::Sender<DataUpdateEvent>,
 
@@ -29,6 +54,18 @@ pub struct BrowserDataCache {
     // Performance monitoring
     cache_hits: Arc<std::sync::atomic::AtomicU64>,
     cache_misses: Arc<std::sync::atomic::AtomicU64>,
+
+    // Short-lived results of read-only tool calls
+    response_cache: Arc<ResponseCache>,
+
+    // Longest network request body kept in history
+    max_retained_body_bytes: usize,
+
+    // Tool invocations kept per tab, 0 to not record them
+    activity_log_entries: usize,
+
+    // Shared copies of repeated DOM snapshot strings
+    interner: Arc<StringInterner>,
 }
 
 impl BrowserDataCache {
@@ -39,287 +76,438 @@ impl BrowserDataCache {
             tab_data: Arc::new(DashMap::new()),
             connection_tabs: Arc::new(DashMap::new()),
             tab_connections: Arc::new(DashMap::new()),
+            js_contexts: Arc::new(DashMap::new()),
+            tab_urls: Arc::new(DashMap::new()),
+            intercept_rules: Arc::new(DashMap::new()),
+            extra_headers: Arc::new(DashMap::new()),
+            init_scripts: Arc::new(DashMap::new()),
+            user_agents: Arc::new(DashMap::new()),
+            emulated_media: Arc::new(DashMap::new()),
+            baseline_screenshots: Arc::new(DashMap::new()),
             update_sender,
             max_cache_size,
             cleanup_interval: Duration::from_secs(300), // 5 minutes
             data_ttl,
             cache_hits: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             cache_misses: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            response_cache: Arc::new(ResponseCache::new(0, Duration::ZERO)),
+            max_retained_body_bytes: usize::MAX,
+            activity_log_entries: 0,
+            interner: Arc::new(StringInterner::new()),
         }
     }
 
-    // Zero-copy data access
-    pub async fn get_tab_data(&self, tab_id: u32) -> Option<Arc<TabData>> {
-        if let Some(data) = self.tab_data.get(&tab_id) {
-            self.cache_hits
-                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            Some(data.value().clone())
-        } else {
-            self.cache_misses
-                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            None
+    pub fn set_response_cache(&mut self, max_entries: usize, ttl: Duration) {
+        self.response_cache = Arc::new(ResponseCache::new(max_entries, ttl));
+    }
+
+    pub fn set_max_retained_body_bytes(&mut self, max_bytes: usize) {
+        self.max_retained_body_bytes = max_bytes;
+    }
+
+    pub fn set_activity_log_entries(&mut self, entries: usize) {
+        self.activity_log_entries = entries;
+    }
+
+    pub fn activity_log_enabled(&self) -> bool {
+        self.activity_log_entries > 0
+    }
+
+    /// Append to the tab's activity log, dropping the oldest entry once it is full.
+    pub fn record_tool_invocation(&self, tab_id: u32, invocation: ToolInvocation) {
+        if !self.activity_log_enabled() {
+            return;
         }
+        self.with_tab(tab_id, |tab| {
+            let mut log = tab.activity_log.write();
+            if log.len() >= self.activity_log_entries {
+                log.pop_front();
+            }
+            log.push_back(invocation);
+        });
     }
 
-    pub async fn get_all_tabs(&self) -> Vec<Arc<TabData>> {
+    pub fn get_tab_activity(&self, tab_id: u32) -> Vec<ToolInvocation> {
         self.tab_data
+            .get(&tab_id)
+            .map(|tab| tab.activity_log.read().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // Tool response cache
+    /// A cached tool result and how long ago it was stored.
+    pub fn get_cached_response(&self, key: &ResponseCacheKey) -> Option<(serde_json::Value, Duration)> {
+        self.response_cache.get_with_age(key)
+    }
+
+    pub fn cache_response(&self, key: ResponseCacheKey, value: serde_json::Value) {
+        self.response_cache.insert(key, value);
+    }
+
+    pub fn invalidate_responses(&self, tab_id: u32) {
+        let removed = self.response_cache.invalidate_tab(tab_id);
+        if removed > 0 {
+            tracing::debug!("Invalidated {} cached responses for tab {}", removed, tab_id);
+        }
+    }
+
+    // Zero-copy data access. Lookups count toward `cache_hit_rate` as a hit only when
+    // the data asked for is there: a known tab without that field is a miss.
+    pub async fn get_tab_data(&self, tab_id: u32) -> Option<Arc<TabData>> {
+        self.record_lookup(self.tab(tab_id))
+    }
+
+    fn tab(&self, tab_id: u32) -> Option<Arc<TabData>> {
+        self.tab_data.get(&tab_id).map(|data| data.value().clone())
+    }
+
+    fn record_lookup<T>(&self, found: Option<T>) -> Option<T> {
+        let counter = if found.is_some() { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        found
+    }
+
+    /// Every cached tab, ordered by tab id so listings built from it are stable.
+    pub async fn get_all_tabs(&self) -> Vec<Arc<TabData>> {
+        let mut tabs: Vec<Arc<TabData>> = self
+            .tab_data
             .iter()
             .map(|entry| entry.value().clone())
-            .collect()
+            .collect();
+        tabs.sort_unstable_by_key(|tab| tab.tab_id);
+        tabs
     }
 
     pub async fn get_page_content(&self, tab_id: u32) -> Option<Arc<PageContent>> {
-        self.get_tab_data(tab_id)
-            .await?
-            .page_content
-            .clone()
+        self.record_lookup(self.tab(tab_id).and_then(|tab| tab.page_content.read().clone()))
     }
 
     pub async fn get_dom_snapshot(&self, tab_id: u32) -> Option<Arc<DomSnapshot>> {
-        self.get_tab_data(tab_id)
-            .await?
-            .dom_snapshot
-            .clone()
+        self.record_lookup(self.tab(tab_id).and_then(|tab| tab.dom_snapshot.read().clone()))
     }
 
+    pub async fn get_accessibility_tree(&self, tab_id: u32) -> Option<Arc<AccessibilityTree>> {
+        self.record_lookup(self.tab(tab_id).and_then(|tab| tab.accessibility_tree.read().clone()))
+    }
+
+    pub async fn get_performance_metrics(&self, tab_id: u32) -> Option<Arc<PerformanceMetrics>> {
+        self.record_lookup(self.tab(tab_id).and_then(|tab| tab.performance_metrics.read().clone()))
+    }
+
+    /// The tab's buffered console messages. An empty buffer still counts as a miss.
     pub async fn get_console_logs(&self, tab_id: u32) -> Option<Vec<ConsoleMessage>> {
-        let tab_data = self.get_tab_data(tab_id).await?;
-        let console_logs = tab_data.console_logs.as_ref()?;
-        let logs = console_logs.read();
-        Some(logs.iter().cloned().collect())
+        let tab_data = self.tab(tab_id);
+        let logs: Option<Vec<ConsoleMessage>> =
+            tab_data.map(|tab| tab.console_logs.read().iter().cloned().collect());
+        self.record_lookup(logs.as_ref().filter(|logs| !logs.is_empty()));
+        logs
     }
 
+    /// The tab's buffered network requests. An empty buffer still counts as a miss.
     pub async fn get_network_requests(&self, tab_id: u32) -> Option<Vec<NetworkRequest>> {
-        let tab_data = self.get_tab_data(tab_id).await?;
-        let network_data = tab_data.network_data.as_ref()?;
-        let requests = network_data.read();
-        Some(requests.iter().cloned().collect())
+        let tab_data = self.tab(tab_id);
+        let requests: Option<Vec<NetworkRequest>> =
+            tab_data.map(|tab| tab.network_data.read().iter().cloned().collect());
+        self.record_lookup(requests.as_ref().filter(|requests| !requests.is_empty()));
+        requests
+    }
+
+    pub async fn get_network_request(&self, tab_id: u32, request_id: &str) -> Option<NetworkRequest> {
+        let request = self.tab(tab_id).and_then(|tab| {
+            let requests = tab.network_data.read();
+            requests.iter().rev().find(|r| r.request_id == request_id).cloned()
+        });
+        self.record_lookup(request)
+    }
+
+    pub fn set_tab_url(&self, tab_id: u32, url: &str) {
+        self.tab_urls.insert(tab_id, url.to_string());
     }
 
-    // Atomic data updates
+    pub fn get_tab_url(&self, tab_id: u32) -> Option<String> {
+        self.tab_urls.get(&tab_id).map(|url| url.clone())
+    }
+
+    // Updates replace one field of the tab in place. The replaced value is returned out
+    // of `with_tab` so it is freed after the locks are released.
     pub async fn update_page_content(&self, tab_id: u32, content: PageContent) {
-        let new_content = Arc::new(content);
-
-        // Update or create tab data
-        let updated_data = if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.page_content = Some(new_content);
-            data.last_updated = SystemTime::now();
-            Arc::new(data)
-        } else {
-            Arc::new(TabData {
-                tab_id,
-                page_content: Some(new_content),
-                dom_snapshot: None,
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: None,
-                accessibility_tree: None,
-                screenshot_data: None,
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            })
-        };
+        if !content.url.is_empty() {
+            self.set_tab_url(tab_id, &content.url);
+        }
 
-        self.tab_data.insert(tab_id, updated_data);
+        let _previous = self.with_tab(tab_id, |tab| {
+            tab.touch();
+            tab.page_content.write().replace(Arc::new(content))
+        });
 
         // Broadcast update event
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::PageContentUpdated,
-            timestamp: chrono::Utc::now(),
-        };
-        let _ = self.update_sender.send(event);
+        self.broadcast(tab_id, DataUpdateType::PageContentUpdated);
     }
 
-    pub async fn update_dom_snapshot(&self, tab_id: u32, snapshot: DomSnapshot) {
-        let new_snapshot = Arc::new(snapshot);
+    pub async fn update_dom_snapshot(&self, tab_id: u32, mut snapshot: DomSnapshot) {
+        intern_dom_node(&self.interner, &mut snapshot.root);
 
-        let updated_data = if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.dom_snapshot = Some(new_snapshot);
-            data.last_updated = SystemTime::now();
-            Arc::new(data)
-        } else {
-            Arc::new(TabData {
-                tab_id,
-                page_content: None,
-                dom_snapshot: Some(new_snapshot),
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: None,
-                accessibility_tree: None,
-                screenshot_data: None,
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            })
-        };
-
-        self.tab_data.insert(tab_id, updated_data);
+        let _previous = self.with_tab(tab_id, |tab| {
+            tab.touch();
+            tab.dom_snapshot.write().replace(Arc::new(snapshot))
+        });
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::DomSnapshotUpdated,
-            timestamp: chrono::Utc::now(),
-        };
-        let _ = self.update_sender.send(event);
+        self.broadcast(tab_id, DataUpdateType::DomSnapshotUpdated);
     }
 
     pub async fn add_console_message(&self, tab_id: u32, message: ConsoleMessage) {
-        self.ensure_tab_data_exists(tab_id).await;
+        let payload = (self.update_sender.receiver_count() > 0)
+            .then(|| serde_json::to_value(&message))
+            .and_then(|serialized| {
+                serialized
+                    .map_err(|e| tracing::warn!("Failed to serialize console message for tab {}: {}", tab_id, e))
+                    .ok()
+            });
+        self.with_tab(tab_id, |tab| {
+            let mut logs = tab.console_logs.write();
+            logs.push_back(message);
 
-        if let Some(tab_data) = self.tab_data.get(&tab_id) {
-            if let Some(console_logs) = &tab_data.console_logs {
-                let mut logs = console_logs.write();
-                logs.push_back(message);
+            // Limit console log size to prevent memory growth
+            while logs.len() > 1000 {
+                logs.pop_front();
+            }
+        });
 
-                // Limit console log size to prevent memory growth
-                while logs.len() > 1000 {
-                    logs.pop_front();
-                }
+        self.broadcast_with_payload(tab_id, DataUpdateType::ConsoleMessageAdded, payload);
+    }
+
+    pub async fn add_network_request(&self, tab_id: u32, mut request: NetworkRequest) {
+        let request_truncated = truncate_body(&mut request.request_body, self.max_retained_body_bytes);
+        let response_truncated = truncate_body(&mut request.response_body, self.max_retained_body_bytes);
+        request.body_truncated |= request_truncated || response_truncated;
+        request.request_body_truncated |= request_truncated;
+
+        let merged = self.with_tab(tab_id, |tab| {
+            let mut requests = tab.network_data.write();
+            // Extensions can report the same request more than once (retries, several
+            // listeners, start then completion); fold those into the existing entry
+            let within_window = |existing: &NetworkRequest| {
+                (request.timestamp - existing.timestamp).num_milliseconds().abs() <= NETWORK_REQUEST_DEDUPE_WINDOW_MS
+            };
+            if let Some(existing) = requests
+                .iter_mut()
+                .rev()
+                .find(|existing| existing.request_id == request.request_id && within_window(existing))
+            {
+                existing.merge(request);
+                return true;
             }
-        }
+            requests.push_back(request);
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::ConsoleMessageAdded,
-            timestamp: chrono::Utc::now(),
+            // Limit network request history to prevent memory growth
+            while requests.len() > 500 {
+                requests.pop_front();
+            }
+            false
+        });
+
+        let update_type = if merged {
+            DataUpdateType::NetworkRequestUpdated
+        } else {
+            DataUpdateType::NetworkRequestAdded
         };
-        let _ = self.update_sender.send(event);
+        self.broadcast(tab_id, update_type);
+    }
+
+    pub async fn update_performance_metrics(&self, tab_id: u32, metrics: PerformanceMetrics) {
+        let _previous = self.with_tab(tab_id, |tab| {
+            tab.touch();
+            tab.performance_metrics.write().replace(Arc::new(metrics))
+        });
+
+        self.broadcast(tab_id, DataUpdateType::PerformanceMetricsUpdated);
     }
 
-    pub async fn add_network_request(&self, tab_id: u32, request: NetworkRequest) {
-        self.ensure_tab_data_exists(tab_id).await;
+    pub async fn update_accessibility_tree(&self, tab_id: u32, tree: AccessibilityTree) {
+        let _previous = self.with_tab(tab_id, |tab| {
+            tab.touch();
+            tab.accessibility_tree.write().replace(Arc::new(tree))
+        });
+
+        self.broadcast(tab_id, DataUpdateType::AccessibilityTreeUpdated);
+    }
 
-        if let Some(tab_data) = self.tab_data.get(&tab_id) {
-            if let Some(network_data) = &tab_data.network_data {
-                let mut requests = network_data.write();
-                requests.push_back(request);
+    pub async fn update_screenshot(&self, tab_id: u32, screenshot: ScreenshotData) {
+        let _previous = self.with_tab(tab_id, |tab| {
+            tab.touch();
+            tab.screenshot_data.write().replace(Arc::new(screenshot))
+        });
 
-                // Limit network request history to prevent memory growth
-                while requests.len() > 500 {
-                    requests.pop_front();
-                }
+        self.broadcast(tab_id, DataUpdateType::ScreenshotCaptured);
+    }
+
+    pub async fn set_debugger_attached(&self, tab_id: u32, attached: bool) {
+        let mark = |tab: &TabData| {
+            tab.debugger_attached.store(attached, Ordering::Relaxed);
+            tab.touch();
+        };
+        if attached {
+            self.with_tab(tab_id, mark);
+        } else {
+            // Interception runs through the debugger and stops when it detaches,
+            // and a detached debugger lets paused scripts run again
+            self.intercept_rules.remove(&tab_id);
+            self.extra_headers.remove(&tab_id);
+            self.init_scripts.remove(&tab_id);
+            self.user_agents.remove(&tab_id);
+            self.emulated_media.remove(&tab_id);
+            if let Some(tab) = self.tab_data.get(&tab_id) {
+                mark(&tab);
+                tab.execution_paused.store(false, Ordering::Relaxed);
             }
         }
+    }
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::NetworkRequestAdded,
-            timestamp: chrono::Utc::now(),
-        };
-        let _ = self.update_sender.send(event);
+    pub async fn is_debugger_attached(&self, tab_id: u32) -> bool {
+        self.tab_data
+            .get(&tab_id)
+            .is_some_and(|data| data.is_debugger_attached())
     }
 
-    pub async fn update_performance_metrics(&self, tab_id: u32, metrics: PerformanceMetrics) {
-        let new_metrics = Arc::new(metrics);
+    pub fn set_execution_paused(&self, tab_id: u32, paused: bool) {
+        self.with_tab(tab_id, |tab| tab.execution_paused.store(paused, Ordering::Relaxed));
+    }
 
-        let updated_data = if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.performance_metrics = Some(new_metrics);
-            data.last_updated = SystemTime::now();
-            Arc::new(data)
-        } else {
-            Arc::new(TabData {
-                tab_id,
-                page_content: None,
-                dom_snapshot: None,
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: Some(new_metrics),
-                accessibility_tree: None,
-                screenshot_data: None,
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            })
-        };
+    pub fn is_execution_paused(&self, tab_id: u32) -> bool {
+        self.tab_data
+            .get(&tab_id)
+            .is_some_and(|data| data.execution_paused.load(Ordering::Relaxed))
+    }
 
-        self.tab_data.insert(tab_id, updated_data);
+    // JavaScript contexts
+    pub fn add_js_context(&self, tab_id: u32, context_id: String) {
+        self.js_contexts.entry(tab_id).or_default().insert(context_id);
+    }
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::PerformanceMetricsUpdated,
-            timestamp: chrono::Utc::now(),
+    pub fn has_js_context(&self, tab_id: u32, context_id: &str) -> bool {
+        self.js_contexts
+            .get(&tab_id)
+            .is_some_and(|contexts| contexts.contains(context_id))
+    }
+
+    pub fn remove_js_context(&self, tab_id: u32, context_id: &str) -> bool {
+        let Some(mut contexts) = self.js_contexts.get_mut(&tab_id) else {
+            return false;
         };
-        let _ = self.update_sender.send(event);
+        let removed = contexts.remove(context_id);
+        if contexts.is_empty() {
+            drop(contexts);
+            self.js_contexts.remove_if(&tab_id, |_, contexts| contexts.is_empty());
+        }
+        removed
     }
 
-    pub async fn update_accessibility_tree(&self, tab_id: u32, tree: AccessibilityTree) {
-        let new_tree = Arc::new(tree);
+    /// Contexts live in the page, so they are gone once the tab navigates.
+    pub fn clear_js_contexts(&self, tab_id: u32) {
+        self.js_contexts.remove(&tab_id);
+    }
 
-        let updated_data = if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.accessibility_tree = Some(new_tree);
-            data.last_updated = SystemTime::now();
-            Arc::new(data)
+    pub fn get_js_contexts(&self, tab_id: u32) -> Vec<String> {
+        let mut contexts: Vec<String> = self
+            .js_contexts
+            .get(&tab_id)
+            .map(|contexts| contexts.iter().cloned().collect())
+            .unwrap_or_default();
+        contexts.sort();
+        contexts
+    }
+
+    // Request interception
+    pub fn set_intercept_rules(&self, tab_id: u32, rules: Vec<InterceptRule>) {
+        if rules.is_empty() {
+            self.intercept_rules.remove(&tab_id);
         } else {
-            Arc::new(TabData {
-                tab_id,
-                page_content: None,
-                dom_snapshot: None,
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: None,
-                accessibility_tree: Some(new_tree),
-                screenshot_data: None,
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            })
-        };
+            self.intercept_rules.insert(tab_id, rules);
+        }
+    }
 
-        self.tab_data.insert(tab_id, updated_data);
+    pub fn get_intercept_rules(&self, tab_id: u32) -> Vec<InterceptRule> {
+        self.intercept_rules
+            .get(&tab_id)
+            .map(|rules| rules.clone())
+            .unwrap_or_default()
+    }
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::AccessibilityTreeUpdated,
-            timestamp: chrono::Utc::now(),
-        };
-        let _ = self.update_sender.send(event);
+    // Extra request headers
+    pub fn set_extra_headers(&self, tab_id: u32, headers: HashMap<String, String>) {
+        if headers.is_empty() {
+            self.extra_headers.remove(&tab_id);
+        } else {
+            self.extra_headers.insert(tab_id, headers);
+        }
     }
 
-    pub async fn update_screenshot(&self, tab_id: u32, screenshot: ScreenshotData) {
-        let new_screenshot = Arc::new(screenshot);
+    pub fn get_extra_headers(&self, tab_id: u32) -> HashMap<String, String> {
+        self.extra_headers
+            .get(&tab_id)
+            .map(|headers| headers.clone())
+            .unwrap_or_default()
+    }
 
-        let updated_data = if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.screenshot_data = Some(new_screenshot);
-            data.last_updated = SystemTime::now();
-            Arc::new(data)
-        } else {
-            Arc::new(TabData {
-                tab_id,
-                page_content: None,
-                dom_snapshot: None,
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: None,
-                accessibility_tree: None,
-                screenshot_data: Some(new_screenshot),
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            })
+    // Init scripts
+    pub fn add_init_script(&self, tab_id: u32, script: InitScript) {
+        self.init_scripts.entry(tab_id).or_default().push(script);
+    }
+
+    /// Forget a registered init script. Returns whether the tab had it.
+    pub fn remove_init_script(&self, tab_id: u32, script_id: &str) -> bool {
+        let Some(mut scripts) = self.init_scripts.get_mut(&tab_id) else {
+            return false;
         };
+        let before = scripts.len();
+        scripts.retain(|script| script.script_id != script_id);
+        let removed = scripts.len() < before;
+        let now_empty = scripts.is_empty();
+        drop(scripts);
+        if now_empty {
+            self.init_scripts.remove(&tab_id);
+        }
+        removed
+    }
 
-        self.tab_data.insert(tab_id, updated_data);
+    pub fn get_init_scripts(&self, tab_id: u32) -> Vec<InitScript> {
+        self.init_scripts
+            .get(&tab_id)
+            .map(|scripts| scripts.clone())
+            .unwrap_or_default()
+    }
 
-        let event = DataUpdateEvent {
-            tab_id,
-            update_type: DataUpdateType::ScreenshotCaptured,
-            timestamp: chrono::Utc::now(),
-        };
-        let _ = self.update_sender.send(event);
+    // User agent overrides
+    pub fn set_user_agent(&self, tab_id: u32, user_agent: UserAgentOverride) {
+        self.user_agents.insert(tab_id, user_agent);
     }
 
-    pub async fn set_debugger_attached(&self, tab_id: u32, attached: bool) {
-        if let Some(mut existing) = self.tab_data.get_mut(&tab_id) {
-            let mut data = (**existing).clone();
-            data.debugger_attached = attached;
-            data.last_updated = SystemTime::now();
-            let updated_data = Arc::new(data);
-            self.tab_data.insert(tab_id, updated_data);
+    pub fn get_user_agent(&self, tab_id: u32) -> Option<UserAgentOverride> {
+        self.user_agents.get(&tab_id).map(|user_agent| user_agent.clone())
+    }
+
+    // Emulated media; an empty override is forgotten rather than stored
+    pub fn set_emulated_media(&self, tab_id: u32, media: EmulatedMedia) {
+        if media == EmulatedMedia::default() {
+            self.emulated_media.remove(&tab_id);
+        } else {
+            self.emulated_media.insert(tab_id, media);
         }
     }
 
+    pub fn get_emulated_media(&self, tab_id: u32) -> Option<EmulatedMedia> {
+        self.emulated_media.get(&tab_id).map(|media| media.clone())
+    }
+
+    // Baseline screenshots
+    pub fn set_baseline_screenshot(&self, tab_id: u32, screenshot: ScreenshotData) {
+        self.baseline_screenshots.insert(tab_id, Arc::new(screenshot));
+    }
+
+    pub fn get_baseline_screenshot(&self, tab_id: u32) -> Option<Arc<ScreenshotData>> {
+        self.baseline_screenshots.get(&tab_id).map(|shot| shot.clone())
+    }
+
     // Connection management
     pub async fn register_connection(&self, connection_id: Uuid, tab_id: u32) {
         self.connection_tabs.insert(connection_id, tab_id);
@@ -352,7 +540,9 @@ impl BrowserDataCache {
     }
 
     // Memory management with LRU eviction
-    pub async fn cleanup_stale_data(&self) {
+    /// Evict tabs not updated within the TTL, then the oldest beyond the size limit.
+    /// Returns the evicted tab IDs.
+    pub async fn cleanup_stale_data(&self) -> Vec<u32> {
         let now = SystemTime::now();
         let stale_threshold = self.data_ttl;
 
@@ -361,7 +551,7 @@ impl BrowserDataCache {
             .iter()
             .filter_map(|entry| {
                 let (tab_id, data) = entry.pair();
-                if now.duration_since(data.last_updated).unwrap_or_default() > stale_threshold {
+                if now.duration_since(data.last_updated()).unwrap_or_default() > stale_threshold {
                     Some(*tab_id)
                 } else {
                     None
@@ -369,16 +559,17 @@ impl BrowserDataCache {
             })
             .collect();
 
-        for tab_id in stale_tabs {
+        for &tab_id in &stale_tabs {
             self.remove_tab_data(tab_id).await;
         }
+        let mut evicted = stale_tabs;
 
         // If we're still over the size limit, remove oldest entries
         if self.tab_data.len() > self.max_cache_size {
             let mut entries: Vec<_> = self
                 .tab_data
                 .iter()
-                .map(|entry| (*entry.key(), entry.value().last_updated))
+                .map(|entry| (*entry.key(), entry.value().last_updated()))
                 .collect();
 
             entries.sort_by_key(|(_, last_updated)| *last_updated);
@@ -386,13 +577,27 @@ impl BrowserDataCache {
             let to_remove = entries.len() - self.max_cache_size;
             for (tab_id, _) in entries.into_iter().take(to_remove) {
                 self.remove_tab_data(tab_id).await;
+                evicted.push(tab_id);
             }
         }
+        // Snapshots replaced since the last run leave strings only the interner holds
+        self.interner.prune_unused();
+        evicted
     }
 
     pub async fn remove_tab_data(&self, tab_id: u32) {
         self.tab_data.remove(&tab_id);
         self.tab_connections.remove(&tab_id);
+        self.response_cache.invalidate_tab(tab_id);
+        self.js_contexts.remove(&tab_id);
+        self.tab_urls.remove(&tab_id);
+        self.intercept_rules.remove(&tab_id);
+        self.extra_headers.remove(&tab_id);
+        self.init_scripts.remove(&tab_id);
+        self.user_agents.remove(&tab_id);
+        self.emulated_media.remove(&tab_id);
+        self.baseline_screenshots.remove(&tab_id);
+        self.interner.prune_unused();
 
         // Remove connection mappings for this tab
         let connections_to_remove: Vec<Uuid> = self
@@ -424,6 +629,16 @@ impl BrowserDataCache {
         (hits, misses, hit_rate)
     }
 
+    /// Returns (unique strings, cache hits) for the DOM string interner.
+    pub fn get_interner_stats(&self) -> (usize, usize) {
+        (self.interner.unique_strings(), self.interner.cache_hits())
+    }
+
+    pub async fn reset_cache_stats(&self) {
+        self.cache_hits.store(0, std::sync::atomic::Ordering::Relaxed);
+        self.cache_misses.store(0, std::sync::atomic::Ordering::Relaxed);
+    }
+
     pub async fn get_memory_usage(&self) -> usize {
         // Rough estimation of memory usage
         let tab_count = self.tab_data.len();
@@ -433,22 +648,288 @@ impl BrowserDataCache {
         (tab_count * 100 * 1024) + (connection_count * 1024)
     }
 
-    async fn ensure_tab_data_exists(&self, tab_id: u32) {
-        if !self.tab_data.contains_key(&tab_id) {
-            let tab_data = Arc::new(TabData {
-                tab_id,
-                page_content: None,
-                dom_snapshot: None,
-                console_logs: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                network_data: Some(Arc::new(RwLock::new(VecDeque::new()))),
-                performance_metrics: None,
-                accessibility_tree: None,
-                screenshot_data: None,
-                debugger_attached: false,
-                last_updated: SystemTime::now(),
-            });
+    /// Run `update` on the tab's entry, creating it empty on first use. Existing tabs
+    /// only take the map's shard read lock, so `update` must not write to `tab_data`.
+    fn with_tab<R>(&self, tab_id: u32, update: impl FnOnce(&TabData) -> R) -> R {
+        if let Some(tab) = self.tab_data.get(&tab_id) {
+            return update(&tab);
+        }
+        let tab = self
+            .tab_data
+            .entry(tab_id)
+            .or_insert_with(|| Arc::new(TabData::new(tab_id)))
+            .downgrade();
+        update(&tab)
+    }
+
+    fn broadcast(&self, tab_id: u32, update_type: DataUpdateType) {
+        self.broadcast_with_payload(tab_id, update_type, None);
+    }
+
+    fn broadcast_with_payload(&self, tab_id: u32, update_type: DataUpdateType, payload: Option<serde_json::Value>) {
+        let event = DataUpdateEvent {
+            tab_id,
+            update_type,
+            timestamp: chrono::Utc::now(),
+            payload,
+        };
+        let _ = self.update_sender.send(event);
+    }
+}
+
+/// How far apart two notifications with the same request id can be and still be
+/// merged; an id seen again after this is kept as a separate request.
+const NETWORK_REQUEST_DEDUPE_WINDOW_MS: i64 = 30_000;
+
+/// Cut a body to at most `max_bytes`, on a char boundary. Returns whether anything was dropped.
+fn truncate_body(body: &mut Option<String>, max_bytes: usize) -> bool {
+    let Some(text) = body.as_mut() else {
+        return false;
+    };
+    if text.len() <= max_bytes {
+        return false;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
+}
 
-            self.tab_data.insert(tab_id, tab_data);
+fn intern_dom_node(interner: &StringInterner, node: &mut DomNode) {
+    node.node_type = interner.intern(&node.node_type);
+    if let Some(tag_name) = node.tag_name.as_mut() {
+        *tag_name = interner.intern(tag_name);
+    }
+    node.attributes = std::mem::take(&mut node.attributes)
+        .into_iter()
+        .map(|(name, value)| (interner.intern(&name), value))
+        .collect();
+    if let Some(styles) = node.computed_styles.as_mut() {
+        *styles = std::mem::take(styles)
+            .into_iter()
+            .map(|(property, value)| (interner.intern(&property), value))
+            .collect();
+    }
+    for child in &mut node.children {
+        intern_dom_node(interner, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(tag: &str, attributes: &[(&str, &str)], children: Vec<DomNode>) -> DomNode {
+        DomNode {
+            node_type: Arc::from("element"),
+            tag_name: Some(Arc::from(tag)),
+            text_content: None,
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| (Arc::from(*name), value.to_string()))
+                .collect(),
+            computed_styles: None,
+            children,
+            xpath: None,
+            selector: None,
+            shadow_root: false,
         }
     }
-}
\ No newline at end of file
+
+    fn snapshot(root: DomNode) -> DomSnapshot {
+        DomSnapshot {
+            root,
+            node_count: 3,
+            max_depth: 2,
+            include_styles: false,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dom_snapshot_strings_are_interned() {
+        let cache = BrowserDataCache::new(100, Duration::from_secs(60));
+        let page = || {
+            node(
+                "div",
+                &[("class", "app")],
+                vec![node("a", &[("class", "link"), ("href", "/a")], vec![])],
+            )
+        };
+
+        cache.update_dom_snapshot(1, snapshot(page())).await;
+        cache.update_dom_snapshot(2, snapshot(page())).await;
+
+        let first = cache.get_dom_snapshot(1).await.unwrap();
+        let second = cache.get_dom_snapshot(2).await.unwrap();
+        let class_key = |node: &DomNode| node.attributes.keys().find(|k| &***k == "class").unwrap().clone();
+
+        assert!(Arc::ptr_eq(&class_key(&first.root), &class_key(&second.root.children[0])));
+        assert!(Arc::ptr_eq(
+            first.root.tag_name.as_ref().unwrap(),
+            second.root.tag_name.as_ref().unwrap()
+        ));
+        assert_eq!(second.root.attributes["class"], "app");
+
+        // element, div, class, a, href
+        let (unique, hits) = cache.get_interner_stats();
+        assert_eq!(unique, 5);
+        assert_eq!(hits, 9);
+    }
+
+    #[tokio::test]
+    async fn test_interned_strings_are_released_with_their_snapshots() {
+        let cache = BrowserDataCache::new(100, Duration::from_secs(60));
+        cache
+            .update_dom_snapshot(1, snapshot(node("div", &[("data-v-7ba5bd90", ""), ("class", "app")], vec![])))
+            .await;
+        cache.update_dom_snapshot(2, snapshot(node("div", &[("class", "app")], vec![]))).await;
+        // element, div, data-v-7ba5bd90, class
+        assert_eq!(cache.get_interner_stats().0, 4);
+
+        cache.remove_tab_data(1).await;
+        assert_eq!(cache.get_interner_stats().0, 3);
+
+        // A replaced snapshot's strings go on the next cleanup pass
+        cache.update_dom_snapshot(2, snapshot(node("span", &[("data-v-1f2e3d4c", "")], vec![]))).await;
+        assert_eq!(cache.get_interner_stats().0, 5);
+        cache.cleanup_stale_data().await;
+        assert_eq!(cache.get_interner_stats().0, 3);
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats_count_missing_fields_as_misses() {
+        let cache = BrowserDataCache::new(100, Duration::from_secs(60));
+        cache.update_dom_snapshot(1, snapshot(node("div", &[], vec![]))).await;
+
+        // Tab 1 is cached but only has a DOM snapshot
+        assert!(cache.get_dom_snapshot(1).await.is_some());
+        assert!(cache.get_page_content(1).await.is_none());
+        assert!(cache.get_accessibility_tree(1).await.is_none());
+        assert_eq!(cache.get_console_logs(1).await.map(|logs| logs.len()), Some(0));
+        assert!(cache.get_page_content(2).await.is_none());
+        assert_eq!(cache.get_cache_stats().await, (1, 4, 0.2));
+
+        cache
+            .update_page_content(1, PageContent {
+                url: String::new(),
+                title: "Home".to_string(),
+                html: "<div></div>".to_string(),
+                text: String::new(),
+                metadata: HashMap::new(),
+                last_updated: SystemTime::now(),
+            })
+            .await;
+        assert!(cache.get_page_content(1).await.is_some());
+        let (hits, misses, _) = cache.get_cache_stats().await;
+        assert_eq!((hits, misses), (2, 4));
+    }
+
+    #[tokio::test]
+    async fn test_network_request_bodies_are_capped() {
+        let mut cache = BrowserDataCache::new(100, Duration::from_secs(60));
+        cache.set_max_retained_body_bytes(8);
+
+        let request = |id: &str, request_body: Option<&str>, response_body: Option<&str>| NetworkRequest {
+            request_id: id.to_string(),
+            url: "https://example.com/upload".to_string(),
+            method: "POST".to_string(),
+            status_code: Some(200),
+            status_text: None,
+            request_headers: std::collections::HashMap::new(),
+            response_headers: None,
+            request_body: request_body.map(|b| b.to_string()),
+            response_body: response_body.map(|b| b.to_string()),
+            body_truncated: false,
+            request_body_truncated: false,
+            timestamp: chrono::Utc::now(),
+            duration_ms: None,
+            failed: false,
+            from_cache: false,
+            resource_type: "fetch".to_string(),
+        };
+
+        cache.add_network_request(1, request("big", Some("0123456789abcdef"), Some("ééééé"))).await;
+        cache.add_network_request(1, request("small", Some("tiny"), None)).await;
+        cache.add_network_request(1, request("download", Some("tiny"), Some("0123456789"))).await;
+
+        let big = cache.get_network_request(1, "big").await.unwrap();
+        assert_eq!(big.request_body.as_deref(), Some("01234567"));
+        // 'é' is two bytes, so the cut lands on a char boundary below the cap
+        assert_eq!(big.response_body.as_deref(), Some("éééé"));
+        assert!(big.body_truncated);
+        assert!(big.request_body_truncated);
+        assert_eq!(big.status_code, Some(200));
+
+        let small = cache.get_network_request(1, "small").await.unwrap();
+        assert_eq!(small.request_body.as_deref(), Some("tiny"));
+        assert!(!small.body_truncated);
+
+        let download = cache.get_network_request(1, "download").await.unwrap();
+        assert!(download.body_truncated);
+        assert!(!download.request_body_truncated);
+    }
+
+    #[tokio::test]
+    async fn test_network_request_notifications_are_merged_by_id() {
+        let cache = BrowserDataCache::new(100, Duration::from_secs(60));
+        let mut updates = cache.subscribe_to_updates();
+        let started = NetworkRequest {
+            request_id: "42.1".to_string(),
+            url: "https://example.com/api/items".to_string(),
+            method: "GET".to_string(),
+            status_code: None,
+            status_text: None,
+            request_headers: HashMap::from([("Accept".to_string(), "application/json".to_string())]),
+            response_headers: None,
+            request_body: None,
+            response_body: None,
+            body_truncated: false,
+            request_body_truncated: false,
+            timestamp: chrono::Utc::now(),
+            duration_ms: None,
+            failed: false,
+            from_cache: false,
+            resource_type: "fetch".to_string(),
+        };
+        let completed = NetworkRequest {
+            status_code: Some(200),
+            status_text: Some("OK".to_string()),
+            request_headers: HashMap::new(),
+            response_headers: Some(HashMap::from([("Content-Type".to_string(), "application/json".to_string())])),
+            response_body: Some("[]".to_string()),
+            timestamp: started.timestamp + chrono::Duration::milliseconds(80),
+            duration_ms: Some(80.0),
+            ..started.clone()
+        };
+
+        cache.add_network_request(1, started.clone()).await;
+        cache.add_network_request(1, completed.clone()).await;
+        // A duplicate start event arriving late must not wipe out the response
+        cache.add_network_request(1, started.clone()).await;
+
+        let requests = cache.get_network_requests(1).await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.status_code, Some(200));
+        assert_eq!(request.response_body.as_deref(), Some("[]"));
+        assert_eq!(request.duration_ms, Some(80.0));
+        assert_eq!(request.request_headers["Accept"], "application/json");
+        assert_eq!(request.timestamp, started.timestamp);
+
+        let update_types: Vec<_> = std::iter::from_fn(|| updates.try_recv().ok())
+            .map(|update| format!("{:?}", update.update_type))
+            .collect();
+        assert_eq!(update_types, ["NetworkRequestAdded", "NetworkRequestUpdated", "NetworkRequestUpdated"]);
+
+        // The same id long after the window is a new request
+        let later = NetworkRequest {
+            timestamp: started.timestamp + chrono::Duration::milliseconds(NETWORK_REQUEST_DEDUPE_WINDOW_MS + 1),
+            ..completed
+        };
+        cache.add_network_request(1, later).await;
+        assert_eq!(cache.get_network_requests(1).await.unwrap().len(), 2);
+    }
+}
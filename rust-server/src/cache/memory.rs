@@ -181,6 +181,14 @@ impl StringInterner {
         self.strings.len()
     }
 
+    /// Drop strings nothing outside the interner still holds, so per-page names such as
+    /// `data-v-<hash>` don't accumulate. Returns how many were dropped.
+    pub fn prune_unused(&self) -> usize {
+        let before = self.strings.len();
+        self.strings.retain(|_, interned| Arc::strong_count(interned) > 1);
+        before - self.strings.len()
+    }
+
     pub fn clear(&self) {
         self.strings.clear();
         self.stats.store(0, Ordering::Relaxed);
@@ -242,4 +250,16 @@ mod tests {
         assert_eq!(interner.cache_hits(), 1);
         assert_eq!(interner.unique_strings(), 1);
     }
+
+    #[test]
+    fn test_string_interner_prunes_unused_strings() {
+        let interner = StringInterner::new();
+
+        let kept = interner.intern("class");
+        drop(interner.intern("data-v-7ba5bd90"));
+
+        assert_eq!(interner.prune_unused(), 1);
+        assert_eq!(interner.unique_strings(), 1);
+        assert!(Arc::ptr_eq(&kept, &interner.intern("class")));
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 pub mod browser_data;
 pub mod memory;
+pub mod response;
 
 pub use browser_data::*;
-pub use memory::*;
\ No newline at end of file
+pub use memory::*;
+pub use response::*;
\ No newline at end of file
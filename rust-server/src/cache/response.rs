@@ -0,0 +1,213 @@
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Identifies a tool call by tab, tool name and its arguments with keys sorted,
+/// so `{"a":1,"b":2}` and `{"b":2,"a":1}` share an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResponseCacheKey {
+    pub tab_id: u32,
+    pub tool: String,
+    pub args: String,
+}
+
+impl ResponseCacheKey {
+    pub fn new(tab_id: u32, tool: &str, args: &serde_json::Value) -> Self {
+        let mut normalized = String::new();
+        write_normalized(args, &mut normalized);
+        Self {
+            tab_id,
+            tool: tool.to_string(),
+            args: normalized,
+        }
+    }
+}
+
+// tabId is already part of the key and null means "use the default"
+fn write_normalized(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map
+                .iter()
+                .filter(|(key, value)| key.as_str() != "tabId" && !value.is_null())
+                .map(|(key, _)| key)
+                .collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_normalized(&map[key], out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_normalized(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+struct CachedResponse {
+    value: serde_json::Value,
+    stored_at: Instant,
+}
+
+/// Short-lived LRU cache of read-only tool results. Entries live for `ttl` and are
+/// dropped early when the tab navigates or updates.
+pub struct ResponseCache {
+    entries: Mutex<IndexMap<ResponseCacheKey, CachedResponse>>,
+    max_entries: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// A `max_entries` of 0 disables caching.
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(IndexMap::new()),
+            max_entries,
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_entries > 0 && !self.ttl.is_zero()
+    }
+
+    pub fn get(&self, key: &ResponseCacheKey) -> Option<serde_json::Value> {
+        self.get_with_age(key).map(|(value, _)| value)
+    }
+
+    /// Like `get`, but also returns how long ago the entry was stored.
+    pub fn get_with_age(&self, key: &ResponseCacheKey) -> Option<(serde_json::Value, Duration)> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let mut entries = self.entries.lock();
+        let fresh = entries
+            .get(key)
+            .map(|entry| entry.stored_at.elapsed() < self.ttl);
+
+        match fresh {
+            Some(true) => {
+                // Move to the back so it is evicted last
+                let entry = entries.shift_remove(key)?;
+                let value = entry.value.clone();
+                let age = entry.stored_at.elapsed();
+                entries.insert(key.clone(), entry);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some((value, age))
+            }
+            Some(false) => {
+                entries.shift_remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: ResponseCacheKey, value: serde_json::Value) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.lock();
+        entries.shift_remove(&key);
+        entries.insert(key, CachedResponse { value, stored_at: Instant::now() });
+        while entries.len() > self.max_entries {
+            entries.shift_remove_index(0);
+        }
+    }
+
+    /// Drop every cached result for a tab. Returns how many entries were removed.
+    pub fn invalidate_tab(&self, tab_id: u32) -> usize {
+        let mut entries = self.entries.lock();
+        let before = entries.len();
+        entries.retain(|key, _| key.tab_id != tab_id);
+        before - entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hit_ignores_argument_order_and_tab_id() {
+        let cache = ResponseCache::new(8, Duration::from_secs(5));
+        let stored = ResponseCacheKey::new(1, "get_dom_snapshot", &json!({ "tabId": 1, "maxDepth": 3, "selector": "main" }));
+        cache.insert(stored, json!({ "nodes": 12 }));
+
+        let lookup = ResponseCacheKey::new(1, "get_dom_snapshot", &json!({ "selector": "main", "maxDepth": 3 }));
+        assert_eq!(cache.get(&lookup), Some(json!({ "nodes": 12 })));
+        assert_eq!(cache.stats(), (1, 0));
+    }
+
+    #[test]
+    fn test_miss_on_different_args_tab_or_expiry() {
+        let cache = ResponseCache::new(8, Duration::from_millis(30));
+        cache.insert(ResponseCacheKey::new(1, "get_dom_snapshot", &json!({ "maxDepth": 3 })), json!(1));
+
+        assert!(cache.get(&ResponseCacheKey::new(1, "get_dom_snapshot", &json!({ "maxDepth": 4 }))).is_none());
+        assert!(cache.get(&ResponseCacheKey::new(2, "get_dom_snapshot", &json!({ "maxDepth": 3 }))).is_none());
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(cache.get(&ResponseCacheKey::new(1, "get_dom_snapshot", &json!({ "maxDepth": 3 }))).is_none());
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats(), (0, 3));
+    }
+
+    #[test]
+    fn test_invalidate_tab_and_lru_eviction() {
+        let cache = ResponseCache::new(2, Duration::from_secs(5));
+        let a = ResponseCacheKey::new(1, "get_page_content", &json!({}));
+        let b = ResponseCacheKey::new(2, "get_page_content", &json!({}));
+        let c = ResponseCacheKey::new(3, "get_page_content", &json!({}));
+
+        cache.insert(a.clone(), json!("a"));
+        cache.insert(b.clone(), json!("b"));
+        assert!(cache.get(&a).is_some()); // a is now most recently used
+        cache.insert(c.clone(), json!("c"));
+        assert!(cache.get(&b).is_none(), "least recently used entry should be evicted");
+
+        assert_eq!(cache.invalidate_tab(1), 1);
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&c).is_some());
+    }
+}
@@ -1,6 +1,9 @@
 use crate::types::errors::BrowserMcpError;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -8,6 +11,12 @@ pub struct ServerConfig {
     pub cache: CacheSettings,
     pub connections: ConnectionSettings,
     pub monitoring: MonitoringSettings,
+    /// Per-tool request timeouts in seconds, keyed by tool name. Tools not listed
+    /// fall back to `server.request_timeout_secs`.
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
+    #[serde(default)]
+    pub security: SecuritySettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +27,33 @@ pub struct ServerSettings {
     pub max_connections: usize,
     pub request_timeout_secs: u64,
     pub cors_origins: Vec<String>,
+    /// Tool calls and resource reads allowed in flight at once; more are rejected as busy.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Largest file `download_resource` will fetch and return.
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Percentage of differing pixels above which `compare_screenshot` reports a mismatch.
+    #[serde(default = "default_screenshot_diff_threshold")]
+    pub screenshot_diff_threshold: f64,
+    /// Screenshots wider or taller than these are downscaled before being returned; 0 for no limit.
+    #[serde(default = "default_max_screenshot_dimension")]
+    pub max_screenshot_width: u32,
+    #[serde(default = "default_max_screenshot_dimension")]
+    pub max_screenshot_height: u32,
+    /// Validate tool calls and answer them with a placeholder result instead of sending
+    /// anything to the browser. A call's `_dryRun` argument overrides this.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Serve the MCP endpoint on this Unix domain socket instead of on `port`, so only
+    /// users the socket's file permissions admit can reach it. Extensions still connect
+    /// to the WebSocket on `port`.
+    #[serde(default)]
+    pub unix_socket_path: Option<PathBuf>,
+    /// MCP sessions unused for this long are ended by the connection cleanup task, as
+    /// if the client had sent `DELETE` (0 keeps them until then).
+    #[serde(default = "default_session_idle_timeout_secs")]
+    pub session_idle_timeout_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +62,17 @@ pub struct CacheSettings {
     pub cleanup_interval_secs: u64,
     pub data_ttl_secs: u64,
     pub enable_persistent_cache: bool,
+    /// Entries kept in the read-only tool response cache (0 disables it).
+    #[serde(default = "default_response_cache_entries")]
+    pub response_cache_entries: usize,
+    #[serde(default = "default_response_cache_ttl_secs")]
+    pub response_cache_ttl_secs: u64,
+    /// Longest request or response body kept per cached network request (0 keeps none).
+    #[serde(default = "default_max_retained_body_bytes")]
+    pub max_retained_body_bytes: usize,
+    /// Tool calls remembered per tab for `get_tab_activity` (0 disables recording).
+    #[serde(default)]
+    pub activity_log_entries: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,8 +80,71 @@ pub struct ConnectionSettings {
     pub websocket_timeout_secs: u64,
     pub health_check_interval_secs: u64,
     pub max_connections_per_tab: usize,
+    /// Seconds between WebSocket pings; a connection that misses two pongs is closed.
     pub heartbeat_interval_secs: u64,
     pub connection_retry_attempts: usize,
+    /// How often connections idle past `websocket_timeout_secs` are reaped. Runs
+    /// independently of `cache.cleanup_interval_secs`.
+    #[serde(default = "default_connection_cleanup_interval_secs")]
+    pub cleanup_interval_secs: u64,
+    /// Largest WebSocket message (and frame) accepted from a browser extension.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// Dispatch requests to the same tab one at a time, in submission order.
+    #[serde(default)]
+    pub serialize_per_tab: bool,
+    /// Consecutive timeouts within the window that trip a tab's circuit breaker (0 disables it).
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub breaker_failure_threshold: usize,
+    #[serde(default = "default_breaker_window_secs")]
+    pub breaker_window_secs: u64,
+    /// How long a tripped breaker fast-fails before letting a probe request through.
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub breaker_cooldown_secs: u64,
+    /// Messages queued for a browser connection before the overflow policy applies.
+    #[serde(default = "default_outbound_queue_size")]
+    pub outbound_queue_size: usize,
+    #[serde(default)]
+    pub outbound_overflow_policy: OutboundOverflowPolicy,
+    /// Encoding for browser messages once an extension connects. `msgpack` is only
+    /// used with extensions that offer it, others stay on JSON.
+    #[serde(default)]
+    pub wire_format: WireFormat,
+    /// Attach the debugger on demand for tools that need it, instead of failing until
+    /// `attach_debugger` has been called.
+    #[serde(default = "default_auto_attach_debugger")]
+    pub auto_attach_debugger: bool,
+    /// Times a request that timed out or lost its connection is sent again (0 disables
+    /// retries). Off by default because a retry repeats side effects such as clicks.
+    #[serde(default)]
+    pub request_retry_attempts: usize,
+    /// Upper bound on the jittered exponential delay between retries.
+    #[serde(default = "default_max_retry_backoff_ms")]
+    pub max_retry_backoff_ms: u64,
+    /// Requests a single tab may receive per second before further ones fail with
+    /// "Rate limit exceeded" (0 disables). Other tabs have their own budgets.
+    #[serde(default)]
+    pub max_requests_per_tab_per_sec: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+/// What to do when a browser falls behind and its outbound queue fills up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboundOverflowPolicy {
+    /// Discard the oldest best-effort message (pongs, broadcasts). Requests are never
+    /// discarded; they fail if the queue holds nothing else.
+    #[default]
+    DropOldest,
+    /// Close the connection so the extension reconnects.
+    Close,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +154,56 @@ pub struct MonitoringSettings {
     pub log_level: String,
     pub enable_request_logging: bool,
     pub enable_performance_monitoring: bool,
+    /// When set, /health/ready also requires this tab to have a connected extension.
+    #[serde(default)]
+    pub readiness_tab_id: Option<u32>,
+    /// Share of failed requests above which /health reports "degraded".
+    #[serde(default = "default_degraded_error_rate")]
+    pub degraded_error_rate: f64,
+    /// Share of `cache.max_size_mb` in use above which /health reports "degraded".
+    #[serde(default = "default_degraded_memory_ratio")]
+    pub degraded_memory_ratio: f64,
+    /// Add a `_meta` object to tool results saying whether they were served from cache.
+    #[serde(default)]
+    pub include_result_metadata: bool,
+    /// Pretty-print JSON in tool results. Off by default since compact output is smaller.
+    #[serde(default)]
+    pub pretty_json: bool,
+    /// Requests per second a tool can make before only a sample of its durations is kept.
+    #[serde(default = "default_duration_sampling_threshold")]
+    pub duration_sampling_threshold: u64,
+    /// Above the threshold, keep one in this many request durations.
+    #[serde(default = "default_duration_sample_rate")]
+    pub duration_sample_rate: u64,
+}
+
+/// Regexes matched against a tab's last known URL before a tool runs against it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecuritySettings {
+    /// Tabs whose URL matches any of these are off limits.
+    #[serde(default)]
+    pub blocked_url_patterns: Vec<String>,
+    /// When non-empty, only tabs with a known URL matching one of these may be used.
+    #[serde(default)]
+    pub allowed_url_patterns: Vec<String>,
+    /// Bearer token required by the admin endpoints `GET /config` and the `metrics/reset` method. Unset leaves them open.
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    /// Allow tools that run arbitrary code in the page or act with its credentials:
+    /// `execute_javascript`, `set_page_content`, `add_init_script` and `replay_request`.
+    #[serde(default = "default_allow_javascript_execution")]
+    pub allow_javascript_execution: bool,
+}
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        Self {
+            blocked_url_patterns: Vec::new(),
+            allowed_url_patterns: Vec::new(),
+            auth_token: None,
+            allow_javascript_execution: default_allow_javascript_execution(),
+        }
+    }
 }
 
 impl Default for ServerConfig {
@@ -56,12 +216,24 @@ impl Default for ServerConfig {
                 max_connections: 1000,
                 request_timeout_secs: 30,
                 cors_origins: vec!["*".to_string()],
+                max_concurrent_requests: default_max_concurrent_requests(),
+                max_body_bytes: default_max_body_bytes(),
+                screenshot_diff_threshold: default_screenshot_diff_threshold(),
+                max_screenshot_width: default_max_screenshot_dimension(),
+                max_screenshot_height: default_max_screenshot_dimension(),
+                dry_run: false,
+                unix_socket_path: None,
+                session_idle_timeout_secs: default_session_idle_timeout_secs(),
             },
             cache: CacheSettings {
                 max_size_mb: 512,
                 cleanup_interval_secs: 300,
                 data_ttl_secs: 3600,
                 enable_persistent_cache: false,
+                response_cache_entries: default_response_cache_entries(),
+                response_cache_ttl_secs: default_response_cache_ttl_secs(),
+                max_retained_body_bytes: default_max_retained_body_bytes(),
+                activity_log_entries: 0,
             },
             connections: ConnectionSettings {
                 websocket_timeout_secs: 300,
@@ -69,6 +241,19 @@ impl Default for ServerConfig {
                 max_connections_per_tab: 10,
                 heartbeat_interval_secs: 30,
                 connection_retry_attempts: 3,
+                cleanup_interval_secs: default_connection_cleanup_interval_secs(),
+                max_message_bytes: default_max_message_bytes(),
+                serialize_per_tab: false,
+                breaker_failure_threshold: default_breaker_failure_threshold(),
+                breaker_window_secs: default_breaker_window_secs(),
+                breaker_cooldown_secs: default_breaker_cooldown_secs(),
+                outbound_queue_size: default_outbound_queue_size(),
+                outbound_overflow_policy: OutboundOverflowPolicy::default(),
+                wire_format: WireFormat::default(),
+                auto_attach_debugger: default_auto_attach_debugger(),
+                request_retry_attempts: 0,
+                max_retry_backoff_ms: default_max_retry_backoff_ms(),
+                max_requests_per_tab_per_sec: 0,
             },
             monitoring: MonitoringSettings {
                 enable_metrics: true,
@@ -76,11 +261,104 @@ impl Default for ServerConfig {
                 log_level: "info".to_string(),
                 enable_request_logging: true,
                 enable_performance_monitoring: true,
+                readiness_tab_id: None,
+                degraded_error_rate: default_degraded_error_rate(),
+                degraded_memory_ratio: default_degraded_memory_ratio(),
+                include_result_metadata: false,
+                pretty_json: false,
+                duration_sampling_threshold: default_duration_sampling_threshold(),
+                duration_sample_rate: default_duration_sample_rate(),
             },
+            timeouts: HashMap::new(),
+            security: SecuritySettings::default(),
         }
     }
 }
 
+fn default_max_concurrent_requests() -> usize {
+    64
+}
+
+fn default_max_body_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_screenshot_diff_threshold() -> f64 {
+    0.1
+}
+
+fn default_max_screenshot_dimension() -> u32 {
+    4096
+}
+
+fn default_session_idle_timeout_secs() -> u64 {
+    3600
+}
+
+fn default_allow_javascript_execution() -> bool {
+    true
+}
+
+fn default_connection_cleanup_interval_secs() -> u64 {
+    30
+}
+
+fn default_response_cache_entries() -> usize {
+    256
+}
+
+fn default_response_cache_ttl_secs() -> u64 {
+    5
+}
+
+fn default_max_retained_body_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_message_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_breaker_failure_threshold() -> usize {
+    5
+}
+
+fn default_breaker_window_secs() -> u64 {
+    60
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    30
+}
+
+fn default_outbound_queue_size() -> usize {
+    1024
+}
+
+fn default_auto_attach_debugger() -> bool {
+    true
+}
+
+fn default_max_retry_backoff_ms() -> u64 {
+    2000
+}
+
+fn default_degraded_error_rate() -> f64 {
+    0.1
+}
+
+fn default_degraded_memory_ratio() -> f64 {
+    0.9
+}
+
+fn default_duration_sampling_threshold() -> u64 {
+    50
+}
+
+fn default_duration_sample_rate() -> u64 {
+    10
+}
+
 impl ServerConfig {
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> crate::types::errors::Result<Self> {
         let settings = config::Config::builder()
@@ -127,9 +405,22 @@ impl ServerConfig {
             })?;
         }
 
+        if let Ok(token) = std::env::var("MCP_AUTH_TOKEN") {
+            config.security.auth_token = Some(token);
+        }
+
         Ok(config)
     }
 
+    /// Copy of the config that is safe to show: secrets are replaced with a placeholder.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if config.security.auth_token.is_some() {
+            config.security.auth_token = Some("[redacted]".to_string());
+        }
+        config
+    }
+
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> crate::types::errors::Result<()> {
         let toml_content = toml::to_string_pretty(self).map_err(|e| BrowserMcpError::ConfigError {
             message: format!("Failed to serialize config: {}", e),
@@ -151,6 +442,17 @@ impl ServerConfig {
 
         // MCP and WebSocket servers now run on the same port
         // MCP server handles Claude Code connections via HTTP, WebSocket server handles browser extensions via HTTP upgrade
+        // unless `unix_socket_path` moves MCP onto a socket; it is served on exactly one of the two
+        if self.server.unix_socket_path.as_ref().is_some_and(|path| path.as_os_str().is_empty()) {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Unix socket path cannot be empty; omit it to serve MCP on the TCP port".to_string(),
+            });
+        }
+        if cfg!(not(unix)) && self.server.unix_socket_path.is_some() {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Unix socket path is only supported on Unix platforms".to_string(),
+            });
+        }
 
         if self.cache.max_size_mb == 0 {
             return Err(BrowserMcpError::ConfigError {
@@ -158,15 +460,142 @@ impl ServerConfig {
             });
         }
 
+        if self.cache.cleanup_interval_secs == 0 || self.connections.cleanup_interval_secs == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Cleanup intervals must be greater than 0".to_string(),
+            });
+        }
+
+        if self.server.worker_threads == Some(0) {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Worker threads must be greater than 0".to_string(),
+            });
+        }
+
+        if self.server.max_concurrent_requests == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Max concurrent requests must be greater than 0".to_string(),
+            });
+        }
+
+        if self.server.max_body_bytes == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Max body bytes must be greater than 0".to_string(),
+            });
+        }
+
+        if !(0.0..=100.0).contains(&self.server.screenshot_diff_threshold) {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Screenshot diff threshold must be a percentage between 0 and 100".to_string(),
+            });
+        }
+
         if self.connections.max_connections_per_tab == 0 {
             return Err(BrowserMcpError::ConfigError {
                 message: "Max connections per tab must be greater than 0".to_string(),
             });
         }
 
+        if self.connections.max_message_bytes == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Max WebSocket message size must be greater than 0".to_string(),
+            });
+        }
+
+        if self.connections.outbound_queue_size == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Outbound queue size must be greater than 0".to_string(),
+            });
+        }
+
+        if self.connections.request_retry_attempts > 0 && self.connections.max_retry_backoff_ms == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Max retry backoff must be greater than 0 when retries are enabled".to_string(),
+            });
+        }
+
+        if !(0.0..=1.0).contains(&self.monitoring.degraded_error_rate) {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Degraded error rate must be between 0 and 1".to_string(),
+            });
+        }
+
+        if !(self.monitoring.degraded_memory_ratio > 0.0 && self.monitoring.degraded_memory_ratio <= 1.0) {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Degraded memory ratio must be greater than 0 and at most 1".to_string(),
+            });
+        }
+
+        if self.monitoring.duration_sample_rate == 0 {
+            return Err(BrowserMcpError::ConfigError {
+                message: "Duration sample rate must be greater than 0".to_string(),
+            });
+        }
+
+        if let Some((tool, _)) = self.timeouts.iter().find(|(_, secs)| **secs == 0) {
+            return Err(BrowserMcpError::ConfigError {
+                message: format!("Timeout for tool '{}' must be greater than 0", tool),
+            });
+        }
+
+        crate::utils::UrlAccessPolicy::from_settings(&self.security)?;
+
         Ok(())
     }
 
+    /// Check what startup will need from the environment: that the server and metrics
+    /// ports can be bound and that the Unix socket's directory is writable. Unlike
+    /// `validate`, every problem is collected into one error instead of stopping at the first.
+    /// There are no TLS or persistent-cache path settings to check, and the config file
+    /// has already been read by the time this runs.
+    pub fn preflight(&self) -> crate::types::errors::Result<()> {
+        let mut problems = Vec::new();
+
+        let metrics_port = self.monitoring.prometheus_port.filter(|_| self.monitoring.enable_metrics);
+        if metrics_port == Some(self.server.port) {
+            problems.push(format!(
+                "Metrics port {} is the same as the server port; pick another monitoring.prometheus_port",
+                self.server.port
+            ));
+        }
+        let ports = [("Server", Some(self.server.port)), ("Metrics", metrics_port)];
+        for (name, port) in ports.into_iter().filter_map(|(name, port)| Some((name, port?))) {
+            if let Err(e) = std::net::TcpListener::bind((self.server.host.as_str(), port)) {
+                problems.push(format!("{} port {}:{} can't be bound: {}", name, self.server.host, port, e));
+            }
+        }
+
+        if let Some(path) = &self.server.unix_socket_path {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            // Creating the socket needs write access to its directory
+            let probe = dir.join(format!(".browser-mcp-preflight-{}", std::process::id()));
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+                Ok(_) => {
+                    let _ = std::fs::remove_file(&probe);
+                }
+                Err(e) => problems.push(format!(
+                    "Unix socket directory {} is not writable: {}",
+                    dir.display(),
+                    e
+                )),
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(BrowserMcpError::ConfigError {
+            message: format!(
+                "startup preflight found {} problem(s):\n  - {}",
+                problems.len(),
+                problems.join("\n  - ")
+            ),
+        })
+    }
+
     pub fn get_mcp_address(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
     }
@@ -180,4 +609,36 @@ impl ServerConfig {
             .prometheus_port
             .map(|port| format!("{}:{}", self.server.host, port))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preflight_reports_every_startup_problem_at_once() {
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = occupied.local_addr().unwrap().port();
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut config = ServerConfig::default();
+        config.server.host = "127.0.0.1".to_string();
+        config.server.port = port;
+        config.monitoring.enable_metrics = true;
+        config.monitoring.prometheus_port = Some(port);
+        config.server.unix_socket_path = Some(dir.path().join("missing").join("mcp.sock"));
+        config.validate().unwrap();
+
+        let message = config.preflight().unwrap_err().to_string();
+        assert!(message.contains("found 4 problem(s)"), "{}", message);
+        assert!(message.contains(&format!("Server port 127.0.0.1:{} can't be bound", port)), "{}", message);
+        assert!(message.contains(&format!("Metrics port {} is the same as the server port", port)), "{}", message);
+        assert!(message.contains("missing is not writable"), "{}", message);
+
+        drop(occupied);
+        config.monitoring.prometheus_port = None;
+        config.server.unix_socket_path = Some(dir.path().join("mcp.sock"));
+        config.preflight().unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,3 +1,6 @@
+// The tools/list schema is one large json! literal
+#![recursion_limit = "256"]
+
 pub mod cache;
 pub mod config;
 pub mod server;
@@ -1,4 +1,4 @@
-use browser_mcp_rust_server::{SimpleBrowserMcpServer, ServerConfig, start_combined_server};
+use browser_mcp_rust_server::{utils::LogFilter, SimpleBrowserMcpServer, ServerConfig, start_combined_server};
 use clap::Parser;
 use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -23,6 +23,10 @@ struct Cli {
     #[arg(long, default_value = "127.0.0.1")]
     host: String,
 
+    /// Serve MCP on this Unix domain socket instead of the port (extensions still use the port)
+    #[arg(long)]
+    unix_socket: Option<std::path::PathBuf>,
+
     /// Enable metrics server
     #[arg(long)]
     enable_metrics: bool,
@@ -30,18 +34,22 @@ struct Cli {
     /// Metrics server port
     #[arg(long, default_value = "9090")]
     metrics_port: u16,
+
+    /// Number of Tokio worker threads (overrides server.worker_threads)
+    #[arg(long)]
+    worker_threads: Option<usize>,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    // Initialize tracing
+    // Initialize tracing; the filter stays reloadable so clients can use logging/setLevel
+    let (filter_layer, log_filter) = LogFilter::new(
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| format!("browser_mcp_rust_server={}", cli.log_level).into()),
+    );
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("browser_mcp_rust_server={}", cli.log_level).into()),
-        )
+        .with(filter_layer)
         .with(tracing_subscriber::fmt::layer())
         .init();
 
@@ -60,23 +68,52 @@ async fn main() -> anyhow::Result<()> {
     if !cli.host.is_empty() {
         config.server.host = cli.host;
     }
+    if let Some(path) = cli.unix_socket {
+        config.server.unix_socket_path = Some(path);
+    }
     if cli.enable_metrics {
         config.monitoring.enable_metrics = true;
         config.monitoring.prometheus_port = Some(cli.metrics_port);
     }
+    if let Some(worker_threads) = cli.worker_threads {
+        config.server.worker_threads = Some(worker_threads);
+    }
 
-    // Validate configuration
+    // Validate configuration, then check that the ports and paths it names are usable
     config.validate()?;
+    config.preflight()?;
 
+    build_runtime(config.server.worker_threads)?.block_on(run(config, log_filter))
+}
+
+/// Build the multi-threaded runtime, with `worker_threads` workers when set and
+/// Tokio's default (one per core) otherwise.
+fn build_runtime(worker_threads: Option<usize>) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    builder.enable_all().build()
+}
+
+async fn run(config: ServerConfig, log_filter: LogFilter) -> anyhow::Result<()> {
     tracing::info!("Starting browser MCP server with configuration:");
     tracing::info!("  Combined Server: http://{}:{}", config.server.host, config.server.port);
-    tracing::info!("  MCP endpoint: http://{}:{}/mcp", config.server.host, config.server.port);
+    match &config.server.unix_socket_path {
+        Some(path) => tracing::info!("  MCP endpoint: unix:{}", path.display()),
+        None => tracing::info!("  MCP endpoint: http://{}:{}/mcp", config.server.host, config.server.port),
+    }
     tracing::info!("  WebSocket endpoint: ws://{}:{}/ws", config.server.host, config.server.port);
     tracing::info!("  Cache size: {} MB", config.cache.max_size_mb);
     tracing::info!("  Max connections: {}", config.server.max_connections);
+    if let Some(worker_threads) = config.server.worker_threads {
+        tracing::info!("  Worker threads: {}", worker_threads);
+    }
 
     // Create MCP server handler
-    let mcp_handler = Arc::new(SimpleBrowserMcpServer::new(config.clone()).await?);
+    let mut mcp_handler = SimpleBrowserMcpServer::new(config.clone()).await?;
+    mcp_handler.set_log_filter(log_filter);
+    let mcp_handler = Arc::new(mcp_handler);
 
     // Start combined HTTP/WebSocket server on single port
     let combined_server_handle = tokio::spawn({
@@ -94,13 +131,39 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
-    // Start background cleanup task
-    let cleanup_handle = tokio::spawn({
+    // Start background cleanup tasks
+    let (cache_cleanup_handle, connection_cleanup_handle) = spawn_cleanup_tasks(
+        &config,
+        {
+            let mcp_handler = mcp_handler.clone();
+            move || {
+                let mcp_handler = mcp_handler.clone();
+                async move { mcp_handler.cleanup_stale_data().await }
+            }
+        },
+        {
+            let mcp_handler = mcp_handler.clone();
+            move || {
+                let mcp_handler = mcp_handler.clone();
+                async move {
+                    mcp_handler.connection_pool.cleanup_stale_connections().await;
+                    mcp_handler.expire_idle_sessions();
+                }
+            }
+        },
+    );
+
+    // Ping browser connections so half-open sockets are closed after two missed pongs
+    let heartbeat_handle = (config.connections.heartbeat_interval_secs > 0).then(|| {
         let mcp_handler = mcp_handler.clone();
-        let cleanup_interval = std::time::Duration::from_secs(config.cache.cleanup_interval_secs);
-        async move {
-            background_cleanup_task(mcp_handler, cleanup_interval).await;
-        }
+        tokio::spawn(background_cleanup_task(
+            "heartbeat",
+            std::time::Duration::from_secs(config.connections.heartbeat_interval_secs),
+            move || {
+                let mcp_handler = mcp_handler.clone();
+                async move { mcp_handler.connection_pool.send_heartbeats().await }
+            },
+        ))
     });
 
     // Start metrics server if enabled
@@ -122,7 +185,10 @@ async fn main() -> anyhow::Result<()> {
     };
 
     tracing::info!("🚀 Browser MCP Rust server starting");
-    tracing::info!("📊 MCP endpoint: http://{}:{}/mcp", config.server.host, config.server.port);
+    match &config.server.unix_socket_path {
+        Some(path) => tracing::info!("📊 MCP endpoint: unix:{} (POST /mcp)", path.display()),
+        None => tracing::info!("📊 MCP endpoint: http://{}:{}/mcp", config.server.host, config.server.port),
+    }
     tracing::info!("🔌 WebSocket endpoint: ws://{}:{}/ws", config.server.host, config.server.port);
     tracing::info!("❤️  Health check: http://{}:{}/health", config.server.host, config.server.port);
 
@@ -147,9 +213,25 @@ async fn main() -> anyhow::Result<()> {
                 tracing::error!("Combined server task error: {:?}", e);
             }
         }
-        result = cleanup_handle => {
+        result = cache_cleanup_handle => {
+            if let Err(e) = result {
+                tracing::error!("Cache cleanup task error: {:?}", e);
+            }
+        }
+        result = connection_cleanup_handle => {
             if let Err(e) = result {
-                tracing::error!("Cleanup task error: {:?}", e);
+                tracing::error!("Connection cleanup task error: {:?}", e);
+            }
+        }
+        result = async {
+            if let Some(handle) = heartbeat_handle {
+                handle.await
+            } else {
+                std::future::pending().await
+            }
+        } => {
+            if let Err(e) = result {
+                tracing::error!("Heartbeat task error: {:?}", e);
             }
         }
         result = async {
@@ -176,22 +258,42 @@ async fn main() -> anyhow::Result<()> {
 // and handles both MCP JSON-RPC and WebSocket upgrades on the same port
 
 
-async fn background_cleanup_task(
-    server: Arc<SimpleBrowserMcpServer>,
+/// Run cache eviction and stale connection reaping on their own timers
+/// (`cache.cleanup_interval_secs` and `connections.cleanup_interval_secs`), so dead
+/// sockets can be reaped promptly without evicting cached data as often.
+fn spawn_cleanup_tasks<C, CacheFut, K, ConnectionFut>(
+    config: &ServerConfig,
+    cache_cleanup: C,
+    connection_cleanup: K,
+) -> (tokio::task::JoinHandle<()>, tokio::task::JoinHandle<()>)
+where
+    C: FnMut() -> CacheFut + Send + 'static,
+    CacheFut: std::future::Future<Output = ()> + Send + 'static,
+    K: FnMut() -> ConnectionFut + Send + 'static,
+    ConnectionFut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let cache_interval = std::time::Duration::from_secs(config.cache.cleanup_interval_secs);
+    let connection_interval = std::time::Duration::from_secs(config.connections.cleanup_interval_secs);
+    (
+        tokio::spawn(background_cleanup_task("cache", cache_interval, cache_cleanup)),
+        tokio::spawn(background_cleanup_task("connection", connection_interval, connection_cleanup)),
+    )
+}
+
+async fn background_cleanup_task<F, Fut>(
+    name: &'static str,
     cleanup_interval: std::time::Duration,
-) {
+    mut cleanup: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
     let mut interval = tokio::time::interval(cleanup_interval);
 
     loop {
         interval.tick().await;
-
-        // Cleanup stale data
-        server.data_cache.cleanup_stale_data().await;
-
-        // Cleanup stale connections
-        server.connection_pool.cleanup_stale_connections().await;
-
-        tracing::debug!("Background cleanup completed");
+        cleanup().await;
+        tracing::debug!("Background {} cleanup completed", name);
     }
 }
 
@@ -279,5 +381,51 @@ enable_performance_monitoring = true
         let cli = Cli::try_parse_from(args).unwrap();
         assert_eq!(cli.port, Some(8080));
         assert_eq!(cli.log_level, "debug");
+        assert_eq!(cli.worker_threads, None);
+
+        let cli = Cli::try_parse_from(["browser-mcp-rust", "--worker-threads", "2"]).unwrap();
+        assert_eq!(cli.worker_threads, Some(2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_runtime_honors_worker_threads() {
+        let runtime = build_runtime(Some(3)).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 3);
+
+        let runtime = build_runtime(None).unwrap();
+        assert!(runtime.metrics().num_workers() >= 1);
+    }
+
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cleanup_tasks_run_on_their_own_intervals() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut config = ServerConfig::default();
+        config.cache.cleanup_interval_secs = 300;
+        config.connections.cleanup_interval_secs = 10;
+
+        let counter = |runs: &Arc<AtomicUsize>| {
+            let runs = runs.clone();
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                std::future::ready(())
+            }
+        };
+        let (cache_runs, connection_runs) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (cache_task, connection_task) =
+            spawn_cleanup_tasks(&config, counter(&cache_runs), counter(&connection_runs));
+
+        // Both run once at startup, then on their own timers
+        tokio::time::sleep(std::time::Duration::from_secs(65)).await;
+        assert_eq!(cache_runs.load(Ordering::SeqCst), 1);
+        assert_eq!(connection_runs.load(Ordering::SeqCst), 7);
+
+        tokio::time::sleep(std::time::Duration::from_secs(240)).await;
+        assert_eq!(cache_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(connection_runs.load(Ordering::SeqCst), 31);
+
+        cache_task.abort();
+        connection_task.abort();
+    }
+}
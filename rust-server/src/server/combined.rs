@@ -1,61 +1,224 @@
-use crate::server::SimpleBrowserMcpServer;
-use crate::utils::truncation;
+use crate::cache::ResponseCacheKey;
+use crate::server::{browser_readiness, DomSnapshotOptions, SimpleBrowserMcpServer, SESSION_HEADER};
+use crate::transport::{
+    reject_unsupported_protocol, McpMessageHandler, ProtocolNegotiation, ToolCallOrigin, TOOL_CALL_ORIGIN,
+};
+use crate::types::browser::{InterceptRule, MediaFeature, ResourceBreakdown, ResourceUsage, ToolInvocation, XPathResult};
+use crate::types::errors::BrowserMcpError;
+use crate::utils::{to_json_string, to_json_value, truncation};
 use axum::{
     extract::{
         ws::{WebSocket, WebSocketUpgrade},
-        ConnectInfo, State, Json,
+        ConnectInfo, Query, State, Json,
+    },
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
     },
-    http::StatusCode,
-    response::IntoResponse,
     routing::{get, post},
     Router,
 };
-use std::{net::SocketAddr, sync::Arc};
+use futures_util::StreamExt;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
 use serde_json::Value;
 
-/// Combined HTTP server that handles both MCP JSON-RPC and WebSocket upgrades on the same port
+/// Combined HTTP server that handles both MCP JSON-RPC and WebSocket upgrades on the same port.
+/// With `server.unix_socket_path` set, MCP is served on that socket instead and the port
+/// only carries extension WebSockets and the operational endpoints.
 pub async fn start_combined_server(
     mcp_handler: Arc<SimpleBrowserMcpServer>,
     host: &str,
     port: u16,
 ) -> anyhow::Result<()> {
-    let app = Router::new()
-        // MCP JSON-RPC endpoint (POST)
-        .route("/mcp", post(handle_mcp_request))
-        // WebSocket upgrade endpoint (GET)
-        .route("/ws", get(handle_websocket_upgrade))
-        // Health check endpoint
-        .route("/health", get(handle_health_check))
-        // Connection cleanup endpoint
-        .route("/cleanup-connections", post(handle_cleanup_connections))
-        .layer(CorsLayer::permissive())
-        .with_state(mcp_handler);
+    let unix_socket_path = mcp_handler.config.server.unix_socket_path.clone();
+    let app = combined_router(mcp_handler.clone());
 
     let addr = format!("{}:{}", host, port);
     let listener = TcpListener::bind(&addr).await?;
 
     tracing::info!("Combined HTTP/WebSocket server listening on {}", addr);
-    tracing::info!("  MCP endpoint: POST http://{}/mcp", addr);
-    tracing::info!("  WebSocket endpoint: GET ws://{}/ws", addr);
+    if unix_socket_path.is_none() {
+        tracing::info!("  MCP endpoint: POST/DELETE http://{}/mcp", addr);
+        tracing::info!("  WebSocket endpoint: GET ws://{}/ws (extensions and MCP clients)", addr);
+    } else {
+        tracing::info!("  WebSocket endpoint: GET ws://{}/ws (extensions)", addr);
+    }
     tracing::info!("  Health check: GET http://{}/health", addr);
+    tracing::info!("  Readiness: GET http://{}/health/ready", addr);
+    tracing::info!("  Connections: GET http://{}/connections", addr);
     tracing::info!("  Cleanup: POST http://{}/cleanup-connections", addr);
+    tracing::info!("  Config: GET http://{}/config", addr);
+    tracing::info!("  Console tail: GET http://{}/events/console (SSE)", addr);
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
+    let tcp = async {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await?;
+        anyhow::Ok(())
+    };
+    match unix_socket_path {
+        Some(path) => {
+            tokio::try_join!(tcp, serve_mcp_unix_socket(mcp_handler, &path))?;
+        }
+        None => tcp.await?,
+    }
 
     Ok(())
 }
 
+/// MCP JSON-RPC endpoint (POST); DELETE ends the caller's session
+fn mcp_router() -> Router<Arc<SimpleBrowserMcpServer>> {
+    Router::new().route("/mcp", post(handle_mcp_request).delete(handle_end_session))
+}
+
+/// Everything served on the TCP port. `/mcp` is left out when MCP has moved to a Unix socket.
+fn combined_router(mcp_handler: Arc<SimpleBrowserMcpServer>) -> Router {
+    let app = Router::new()
+        // WebSocket upgrade endpoint (GET), for extensions and JSON-RPC MCP clients
+        .route("/ws", get(handle_websocket_upgrade))
+        // Health check endpoint
+        .route("/health", get(handle_health_check))
+        // Readiness: 503 until a browser extension is connected
+        .route("/health/ready", get(handle_readiness_check))
+        // Connection introspection endpoint
+        .route("/connections", get(handle_list_connections))
+        // Connection cleanup endpoint
+        .route("/cleanup-connections", post(handle_cleanup_connections))
+        // Effective configuration, secrets redacted
+        .route("/config", get(handle_get_config))
+        // Server-sent events: live tail of console messages
+        .route("/events/console", get(handle_tail_console));
+    let app = if mcp_handler.config.server.unix_socket_path.is_none() {
+        app.merge(mcp_router())
+    } else {
+        app
+    };
+    app.layer(CorsLayer::permissive()).with_state(mcp_handler)
+}
+
+/// Serve `/mcp` on a Unix domain socket readable and writable only by the server's user.
+#[cfg(unix)]
+async fn serve_mcp_unix_socket(mcp_handler: Arc<SimpleBrowserMcpServer>, path: &std::path::Path) -> anyhow::Result<()> {
+    use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a previous run would make bind fail
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = bind_private_unix_socket(path)?;
+    tracing::info!("MCP endpoint: POST/DELETE /mcp on unix:{}", path.display());
+
+    let app = mcp_router().with_state(mcp_handler);
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept Unix socket connection: {}", e);
+                continue;
+            }
+        };
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Unix socket connection error: {}", e);
+            }
+        });
+    }
+}
+
+/// Bind a socket at `path` that only the server's user can connect to. The socket is
+/// created inside a fresh 0700 directory, made 0600, and only then moved into place,
+/// so there is no moment where it is reachable with the umask's looser permissions.
+#[cfg(unix)]
+fn bind_private_unix_socket(path: &std::path::Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let staging = parent.join(format!(".mcp-socket-{}", uuid::Uuid::new_v4()));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("mcp.sock");
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    std::fs::remove_dir(&staging)?;
+    Ok(bound?)
+}
+
+#[cfg(not(unix))]
+async fn serve_mcp_unix_socket(_mcp_handler: Arc<SimpleBrowserMcpServer>, _path: &std::path::Path) -> anyhow::Result<()> {
+    anyhow::bail!("Unix domain sockets are not supported on this platform")
+}
+
 /// Handle MCP JSON-RPC requests over HTTP
 async fn handle_mcp_request(
     State(server): State<Arc<SimpleBrowserMcpServer>>,
+    headers: HeaderMap,
     Json(request): Json<Value>,
-) -> impl IntoResponse {
+) -> Response {
+    let session_id = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok());
+    let authorized = is_authorized(&server, &headers);
+    let reply = dispatch_mcp_request(server, session_id, authorized, request).await;
+    match reply.session {
+        Some(session) => (reply.status, [(SESSION_HEADER, session)], Json(reply.body)).into_response(),
+        None => (reply.status, Json(reply.body)).into_response(),
+    }
+}
+
+/// What one JSON-RPC message produced, before a transport frames it.
+struct McpReply {
+    status: StatusCode,
+    /// Set when `initialize` started a new session.
+    session: Option<String>,
+    body: Value,
+}
+
+impl McpReply {
+    fn new(status: StatusCode, body: Value) -> Self {
+        Self { status, session: None, body }
+    }
+}
+
+/// Whether the request carries `Authorization: Bearer <auth_token>`, or no token is configured.
+fn is_authorized(server: &SimpleBrowserMcpServer, headers: &HeaderMap) -> bool {
+    let Some(token) = &server.config.security.auth_token else {
+        return true;
+    };
+    let presented = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    presented == Some(token.as_str())
+}
+
+/// Run a JSON-RPC message through the MCP methods. Shared by `/mcp` and by MCP
+/// clients that keep a socket open on `/ws`. `authorized` says whether the caller
+/// presented the auth token, which methods that change server state require.
+async fn dispatch_mcp_request(
+    server: Arc<SimpleBrowserMcpServer>,
+    session_id: Option<&str>,
+    authorized: bool,
+    request: Value,
+) -> McpReply {
     tracing::debug!("Received MCP request: {}", serde_json::to_string(&request).unwrap_or_default());
 
     // Validate JSON-RPC format
@@ -72,16 +235,101 @@ async fn handle_mcp_request(
                     "data": "Missing 'method' field"
                 }
             });
-            return (StatusCode::BAD_REQUEST, Json(error_response));
+            return McpReply::new(StatusCode::BAD_REQUEST, error_response);
+        }
+    };
+
+    // Requests without a session header share the global state; a session the
+    // server doesn't know (ended or from before a restart) must re-initialize
+    if let Some(session) = session_id.filter(|_| method != "initialize") {
+        if !server.sessions.touch(session) {
+            let error_response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {
+                    "code": -32001,
+                    "message": "Session not found",
+                    "data": format!("Unknown session {}; send initialize to start a new one", session)
+                }
+            });
+            return McpReply::new(StatusCode::NOT_FOUND, error_response);
+        }
+    }
+
+    // A session may not reuse an id while the request that first used it is still
+    // running; the two responses would be indistinguishable
+    let _in_flight = match session_id.filter(|_| !id.is_null() && method != "initialize") {
+        Some(session) => match server.sessions.begin_request(session, id.to_string()) {
+            Some(guard) => Some(guard),
+            None => {
+                tracing::warn!("Rejecting {} {}: id already in flight in session {}", method, id, session);
+                let error_response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32600,
+                        "message": "Invalid Request",
+                        "data": format!("Request id {} is already in flight in this session", id)
+                    }
+                });
+                return McpReply::new(StatusCode::OK, error_response);
+            }
+        },
+        None => None,
+    };
+
+    // Calls that reach the browser hold a slot until they finish
+    let _request_slot = if matches!(method, "tools/call" | "resources/read") {
+        match server.try_acquire_request_slot() {
+            Some(permit) => Some(permit),
+            None => {
+                tracing::warn!("Rejecting {} {}: too many concurrent requests", method, id);
+                let busy_response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32000,
+                        "message": "Server busy",
+                        "data": format!(
+                            "Too many concurrent requests (limit {})",
+                            server.config.server.max_concurrent_requests
+                        )
+                    }
+                });
+                return McpReply::new(StatusCode::OK, busy_response);
+            }
         }
+    } else {
+        None
     };
 
     // Handle JSON-RPC methods
     let result = match method {
-        "initialize" => handle_initialize(request.get("params")),
+        "initialize" => match handle_initialize(request.get("params"), server.supports_log_level_changes()) {
+            Ok(result) => {
+                let session = server.sessions.create();
+                tracing::info!("Started MCP session {} ({} active)", session, server.sessions.len());
+                let response = serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                return McpReply { status: StatusCode::OK, session: Some(session), body: response };
+            }
+            Err(error) => {
+                return McpReply::new(StatusCode::OK, serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }));
+            }
+        },
         "notifications/initialized" => {
             tracing::info!("Client initialized successfully");
-            return (StatusCode::OK, Json(serde_json::json!({})));
+            return McpReply::new(StatusCode::OK, serde_json::json!({}));
+        }
+        "notifications/cancelled" => {
+            let params = request.get("params");
+            let request_id = params.and_then(|p| p.get("requestId")).cloned().unwrap_or(Value::Null);
+            let reason = params.and_then(|p| p.get("reason")).and_then(|v| v.as_str()).unwrap_or("none given");
+            if server.cancel_call(session_id, &request_id.to_string()) {
+                tracing::info!("Cancelled request {} (reason: {})", request_id, reason);
+            } else {
+                tracing::debug!("Cancellation for unknown or finished request {}", request_id);
+            }
+            return McpReply::new(StatusCode::OK, serde_json::json!({}));
         }
         "tools/list" => handle_tools_list().await,
         "resources/list" => handle_resources_list(server.clone()).await,
@@ -93,10 +341,70 @@ async fn handle_mcp_request(
         }
         "tools/call" => {
             match request.get("params") {
-                Some(params) => handle_tool_call(server.clone(), params).await,
+                Some(params) => {
+                    let started = server.request_handler.record_request_start();
+                    let call_key = id.to_string();
+                    let cancel = server.begin_cancellable_call(session_id, &call_key);
+                    let origin = ToolCallOrigin {
+                        rpc_id: id.to_string(),
+                        tool: params.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                    };
+                    // Dropping the tool future on cancellation also drops its pending browser requests
+                    let result = tokio::select! {
+                        result = TOOL_CALL_ORIGIN.scope(origin, handle_session_tool_call(server.clone(), session_id, params)) => result,
+                        _ = cancel.cancelled() => Err(CANCELLED_BY_CLIENT.to_string()),
+                    };
+                    server.finish_cancellable_call(session_id, &call_key);
+                    log_tool_call(
+                        server.config.monitoring.enable_request_logging,
+                        params,
+                        started.elapsed(),
+                        &result,
+                    );
+                    match &result {
+                        Ok(_) => server.request_handler.record_tool_success(
+                            params.get("name").and_then(|v| v.as_str()).unwrap_or("unknown"),
+                            started,
+                        ),
+                        Err(message) if message == CANCELLED_BY_CLIENT => {
+                            server.request_handler.record_request_failure_category(started, "cancelled")
+                        }
+                        Err(message) => server.request_handler.record_request_failure_category(
+                            started,
+                            BrowserMcpError::category_of_message(message),
+                        ),
+                    }
+                    result
+                }
                 None => Err("Missing params for tools/call".to_string()),
             }
         }
+        "logging/setLevel" => {
+            match request.get("params").and_then(|p| p.get("level")).and_then(|v| v.as_str()) {
+                Some(level) => server
+                    .set_log_level(level)
+                    .map(|_| serde_json::json!({}))
+                    .map_err(|e| format!("Failed to set log level: {}", e)),
+                None => Err("Missing level for logging/setLevel".to_string()),
+            }
+        }
+        "metrics/reset" => {
+            if !authorized {
+                tracing::warn!("Rejecting metrics/reset {}: missing or invalid bearer token", id);
+                let error_response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32002,
+                        "message": "Unauthorized",
+                        "data": "metrics/reset requires a valid bearer token"
+                    }
+                });
+                return McpReply::new(StatusCode::UNAUTHORIZED, error_response);
+            }
+            Ok(server.reset_metrics().await)
+        }
+        "tabs/describe" => Ok(serde_json::json!({ "tabs": server.describe_tabs().await })),
         _ => Err(format!("Unknown method: {}", method)),
     };
 
@@ -119,7 +427,23 @@ async fn handle_mcp_request(
     };
 
     tracing::debug!("Sending MCP response: {}", serde_json::to_string(&response).unwrap_or_default());
-    (StatusCode::OK, Json(response))
+    McpReply::new(StatusCode::OK, response)
+}
+
+/// End the caller's MCP session
+async fn handle_end_session(
+    State(server): State<Arc<SimpleBrowserMcpServer>>,
+    headers: HeaderMap,
+) -> StatusCode {
+    let Some(session) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if server.sessions.remove(session) {
+        tracing::info!("Ended MCP session {} ({} active)", session, server.sessions.len());
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
 }
 
 /// Handle WebSocket upgrade requests
@@ -127,24 +451,86 @@ async fn handle_websocket_upgrade(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(server): State<Arc<SimpleBrowserMcpServer>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     tracing::info!("WebSocket upgrade request from {}", addr);
-    ws.on_upgrade(move |socket| handle_websocket_connection(socket, addr, server))
+    let max_message_bytes = server.config.connections.max_message_bytes;
+    let negotiation = ProtocolNegotiation::from_headers(&headers);
+    let ws = negotiation
+        .apply(ws)
+        .max_message_size(max_message_bytes)
+        .max_frame_size(max_message_bytes);
+    match negotiation {
+        ProtocolNegotiation::Unsupported(offered) => {
+            ws.on_upgrade(move |socket| reject_unsupported_protocol(socket, offered))
+        }
+        negotiation => {
+            let protocol = negotiation.protocol();
+            let authorized = is_authorized(&server, &headers);
+            ws.on_upgrade(move |socket| handle_websocket_connection(socket, addr, protocol, authorized, server))
+        }
+    }
 }
 
-/// Handle individual WebSocket connections
+/// Handle individual WebSocket connections. Extensions speak `BrowserMessage`; MCP
+/// clients may instead send JSON-RPC here and get the same methods as `/mcp`.
 async fn handle_websocket_connection(
     socket: WebSocket,
     addr: SocketAddr,
+    protocol: Option<&'static str>,
+    authorized: bool,
     server: Arc<SimpleBrowserMcpServer>,
 ) {
     tracing::info!("New WebSocket connection from {}", addr);
+    // MCP clients must use the Unix socket when one is configured
+    if server.config.server.unix_socket_path.is_some() {
+        server.connection_pool.handle_connection(socket, Some(addr), protocol).await;
+        return;
+    }
+    let mcp_handler = mcp_socket_handler(server.clone(), authorized);
     server
         .connection_pool
-        .handle_connection(socket, Some(addr))
+        .handle_connection_with_mcp(socket, Some(addr), protocol, mcp_handler)
         .await;
 }
 
+/// The session `initialize` issues to an MCP client socket. The socket stands in for
+/// the session header, and the session ends when the socket closes. The upgrade
+/// request's bearer token covers every message on the socket.
+struct SocketSession {
+    server: Arc<SimpleBrowserMcpServer>,
+    id: parking_lot::Mutex<Option<String>>,
+}
+
+impl Drop for SocketSession {
+    fn drop(&mut self) {
+        if let Some(session) = self.id.get_mut().take() {
+            self.server.sessions.remove(&session);
+        }
+    }
+}
+
+fn mcp_socket_handler(server: Arc<SimpleBrowserMcpServer>, authorized: bool) -> McpMessageHandler {
+    let session = Arc::new(SocketSession {
+        server,
+        id: parking_lot::Mutex::new(None),
+    });
+    Arc::new(move |request| {
+        let session = session.clone();
+        Box::pin(async move {
+            let is_notification = request.get("id").is_none();
+            let session_id = session.id.lock().clone();
+            let reply = dispatch_mcp_request(session.server.clone(), session_id.as_deref(), authorized, request).await;
+            if let Some(started) = reply.session {
+                if let Some(previous) = session.id.lock().replace(started) {
+                    session.server.sessions.remove(&previous);
+                }
+            }
+            (!is_notification).then_some(reply.body)
+        })
+    })
+}
+
 /// Handle health check requests
 async fn handle_health_check(
     State(server): State<Arc<SimpleBrowserMcpServer>>,
@@ -153,6 +539,93 @@ async fn handle_health_check(
     (StatusCode::OK, Json(health_status))
 }
 
+/// Handle readiness probes
+async fn handle_readiness_check(
+    State(server): State<Arc<SimpleBrowserMcpServer>>,
+) -> impl IntoResponse {
+    browser_readiness(&server.connection_pool, server.config.monitoring.readiness_tab_id).await
+}
+
+/// List live extension connections and per-tab circuit breaker state
+async fn handle_list_connections(
+    State(server): State<Arc<SimpleBrowserMcpServer>>,
+) -> impl IntoResponse {
+    (StatusCode::OK, Json(serde_json::json!({
+        "connections": server.connection_pool.describe_connections(),
+        "circuitBreakers": server.connection_pool.get_breaker_states(),
+    })))
+}
+
+async fn handle_get_config(
+    State(server): State<Arc<SimpleBrowserMcpServer>>,
+    headers: HeaderMap,
+) -> Response {
+    if !is_authorized(&server, &headers) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({
+            "error": "A valid bearer token is required"
+        })))
+            .into_response();
+    }
+    (StatusCode::OK, Json(server.config.redacted())).into_response()
+}
+
+/// Filters for `/events/console`. `level` is a comma-separated list, e.g. `error,warn`.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConsoleTailQuery {
+    tab_id: Option<u32>,
+    level: Option<String>,
+}
+
+/// Live-tail equivalent of `get_console_messages`: each console message added to the
+/// cache is pushed as a `console` event, filtered by tab and level.
+async fn handle_tail_console(
+    State(server): State<Arc<SimpleBrowserMcpServer>>,
+    Query(query): Query<ConsoleTailQuery>,
+) -> Response {
+    if let Err(e) = server.check_tab_access(query.tab_id) {
+        return (StatusCode::FORBIDDEN, Json(serde_json::json!({ "error": e.to_string() }))).into_response();
+    }
+    let events = console_tail(server, query)
+        .map(|message| Ok::<_, std::convert::Infallible>(Event::default().event("console").data(message.to_string())));
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+fn console_tail(server: Arc<SimpleBrowserMcpServer>, query: ConsoleTailQuery) -> impl futures_util::Stream<Item = Value> {
+    let levels: Option<Vec<String>> = query
+        .level
+        .map(|levels| levels.split(',').map(|level| level.trim().to_ascii_lowercase()).collect());
+    let updates = server.data_cache.subscribe_to_updates();
+    futures_util::stream::unfold((updates, server), move |(mut updates, server)| {
+        let levels = levels.clone();
+        async move {
+            loop {
+                let update = match updates.recv().await {
+                    Ok(update) => update,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Console tail fell behind, skipped {} updates", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                };
+                if !matches!(update.update_type, crate::types::messages::DataUpdateType::ConsoleMessageAdded)
+                    || query.tab_id.is_some_and(|tab_id| tab_id != update.tab_id)
+                    || server.check_tab_access(Some(update.tab_id)).is_err()
+                {
+                    continue;
+                }
+                let Some(message) = update.payload else { continue };
+                let level = message.get("level").and_then(|l| l.as_str()).unwrap_or_default().to_ascii_lowercase();
+                if levels.as_ref().is_some_and(|levels| !levels.contains(&level)) {
+                    continue;
+                }
+                let event = serde_json::json!({ "tabId": update.tab_id, "message": message });
+                return Some((event, (updates, server)));
+            }
+        }
+    })
+}
+
 /// Handle connection cleanup requests
 async fn handle_cleanup_connections(
     State(server): State<Arc<SimpleBrowserMcpServer>>,
@@ -168,22 +641,120 @@ async fn handle_cleanup_connections(
 
 // ─── MCP JSON-RPC handlers ───────────────────────────────────────────────────
 
-fn handle_initialize(_params: Option<&Value>) -> Result<Value, String> {
+/// Error for a tool call the client cancelled, counted under its own `cancelled` category.
+const CANCELLED_BY_CLIENT: &str = "Request cancelled by client";
+
+/// MCP protocol revisions this server speaks, newest first.
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+/// Used when the client doesn't say which revision it wants.
+const DEFAULT_PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Negotiate the protocol version and report what the server implements. The error is a
+/// JSON-RPC error object for clients that only speak revisions older than ours.
+fn handle_initialize(params: Option<&Value>, logging: bool) -> Result<Value, Value> {
+    let requested = params
+        .and_then(|p| p.get("protocolVersion"))
+        .and_then(|v| v.as_str());
+    if let Some(client_capabilities) = params.and_then(|p| p.get("capabilities")) {
+        tracing::debug!("Client capabilities: {}", client_capabilities);
+    }
+
+    let protocol_version = negotiate_protocol_version(requested).ok_or_else(|| {
+        tracing::warn!("Rejecting initialize with unsupported protocol version {:?}", requested);
+        serde_json::json!({
+            "code": -32602,
+            "message": "Unsupported protocol version",
+            "data": {
+                "requested": requested,
+                "supported": SUPPORTED_PROTOCOL_VERSIONS
+            }
+        })
+    })?;
+
     Ok(serde_json::json!({
-        "protocolVersion": "2024-11-05",
+        "protocolVersion": protocol_version,
         "serverInfo": {
             "name": "browser-mcp-rust-server",
             "version": "1.0.0"
         },
-        "capabilities": {
-            "tools": {},
-            "resources": {}
-        }
+        "capabilities": server_capabilities(logging)
     }))
 }
 
+/// The version we share with the client, or our newest if the client is newer. Versions
+/// are dates, so they order as strings.
+fn negotiate_protocol_version(requested: Option<&str>) -> Option<&'static str> {
+    let Some(requested) = requested else {
+        return Some(DEFAULT_PROTOCOL_VERSION);
+    };
+    let is_date = requested.len() == 10
+        && requested
+            .char_indices()
+            .all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() });
+    if !is_date {
+        return None;
+    }
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .copied()
+        .find(|supported| *supported <= requested)
+}
+
+/// Only what is actually wired up: tools and resources, no prompts. Logging is offered
+/// when the process installed a reloadable filter.
+fn server_capabilities(logging: bool) -> Value {
+    let mut capabilities = serde_json::json!({
+        "tools": { "listChanged": false },
+        "resources": { "subscribe": false, "listChanged": false }
+    });
+    if logging {
+        capabilities["logging"] = serde_json::json!({});
+    }
+    capabilities
+}
+
 async fn handle_tools_list() -> Result<Value, String> {
-    Ok(serde_json::json!({
+    let mut definitions = tool_definitions();
+    advertise_tab_labels(&mut definitions);
+    Ok(definitions)
+}
+
+/// Offer `tabLabel` wherever a tool takes `tabId`. Labels are swapped for the tab ID
+/// before arguments are validated, so the validators keep the plain schemas.
+fn advertise_tab_labels(definitions: &mut Value) {
+    let schemas = definitions["tools"]
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .map(|tool| &mut tool["inputSchema"]);
+    for schema in schemas {
+        let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) else {
+            continue;
+        };
+        if !properties.contains_key("tabId") {
+            continue;
+        }
+        properties.insert(
+            "tabLabel".to_string(),
+            serde_json::json!({
+                "type": "string",
+                "description": "Label the extension gave the tab, e.g. checkout-page; use instead of tabId"
+            }),
+        );
+        let Some(required) = schema.get_mut("required").and_then(|r| r.as_array_mut()) else {
+            continue;
+        };
+        if required.iter().any(|field| field == "tabId") {
+            required.retain(|field| field != "tabId");
+            schema["anyOf"] = serde_json::json!([{ "required": ["tabId"] }, { "required": ["tabLabel"] }]);
+        }
+    }
+}
+
+/// Every tool with its `inputSchema`, as returned by `tools/list` before `tabLabel` is added.
+fn tool_definitions() -> Value {
+    serde_json::json!({
         "tools": [
             {
                 "name": "get_page_content",
@@ -213,6 +784,36 @@ async fn handle_tools_list() -> Result<Value, String> {
                     }
                 }
             },
+            {
+                "name": "get_multiple_page_contents",
+                "description": "Get the content of several tabs in one call, fetched concurrently. Returns a map of tab ID to content, or to an error for tabs that failed, so one bad tab doesn't fail the rest.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabIds": {
+                            "type": "array",
+                            "items": { "type": "number" },
+                            "description": "Browser tab IDs to read"
+                        },
+                        "includeMetadata": {
+                            "type": "boolean",
+                            "description": "Include page metadata like title, meta tags, etc.",
+                            "default": true
+                        },
+                        "includeHtml": {
+                            "type": "boolean",
+                            "description": "Include full HTML (may be large, truncated at 50KB). Default: false",
+                            "default": false
+                        },
+                        "maxTextLength": {
+                            "type": "number",
+                            "description": "Maximum length of each page's text content (default: 30000 chars)",
+                            "default": 30000
+                        }
+                    },
+                    "required": ["tabIds"]
+                }
+            },
             {
                 "name": "get_dom_snapshot",
                 "description": "Get a structured DOM snapshot with filtering. Limits to 500 nodes by default. Use selector to target specific elements for detailed inspection.",
@@ -252,6 +853,16 @@ async fn handle_tools_list() -> Result<Value, String> {
                             "type": "boolean",
                             "description": "Exclude <style> tags from snapshot. Default: true",
                             "default": true
+                        },
+                        "pierce": {
+                            "type": "boolean",
+                            "description": "Descend into open shadow roots, which appear as nodes with shadow_root: true under their host. The selector also matches inside them. Default: false",
+                            "default": false
+                        },
+                        "flat": {
+                            "type": "boolean",
+                            "description": "Return a pre-order `nodes` array, each node with node_index and parent_index, instead of a nested `root` tree. Default: false",
+                            "default": false
                         }
                     }
                 }
@@ -266,11 +877,76 @@ async fn handle_tools_list() -> Result<Value, String> {
                         "code": {
                             "type": "string",
                             "description": "JavaScript code to execute"
+                        },
+                        "contextId": {
+                            "type": "string",
+                            "description": "Run in a persistent context from create_js_context so state carries across calls (requires tabId)"
+                        },
+                        "awaitPromise": {
+                            "type": "boolean",
+                            "description": "If the code returns a Promise, wait for it and return its resolved value (default: false)",
+                            "default": false
                         }
                     },
                     "required": ["code"]
                 }
             },
+            {
+                "name": "list_running_scripts",
+                "description": "List execute_javascript calls still waiting on the browser, with how long each has been running. Use it to find a runaway script for terminate_script.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Only list scripts running in this tab" }
+                    }
+                }
+            },
+            {
+                "name": "terminate_script",
+                "description": "Abort a running execute_javascript call, e.g. one stuck in an infinite loop, through the debugger's Runtime.terminateExecution. Stops whatever JavaScript its tab is executing at that moment.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "scriptId": { "type": "string", "description": "scriptId from list_running_scripts" }
+                    },
+                    "required": ["scriptId"]
+                }
+            },
+            {
+                "name": "set_page_content",
+                "description": "Replace the tab's whole document with the given HTML, e.g. to load a test fixture. Scripts in the HTML run. Disabled when the server disallows JavaScript execution.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "html": { "type": "string", "description": "Complete HTML document to load" }
+                    },
+                    "required": ["tabId", "html"]
+                }
+            },
+            {
+                "name": "create_js_context",
+                "description": "Create a persistent JavaScript context in a tab. Variables and helpers defined with execute_javascript in this context survive between calls until disposed or the page navigates.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "dispose_js_context",
+                "description": "Dispose a persistent JavaScript context created with create_js_context",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "contextId": { "type": "string", "description": "Context ID returned by create_js_context" }
+                    },
+                    "required": ["tabId", "contextId"]
+                }
+            },
             {
                 "name": "get_console_messages",
                 "description": "Get console messages from the browser with filtering and pagination. Returns errors/warnings by default for optimal relevance.",
@@ -365,9 +1041,45 @@ async fn handle_tools_list() -> Result<Value, String> {
                     }
                 }
             },
+            {
+                "name": "get_network_request_body",
+                "description": "Get the request and response body of a single network request by its requestId from get_network_requests. Bodies that were not retained are fetched again by the browser for GET/HEAD requests.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "requestId": { "type": "string", "description": "The requestId of the network request" }
+                    },
+                    "required": ["requestId"]
+                }
+            },
+            {
+                "name": "replay_request",
+                "description": "Send a request from get_network_requests again from inside the tab, with the same method, URL, headers and body, and return the new response. Headers the browser controls (Cookie, Origin, Sec-*...) are left to the browser and listed in droppedHeaders. Can change server state, so it needs JavaScript execution to be allowed.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID the request was captured in" },
+                        "requestId": { "type": "string", "description": "The requestId of the network request to replay" }
+                    },
+                    "required": ["tabId", "requestId"]
+                }
+            },
+            {
+                "name": "download_resource",
+                "description": "Download a file (PDF, CSV, image...) by fetching its URL from inside the tab, so the page's cookies and auth apply. Returns the bytes as a base64 resource with the detected mime type. Use get_network_request_body instead to read traffic the page already made.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "url": { "type": "string", "description": "URL to fetch, absolute or relative to the page" }
+                    },
+                    "required": ["tabId", "url"]
+                }
+            },
             {
                 "name": "capture_screenshot",
-                "description": "Capture a screenshot of the current browser tab",
+                "description": "Capture a screenshot of the current browser tab. Captures larger than maxWidth/maxHeight are downscaled, keeping their aspect ratio, and the scale factor is reported.",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
@@ -382,10 +1094,56 @@ async fn handle_tools_list() -> Result<Value, String> {
                             "minimum": 0,
                             "maximum": 100,
                             "default": 90
+                        },
+                        "maxWidth": { "type": "number", "minimum": 0, "description": "Widest image to return in pixels, 0 for no limit (default: server setting, 4096)" },
+                        "maxHeight": { "type": "number", "minimum": 0, "description": "Tallest image to return in pixels, 0 for no limit (default: server setting, 4096)" },
+                        "clip": {
+                            "type": "object",
+                            "description": "Capture only this region, in CSS pixels relative to the top left of the page (not the scrolled viewport)",
+                            "properties": {
+                                "x": { "type": "number" },
+                                "y": { "type": "number" },
+                                "width": { "type": "number", "exclusiveMinimum": 0 },
+                                "height": { "type": "number", "exclusiveMinimum": 0 }
+                            },
+                            "required": ["x", "y", "width", "height"]
+                        },
+                        "captureSelector": {
+                            "type": "string",
+                            "description": "Capture only the first element matching this CSS selector. Overrides clip; the region used is returned as clip."
                         }
                     }
                 }
             },
+            {
+                "name": "set_baseline_screenshot",
+                "description": "Capture the tab now and keep it as the reference image for compare_screenshot. Replaces any earlier baseline for the tab.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "compare_screenshot",
+                "description": "Capture the tab and diff it pixel by pixel against its baseline screenshot. Returns the percentage of differing pixels and a match/mismatch verdict against the threshold, optionally with a PNG highlighting the differences in red.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "threshold": {
+                            "type": "number",
+                            "minimum": 0,
+                            "maximum": 100,
+                            "description": "Percentage of differing pixels still counted as a match (default: server setting, 0.1)"
+                        },
+                        "includeDiffImage": { "type": "boolean", "default": false }
+                    },
+                    "required": ["tabId"]
+                }
+            },
             {
                 "name": "get_performance_metrics",
                 "description": "Get performance metrics from the browser",
@@ -397,33 +1155,175 @@ async fn handle_tools_list() -> Result<Value, String> {
                 }
             },
             {
-                "name": "get_accessibility_tree",
-                "description": "Get the accessibility tree of the page",
+                "name": "get_web_vitals_report",
+                "description": "Classify the tab's cached Core Web Vitals (LCP, FID, CLS) as good, needs-improvement or poor using the standard thresholds. Returns an overall verdict plus the raw values.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_resource_breakdown",
+                "description": "Total the tab's cached network requests by resource type (script, image, stylesheet, ...): count, transfer size and decoded size, heaviest first. Sizes come from cached resource timings; call get_network_requests or get_performance_metrics first.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_resource_usage",
+                "description": "Get a compact snapshot of the tab's JS heap usage, DOM node count and (with debugger attached) CPU time. Cheaper than get_performance_metrics for polling.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    }
+                }
+            },
+            {
+                "name": "wait_for_network_idle",
+                "description": "Wait until the page's network activity settles: fewer than maxInflight requests active for idleMs. Use before scraping SPA content that keeps loading after navigation.",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
                         "tabId": { "type": "number", "description": "Browser tab ID" },
-                        "timeout": {
-                            "type": "number",
-                            "description": "Timeout in milliseconds (default: 30000, max: 120000)",
-                            "default": 30000,
-                            "minimum": 5000,
-                            "maximum": 120000
+                        "idleMs": { "type": "number", "description": "How long the network must stay quiet (default: 500)" },
+                        "timeoutMs": { "type": "number", "description": "Give up after this many milliseconds (default: 10000)" },
+                        "maxInflight": { "type": "number", "description": "Idle means fewer than this many requests in flight (default: 1, i.e. none)" }
+                    }
+                }
+            },
+            {
+                "name": "wait_for_console_error",
+                "description": "Wait for the page to log a console error or throw an uncaught exception, optionally one matching a pattern, and return it. Start it, then trigger the action you want to watch; fails if nothing matches within timeoutMs.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "timeoutMs": { "type": "number", "description": "Give up after this many milliseconds (default: 10000)" },
+                        "pattern": { "type": "string", "description": "JavaScript regular expression the error text must match (default: any error)" }
+                    }
+                }
+            },
+            {
+                "name": "get_computed_style",
+                "description": "Get computed CSS properties of the first element matching a selector. Much cheaper than get_dom_snapshot with includeStyles for checking one element.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "selector": { "type": "string", "description": "CSS selector of the element" },
+                        "properties": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "CSS properties to return, e.g. ['display', 'width'] (default: all)"
+                        }
+                    },
+                    "required": ["selector"]
+                }
+            },
+            {
+                "name": "get_links",
+                "description": "Get the page's links as a list of { href, text, rel }. Much cheaper than get_page_content for link discovery.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "sameOriginOnly": {
+                            "type": "boolean",
+                            "description": "Only return links to the page's own origin (default: false)",
+                            "default": false
+                        },
+                        "includeText": {
+                            "type": "boolean",
+                            "description": "Include each link's visible text (default: true)",
+                            "default": true
                         }
                     }
                 }
             },
             {
-                "name": "get_browser_tabs",
-                "description": "Get information about all open browser tabs",
+                "name": "press_key",
+                "description": "Dispatch a real key press (keydown and keyup) through the debugger, e.g. Enter to submit a form, arrow keys or shortcuts. Focuses the element first when a selector is given.",
                 "inputSchema": {
                     "type": "object",
-                    "properties": {}
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "key": { "type": "string", "description": "Key value, e.g. Enter, ArrowDown, a" },
+                        "selector": { "type": "string", "description": "CSS selector of the element to focus before pressing" },
+                        "modifiers": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Modifier keys held during the press: Alt, Control, Meta or Shift (ctrl, cmd and option are accepted too)"
+                        }
+                    },
+                    "required": ["key"]
                 }
             },
             {
-                "name": "attach_debugger",
-                "description": "Attach Chrome debugger to a tab for advanced inspection",
+                "name": "fill_form",
+                "description": "Fill several form fields in one call, keyed by CSS selector. Fires input and change events for each field so frameworks notice, and reports per field whether it was filled or not found.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "fields": {
+                            "type": "object",
+                            "additionalProperties": { "type": ["string", "number", "boolean"] },
+                            "description": "Map of CSS selector to value, e.g. {\"#email\": \"a@b.com\", \"#terms\": true}. Booleans check or uncheck checkboxes and radio buttons."
+                        }
+                    },
+                    "required": ["fields"]
+                }
+            },
+            {
+                "name": "evaluate_xpath",
+                "description": "Evaluate an XPath expression in the page. Returns matched nodes (without children) for node-set results, or the string, number or boolean the expression produces. Useful for text-based matching CSS selectors can't express.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "expression": { "type": "string", "description": "XPath expression, e.g. //button[contains(., 'Submit')]" }
+                    },
+                    "required": ["expression"]
+                }
+            },
+            {
+                "name": "get_element_rect",
+                "description": "Get an element's bounding box in viewport CSS pixels and whether it is visible and inside the viewport. Cheaper than a styled DOM snapshot; use it before coordinate-based interaction.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "selector": { "type": "string", "description": "CSS selector; the first match is measured" }
+                    },
+                    "required": ["selector"]
+                }
+            },
+            {
+                "name": "observe_mutations",
+                "description": "Watch the page's DOM for a while and return every change it made: attribute changes and added or removed nodes. Catches transient UI (toasts, spinners, flashes) that single snapshots miss.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "selector": { "type": "string", "description": "CSS selector of the element to watch (default: the whole document)" },
+                        "durationMs": { "type": "number", "description": "How long to observe, up to 60000 (default: 2000)" },
+                        "attributes": { "type": "boolean", "description": "Record attribute changes (default: true)" },
+                        "childList": { "type": "boolean", "description": "Record added and removed nodes (default: true)" },
+                        "subtree": { "type": "boolean", "description": "Include descendants, not just the element itself (default: true)" }
+                    }
+                }
+            },
+            {
+                "name": "get_navigation_history",
+                "description": "Get the tab's back/forward history as a list of { url, title } entries, with current_index marking the page being shown",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
@@ -433,8 +1333,22 @@ async fn handle_tools_list() -> Result<Value, String> {
                 }
             },
             {
-                "name": "detach_debugger",
-                "description": "Detach Chrome debugger from a tab",
+                "name": "clear_browsing_data",
+                "description": "Clear stored browser data between automation runs: the HTTP cache and cookies (browser-wide) and/or local storage for the tab's origin. Select at least one category.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "cache": { "type": "boolean", "default": false, "description": "Clear the HTTP cache" },
+                        "cookies": { "type": "boolean", "default": false, "description": "Clear all cookies" },
+                        "localStorage": { "type": "boolean", "default": false, "description": "Clear local storage for the tab's origin" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "capture_mhtml",
+                "description": "Archive the page with its images, stylesheets and frames as a single MHTML document (multipart/related), returned as a resource blob. Preserves the page more faithfully than HTML-only content.",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
@@ -442,339 +1356,5518 @@ async fn handle_tools_list() -> Result<Value, String> {
                     },
                     "required": ["tabId"]
                 }
-            }
-        ]
-    }))
-}
-
-async fn handle_resources_list(server: Arc<SimpleBrowserMcpServer>) -> Result<Value, String> {
-    let mut resources = Vec::new();
-
-    let all_tabs = server.data_cache.get_all_tabs().await;
-    for tab_data in &all_tabs {
-        let tab_id = tab_data.tab_id;
-
-        if let Some(pc) = &tab_data.page_content {
-            resources.push(serde_json::json!({
-                "uri": format!("browser://tab/{}/content", tab_id),
-                "name": format!("Page Content - {}", if pc.title.is_empty() { &pc.url } else { &pc.title }),
-                "description": format!("Full page content from {}", pc.url),
-                "mimeType": "text/html"
-            }));
-        }
-
-        if tab_data.dom_snapshot.is_some() {
-            resources.push(serde_json::json!({
-                "uri": format!("browser://tab/{}/dom", tab_id),
-                "name": format!("DOM Snapshot - tab {}", tab_id),
-                "description": "Structured DOM tree",
-                "mimeType": "application/json"
-            }));
-        }
-
-        if let Some(console_logs) = &tab_data.console_logs {
-            let count = console_logs.read().len();
-            if count > 0 {
-                resources.push(serde_json::json!({
-                    "uri": format!("browser://tab/{}/console", tab_id),
-                    "name": format!("Console Messages - {} messages", count),
-                    "description": "Console logs, errors, and warnings",
-                    "mimeType": "application/json"
-                }));
-            }
-        }
-    }
-
-    Ok(serde_json::json!({ "resources": resources }))
-}
-
-async fn handle_resource_read(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
-    let uri = params.get("uri")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing 'uri' parameter")?;
-
-    // Parse URI: browser://tab/{id}/{type}
-    let re = regex::Regex::new(r"^browser://tab/(\d+)/(content|dom|console)$")
-        .map_err(|e| e.to_string())?;
+            },
+            {
+                "name": "get_layout_metrics",
+                "description": "Get the tab's scroll position, full document size, visible viewport size and device pixel ratio, in CSS pixels. Use it to work out how many viewport-sized scrolls cover the page.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_capabilities",
+                "description": "Check what the extension can currently do in a tab (attach the debugger, capture screenshots, read cookies), with a reason for anything unavailable. Call this before a workflow to avoid permission errors partway through.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "go_back_forward",
+                "description": "Go back or forward in the tab's history, like the browser's back and forward buttons. Returns the URL navigated to.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "delta": { "type": "number", "description": "Entries to move: -1 goes back one page, 1 goes forward one page" }
+                    },
+                    "required": ["delta"]
+                }
+            },
+            {
+                "name": "set_intercept_rules",
+                "description": "Block, delay or mock requests in a tab. Replaces the tab's current rules; the first rule whose pattern matches a request is applied. Active rules are listed by tabs/describe.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "rules": {
+                            "type": "array",
+                            "description": "Interception rules, checked in order",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "urlPattern": { "type": "string", "description": "URL wildcard pattern, e.g. *://api.example.com/*" },
+                                    "action": { "type": "string", "enum": ["block", "delay", "respond_with"] },
+                                    "delayMs": { "type": "number", "description": "Delay before the request continues (delay only)" },
+                                    "status": { "type": "number", "description": "Response status (respond_with only, default: 200)" },
+                                    "headers": { "type": "object", "description": "Response headers (respond_with only)" },
+                                    "body": { "type": "string", "description": "Response body (respond_with only)" }
+                                },
+                                "required": ["urlPattern", "action"]
+                            }
+                        }
+                    },
+                    "required": ["tabId", "rules"]
+                }
+            },
+            {
+                "name": "clear_intercept_rules",
+                "description": "Remove all request interception rules from a tab",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "set_extra_headers",
+                "description": "Add headers (e.g. an auth header) to every request the tab makes. Replaces the tab's current extra headers; the names of active headers are listed by tabs/describe.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "headers": {
+                            "type": "object",
+                            "description": "Header names and values, e.g. { \"X-Api-Key\": \"...\" }",
+                            "additionalProperties": { "type": "string" }
+                        }
+                    },
+                    "required": ["tabId", "headers"]
+                }
+            },
+            {
+                "name": "clear_extra_headers",
+                "description": "Stop adding extra headers to a tab's requests",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_user_agent",
+                "description": "Get the user agent and language the tab's pages see, and any override set with set_user_agent",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "set_user_agent",
+                "description": "Make a tab report a different user agent, e.g. a mobile browser's, to test device-specific behaviour. Applies to requests and navigator.userAgent until the debugger detaches.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "userAgent": { "type": "string", "description": "User agent string to report" },
+                        "acceptLanguage": { "type": "string", "description": "Accept-Language value to send with it, e.g. \"de-DE,de;q=0.9\"" }
+                    },
+                    "required": ["tabId", "userAgent"]
+                }
+            },
+            {
+                "name": "get_emulated_media",
+                "description": "Get the CSS media type and media features set on a tab with set_emulated_media",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "set_emulated_media",
+                "description": "Make a tab's CSS match print or screen media and media features such as prefers-color-scheme: dark, e.g. before capture_screenshot to check dark mode or print styles. Replaces the previous emulation; call with no media and no features to clear it. Lasts until the debugger detaches.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "media": { "type": "string", "enum": ["print", "screen"], "description": "CSS media type to emulate" },
+                        "features": {
+                            "type": "array",
+                            "description": "Media features to force",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string", "description": "Feature name, e.g. \"prefers-color-scheme\" or \"prefers-reduced-motion\"" },
+                                    "value": { "type": "string", "description": "Value to match, e.g. \"dark\" or \"reduce\"" }
+                                },
+                                "required": ["name", "value"]
+                            }
+                        }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "add_init_script",
+                "description": "Run JavaScript in every new document the tab loads, before the page's own scripts, e.g. to stub a global. Returns a scriptId for remove_init_script.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "source": { "type": "string", "description": "JavaScript source to evaluate" }
+                    },
+                    "required": ["tabId", "source"]
+                }
+            },
+            {
+                "name": "remove_init_script",
+                "description": "Stop running an init script added with add_init_script. Documents already loaded are unaffected.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "scriptId": { "type": "string", "description": "scriptId returned by add_init_script" }
+                    },
+                    "required": ["tabId", "scriptId"]
+                }
+            },
+            {
+                "name": "get_accessibility_node",
+                "description": "Get the accessible role, name, description, value and ARIA state of the first element matching a selector. Much cheaper than get_accessibility_tree when checking a single control.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "selector": { "type": "string", "description": "CSS selector; the first match is described" }
+                    },
+                    "required": ["selector"]
+                }
+            },
+            {
+                "name": "get_accessibility_tree",
+                "description": "Get the accessibility tree of the page",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "timeout": {
+                            "type": "number",
+                            "description": "Timeout in milliseconds (default: 30000, max: 120000)",
+                            "default": 30000,
+                            "minimum": 5000,
+                            "maximum": 120000
+                        }
+                    }
+                }
+            },
+            {
+                "name": "get_accessibility_issues",
+                "description": "Audit the tab's cached DOM snapshot and accessibility tree for missing alt text, low-contrast text, unlabelled form controls and skipped heading levels. Returns violations with node references and severity, most severe first. Call get_dom_snapshot (with includeStyles for contrast checks) or get_accessibility_tree first.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_browser_tabs",
+                "description": "Get information about all open browser tabs",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {}
+                }
+            },
+            {
+                "name": "get_windows",
+                "description": "Get open browser windows, each with its tabs in tab strip order and whether it is the focused window",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {}
+                }
+            },
+            {
+                "name": "get_current_location",
+                "description": "Cheapest way to find out what page a tab is on: returns only its url and title, from cached tab state when available. Without tabId, describes the active tab.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID (default: the active tab)" }
+                    }
+                }
+            },
+            {
+                "name": "get_structured_data",
+                "description": "Extract JSON-LD blocks (parsed as JSON) and all <meta> tags from a page, e.g. schema.org products or Open Graph tags. Parsed from recently cached HTML without a browser round trip when possible.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "get_tab_activity",
+                "description": "List the tool calls made against a tab (tool, argument names, timestamp, duration, outcome), oldest first. Records the agent's actions rather than the page's; requires cache.activity_log_entries to be set.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" },
+                        "limit": { "type": "number", "description": "Return only the most recent calls", "minimum": 1 }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "attach_debugger",
+                "description": "Attach Chrome debugger to a tab for advanced inspection",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "detach_debugger",
+                "description": "Detach Chrome debugger from a tab",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "pause_execution",
+                "description": "Pause the page's JavaScript through the debugger (Debugger.pause) for interactive debugging. Until resume_execution, tools that need the page to run are rejected.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "resume_execution",
+                "description": "Resume JavaScript paused by pause_execution",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID" }
+                    },
+                    "required": ["tabId"]
+                }
+            },
+            {
+                "name": "run_macro",
+                "description": "Run a sequence of tool calls in order against a tab and return each step's result. Stops at the first failing step unless continueOnError is set.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tabId": { "type": "number", "description": "Browser tab ID, used by steps that don't set their own" },
+                        "steps": {
+                            "type": "array",
+                            "description": "Ordered steps to run",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "tool": { "type": "string", "description": "Tool name" },
+                                    "arguments": { "type": "object", "description": "Tool arguments" }
+                                },
+                                "required": ["tool"]
+                            }
+                        },
+                        "continueOnError": {
+                            "type": "boolean",
+                            "description": "Keep running after a step fails (default: false)",
+                            "default": false
+                        }
+                    },
+                    "required": ["steps"]
+                }
+            }
+        ]
+    })
+}
+
+async fn handle_resources_list(server: Arc<SimpleBrowserMcpServer>) -> Result<Value, String> {
+    let mut resources = Vec::new();
+
+    let all_tabs = server.data_cache.get_all_tabs().await;
+    for tab_data in &all_tabs {
+        let tab_id = tab_data.tab_id;
+
+        if let Some(pc) = tab_data.page_content.read().clone() {
+            resources.push(serde_json::json!({
+                "uri": format!("browser://tab/{}/content", tab_id),
+                "name": format!("Page Content - {}", if pc.title.is_empty() { &pc.url } else { &pc.title }),
+                "description": format!("Full page content from {}", pc.url),
+                "mimeType": "text/html"
+            }));
+        }
+
+        if tab_data.dom_snapshot.read().is_some() {
+            resources.push(serde_json::json!({
+                "uri": format!("browser://tab/{}/dom", tab_id),
+                "name": format!("DOM Snapshot - tab {}", tab_id),
+                "description": "Structured DOM tree",
+                "mimeType": "application/json"
+            }));
+        }
+
+        let count = tab_data.console_logs.read().len();
+        if count > 0 {
+            resources.push(serde_json::json!({
+                "uri": format!("browser://tab/{}/console", tab_id),
+                "name": format!("Console Messages - {} messages", count),
+                "description": "Console logs, errors, and warnings",
+                "mimeType": "application/json"
+            }));
+        }
+    }
+
+    Ok(serde_json::json!({ "resources": resources }))
+}
+
+async fn handle_resource_read(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
+    let uri = params.get("uri")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'uri' parameter")?;
+
+    // Parse URI: browser://tab/{id}/{type}
+    let re = regex::Regex::new(r"^browser://tab/(\d+)/(content|dom|console)$")
+        .map_err(|e| e.to_string())?;
+
+    let caps = re.captures(uri)
+        .ok_or_else(|| format!("Invalid resource URI: {}", uri))?;
+
+    let tab_id: u32 = caps.get(1).unwrap().as_str().parse()
+        .map_err(|_| "Invalid tab ID".to_string())?;
+    let resource_type = caps.get(2).unwrap().as_str();
+    let pretty = server.config.monitoring.pretty_json;
+    server.check_tab_access(Some(tab_id)).map_err(|e| e.to_string())?;
+
+    let tab_data = server.data_cache.get_tab_data(tab_id).await
+        .ok_or_else(|| format!("No data available for tab {}", tab_id))?;
+
+    match resource_type {
+        "content" => {
+            let page_content = tab_data.page_content.read();
+            let html = page_content.as_ref()
+                .map(|pc| pc.html.as_str())
+                .unwrap_or("");
+            let (truncated_html, _) = truncation::truncate_string(html, truncation::MAX_HTML_SIZE);
+
+            Ok(serde_json::json!({
+                "contents": [{
+                    "uri": uri,
+                    "mimeType": "text/html",
+                    "text": truncated_html
+                }]
+            }))
+        }
+        "dom" => {
+            let dom_text = if let Some(dom) = tab_data.dom_snapshot.read().clone() {
+                let dom_value = to_json_value(dom.as_ref()).map_err(|e| e.to_string())?;
+
+                // Truncate DOM tree
+                if let Some(root) = dom_value.get("root") {
+                    let mut count = 0;
+                    let truncated_root = crate::utils::dom::truncate_dom_tree(
+                        root, truncation::MAX_DOM_NODES, &mut count
+                    );
+                    let mut result = dom_value.clone();
+                    result["root"] = truncated_root;
+                    result["truncated"] = Value::Bool(count >= truncation::MAX_DOM_NODES);
+                    result["returnedNodeCount"] = Value::Number(count.into());
+                    to_json_string(&result, pretty).map_err(|e| e.to_string())?
+                } else {
+                    to_json_string(&dom_value, pretty).map_err(|e| e.to_string())?
+                }
+            } else {
+                "null".to_string()
+            };
+
+            Ok(serde_json::json!({
+                "contents": [{
+                    "uri": uri,
+                    "mimeType": "application/json",
+                    "text": dom_text
+                }]
+            }))
+        }
+        "console" => {
+            let console_data = {
+                let logs = tab_data.console_logs.read();
+                let total = logs.len();
+                let limited = total > 100;
+                let messages: Vec<_> = if limited {
+                    logs.iter().skip(total - 100).cloned().collect()
+                } else {
+                    logs.iter().cloned().collect()
+                };
+                serde_json::json!({
+                    "messages": messages,
+                    "count": messages.len(),
+                    "limited": limited
+                })
+            };
+
+            Ok(serde_json::json!({
+                "contents": [{
+                    "uri": uri,
+                    "mimeType": "application/json",
+                    "text": to_json_string(&console_data, pretty).map_err(|e| e.to_string())?
+                }]
+            }))
+        }
+        _ => Err(format!("Unknown resource type: {}", resource_type)),
+    }
+}
+
+async fn handle_tool_call(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
+    let pretty = server.config.monitoring.pretty_json;
+    call_tool(server, params).await.and_then(|result| tool_result_content(&result, pretty))
+}
+
+/// Run a tool on behalf of an MCP session. Calls that omit `tabId` go to the tab the
+/// session last named, and persistent JavaScript contexts can only be used by the
+/// session that created them.
+async fn handle_session_tool_call(
+    server: Arc<SimpleBrowserMcpServer>,
+    session_id: Option<&str>,
+    params: &Value,
+) -> Result<Value, String> {
+    let Some(session_id) = session_id else {
+        return handle_tool_call(server, params).await;
+    };
+
+    let mut params = params.clone();
+    let tool_name = params.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    if let Some(args) = params
+        .as_object_mut()
+        .and_then(|p| p.entry("arguments").or_insert_with(|| serde_json::json!({})).as_object_mut())
+    {
+        if let Some(tab_id) = server.sessions.active_tab(session_id).filter(|_| !args.contains_key("tabLabel")) {
+            args.entry("tabId").or_insert_with(|| Value::from(tab_id));
+        }
+
+        // Macro steps can name contexts too
+        let steps = args.get("steps").and_then(|v| v.as_array()).into_iter().flatten();
+        let context_ids = std::iter::once(&*args)
+            .chain(steps.filter_map(|step| step.get("arguments").and_then(|v| v.as_object())))
+            .filter_map(|step_args| step_args.get("contextId").and_then(|v| v.as_str()));
+        for context_id in context_ids {
+            if !server.sessions.owns_js_context(session_id, context_id) {
+                return Err(format!("Unknown JavaScript context {} for this session", context_id));
+            }
+        }
+    }
+
+    let result = call_tool(server.clone(), &params).await?;
+
+    let args = &params["arguments"];
+    if let Some(tab_id) = args.get("tabId").and_then(|v| v.as_u64()) {
+        server.sessions.set_active_tab(session_id, tab_id as u32);
+    }
+    match tool_name.as_str() {
+        "create_js_context" => {
+            if let Some(context_id) = result.get("contextId").and_then(|v| v.as_str()) {
+                server.sessions.add_js_context(session_id, context_id.to_string());
+            }
+        }
+        "dispose_js_context" => {
+            if let Some(context_id) = args.get("contextId").and_then(|v| v.as_str()) {
+                server.sessions.remove_js_context(session_id, context_id);
+            }
+        }
+        _ => {}
+    }
+    tool_result_content(&result, server.config.monitoring.pretty_json)
+}
+
+/// Compiled `inputSchema` of every tool, keyed by tool name.
+static TOOL_ARGUMENT_VALIDATORS: LazyLock<HashMap<String, jsonschema::Validator>> = LazyLock::new(|| {
+    tool_definitions()["tools"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tool| {
+            let name = tool["name"].as_str()?;
+            match jsonschema::validator_for(&tool["inputSchema"]) {
+                Ok(validator) => Some((name.to_string(), validator)),
+                Err(e) => {
+                    tracing::error!("Tool {} has an invalid inputSchema: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+/// Check a call's arguments against the tool's declared `inputSchema` before dispatch,
+/// naming each offending field. Unknown tools are left for dispatch to reject.
+fn validate_tool_arguments(tool_name: &str, args: &Value) -> Result<(), BrowserMcpError> {
+    let Some(validator) = TOOL_ARGUMENT_VALIDATORS.get(tool_name) else {
+        return Ok(());
+    };
+    let problems: Vec<String> = validator
+        .iter_errors(args)
+        .map(|error| match &error.kind {
+            jsonschema::error::ValidationErrorKind::Required { property } => {
+                format!("missing required field `{}`", property.as_str().unwrap_or_default())
+            }
+            _ => match error.instance_path.as_str().trim_start_matches('/') {
+                "" => format!("arguments: {}", error),
+                field => format!("field `{}`: {}", field.replace('/', "."), error),
+            },
+        })
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(BrowserMcpError::InvalidParameters {
+        message: format!("{}: {}", tool_name, problems.join("; ")),
+    })
+}
+
+/// Swap a `tabLabel` argument for the `tabId` of the tab the extension gave that label.
+fn resolve_tab_label(server: &SimpleBrowserMcpServer, args: &Value, label: &str) -> Result<Value, BrowserMcpError> {
+    if args.get("tabId").is_some() {
+        return Err(BrowserMcpError::InvalidParameters {
+            message: "Pass either tabId or tabLabel, not both".to_string(),
+        });
+    }
+    let tab_id = server.connection_pool.resolve_tab_label(label).ok_or_else(|| BrowserMcpError::InvalidParameters {
+        message: format!("No connected tab is labelled '{}'", label),
+    })?;
+    let mut args = args.clone();
+    if let Some(args) = args.as_object_mut() {
+        args.remove("tabLabel");
+        args.insert("tabId".to_string(), Value::from(tab_id));
+    }
+    Ok(args)
+}
+
+/// Run a tool and return its raw result, before it is wrapped as MCP content. Calls
+/// naming a tab are added to its activity log when that is enabled.
+async fn call_tool(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
+    if !server.data_cache.activity_log_enabled() {
+        return run_tool(server, params).await;
+    }
+    let timestamp = chrono::Utc::now();
+    let started = std::time::Instant::now();
+    let result = run_tool(server.clone(), params).await;
+
+    let tool = params.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+    let args = params.get("arguments");
+    let tab_id = args.and_then(|args| args.get("tabId")).and_then(|v| v.as_u64()).map(|v| v as u32).or_else(|| {
+        let label = args?.get("tabLabel")?.as_str()?;
+        server.connection_pool.resolve_tab_label(label)
+    });
+    if let Some(tab_id) = tab_id.filter(|_| tool != "get_tab_activity") {
+        // Only the argument names: values can be passwords, header tokens or scripts
+        let arguments = args
+            .and_then(|args| args.as_object())
+            .map(|args| args.keys().cloned().collect())
+            .unwrap_or_default();
+        server.data_cache.record_tool_invocation(tab_id, ToolInvocation {
+            tool: tool.to_string(),
+            arguments,
+            timestamp,
+            duration_ms: started.elapsed().as_millis() as u64,
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        });
+    }
+    result
+}
+
+async fn run_tool(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
+    let tool_name = params.get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing tool name")?;
+
+    let empty_args = Value::Object(serde_json::Map::new());
+    let labelled_args;
+    let mut args = params.get("arguments").unwrap_or(&empty_args);
+    if let Some(label) = args.get("tabLabel").and_then(|v| v.as_str()) {
+        labelled_args = resolve_tab_label(&server, args, label).map_err(|e| e.to_string())?;
+        args = &labelled_args;
+    }
+    validate_tool_arguments(tool_name, args).map_err(|e| e.to_string())?;
+
+    let dry_run = args.get("_dryRun").and_then(|v| v.as_bool()).unwrap_or(server.config.server.dry_run);
+    if dry_run {
+        return dry_run_result(tool_name, args);
+    }
+
+    if !TABLESS_TOOLS.contains(&tool_name) {
+        let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+        server.check_tab_access(tab_id).map_err(|e| e.to_string())?;
+        if !RUNS_WHILE_PAUSED_TOOLS.contains(&tool_name) {
+            server.check_not_paused(tool_name, tab_id).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Read-only calls against an explicit tab can be answered from the response cache
+    let cache_key = args
+        .get("tabId")
+        .and_then(|v| v.as_u64())
+        .filter(|_| CACHEABLE_TOOLS.contains(&tool_name))
+        .map(|tab_id| ResponseCacheKey::new(tab_id as u32, tool_name, args));
+    if let Some((cached, age)) = cache_key.as_ref().and_then(|key| server.data_cache.get_cached_response(key)) {
+        tracing::debug!("Serving {} from response cache", tool_name);
+        return Ok(with_result_metadata(&server, cached, "cache", age));
+    }
+
+    // Tools built from cached browser data report that data's age instead
+    let mut provenance = ("live", Duration::ZERO);
+    let result = match tool_name {
+        "get_page_content" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let include_metadata = args.get("includeMetadata").and_then(|v| v.as_bool()).unwrap_or(true);
+            let include_html = args.get("includeHtml").and_then(|v| v.as_bool()).unwrap_or(false);
+            let max_text_length = args.get("maxTextLength").and_then(|v| v.as_u64()).unwrap_or(30000) as usize;
+
+            server.handle_get_page_content(tab_id, include_metadata, include_html, max_text_length).await
+                .map_err(|e| format!("Failed to get page content: {}", e))?
+        }
+        "get_multiple_page_contents" => {
+            let tab_ids: Vec<u32> = args.get("tabIds").and_then(|v| v.as_array())
+                .ok_or("tabIds is required for get_multiple_page_contents")?
+                .iter()
+                .map(|v| v.as_u64().map(|id| id as u32).ok_or("tabIds must be numbers"))
+                .collect::<Result<_, _>>()?;
+            let include_metadata = args.get("includeMetadata").and_then(|v| v.as_bool()).unwrap_or(true);
+            let include_html = args.get("includeHtml").and_then(|v| v.as_bool()).unwrap_or(false);
+            let max_text_length = args.get("maxTextLength").and_then(|v| v.as_u64()).unwrap_or(30000) as usize;
+
+            server.handle_get_multiple_page_contents(&tab_ids, include_metadata, include_html, max_text_length).await
+                .map_err(|e| format!("Failed to get page contents: {}", e))?
+        }
+        "get_dom_snapshot" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let selector = args.get("selector").and_then(|v| v.as_str());
+            let max_nodes = args.get("maxNodes").and_then(|v| v.as_u64()).unwrap_or(500) as usize;
+            let include_styles = args.get("includeStyles").and_then(|v| v.as_bool()).unwrap_or(false);
+            let exclude_scripts = args.get("excludeScripts").and_then(|v| v.as_bool()).unwrap_or(true);
+            let exclude_styles = args.get("excludeStyles").and_then(|v| v.as_bool()).unwrap_or(true);
+            let pierce = args.get("pierce").and_then(|v| v.as_bool()).unwrap_or(false);
+            let flat = args.get("flat").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            let options = DomSnapshotOptions { selector, max_nodes, include_styles, exclude_scripts, exclude_styles, pierce };
+            let mut snapshot = server.handle_get_dom_snapshot(tab_id, options).await
+                .map_err(|e| format!("Failed to get DOM snapshot: {}", e))?;
+            if flat {
+                if let Some(root) = snapshot.as_object_mut().and_then(|snapshot| snapshot.remove("root")) {
+                    snapshot["nodes"] = crate::utils::dom::flatten_dom_tree(&root).into();
+                }
+            }
+            snapshot
+        }
+        "execute_javascript" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let code = args.get("code").and_then(|v| v.as_str()).ok_or("Missing JavaScript code")?;
+            let context_id = args.get("contextId").and_then(|v| v.as_str()).map(|s| s.to_string());
+            let await_promise = args.get("awaitPromise").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            server.handle_execute_javascript(tab_id, code.to_string(), context_id, await_promise).await
+                .map_err(|e| format!("Failed to execute JavaScript: {}", e))?
+        }
+        "list_running_scripts" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+
+            server.handle_list_running_scripts(tab_id)
+        }
+        "terminate_script" => {
+            let script_id = args.get("scriptId").and_then(|v| v.as_str()).ok_or("Missing scriptId")?;
+
+            server.handle_terminate_script(script_id).await
+                .map_err(|e| format!("Failed to terminate script: {}", e))?
+        }
+        "set_page_content" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for set_page_content")? as u32;
+            let html = args.get("html").and_then(|v| v.as_str()).ok_or("Missing html")?;
+
+            server.handle_set_page_content(tab_id, html.to_string()).await
+                .map_err(|e| format!("Failed to set page content: {}", e))?
+        }
+        "create_js_context" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for JavaScript contexts")? as u32;
+
+            server.handle_create_js_context(tab_id).await
+                .map_err(|e| format!("Failed to create JavaScript context: {}", e))?
+        }
+        "dispose_js_context" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for JavaScript contexts")? as u32;
+            let context_id = args.get("contextId").and_then(|v| v.as_str()).ok_or("Missing contextId")?;
+
+            server.handle_dispose_js_context(tab_id, context_id.to_string()).await
+                .map_err(|e| format!("Failed to dispose JavaScript context: {}", e))?
+        }
+        "get_console_messages" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let log_levels = args.get("logLevels").and_then(|v| v.as_array()).map(|arr| {
+                arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>()
+            });
+            let search_term = args.get("searchTerm").and_then(|v| v.as_str());
+            let since = args.get("since").and_then(|v| v.as_f64());
+            let page_size = args.get("pageSize").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+            let cursor = args.get("cursor").and_then(|v| v.as_str());
+
+            server.handle_get_console_messages(tab_id, log_levels, search_term, since, page_size, cursor).await
+                .map_err(|e| format!("Failed to get console messages: {}", e))?
+        }
+        "get_network_requests" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let method = args.get("method").and_then(|v| v.as_str());
+            let status = args.get("status");
+            let resource_type = args.get("resourceType").and_then(|v| v.as_str());
+            let domain = args.get("domain").and_then(|v| v.as_str());
+            let failed_only = args.get("failedOnly").and_then(|v| v.as_bool()).unwrap_or(false);
+            let page_size = args.get("pageSize").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+            let cursor = args.get("cursor").and_then(|v| v.as_str());
+            let include_response_bodies = args.get("includeResponseBodies").and_then(|v| v.as_bool()).unwrap_or(false);
+            let include_request_bodies = args.get("includeRequestBodies").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            server.handle_get_network_requests(
+                tab_id, method, status, resource_type, domain, failed_only,
+                page_size, cursor, include_response_bodies, include_request_bodies
+            ).await
+                .map_err(|e| format!("Failed to get network requests: {}", e))?
+        }
+        "get_network_request_body" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let request_id = args.get("requestId").and_then(|v| v.as_str()).ok_or("Missing requestId")?;
+
+            server.handle_get_network_request_body(tab_id, request_id).await
+                .map_err(|e| format!("Failed to get network request body: {}", e))?
+        }
+        "replay_request" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).ok_or("tabId is required for replay_request")? as u32;
+            let request_id = args.get("requestId").and_then(|v| v.as_str()).ok_or("Missing requestId")?;
+
+            server.handle_replay_request(tab_id, request_id).await
+                .map_err(|e| format!("Failed to replay request: {}", e))?
+        }
+        "download_resource" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for downloads")? as u32;
+            let url = args.get("url").and_then(|v| v.as_str()).ok_or("Missing url")?;
+
+            server.handle_download_resource(tab_id, url).await
+                .map_err(|e| format!("Failed to download resource: {}", e))?
+        }
+        "capture_screenshot" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let format = args.get("format").and_then(|v| v.as_str()).unwrap_or("png");
+            let quality = args.get("quality").and_then(|v| v.as_f64()).unwrap_or(90.0) as f32;
+            let max_width = args.get("maxWidth").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let max_height = args.get("maxHeight").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let clip = match args.get("clip") {
+                Some(clip) => Some(serde_json::from_value(clip.clone()).map_err(|e| format!("Invalid clip: {}", e))?),
+                None => None,
+            };
+            let capture_selector = args.get("captureSelector").and_then(|v| v.as_str()).map(|s| s.to_string());
+
+            server.handle_capture_screenshot(tab_id, format, quality, (max_width, max_height), clip, capture_selector).await
+                .map_err(|e| format!("Failed to capture screenshot: {}", e))?
+        }
+        "set_baseline_screenshot" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for baseline screenshots")? as u32;
+
+            server.handle_set_baseline_screenshot(tab_id).await
+                .map_err(|e| format!("Failed to set baseline screenshot: {}", e))?
+        }
+        "compare_screenshot" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for screenshot comparison")? as u32;
+            let threshold = args.get("threshold").and_then(|v| v.as_f64());
+            let include_diff_image = args.get("includeDiffImage").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            server.handle_compare_screenshot(tab_id, threshold, include_diff_image).await
+                .map_err(|e| format!("Failed to compare screenshot: {}", e))?
+        }
+        "get_performance_metrics" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+
+            server.handle_get_performance_metrics(tab_id).await
+                .map_err(|e| format!("Failed to get performance metrics: {}", e))?
+        }
+        "get_web_vitals_report" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for the web vitals report")? as u32;
+
+            let (report, age) = server.handle_get_web_vitals_report(tab_id).await
+                .map_err(|e| format!("Failed to get web vitals report: {}", e))?;
+            provenance = ("cache", age);
+            report
+        }
+        "get_resource_breakdown" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for get_resource_breakdown")? as u32;
+
+            let (breakdown, age) = server.handle_get_resource_breakdown(tab_id).await
+                .map_err(|e| format!("Failed to get resource breakdown: {}", e))?;
+            provenance = ("cache", age);
+            breakdown
+        }
+        "get_resource_usage" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+
+            server.handle_get_resource_usage(tab_id).await
+                .map_err(|e| format!("Failed to get resource usage: {}", e))?
+        }
+        "wait_for_network_idle" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let idle_ms = args.get("idleMs").and_then(|v| v.as_u64()).unwrap_or(500);
+            let timeout_ms = args.get("timeoutMs").and_then(|v| v.as_u64()).unwrap_or(10_000);
+            let max_inflight = args.get("maxInflight").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+
+            server.handle_wait_for_network_idle(tab_id, idle_ms, timeout_ms, max_inflight).await
+                .map_err(|e| format!("Failed waiting for network idle: {}", e))?
+        }
+        "wait_for_console_error" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let timeout_ms = args.get("timeoutMs").and_then(|v| v.as_u64()).unwrap_or(10_000);
+            let pattern = args.get("pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
+
+            server.handle_wait_for_console_error(tab_id, timeout_ms, pattern).await
+                .map_err(|e| format!("Failed waiting for console error: {}", e))?
+        }
+        "get_computed_style" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let selector = args.get("selector").and_then(|v| v.as_str()).ok_or("Missing selector")?;
+            let properties = args.get("properties").and_then(|v| v.as_array()).map(|arr| {
+                arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>()
+            });
+
+            server.handle_get_computed_style(tab_id, selector.to_string(), properties).await
+                .map_err(|e| format!("Failed to get computed style: {}", e))?
+        }
+        "get_links" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let same_origin_only = args.get("sameOriginOnly").and_then(|v| v.as_bool()).unwrap_or(false);
+            let include_text = args.get("includeText").and_then(|v| v.as_bool()).unwrap_or(true);
+
+            server.handle_get_links(tab_id, same_origin_only, include_text).await
+                .map_err(|e| format!("Failed to get links: {}", e))?
+        }
+        "press_key" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let key = args.get("key").and_then(|v| v.as_str()).ok_or("Missing key")?;
+            let selector = args.get("selector").and_then(|v| v.as_str()).map(|s| s.to_string());
+            let modifiers = args.get("modifiers")
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default();
+
+            server.handle_press_key(tab_id, selector, key.to_string(), modifiers).await
+                .map_err(|e| format!("Failed to press key: {}", e))?
+        }
+        "fill_form" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let fields = args.get("fields").and_then(|v| v.as_object()).ok_or("Missing fields")?
+                .iter()
+                .map(|(selector, value)| (selector.clone(), value.clone()))
+                .collect();
+
+            server.handle_fill_form(tab_id, fields).await
+                .map_err(|e| format!("Failed to fill form: {}", e))?
+        }
+        "get_element_rect" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let selector = args.get("selector").and_then(|v| v.as_str()).ok_or("Missing selector")?;
+
+            server.handle_get_element_rect(tab_id, selector.to_string()).await
+                .map_err(|e| format!("Failed to get element rect: {}", e))?
+        }
+        "evaluate_xpath" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let expression = args.get("expression").and_then(|v| v.as_str()).ok_or("Missing XPath expression")?;
+
+            server.handle_evaluate_xpath(tab_id, expression.to_string()).await
+                .map_err(|e| format!("Failed to evaluate XPath: {}", e))?
+        }
+        "observe_mutations" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let selector = args.get("selector").and_then(|v| v.as_str()).map(|s| s.to_string());
+            let duration_ms = args.get("durationMs").and_then(|v| v.as_u64()).unwrap_or(2_000);
+            let attributes = args.get("attributes").and_then(|v| v.as_bool()).unwrap_or(true);
+            let child_list = args.get("childList").and_then(|v| v.as_bool()).unwrap_or(true);
+            let subtree = args.get("subtree").and_then(|v| v.as_bool()).unwrap_or(true);
+
+            server.handle_observe_mutations(tab_id, selector, duration_ms, attributes, child_list, subtree).await
+                .map_err(|e| format!("Failed to observe mutations: {}", e))?
+        }
+        "get_navigation_history" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for navigation history")? as u32;
+
+            server.handle_get_navigation_history(tab_id).await
+                .map_err(|e| format!("Failed to get navigation history: {}", e))?
+        }
+        "clear_browsing_data" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for clear_browsing_data")? as u32;
+            let flag = |name: &str| args.get(name).and_then(|v| v.as_bool()).unwrap_or(false);
+
+            server.handle_clear_browsing_data(tab_id, flag("cache"), flag("cookies"), flag("localStorage")).await
+                .map_err(|e| format!("Failed to clear browsing data: {}", e))?
+        }
+        "capture_mhtml" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for capture_mhtml")? as u32;
+
+            server.handle_capture_mhtml(tab_id).await
+                .map_err(|e| format!("Failed to capture MHTML: {}", e))?
+        }
+        "get_layout_metrics" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for layout metrics")? as u32;
+
+            server.handle_get_layout_metrics(tab_id).await
+                .map_err(|e| format!("Failed to get layout metrics: {}", e))?
+        }
+        "get_capabilities" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for capabilities")? as u32;
+
+            server.handle_get_capabilities(tab_id).await
+                .map_err(|e| format!("Failed to get capabilities: {}", e))?
+        }
+        "go_back_forward" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let delta = args.get("delta").and_then(|v| v.as_i64()).ok_or("Missing delta")?;
+            let delta = i32::try_from(delta).map_err(|_| "delta is out of range")?;
+
+            server.handle_go_back_forward(tab_id, delta).await
+                .map_err(|e| format!("Failed to navigate history: {}", e))?
+        }
+        "set_intercept_rules" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for intercept rules")? as u32;
+            let rules = args.get("rules").cloned().ok_or("Missing rules")?;
+            let rules: Vec<InterceptRule> = serde_json::from_value(rules)
+                .map_err(|e| format!("Invalid intercept rules: {}", e))?;
+
+            server.handle_set_intercept_rules(tab_id, rules).await
+                .map_err(|e| format!("Failed to set intercept rules: {}", e))?
+        }
+        "clear_intercept_rules" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for intercept rules")? as u32;
+
+            server.handle_clear_intercept_rules(tab_id).await
+                .map_err(|e| format!("Failed to clear intercept rules: {}", e))?
+        }
+        "set_extra_headers" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for extra headers")? as u32;
+            let headers = args.get("headers").cloned().ok_or("Missing headers")?;
+            let headers: std::collections::HashMap<String, String> = serde_json::from_value(headers)
+                .map_err(|e| format!("Invalid headers: {}", e))?;
+
+            server.handle_set_extra_headers(tab_id, headers).await
+                .map_err(|e| format!("Failed to set extra headers: {}", e))?
+        }
+        "clear_extra_headers" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for extra headers")? as u32;
+
+            server.handle_clear_extra_headers(tab_id).await
+                .map_err(|e| format!("Failed to clear extra headers: {}", e))?
+        }
+        "get_user_agent" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for get_user_agent")? as u32;
+
+            server.handle_get_user_agent(tab_id).await
+                .map_err(|e| format!("Failed to get user agent: {}", e))?
+        }
+        "set_user_agent" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for set_user_agent")? as u32;
+            let user_agent = args.get("userAgent").and_then(|v| v.as_str()).ok_or("Missing userAgent")?;
+            let accept_language = args.get("acceptLanguage").and_then(|v| v.as_str()).map(String::from);
+
+            server.handle_set_user_agent(tab_id, user_agent.to_string(), accept_language).await
+                .map_err(|e| format!("Failed to set user agent: {}", e))?
+        }
+        "get_emulated_media" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for get_emulated_media")? as u32;
+
+            server.handle_get_emulated_media(tab_id)
+        }
+        "set_emulated_media" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for set_emulated_media")? as u32;
+            let media = args.get("media").and_then(|v| v.as_str()).map(String::from);
+            let features: Vec<MediaFeature> = match args.get("features") {
+                Some(features) => serde_json::from_value(features.clone())
+                    .map_err(|e| format!("Invalid features: {}", e))?,
+                None => Vec::new(),
+            };
+
+            server.handle_set_emulated_media(tab_id, media, features).await
+                .map_err(|e| format!("Failed to set emulated media: {}", e))?
+        }
+        "add_init_script" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for init scripts")? as u32;
+            let source = args.get("source").and_then(|v| v.as_str()).ok_or("Missing source")?;
+
+            server.handle_add_init_script(tab_id, source.to_string()).await
+                .map_err(|e| format!("Failed to add init script: {}", e))?
+        }
+        "remove_init_script" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for init scripts")? as u32;
+            let script_id = args.get("scriptId").and_then(|v| v.as_str()).ok_or("Missing scriptId")?;
+
+            server.handle_remove_init_script(tab_id, script_id).await
+                .map_err(|e| format!("Failed to remove init script: {}", e))?
+        }
+        "get_accessibility_tree" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let timeout = args.get("timeout").and_then(|v| v.as_u64());
+
+            server.handle_get_accessibility_tree(tab_id, timeout).await
+                .map_err(|e| format!("Failed to get accessibility tree: {}", e))?
+        }
+        "get_accessibility_node" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+            let selector = args.get("selector").and_then(|v| v.as_str()).ok_or("Missing selector")?;
+
+            server.handle_get_accessibility_node(tab_id, selector.to_string()).await
+                .map_err(|e| format!("Failed to get accessibility node: {}", e))?
+        }
+        "get_accessibility_issues" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for get_accessibility_issues")? as u32;
+
+            server.handle_get_accessibility_issues(tab_id).await
+                .map_err(|e| format!("Failed to get accessibility issues: {}", e))?
+        }
+        "get_browser_tabs" => {
+            server.handle_get_browser_tabs().await
+                .map_err(|e| format!("Failed to get browser tabs: {}", e))?
+        }
+        "get_windows" => {
+            server.handle_get_windows().await
+                .map_err(|e| format!("Failed to get windows: {}", e))?
+        }
+        "get_current_location" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+
+            server.handle_get_current_location(tab_id).await
+                .map_err(|e| format!("Failed to get current location: {}", e))?
+        }
+        "get_structured_data" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required")? as u32;
+
+            let (data, age) = server.handle_get_structured_data(tab_id).await
+                .map_err(|e| format!("Failed to get structured data: {}", e))?;
+            if let Some(age) = age {
+                provenance = ("cache", age);
+            }
+            data
+        }
+        "get_tab_activity" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required")? as u32;
+            let limit = args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize);
+
+            server.handle_get_tab_activity(tab_id, limit)
+                .map_err(|e| format!("Failed to get tab activity: {}", e))?
+        }
+        "pause_execution" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for debugger operations")? as u32;
+
+            server.handle_pause_execution(tab_id).await
+                .map_err(|e| format!("Failed to pause execution: {}", e))?
+        }
+        "resume_execution" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for debugger operations")? as u32;
+
+            server.handle_resume_execution(tab_id).await
+                .map_err(|e| format!("Failed to resume execution: {}", e))?
+        }
+        "attach_debugger" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for debugger operations")? as u32;
+
+            server.handle_attach_debugger(tab_id).await
+                .map_err(|e| format!("Failed to attach debugger: {}", e))?
+        }
+        "detach_debugger" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
+                .ok_or("tabId is required for debugger operations")? as u32;
+
+            server.handle_detach_debugger(tab_id).await
+                .map_err(|e| format!("Failed to detach debugger: {}", e))?
+        }
+        "run_macro" => {
+            let tab_id = args.get("tabId").and_then(|v| v.as_u64());
+            let steps = args.get("steps").and_then(|v| v.as_array()).ok_or("Missing steps")?;
+            let continue_on_error = args.get("continueOnError").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            run_macro(server.clone(), tab_id, steps, continue_on_error).await?
+        }
+        _ => return Err(format!("Unknown tool: {}", tool_name)),
+    };
+
+    if let Some(key) = cache_key {
+        server.data_cache.cache_response(key, result.clone());
+    } else if let Some(tab_id) = args.get("tabId").and_then(|v| v.as_u64()) {
+        // Any other tool may have changed the page, so cached reads of the tab are stale
+        server.data_cache.invalidate_responses(tab_id as u32);
+    }
+
+    let (source, age) = provenance;
+    Ok(with_result_metadata(&server, result, source, age))
+}
+
+/// Stand-in result for a validated call in dry-run mode, so MCP integrations can be
+/// exercised without a browser. Nothing is sent and nothing is cached.
+fn dry_run_result(tool_name: &str, args: &Value) -> Result<Value, String> {
+    if !TOOL_ARGUMENT_VALIDATORS.contains_key(tool_name) {
+        return Err(format!("Unknown tool: {}", tool_name));
+    }
+    let mut arguments = args.clone();
+    if let Some(arguments) = arguments.as_object_mut() {
+        arguments.remove("_dryRun");
+    }
+    let mut result = dry_run_stub(tool_name, &arguments)?;
+    if let Some(object) = result.as_object_mut() {
+        object.insert("dryRun".to_string(), true.into());
+        object.insert("tool".to_string(), tool_name.into());
+        object.insert("arguments".to_string(), arguments);
+        object.insert(
+            "message".to_string(),
+            format!("Arguments are valid for {}; nothing was sent to the browser", tool_name).into(),
+        );
+    }
+    Ok(result)
+}
+
+/// Empty result in the shape the tool returns live, so callers can exercise their parsing
+/// in dry-run mode. Tools that only report success get an empty object.
+fn dry_run_stub(tool_name: &str, args: &Value) -> Result<Value, String> {
+    let tab_id = args.get("tabId").cloned().unwrap_or(Value::Null);
+    let stub = match tool_name {
+        "get_page_content" => serde_json::json!({
+            "url": "",
+            "title": "",
+            "text": "",
+            "textTruncated": false,
+            "originalTextSize": 0
+        }),
+        "get_multiple_page_contents" => serde_json::json!({
+            "pages": {},
+            "succeeded": 0,
+            "failed": 0,
+            "elapsedMs": 0
+        }),
+        "get_dom_snapshot" => serde_json::json!({
+            "root": null,
+            "nodeCount": 0,
+            "originalNodeCount": 0,
+            "truncated": false,
+            "filters": {
+                "selector": args.get("selector"),
+                "maxNodes": args.get("maxNodes"),
+                "excludeScripts": args.get("excludeScripts"),
+                "excludeStyles": args.get("excludeStyles"),
+                "pierce": args.get("pierce")
+            }
+        }),
+        "execute_javascript" => serde_json::json!({ "result": null }),
+        "get_console_messages" => serde_json::json!({
+            "messages": [],
+            "count": 0,
+            "total": 0,
+            "hasMore": false,
+            "nextCursor": null,
+            "filters": {
+                "logLevels": args.get("logLevels"),
+                "searchTerm": args.get("searchTerm"),
+                "since": args.get("since")
+            }
+        }),
+        "get_network_requests" => serde_json::json!({
+            "requests": [],
+            "count": 0,
+            "total": 0,
+            "hasMore": false,
+            "nextCursor": null,
+            "filters": {
+                "method": args.get("method"),
+                "status": args.get("status"),
+                "resourceType": args.get("resourceType"),
+                "domain": args.get("domain"),
+                "failedOnly": args.get("failedOnly")
+            }
+        }),
+        "capture_screenshot" => {
+            let format = args.get("format").and_then(|v| v.as_str()).unwrap_or("png");
+            serde_json::json!({
+                "format": format,
+                "mimeType": format!("image/{}", format),
+                "width": 0,
+                "height": 0,
+                "dataLength": 0,
+                "scale": 1.0
+            })
+        }
+        "get_performance_metrics" => serde_json::json!({
+            "timing": {},
+            "resources": [],
+            "webVitals": {},
+            "memory": {}
+        }),
+        "get_web_vitals_report" => serde_json::json!({
+            "verdict": null,
+            "passed": false,
+            "largest_contentful_paint": null,
+            "first_input_delay": null,
+            "cumulative_layout_shift": null,
+            "tabId": tab_id,
+            "measuredAt": null
+        }),
+        "get_resource_breakdown" => {
+            let mut value = to_json_value(&ResourceBreakdown::new(&[], &[])).map_err(|e| e.to_string())?;
+            value["tabId"] = tab_id;
+            value
+        }
+        "get_resource_usage" => to_json_value(&ResourceUsage::default()).map_err(|e| e.to_string())?,
+        "get_accessibility_tree" => serde_json::json!({
+            "tree": null,
+            "summary": { "totalNodes": 0 }
+        }),
+        "get_browser_tabs" => serde_json::json!({ "tabs": [] }),
+        "get_windows" => serde_json::json!({
+            "windows": [],
+            "windowCount": 0,
+            "tabCount": 0
+        }),
+        "get_current_location" => serde_json::json!({ "url": "", "title": "" }),
+        "get_structured_data" => serde_json::json!({
+            "url": "",
+            "jsonLd": [],
+            "invalidJsonLd": [],
+            "meta": {},
+            "source": "live"
+        }),
+        "get_links" => serde_json::json!({ "count": 0, "links": [] }),
+        "get_element_rect" => serde_json::json!({
+            "boundingBox": { "x": 0.0, "y": 0.0, "width": 0.0, "height": 0.0 },
+            "isVisible": false,
+            "isInViewport": false,
+            "selector": args.get("selector")
+        }),
+        "evaluate_xpath" => {
+            let mut value = to_json_value(&XPathResult::Nodes(Vec::new())).map_err(|e| e.to_string())?;
+            value["count"] = 0.into();
+            value
+        }
+        "get_navigation_history" | "go_back_forward" => {
+            let mut value = serde_json::json!({
+                "current_index": 0,
+                "entries": [],
+                "tabId": tab_id,
+                "count": 0
+            });
+            if let Some(delta) = args.get("delta") {
+                value["delta"] = delta.clone();
+            }
+            value
+        }
+        "get_layout_metrics" => serde_json::json!({
+            "tabId": tab_id,
+            "layoutMetrics": {
+                "scrollX": 0.0,
+                "scrollY": 0.0,
+                "scrollWidth": 0.0,
+                "scrollHeight": 0.0,
+                "viewportWidth": 0.0,
+                "viewportHeight": 0.0,
+                "devicePixelRatio": 1.0
+            }
+        }),
+        "get_tab_activity" => serde_json::json!({
+            "tabId": tab_id,
+            "count": 0,
+            "invocations": []
+        }),
+        _ => serde_json::json!({}),
+    };
+    Ok(stub)
+}
+
+/// Record where a tool result came from when `monitoring.include_result_metadata` is on.
+/// Only object results have room for it.
+fn with_result_metadata(server: &SimpleBrowserMcpServer, mut result: Value, source: &str, age: Duration) -> Value {
+    if server.config.monitoring.include_result_metadata {
+        if let Some(object) = result.as_object_mut() {
+            object.insert(
+                "_meta".to_string(),
+                serde_json::json!({ "source": source, "age_ms": age.as_millis() as u64 }),
+            );
+        }
+    }
+    result
+}
+
+/// Run macro steps in order, stopping at the first failure unless `continue_on_error`.
+/// Steps without a tabId run against the macro's tab.
+async fn run_macro(
+    server: Arc<SimpleBrowserMcpServer>,
+    tab_id: Option<u64>,
+    steps: &[Value],
+    continue_on_error: bool,
+) -> Result<Value, String> {
+    let mut calls = Vec::with_capacity(steps.len());
+    for (index, step) in steps.iter().enumerate() {
+        let tool = step
+            .get("tool")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Step {} is missing a tool name", index))?;
+        if tool == "run_macro" {
+            return Err(format!("Step {}: macros cannot be nested", index));
+        }
+        let mut arguments = match step.get("arguments") {
+            None | Some(Value::Null) => serde_json::Map::new(),
+            Some(Value::Object(map)) => map.clone(),
+            Some(_) => return Err(format!("Step {}: arguments must be an object", index)),
+        };
+        if let Some(tab_id) = tab_id {
+            arguments.entry("tabId").or_insert_with(|| Value::from(tab_id));
+        }
+        calls.push(serde_json::json!({ "name": tool, "arguments": arguments }));
+    }
+
+    let mut results = Vec::with_capacity(calls.len());
+    let mut failed = 0;
+    for (index, params) in calls.iter().enumerate() {
+        let tool = &params["name"];
+        match Box::pin(call_tool(server.clone(), params)).await {
+            Ok(result) => results.push(serde_json::json!({
+                "step": index,
+                "tool": tool,
+                "success": true,
+                "result": result
+            })),
+            Err(error) => {
+                failed += 1;
+                results.push(serde_json::json!({
+                    "step": index,
+                    "tool": tool,
+                    "success": false,
+                    "error": error
+                }));
+                if !continue_on_error {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(serde_json::json!({
+        "success": failed == 0,
+        "completed": results.len(),
+        "total": calls.len(),
+        "aborted": results.len() < calls.len(),
+        "steps": results
+    }))
+}
+
+/// Emit one structured event per tools/call for log pipelines. With request logging
+/// disabled only failures are logged.
+fn log_tool_call(enabled: bool, params: &Value, duration: Duration, result: &Result<Value, String>) {
+    let tool = params.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+    let tab_id = params.get("arguments").and_then(|a| a.get("tabId")).and_then(|v| v.as_u64());
+    let duration_ms = duration.as_millis() as u64;
+
+    match result {
+        Ok(_) if enabled => {
+            tracing::info!(tool, tab_id, duration_ms, success = true, "tool call completed");
+        }
+        Ok(_) => {}
+        Err(error) => {
+            tracing::warn!(tool, tab_id, duration_ms, success = false, error = error.as_str(), "tool call failed");
+        }
+    }
+}
+
+/// Tools that don't act on a single tab, so URL access control doesn't apply here.
+/// Macro steps and each of get_multiple_page_contents' tabs are checked as they run.
+const TABLESS_TOOLS: &[&str] = &[
+    "get_browser_tabs",
+    "get_windows",
+    "run_macro",
+    "get_multiple_page_contents",
+    "list_running_scripts",
+    "terminate_script",
+];
+
+/// Tools that work while the tab's JavaScript is paused in the debugger.
+const RUNS_WHILE_PAUSED_TOOLS: &[&str] = &[
+    "pause_execution",
+    "resume_execution",
+    "detach_debugger",
+    "capture_screenshot",
+    "get_console_messages",
+    "get_network_requests",
+    "get_current_location",
+    "get_tab_activity",
+];
+
+/// Tools whose results only change when the page does, so they are safe to cache briefly.
+const CACHEABLE_TOOLS: &[&str] = &[
+    "get_page_content",
+    "get_dom_snapshot",
+    "get_accessibility_tree",
+    "get_performance_metrics",
+    "get_computed_style",
+    "get_links",
+];
+
+/// Wrap result in MCP tool response format. A `_meta` object moves up to the
+/// MCP result, where the protocol reserves it, and a `_resource` becomes an
+/// embedded resource next to the text. `pretty` follows `monitoring.pretty_json`.
+fn tool_result_content(result: &Value, pretty: bool) -> Result<Value, String> {
+    let meta = result.get("_meta").cloned();
+    let resource = result.get("_resource").cloned();
+    let text = match result.as_object() {
+        Some(object) if meta.is_some() || resource.is_some() => {
+            let body: serde_json::Map<String, Value> = object
+                .iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "_meta" | "_resource"))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            to_json_string(&body, pretty)
+        }
+        _ => to_json_string(result, pretty),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut content = vec![serde_json::json!({
+        "type": "text",
+        "text": text
+    })];
+    if let Some(resource) = resource {
+        content.push(serde_json::json!({ "type": "resource", "resource": resource }));
+    }
+
+    let mut wrapped = serde_json::json!({ "content": content });
+    if let Some(meta) = meta {
+        wrapped["_meta"] = meta;
+    }
+    Ok(wrapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::types::browser::{InterceptAction, PageContent};
+    use axum_test::TestServer;
+
+    /// Requests a mock browser has received, oldest first.
+    type SentRequests = Arc<parking_lot::Mutex<Vec<Value>>>;
+
+    /// Attach a mock browser to `tab_id` that records each request before `respond`
+    /// answers it.
+    fn recording_mock_browser<F, Fut>(server: &SimpleBrowserMcpServer, tab_id: u32, respond: F) -> SentRequests
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Value, String>> + Send + 'static,
+    {
+        let sent = SentRequests::default();
+        server.connection_pool.attach_mock_browser(Some(tab_id), {
+            let sent = sent.clone();
+            move |request| {
+                sent.lock().push(request.clone());
+                respond(request)
+            }
+        });
+        sent
+    }
+
+    /// The `action` of each request a recording mock browser received.
+    fn sent_actions(sent: &SentRequests) -> Vec<Value> {
+        sent.lock().iter().map(|request| request["action"].clone()).collect()
+    }
+
+    /// Params for calling `name` through `call_tool` or `handle_tool_call`.
+    fn tool_params(name: &str, arguments: Value) -> Value {
+        serde_json::json!({ "name": name, "arguments": arguments })
+    }
+
+    /// Start a tool call in the background, for calls that only finish once the test
+    /// does something else.
+    fn spawn_tool_call(
+        server: &Arc<SimpleBrowserMcpServer>,
+        name: &str,
+        arguments: Value,
+    ) -> tokio::task::JoinHandle<Result<Value, String>> {
+        let (server, params) = (server.clone(), tool_params(name, arguments));
+        tokio::spawn(async move { call_tool(server, &params).await })
+    }
+
+    /// The JSON a tool wrote as the text of its MCP result.
+    fn result_text(result: &Value) -> Value {
+        serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_combined_server_creation() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .route("/ws", get(handle_websocket_upgrade))
+            .route("/health", get(handle_health_check))
+            .route("/cleanup-connections", post(handle_cleanup_connections))
+            .layer(CorsLayer::permissive())
+            .with_state(server);
+
+        let test_server = TestServer::new(app).unwrap();
+
+        // Test health endpoint
+        let response = test_server.get("/health").await;
+        assert_eq!(response.status_code(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_mcp_initialize() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+
+        let test_server = TestServer::new(app).unwrap();
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {}
+        });
+
+        let response = test_server.post("/mcp").json(&request).await;
+        assert_eq!(response.status_code(), 200);
+
+        let body: Value = response.json();
+        assert_eq!(body["jsonrpc"], "2.0");
+        assert_eq!(body["id"], 1);
+        assert!(body["result"].is_object());
+        assert_eq!(body["result"]["protocolVersion"], DEFAULT_PROTOCOL_VERSION);
+        assert!(body["result"]["capabilities"]["tools"].is_object());
+        assert!(body["result"]["capabilities"].get("prompts").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_protocol_version() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+        let test_server = TestServer::new(app).unwrap();
+
+        let initialize = |version: &str| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": version, "capabilities": {} }
+            })
+        };
+
+        // A version we support is echoed back
+        let body: Value = test_server.post("/mcp").json(&initialize("2025-03-26")).await.json();
+        assert_eq!(body["result"]["protocolVersion"], "2025-03-26");
+
+        // A newer client gets our newest version
+        let body: Value = test_server.post("/mcp").json(&initialize("2099-01-01")).await.json();
+        assert_eq!(body["result"]["protocolVersion"], SUPPORTED_PROTOCOL_VERSIONS[0]);
+
+        // An older-than-supported or malformed version is rejected
+        for version in ["2023-01-01", "v1"] {
+            let body: Value = test_server.post("/mcp").json(&initialize(version)).await.json();
+            assert!(body.get("result").is_none());
+            assert_eq!(body["error"]["code"], -32602);
+            assert_eq!(body["error"]["data"]["requested"], version);
+            assert_eq!(body["error"]["data"]["supported"][1], "2024-11-05");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_returns_59_tools() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+
+        let test_server = TestServer::new(app).unwrap();
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/list"
+        });
+
+        let response = test_server.post("/mcp").json(&request).await;
+        let body: Value = response.json();
+        let tools = body["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 59, "Expected 59 tools, got {}", tools.len());
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_can_list_tools_over_websocket() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let app = Router::new()
+            .route("/ws", get(handle_websocket_upgrade))
+            .with_state(server.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+
+        let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        type ClientSocket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+        async fn reply(socket: &mut ClientSocket, request: Value) -> Value {
+            socket.send(TungsteniteMessage::Text(request.to_string())).await.unwrap();
+            loop {
+                match tokio::time::timeout(std::time::Duration::from_secs(5), socket.next()).await {
+                    Ok(Some(Ok(TungsteniteMessage::Text(text)))) => return serde_json::from_str(&text).unwrap(),
+                    Ok(Some(Ok(_))) => continue,
+                    other => panic!("no reply over WebSocket: {:?}", other),
+                }
+            }
+        }
+
+        let body = reply(&mut socket, serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })).await;
+        assert_eq!(body["id"], 1);
+        assert!(body["result"]["capabilities"]["tools"].is_object());
+        assert_eq!(server.sessions.len(), 1);
+
+        let body = reply(&mut socket, serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).await;
+        assert_eq!(body["id"], 2);
+        let tools = body["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "get_page_content"));
+
+        // The MCP client is not a browser connection, so requests are never routed to it
+        assert_eq!(server.get_health_status().await.active_connections, 0);
+        assert!(server.connection_pool.get_active_connections().await.is_empty());
+
+        // Closing the socket ends its session
+        socket.close(None).await.unwrap();
+        for _ in 0..50 {
+            if server.sessions.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(server.sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_upgrade_negotiates_protocol_version() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::{
+            connect_async,
+            tungstenite::{client::IntoClientRequest, protocol::frame::coding::CloseCode, Message as TungsteniteMessage},
+        };
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let app = Router::new()
+            .route("/ws", get(handle_websocket_upgrade))
+            .with_state(server.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+        let request = |protocols: &str| {
+            let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+            request.headers_mut().insert("Sec-WebSocket-Protocol", protocols.parse().unwrap());
+            request
+        };
+
+        let (_socket, response) = connect_async(request("browser-mcp.v2, browser-mcp.v1")).await.unwrap();
+        assert_eq!(response.headers()["Sec-WebSocket-Protocol"], "browser-mcp.v1");
+        for _ in 0..50 {
+            if !server.connection_pool.describe_connections().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.connection_pool.describe_connections()[0]["protocol"], "browser-mcp.v1");
+
+        let (mut socket, _) = connect_async(request("browser-mcp.v9")).await.unwrap();
+        match tokio::time::timeout(std::time::Duration::from_secs(5), socket.next()).await {
+            Ok(Some(Ok(TungsteniteMessage::Close(Some(frame))))) => {
+                assert_eq!(frame.code, CloseCode::Protocol);
+                assert!(frame.reason.contains("browser-mcp.v9"), "{}", frame.reason);
+                assert!(frame.reason.contains("server speaks browser-mcp.v1"), "{}", frame.reason);
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        assert_eq!(server.connection_pool.describe_connections().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_websocket_frame_drops_connection() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
+
+        let mut config = ServerConfig::default();
+        config.connections.max_message_bytes = 1024;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/ws", get(handle_websocket_upgrade))
+            .with_state(server.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+
+        let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        socket
+            .send(TungsteniteMessage::Text("x".repeat(1025)))
+            .await
+            .unwrap();
+
+        // The server must close (or drop) the socket rather than process the frame
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                match socket.next().await {
+                    Some(Ok(TungsteniteMessage::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok(), "connection was not dropped after an oversized frame");
+
+        // Pool cleanup runs right after the close is queued
+        for _ in 0..50 {
+            if server.connection_pool.get_active_connections().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let stats = server.connection_pool.get_stats();
+        assert_eq!(stats.connection_errors.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert!(server.connection_pool.get_active_connections().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reset_returns_snapshot_and_zeroes_counters() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server.clone());
+        let test_server = TestServer::new(app).unwrap();
+
+        // Record a tool call and a cache miss
+        test_server
+            .post("/mcp")
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "get_browser_tabs", "arguments": {} }
+            }))
+            .await;
+        assert!(server.data_cache.get_tab_data(42).await.is_none());
+
+        let response = test_server
+            .post("/mcp")
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "metrics/reset" }))
+            .await;
+        let body: Value = response.json();
+        assert_eq!(body["result"]["requests"]["total"], 1);
+        assert_eq!(body["result"]["requests"]["successful"], 1);
+        assert_eq!(body["result"]["cache"]["misses"], 1);
+
+        let metrics = server.request_handler.get_metrics();
+        assert_eq!(metrics.total_requests, 0);
+        assert_eq!(metrics.successful_requests, 0);
+        assert_eq!(server.data_cache.get_cache_stats().await, (0, 0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reset_requires_the_auth_token_when_configured() {
+        let mut config = ServerConfig::default();
+        config.security.auth_token = Some("s3cret".to_string());
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server.clone());
+        let test_server = TestServer::new(app).unwrap();
+        let reset = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "metrics/reset" });
+
+        test_server
+            .post("/mcp")
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "tools/call",
+                "params": { "name": "get_browser_tabs", "arguments": {} }
+            }))
+            .await;
+
+        let response = test_server.post("/mcp").json(&reset).await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+        let body: Value = response.json();
+        assert_eq!(body["error"]["message"], "Unauthorized");
+        let response = test_server
+            .post("/mcp")
+            .add_header(axum::http::header::AUTHORIZATION, axum::http::HeaderValue::from_static("Bearer wrong"))
+            .json(&reset)
+            .await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request_handler.get_metrics().total_requests, 1);
+
+        let response = test_server
+            .post("/mcp")
+            .add_header(axum::http::header::AUTHORIZATION, axum::http::HeaderValue::from_static("Bearer s3cret"))
+            .json(&reset)
+            .await;
+        let body: Value = response.json();
+        assert_eq!(body["result"]["requests"]["total"], 1);
+        assert_eq!(server.request_handler.get_metrics().total_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_logging_set_level_reloads_filter() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (layer, log_filter) = crate::utils::LogFilter::new("browser_mcp_rust_server=info".into());
+        // The handle only works while the layer is installed somewhere
+        let _subscriber = tracing_subscriber::Registry::default().with(layer);
+
+        let mut server = SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap();
+        server.set_log_filter(log_filter.clone());
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(Arc::new(server));
+        let test_server = TestServer::new(app).unwrap();
+        let set_level = |level: &str| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "logging/setLevel",
+                "params": { "level": level }
+            })
+        };
+
+        let body: Value = test_server
+            .post("/mcp")
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }))
+            .await
+            .json();
+        assert!(body["result"]["capabilities"]["logging"].is_object());
+
+        let body: Value = test_server.post("/mcp").json(&set_level("debug")).await.json();
+        assert_eq!(body["result"], serde_json::json!({}));
+        assert_eq!(log_filter.current(), "browser_mcp_rust_server=debug");
+
+        let body: Value = test_server.post("/mcp").json(&set_level("warning")).await.json();
+        assert_eq!(body["result"], serde_json::json!({}));
+        assert_eq!(log_filter.current(), "browser_mcp_rust_server=warn");
+
+        // Unknown levels leave the filter alone
+        let body: Value = test_server.post("/mcp").json(&set_level("verbose")).await.json();
+        assert!(body["error"]["data"].as_str().unwrap().contains("Unknown log level 'verbose'"));
+        assert_eq!(log_filter.current(), "browser_mcp_rust_server=warn");
+    }
+
+    #[tokio::test]
+    async fn test_browser_requests_are_logged_with_tool_call_correlation() {
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(3, true).await;
+        let sent = recording_mock_browser(&server, 3, |_| async { Ok(serde_json::json!({})) });
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+        let test_server = TestServer::new(app).unwrap();
+
+        test_server
+            .post("/mcp")
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 42,
+                "method": "tools/call",
+                "params": { "name": "get_performance_metrics", "arguments": { "tabId": 3 } }
+            }))
+            .await;
+
+        let request_id = sent.lock().first().expect("browser request was sent")["requestId"].as_str().unwrap().to_string();
+        let output = logs.contents();
+        let line = output
+            .lines()
+            .find(|line| line.contains("Sending browser request for tool call"))
+            .unwrap_or_else(|| panic!("no correlation line in logs:\n{}", output));
+        for field in [
+            "rpc_id=42".to_string(),
+            "tool=get_performance_metrics".to_string(),
+            format!("request_id={}", request_id),
+            "tab_id=3".to_string(),
+            "action=\"getPerformanceMetrics\"".to_string(),
+        ] {
+            assert!(line.contains(&field), "missing {} in: {}", field, line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_notification_aborts_in_flight_tool_call() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(5, true).await;
+        let sent = recording_mock_browser(&server, 5, |request| async move {
+            if request["action"] == "getPerformanceMetrics" {
+                std::future::pending::<()>().await;
+            }
+            Ok(serde_json::json!({}))
+        });
+
+        // Call the handler directly: axum-test serializes requests, so it can't overlap them
+        let call = tokio::spawn(handle_mcp_request(
+            State(server.clone()),
+            HeaderMap::new(),
+            Json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "tools/call",
+                "params": { "name": "get_performance_metrics", "arguments": { "tabId": 5 } }
+            })),
+        ));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        handle_mcp_request(
+            State(server.clone()),
+            HeaderMap::new(),
+            Json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": 7, "reason": "user aborted" }
+            })),
+        )
+        .await;
+
+        let response = tokio::time::timeout(Duration::from_secs(2), call)
+            .await
+            .expect("cancelled call did not terminate promptly")
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["id"], 7);
+        assert_eq!(body["error"]["data"], "Request cancelled by client");
+        assert_eq!(server.connection_pool.pending_request_count(), 0);
+
+        // The mock browser is told to stop working on the abandoned request
+        for _ in 0..50 {
+            if sent.lock().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(sent_actions(&sent), vec!["getPerformanceMetrics", "cancelRequest"]);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_only_reaches_the_sessions_own_call() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(5, true).await;
+        server.connection_pool.attach_mock_browser(Some(5), |_| async move {
+            std::future::pending::<()>().await;
+            Ok(serde_json::json!({}))
+        });
+        let session_headers = || {
+            let mut headers = HeaderMap::new();
+            headers.insert(SESSION_HEADER, server.sessions.create().parse().unwrap());
+            headers
+        };
+        let (session_a, session_b) = (session_headers(), session_headers());
+        // Both sessions number their first call 1
+        let call = |headers: &HeaderMap| {
+            tokio::spawn(handle_mcp_request(
+                State(server.clone()),
+                headers.clone(),
+                Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "tools/call",
+                    "params": { "name": "get_performance_metrics", "arguments": { "tabId": 5 } }
+                })),
+            ))
+        };
+        let cancel = |headers: &HeaderMap| {
+            handle_mcp_request(
+                State(server.clone()),
+                headers.clone(),
+                Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/cancelled",
+                    "params": { "requestId": 1 }
+                })),
+            )
+        };
+
+        // B's call starts first, so A's would overwrite it in a map keyed by id alone
+        let call_b = call(&session_b);
+        while server.connection_pool.pending_request_count() < 1 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let call_a = call(&session_a);
+        while server.connection_pool.pending_request_count() < 2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        cancel(&session_b).await;
+        let response = tokio::time::timeout(Duration::from_secs(2), call_b)
+            .await
+            .expect("session B's call was not cancelled")
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["data"], "Request cancelled by client");
+
+        // Session A's call with the same id is still running
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!call_a.is_finished());
+        assert_eq!(server.connection_pool.pending_request_count(), 1);
+
+        cancel(&session_a).await;
+        tokio::time::timeout(Duration::from_secs(2), call_a)
+            .await
+            .expect("session A's call was not cancelled")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_js_context_persists_state_between_calls() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(9, true).await;
+
+        // Fake extension: each context is a tiny variable store fed by `name = value` scripts
+        let contexts: Arc<dashmap::DashMap<String, std::collections::HashMap<String, Value>>> =
+            Arc::new(dashmap::DashMap::new());
+        server.connection_pool.attach_mock_browser(Some(9), {
+            let contexts = contexts.clone();
+            move |request| {
+                let contexts = contexts.clone();
+                async move {
+                    match request["action"].as_str() {
+                        Some("createJsContext") => {
+                            contexts.insert("ctx-1".to_string(), Default::default());
+                            Ok(serde_json::json!({ "contextId": "ctx-1" }))
+                        }
+                        Some("executeScript") => {
+                            let id = request["contextId"].as_str().ok_or("no context")?;
+                            let mut vars = contexts.get_mut(id).ok_or("context gone")?;
+                            let script = request["script"].as_str().unwrap_or_default();
+                            Ok(match script.split_once('=') {
+                                Some((name, value)) => {
+                                    let value: Value = serde_json::from_str(value.trim()).unwrap();
+                                    vars.insert(name.trim().to_string(), value.clone());
+                                    value
+                                }
+                                None => vars.get(script.trim()).cloned().unwrap_or(Value::Null),
+                            })
+                        }
+                        Some("disposeJsContext") => {
+                            contexts.remove(request["contextId"].as_str().unwrap_or_default());
+                            Ok(serde_json::json!({ "status": "disposed" }))
+                        }
+                        other => Err(format!("unexpected action {:?}", other)),
+                    }
+                }
+            }
+        });
+
+        let created = call_tool(
+            server.clone(),
+            &tool_params("create_js_context", serde_json::json!({ "tabId": 9 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(created["contextId"], "ctx-1");
+        assert_eq!(server.data_cache.get_js_contexts(9), vec!["ctx-1"]);
+
+        call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 9, "contextId": "ctx-1", "code": "helper = 41" })),
+        )
+        .await
+        .unwrap();
+        let read = call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 9, "contextId": "ctx-1", "code": "helper" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(read["result"], 41);
+
+        call_tool(
+            server.clone(),
+            &tool_params("dispose_js_context", serde_json::json!({ "tabId": 9, "contextId": "ctx-1" })),
+        )
+        .await
+        .unwrap();
+        assert!(server.data_cache.get_js_contexts(9).is_empty());
+        let err = call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 9, "contextId": "ctx-1", "code": "helper" })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Unknown JavaScript context"), "{}", err);
+    }
+
+    /// Collects formatted tracing output so tests can assert on log fields.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<parking_lot::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl CapturedLogs {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock()).into_owned()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_emit_structured_log_events() {
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .with_max_level(tracing::Level::INFO)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut config = ServerConfig::default();
+        config.monitoring.enable_request_logging = true;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(4), |_| async { Ok(serde_json::json!({})) });
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server.clone());
+        let test_server = TestServer::new(app).unwrap();
+        let call = |id: u64, name: &str, arguments: Value| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+        };
+
+        test_server
+            .post("/mcp")
+            .json(&call(1, "get_performance_metrics", serde_json::json!({ "tabId": 4 })))
+            .await;
+        test_server
+            .post("/mcp")
+            .json(&call(2, "detach_debugger", serde_json::json!({})))
+            .await;
+
+        let output = logs.contents();
+        let success = output.lines().find(|l| l.contains("tool call completed")).expect(&output);
+        assert!(success.contains("tool=\"get_performance_metrics\""), "{}", success);
+        assert!(success.contains("tab_id=4"), "{}", success);
+        assert!(success.contains("duration_ms="), "{}", success);
+        assert!(success.contains("success=true"), "{}", success);
+
+        let failure = output.lines().find(|l| l.contains("tool call failed")).expect(&output);
+        assert!(failure.contains("tool=\"detach_debugger\""), "{}", failure);
+        assert!(failure.contains("success=false"), "{}", failure);
+        assert!(failure.contains("missing required field `tabId`"), "{}", failure);
+
+        // With request logging off, only the failure is logged
+        let mut config = ServerConfig::default();
+        config.monitoring.enable_request_logging = false;
+        let quiet = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        quiet.connection_pool.attach_mock_browser(Some(4), |_| async { Ok(serde_json::json!({})) });
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(quiet);
+        let quiet_server = TestServer::new(app).unwrap();
+        logs.0.lock().clear();
+
+        quiet_server
+            .post("/mcp")
+            .json(&call(3, "get_performance_metrics", serde_json::json!({ "tabId": 4 })))
+            .await;
+        quiet_server
+            .post("/mcp")
+            .json(&call(4, "detach_debugger", serde_json::json!({})))
+            .await;
+
+        let output = logs.contents();
+        assert!(!output.contains("tool call completed"), "{}", output);
+        assert!(output.contains("tool call failed"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_concurrency_limit_are_rejected() {
+        let mut config = ServerConfig::default();
+        config.server.max_concurrent_requests = 1;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .route("/health", get(handle_health_check))
+            .with_state(server.clone());
+        let test_server = TestServer::new(app).unwrap();
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "get_browser_tabs", "arguments": {} }
+        });
+
+        // Saturate the limit as if another call were still running
+        let slot = server.try_acquire_request_slot().unwrap();
+        let health: Value = test_server.get("/health").await.json();
+        assert_eq!(health["in_flight_requests"], 1);
+
+        let body: Value = test_server.post("/mcp").json(&request).await.json();
+        assert_eq!(body["error"]["code"], -32000);
+        assert_eq!(body["error"]["message"], "Server busy");
+
+        // Cheap methods are never limited
+        let body: Value = test_server
+            .post("/mcp")
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+            .await
+            .json();
+        assert!(body.get("error").is_none());
+
+        drop(slot);
+        let body: Value = test_server.post("/mcp").json(&request).await.json();
+        assert!(body.get("error").is_none(), "{}", body);
+        assert_eq!(server.in_flight_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_network_idle_passes_thresholds_to_extension() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(6), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request;
+                async { Ok(serde_json::json!({ "idle": true, "waitedMs": 140 })) }
+            }
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "wait_for_network_idle",
+                "arguments": { "tabId": 6, "idleMs": 40, "timeoutMs": 2000, "maxInflight": 2 }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "waitForNetworkIdle");
+        assert_eq!(request["idleMs"], 40);
+        assert_eq!(request["timeoutMs"], 2000);
+        assert_eq!(request["maxInflight"], 2);
+        let body = result_text(&result);
+        assert_eq!(body, serde_json::json!({ "idle": true, "waitedMs": 140 }));
+    }
+
+    #[tokio::test]
+    async fn test_get_computed_style_dispatches_selector_and_properties() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(2), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request;
+                async { Ok(serde_json::json!({ "display": "flex", "width": "320px" })) }
+            }
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "get_computed_style",
+                "arguments": { "tabId": 2, "selector": "#nav", "properties": ["display", "width"] }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "getComputedStyle");
+        assert_eq!(request["selector"], "#nav");
+        assert_eq!(request["properties"], serde_json::json!(["display", "width"]));
+
+        let body = result_text(&result);
+        assert_eq!(body["selector"], "#nav");
+        assert_eq!(body["styles"]["display"], "flex");
+        assert_eq!(body["styles"]["width"], "320px");
+    }
+
+    #[tokio::test]
+    async fn test_get_resource_usage_dispatches_and_parses() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(3), |request| async move {
+            assert_eq!(request["action"], "getResourceUsage");
+            assert_eq!(request["tabId"], 3);
+            Ok(serde_json::json!({
+                "usedJSHeapSize": 1024,
+                "totalJSHeapSize": 2048,
+                "jsHeapSizeLimit": 4096,
+                "domNodeCount": 57
+            }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_resource_usage", "arguments": { "tabId": 3 } }),
+        )
+        .await
+        .unwrap();
+
+        let usage = result_text(&result);
+        assert_eq!(usage["usedJSHeapSize"], 1024);
+        assert_eq!(usage["domNodeCount"], 57);
+        assert!(usage.get("cpuTimeMs").is_none());
+    }
+
+    fn network_request(request_id: &str, response_body: Option<&str>) -> crate::types::browser::NetworkRequest {
+        crate::types::browser::NetworkRequest {
+            request_id: request_id.to_string(),
+            url: "https://example.com/api/items".to_string(),
+            method: "GET".to_string(),
+            status_code: Some(200),
+            status_text: Some("OK".to_string()),
+            request_headers: std::collections::HashMap::new(),
+            response_headers: None,
+            request_body: None,
+            response_body: response_body.map(|b| b.to_string()),
+            body_truncated: false,
+            request_body_truncated: false,
+            timestamp: chrono::Utc::now(),
+            duration_ms: Some(12.0),
+            failed: false,
+            from_cache: false,
+            resource_type: "xhr".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_network_request_body_returns_cached_body() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.add_network_request(4, network_request("101.1", Some(r#"{"items":[]}"#))).await;
+        server.data_cache.add_network_request(4, network_request("101.2", Some("other"))).await;
+
+        // No browser is attached, so this must be answered from the cache
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_network_request_body", "arguments": { "tabId": 4, "requestId": "101.1" } }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["requestId"], "101.1");
+        assert_eq!(body["responseBody"], r#"{"items":[]}"#);
+        assert_eq!(body["requestBody"], Value::Null);
+        assert_eq!(body["source"], "cache");
+        assert_eq!(body["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn test_get_network_request_body_not_found() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(4), |request| async move {
+            assert_eq!(request["action"], "getNetworkRequestBody");
+            assert_eq!(request["networkRequestId"], "missing");
+            Err("Network request missing not found".to_string())
+        });
+
+        let err = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_network_request_body", "arguments": { "tabId": 4, "requestId": "missing" } }),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.contains("not found"), "unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_download_resource_returns_blob_with_detected_mime_type() {
+        use base64::Engine;
+
+        let mut config = ServerConfig::default();
+        config.server.max_body_bytes = 64;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let pdf = b"%PDF-1.7\n%mock\n".to_vec();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&pdf);
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(4), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request.clone();
+                let data = if request["url"] == "/big.csv" {
+                    base64::engine::general_purpose::STANDARD.encode([b'a'; 65])
+                } else {
+                    encoded.clone()
+                };
+                async move {
+                    Ok(serde_json::json!({
+                        "url": "https://example.com/files/report.pdf",
+                        "status": 200,
+                        "contentType": "application/octet-stream",
+                        "data": data
+                    }))
+                }
+            }
+        });
+
+        let result = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "download_resource", "arguments": { "tabId": 4, "url": "/report" } }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "downloadResource");
+        assert_eq!(request["url"], "/report");
+        assert_eq!(request["tabId"], 4);
+        assert_eq!(request["maxBytes"], 64);
+
+        let body = result_text(&result);
+        assert_eq!(body["mimeType"], "application/pdf");
+        assert_eq!(body["size"], pdf.len());
+        assert!(body.get("_resource").is_none());
+
+        let resource = &result["content"][1];
+        assert_eq!(resource["type"], "resource");
+        assert_eq!(resource["resource"]["uri"], "https://example.com/files/report.pdf");
+        assert_eq!(resource["resource"]["mimeType"], "application/pdf");
+        let blob = resource["resource"]["blob"].as_str().unwrap();
+        assert_eq!(base64::engine::general_purpose::STANDARD.decode(blob).unwrap(), pdf);
+
+        let err = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "download_resource", "arguments": { "tabId": 4, "url": "/big.csv" } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("over the 64-byte download limit"), "unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_download_resource_refuses_blocked_urls() {
+        let mut config = ServerConfig::default();
+        config.security.blocked_url_patterns = vec![r"^https://bank\.example/".to_string()];
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_tab_url(4, "https://shop.example/cart");
+        let sent = recording_mock_browser(&server, 4, |_| async {
+            Ok(serde_json::json!({ "url": "https://shop.example/receipt.pdf", "data": "JVBERi0=" }))
+        });
+        let download = |url: &str| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "download_resource", "arguments": { "tabId": 4, "url": url } });
+            async move { call_tool(server, &params).await }
+        };
+
+        let err = download("https://bank.example/statement.pdf").await.unwrap_err();
+        assert!(err.contains("matches blocked pattern"), "{}", err);
+        assert!(sent.lock().is_empty());
+
+        // Relative URLs are checked against the page they resolve on
+        download("/receipt.pdf").await.unwrap();
+        assert_eq!(sent.lock().len(), 1);
+        assert_eq!(sent.lock()[0]["url"], "/receipt.pdf");
+    }
+
+    #[tokio::test]
+    async fn test_compare_screenshot_reports_differing_pixels_against_baseline() {
+        use base64::Engine;
+
+        let png_data_url = |changed: &[(u32, u32)]| {
+            let mut image = image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 255, 255, 255]));
+            for &(x, y) in changed {
+                image.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+            }
+            let mut png = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+            format!(
+                "data:image/png;base64,{}",
+                base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+            )
+        };
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let frame = Arc::new(parking_lot::Mutex::new(png_data_url(&[])));
+        server.connection_pool.attach_mock_browser(Some(6), {
+            let frame = frame.clone();
+            move |request| {
+                assert_eq!(request["action"], "captureScreenshot");
+                let data_url = frame.lock().clone();
+                async move { Ok(Value::String(data_url)) }
+            }
+        });
+        let err = handle_tool_call(
+            server.clone(),
+            &tool_params("compare_screenshot", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("No baseline screenshot for tab 6"), "unexpected error: {}", err);
+
+        let result = handle_tool_call(
+            server.clone(),
+            &tool_params("set_baseline_screenshot", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        let body = result_text(&result);
+        assert_eq!((body["width"].as_u64(), body["height"].as_u64()), (Some(4), Some(4)));
+
+        // Two of the sixteen pixels change
+        *frame.lock() = png_data_url(&[(0, 0), (3, 2)]);
+        let result = handle_tool_call(
+            server.clone(),
+            &tool_params("compare_screenshot", serde_json::json!({ "tabId": 6, "includeDiffImage": true })),
+        )
+        .await
+        .unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["differingPixels"], 2);
+        assert_eq!(body["differencePercent"], 12.5);
+        assert_eq!(body["threshold"], 0.1);
+        assert_eq!(body["verdict"], "mismatch");
+
+        let resource = &result["content"][1]["resource"];
+        assert_eq!(resource["uri"], "browser://tab/6/screenshot-diff");
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(resource["blob"].as_str().unwrap())
+            .unwrap();
+        let diff = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(diff.get_pixel(0, 0), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(diff.get_pixel(3, 2), &image::Rgba([255, 0, 0, 255]));
+        assert_ne!(diff.get_pixel(1, 1), &image::Rgba([255, 0, 0, 255]));
+
+        // A looser threshold accepts the same change, and no image is sent unless asked for
+        let result = handle_tool_call(
+            server.clone(),
+            &tool_params("compare_screenshot", serde_json::json!({ "tabId": 6, "threshold": 20 })),
+        )
+        .await
+        .unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["verdict"], "match");
+        assert!(result["content"].get(1).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_capture_screenshot_downscales_oversized_images() {
+        use base64::Engine;
+
+        let mut config = ServerConfig::default();
+        config.server.max_screenshot_height = 50;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(8, true).await;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(400, 100, image::Rgba([0, 128, 255, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let data_url = format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+        );
+        server.connection_pool.attach_mock_browser(Some(8), move |_| {
+            let data_url = data_url.clone();
+            async move { Ok(Value::String(data_url)) }
+        });
+        let capture = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "capture_screenshot", "arguments": arguments });
+            async move { result_text(&handle_tool_call(server, &params).await.unwrap()) }
+        };
+
+        // A per-call maxWidth is tighter than the configured maxHeight
+        let body = capture(serde_json::json!({ "tabId": 8, "maxWidth": 100 })).await;
+        assert_eq!((body["width"].as_u64(), body["height"].as_u64()), (Some(100), Some(25)));
+        assert_eq!(body["scale"], 0.25);
+        assert_eq!((body["originalWidth"].as_u64(), body["originalHeight"].as_u64()), (Some(400), Some(100)));
+
+        let cached = server.data_cache.get_tab_data(8).await.unwrap().screenshot_data.read().clone().unwrap();
+        assert_eq!((cached.width, cached.height), (100, 25));
+        assert_eq!(crate::utils::image_dimensions(&cached.data).unwrap(), (100, 25));
+
+        // The configured limit applies when the call sets none
+        let body = capture(serde_json::json!({ "tabId": 8 })).await;
+        assert_eq!((body["width"].as_u64(), body["height"].as_u64()), (Some(200), Some(50)));
+        assert_eq!(body["scale"], 0.5);
+
+        // Images within the limits pass through untouched
+        let body = capture(serde_json::json!({ "tabId": 8, "maxWidth": 0, "maxHeight": 0 })).await;
+        assert_eq!((body["width"].as_u64(), body["height"].as_u64()), (Some(400), Some(100)));
+        assert_eq!(body["scale"], 1.0);
+        assert!(body.get("originalWidth").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resources_list_is_ordered_by_tab() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        for tab_id in [42, 7, 19, 3, 100, 1, 58, 23] {
+            let message = crate::types::browser::ConsoleMessage {
+                level: "log".to_string(),
+                message: format!("tab {}", tab_id),
+                timestamp: chrono::Utc::now(),
+                source: None,
+                line_number: None,
+                column_number: None,
+                stack_trace: None,
+            };
+            server.data_cache.add_console_message(tab_id, message).await;
+        }
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+        let test_server = TestServer::new(app).unwrap();
+        let list = || async {
+            let body: Value = test_server
+                .post("/mcp")
+                .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "resources/list" }))
+                .await
+                .json();
+            body["result"]["resources"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|resource| resource["uri"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let first = list().await;
+        let expected: Vec<String> = [1, 3, 7, 19, 23, 42, 58, 100]
+            .iter()
+            .map(|tab_id| format!("browser://tab/{}/console", tab_id))
+            .collect();
+        assert_eq!(first, expected);
+        for _ in 0..5 {
+            assert_eq!(list().await, first);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tabs_describe_reports_cached_data_per_tab() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.add_network_request(7, network_request("7.1", None)).await;
+        server.data_cache.add_network_request(7, network_request("7.2", None)).await;
+        server
+            .data_cache
+            .update_page_content(
+                3,
+                crate::types::browser::PageContent {
+                    url: "https://example.com".to_string(),
+                    title: "Example".to_string(),
+                    text: "Hello".to_string(),
+                    html: "<p>Hello</p>".to_string(),
+                    metadata: std::collections::HashMap::new(),
+                    last_updated: std::time::SystemTime::now(),
+                },
+            )
+            .await;
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request))
+            .with_state(server);
+        let test_server = TestServer::new(app).unwrap();
+
+        let response = test_server
+            .post("/mcp")
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "tabs/describe" }))
+            .await;
+        let body: Value = response.json();
+        let tabs = body["result"]["tabs"].as_array().unwrap();
+
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0]["tab_id"], 3);
+        assert_eq!(tabs[0]["has_page_content"], true);
+        assert_eq!(tabs[0]["has_dom_snapshot"], false);
+        assert_eq!(tabs[0]["network_count"], 0);
+        assert_eq!(tabs[1]["tab_id"], 7);
+        assert_eq!(tabs[1]["has_page_content"], false);
+        assert_eq!(tabs[1]["network_count"], 2);
+        assert_eq!(tabs[1]["console_count"], 0);
+        assert_eq!(tabs[1]["has_screenshot"], false);
+        assert_eq!(tabs[1]["debugger_attached"], false);
+        assert!(tabs[1]["last_updated"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_run_macro_runs_steps_in_order() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 6, |request| async move {
+            assert_eq!(request["tabId"], 6);
+            match request["action"].as_str().unwrap_or_default() {
+                "getResourceUsage" => Ok(serde_json::json!({
+                    "usedJSHeapSize": 10,
+                    "totalJSHeapSize": 20,
+                    "jsHeapSizeLimit": 40
+                })),
+                "executeScript" => Ok(serde_json::json!({ "result": request["code"] })),
+                other => Err(format!("unexpected action {}", other)),
+            }
+        });
+
+        let result = call_tool(
+            server.clone(),
+            &serde_json::json!({
+                "name": "run_macro",
+                "arguments": {
+                    "tabId": 6,
+                    "steps": [
+                        { "tool": "get_resource_usage" },
+                        { "tool": "execute_javascript", "arguments": { "code": "document.title" } }
+                    ]
+                }
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sent_actions(&sent), vec!["getResourceUsage", "executeScript"]);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["completed"], 2);
+        let steps = result["steps"].as_array().unwrap();
+        assert_eq!(steps[0]["tool"], "get_resource_usage");
+        assert_eq!(steps[0]["result"]["usedJSHeapSize"], 10);
+        assert_eq!(steps[1]["tool"], "execute_javascript");
+        assert_eq!(steps[1]["success"], true);
+
+        // A failing step stops the macro unless continueOnError is set
+        let result = call_tool(
+            server,
+            &serde_json::json!({
+                "name": "run_macro",
+                "arguments": {
+                    "tabId": 6,
+                    "steps": [
+                        { "tool": "no_such_tool" },
+                        { "tool": "get_resource_usage" }
+                    ]
+                }
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result["success"], false);
+        assert_eq!(result["aborted"], true);
+        assert_eq!(result["steps"].as_array().unwrap().len(), 1);
+        assert_eq!(result["steps"][0]["error"], "Unknown tool: no_such_tool");
+        assert_eq!(sent.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_url_patterns_deny_tools_on_matching_tabs() {
+        let mut config = ServerConfig::default();
+        config.security.blocked_url_patterns = vec![r"^https://online\.mybank\.com/".to_string()];
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let [blocked_sent, allowed_sent] = [8, 9].map(|tab_id| {
+            recording_mock_browser(&server, tab_id, |_| async {
+                Ok(serde_json::json!({ "usedJSHeapSize": 1, "totalJSHeapSize": 2, "jsHeapSizeLimit": 4 }))
+            })
+        });
+        server.data_cache.set_tab_url(8, "https://online.mybank.com/accounts");
+        server.data_cache.set_tab_url(9, "https://example.com/");
+
+        let err = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "get_resource_usage", "arguments": { "tabId": 8 } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.starts_with("Permission denied"), "unexpected error: {}", err);
+
+        let result = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "get_resource_usage", "arguments": { "tabId": 9 } }),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert!(blocked_sent.lock().is_empty());
+        assert_eq!(allowed_sent.lock().len(), 1);
+
+        // The target tab can't be checked without a tabId
+        let err = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_resource_usage", "arguments": {} }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("tabId is required"), "unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_get_links_dispatches_flags_and_parses_links() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(5), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request;
+                async {
+                    Ok(serde_json::json!({
+                        "links": [
+                            { "href": "https://example.com/about", "rel": "nofollow" },
+                            { "href": "https://example.com/blog" }
+                        ]
+                    }))
+                }
+            }
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "get_links",
+                "arguments": { "tabId": 5, "sameOriginOnly": true, "includeText": false }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "getLinks");
+        assert_eq!(request["sameOriginOnly"], true);
+        assert_eq!(request["includeText"], false);
+
+        let body = result_text(&result);
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["links"][0]["href"], "https://example.com/about");
+        assert_eq!(body["links"][0]["rel"], "nofollow");
+        assert!(body["links"][1].get("text").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_intercept_rules_are_forwarded_and_listed() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |_| async { Ok(serde_json::json!({ "status": "success" })) });
+        let rules = serde_json::json!([
+            { "urlPattern": "*://ads.example.com/*", "action": "block" },
+            { "urlPattern": "*/api/slow*", "action": "delay", "delayMs": 500 },
+            { "urlPattern": "*/api/user", "action": "respond_with", "body": "{\"name\":\"test\"}" }
+        ]);
+        handle_tool_call(
+            server.clone(),
+            &tool_params("set_intercept_rules", serde_json::json!({ "tabId": 6, "rules": rules })),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "setInterceptRules");
+        assert_eq!(request["rules"][1]["delayMs"], 500);
+        assert_eq!(request["rules"][2]["status"], 200);
+
+        let active = server.data_cache.get_intercept_rules(6);
+        assert_eq!(active.len(), 3);
+        assert_eq!(active[0].action, InterceptAction::Block);
+
+        handle_tool_call(
+            server.clone(),
+            &tool_params("clear_intercept_rules", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[1]["rules"], serde_json::json!([]));
+        assert!(server.data_cache.get_intercept_rules(6).is_empty());
+
+        // Invalid rules are rejected before anything is sent
+        for bad in [
+            serde_json::json!({ "urlPattern": "", "action": "block" }),
+            serde_json::json!({ "urlPattern": "*/api\\", "action": "block" }),
+            serde_json::json!({ "urlPattern": "*/api", "action": "delay", "delayMs": 600_000 }),
+            serde_json::json!({ "urlPattern": "*/api", "action": "respond_with", "status": 42 }),
+            serde_json::json!({ "urlPattern": "*/api", "action": "redirect" }),
+        ] {
+            let result = handle_tool_call(
+                server.clone(),
+                &tool_params("set_intercept_rules", serde_json::json!({ "tabId": 6, "rules": [bad] })),
+            )
+            .await;
+            assert!(result.is_err(), "expected rule to be rejected");
+        }
+        assert_eq!(sent.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_extra_headers_are_forwarded_and_listed() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |_| async { Ok(serde_json::json!({ "status": "success" })) });
+        let set_headers = |headers: &Value| {
+            serde_json::json!({ "name": "set_extra_headers", "arguments": { "tabId": 6, "headers": headers } })
+        };
+
+        let headers = serde_json::json!({ "Authorization": "Bearer abc", "X-Tenant": "acme" });
+        let result = call_tool(server.clone(), &set_headers(&headers)).await.unwrap();
+        assert!(!result.to_string().contains("Bearer abc"), "{}", result);
+        assert_eq!(result["headers"], serde_json::json!(["Authorization", "X-Tenant"]));
+
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "setExtraHeaders");
+        assert_eq!(request["tabId"], 6);
+        assert_eq!(request["headers"], headers);
+
+        // Only the names come back; the values are credentials
+        let tabs = server.describe_tabs().await;
+        assert_eq!(tabs[0].extra_headers, vec!["Authorization".to_string(), "X-Tenant".to_string()]);
+        let described = serde_json::to_string(&tabs).unwrap();
+        assert!(!described.contains("Bearer abc") && !described.contains("acme"), "{}", described);
+
+        let params = serde_json::json!({ "name": "clear_extra_headers", "arguments": { "tabId": 6 } });
+        call_tool(server.clone(), &params).await.unwrap();
+        assert_eq!(sent.lock()[1]["headers"], serde_json::json!({}));
+        assert!(server.data_cache.get_extra_headers(6).is_empty());
+
+        // Header names and values that couldn't go on the wire are rejected before sending
+        for bad in [
+            serde_json::json!({}),
+            serde_json::json!({ "Bad Header": "x" }),
+            serde_json::json!({ "X-Ok": "line\r\nbreak" }),
+        ] {
+            let result = call_tool(server.clone(), &set_headers(&bad)).await;
+            assert!(result.is_err(), "expected headers to be rejected");
+        }
+        assert_eq!(sent.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_result_metadata_reports_cache_and_live_sources() {
+        let mut config = ServerConfig::default();
+        config.monitoring.include_result_metadata = true;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        server.connection_pool.attach_mock_browser(Some(5), {
+            let calls = calls.clone();
+            move |_request| {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async { Ok(serde_json::json!({ "url": "https://example.com", "title": "Example", "text": "Hello" })) }
+            }
+        });
+        let params = serde_json::json!({ "name": "get_page_content", "arguments": { "tabId": 5 } });
+
+        let live = handle_tool_call(server.clone(), &params).await.unwrap();
+        assert_eq!(live["_meta"], serde_json::json!({ "source": "live", "age_ms": 0 }));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let cached = handle_tool_call(server.clone(), &params).await.unwrap();
+        assert_eq!(cached["_meta"]["source"], "cache");
+        assert!(cached["_meta"]["age_ms"].as_u64().unwrap() >= 20);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // The metadata sits beside the content, not inside the tool's own output
+        let body = result_text(&cached);
+        assert_eq!(body["text"], "Hello");
+        assert!(body.get("_meta").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_cache_is_invalidated_by_other_tools_on_the_tab() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(5, true).await;
+        let reads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        server.connection_pool.attach_mock_browser(Some(5), {
+            let reads = reads.clone();
+            move |request| {
+                if request["action"] == "getPageContent" {
+                    reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+                async { Ok(serde_json::json!({ "url": "https://example.com", "title": "Example", "text": "Hello" })) }
+            }
+        });
+        let read = serde_json::json!({ "name": "get_page_content", "arguments": { "tabId": 5 } });
+
+        call_tool(server.clone(), &read).await.unwrap();
+        call_tool(server.clone(), &read).await.unwrap();
+        assert_eq!(reads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let press = serde_json::json!({ "name": "press_key", "arguments": { "tabId": 5, "key": "Enter" } });
+        call_tool(server.clone(), &press).await.unwrap();
+        call_tool(server.clone(), &read).await.unwrap();
+        assert_eq!(reads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_result_metadata_is_off_by_default() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(5), |_request| async {
+            Ok(serde_json::json!({ "url": "https://example.com", "title": "Example", "text": "Hello" }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_page_content", "arguments": { "tabId": 5 } }),
+        )
+        .await
+        .unwrap();
+        assert!(result.get("_meta").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_result_metadata_reports_age_of_cached_browser_data() {
+        let mut config = ServerConfig::default();
+        config.monitoring.include_result_metadata = true;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let call = |name: &str| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": name, "arguments": { "tabId": 4 } });
+            async move { call_tool(server, &params).await.unwrap() }
+        };
+
+        let mut metrics = crate::types::browser::PerformanceMetrics::from_extension(&serde_json::json!({
+            "timing": { "loadTime": 1800 },
+            "webVitals": { "largestContentfulPaint": 1200.0 },
+            "resources": [
+                { "name": "https://example.com/app.js", "type": "script", "duration": 40.0,
+                  "size": 12000, "encodedBodySize": 11700, "decodedBodySize": 48000 }
+            ]
+        }))
+        .unwrap();
+        metrics.timestamp = chrono::Utc::now() - chrono::Duration::seconds(5);
+        server.data_cache.update_performance_metrics(4, metrics).await;
+
+        for name in ["get_web_vitals_report", "get_resource_breakdown"] {
+            let result = call(name).await;
+            assert_eq!(result["_meta"]["source"], "cache", "{}", name);
+            assert!(result["_meta"]["age_ms"].as_u64().unwrap() >= 5000, "{}", name);
+        }
+
+        server
+            .data_cache
+            .update_page_content(4, PageContent {
+                url: "https://example.com/".to_string(),
+                title: "Example".to_string(),
+                text: String::new(),
+                html: r#"<head><meta name="description" content="Example"></head>"#.to_string(),
+                metadata: HashMap::new(),
+                last_updated: std::time::SystemTime::now() - Duration::from_secs(3),
+            })
+            .await;
+        let result = call("get_structured_data").await;
+        assert_eq!(result["_meta"]["source"], "cache");
+        assert!(result["_meta"]["age_ms"].as_u64().unwrap() >= 3000);
+    }
+
+    #[tokio::test]
+    async fn test_web_vitals_report_uses_metrics_fetched_from_the_browser() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(4), |request| async move {
+            match request["action"].as_str() {
+                Some("getPerformanceMetrics") => Ok(serde_json::json!({
+                    "timing": { "loadTime": 1800, "domInteractive": 700, "firstContentfulPaint": 900.0 },
+                    "navigation": { "type": 0, "redirectCount": 0 },
+                    "webVitals": {
+                        "largestContentfulPaint": 3100.0,
+                        "firstInputDelay": 40.0,
+                        "cumulativeLayoutShift": 0.02
+                    },
+                    "resources": [],
+                    "memory": null
+                })),
+                _ => Ok(serde_json::json!({})),
+            }
+        });
+        let params = serde_json::json!({ "name": "get_web_vitals_report", "arguments": { "tabId": 4 } });
+
+        assert!(handle_tool_call(server.clone(), &params).await.is_err());
+
+        let fetch = serde_json::json!({ "name": "get_performance_metrics", "arguments": { "tabId": 4 } });
+        handle_tool_call(server.clone(), &fetch).await.unwrap();
+
+        let result = handle_tool_call(server, &params).await.unwrap();
+        let report = result_text(&result);
+        assert_eq!(report["verdict"], "needs-improvement");
+        assert_eq!(report["passed"], false);
+        assert_eq!(report["largest_contentful_paint"]["value"], 3100.0);
+        assert_eq!(report["first_input_delay"]["rating"], "good");
+        assert_eq!(report["tabId"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_resource_breakdown_uses_timings_fetched_from_the_browser() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(4), |request| async move {
+            match request["action"].as_str() {
+                Some("getPerformanceMetrics") => Ok(serde_json::json!({
+                    "timing": { "loadTime": 1800 },
+                    "resources": [
+                        { "name": "https://example.com/app.js", "type": "script", "duration": 40.0,
+                          "size": 12000, "encodedBodySize": 11700, "decodedBodySize": 48000 },
+                        { "name": "https://example.com/hero.png", "type": "img", "duration": 90.0,
+                          "size": 80000, "encodedBodySize": 79700, "decodedBodySize": 79700 }
+                    ]
+                })),
+                _ => Ok(serde_json::json!({})),
+            }
+        });
+        let params = serde_json::json!({ "name": "get_resource_breakdown", "arguments": { "tabId": 4 } });
+
+        assert!(handle_tool_call(server.clone(), &params).await.is_err());
+
+        let fetch = serde_json::json!({ "name": "get_performance_metrics", "arguments": { "tabId": 4 } });
+        handle_tool_call(server.clone(), &fetch).await.unwrap();
+
+        let result = handle_tool_call(server, &params).await.unwrap();
+        let breakdown = result_text(&result);
+        assert_eq!(breakdown["totalCount"], 2);
+        assert_eq!(breakdown["totalTransferSize"], 92000);
+        assert_eq!(breakdown["totalDecodedSize"], 127700);
+        assert_eq!(breakdown["unsizedCount"], 0);
+        assert_eq!(breakdown["byType"][0]["resourceType"], "image");
+        assert_eq!(breakdown["byType"][0]["transferSize"], 80000);
+        assert_eq!(breakdown["byType"][1]["resourceType"], "script");
+    }
+
+    #[tokio::test]
+    async fn test_debugger_tools_require_attached_debugger() {
+        let mut config = ServerConfig::default();
+        config.connections.auto_attach_debugger = false;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(3), |_| async { Ok(serde_json::json!({})) });
+        let params = serde_json::json!({ "name": "get_performance_metrics", "arguments": { "tabId": 3 } });
+
+        let error = handle_tool_call(server.clone(), &params).await.unwrap_err();
+        assert!(error.contains("debugger is not attached to tab 3"), "{}", error);
+
+        server.data_cache.set_debugger_attached(3, true).await;
+        assert!(handle_tool_call(server, &params).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_debugger_tools_refuse_until_attach_debugger() {
+        let mut config = ServerConfig::default();
+        config.connections.auto_attach_debugger = false;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 6, |request| async move {
+            assert_eq!(request["action"], "executeScript", "unexpected browser request {}", request);
+            // Runs until something terminates it
+            std::future::pending::<()>().await;
+            Ok(Value::Null)
+        });
+
+        let calls = [
+            ("create_js_context", serde_json::json!({ "tabId": 6 })),
+            (
+                "set_intercept_rules",
+                serde_json::json!({ "tabId": 6, "rules": [{ "urlPattern": "*://ads.example.com/*", "action": "block" }] }),
+            ),
+            ("set_extra_headers", serde_json::json!({ "tabId": 6, "headers": { "X-Tenant": "acme" } })),
+            ("get_navigation_history", serde_json::json!({ "tabId": 6 })),
+            ("go_back_forward", serde_json::json!({ "tabId": 6, "delta": -1 })),
+            ("press_key", serde_json::json!({ "tabId": 6, "key": "Enter" })),
+            ("add_init_script", serde_json::json!({ "tabId": 6, "source": "window.a = 1" })),
+        ];
+        for (name, arguments) in calls {
+            let err = call_tool(server.clone(), &tool_params(name, arguments)).await.unwrap_err();
+            assert!(err.contains("call attach_debugger first"), "{}: {}", name, err);
+        }
+
+        // Refused calls leave nothing recorded against the tab
+        assert!(server.data_cache.get_js_contexts(6).is_empty());
+        assert!(server.data_cache.get_intercept_rules(6).is_empty());
+        assert!(server.data_cache.get_extra_headers(6).is_empty());
+        assert!(server.data_cache.get_init_scripts(6).is_empty());
+
+        // Without a debugger session there are no headers left in the browser to clear
+        let params = tool_params("clear_extra_headers", serde_json::json!({ "tabId": 6 }));
+        let cleared = call_tool(server.clone(), &params).await.unwrap();
+        assert_eq!(cleared["cleared"], 0);
+
+        // Scripts run without the debugger, but can't be terminated through it
+        let script = spawn_tool_call(&server, "execute_javascript", serde_json::json!({ "tabId": 6, "code": "while (true) {}" }));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let listed = call_tool(server.clone(), &tool_params("list_running_scripts", serde_json::json!({}))).await.unwrap();
+        let script_id = listed["scripts"][0]["scriptId"].clone();
+        let params = tool_params("terminate_script", serde_json::json!({ "scriptId": script_id }));
+        let err = call_tool(server.clone(), &params).await.unwrap_err();
+        assert!(err.contains("call attach_debugger first"), "{}", err);
+        assert_eq!(sent_actions(&sent), vec!["executeScript"]);
+        script.abort();
+    }
+
+    #[tokio::test]
+    async fn test_debugger_is_auto_attached_once() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 3, |_| async { Ok(serde_json::json!({})) });
+
+        for name in ["get_performance_metrics", "capture_screenshot"] {
+            let params = serde_json::json!({ "name": name, "arguments": { "tabId": 3 } });
+            handle_tool_call(server.clone(), &params).await.unwrap();
+        }
+        assert_eq!(sent_actions(&sent), vec!["attachDebugger", "getPerformanceMetrics", "captureScreenshot"]);
+        assert!(server.data_cache.is_debugger_attached(3).await);
+    }
+
+    #[tokio::test]
+    async fn test_health_status_is_computed_from_conditions() {
+        use crate::server::simple::compute_health_status;
+
+        let server = SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap();
+        assert_eq!(server.get_health_status().await.status, "unhealthy");
+
+        let mut config = ServerConfig::default();
+        config.cache.max_size_mb = 100;
+        config.monitoring.degraded_error_rate = 0.2;
+        config.monitoring.degraded_memory_ratio = 0.8;
+
+        assert_eq!(compute_health_status(&config, 1, 0.05, 10.0), "healthy");
+        assert_eq!(compute_health_status(&config, 2, 0.2, 80.0), "healthy");
+        assert_eq!(compute_health_status(&config, 1, 0.35, 10.0), "degraded");
+        assert_eq!(compute_health_status(&config, 1, 0.0, 85.0), "degraded");
+        // Losing the browser outranks everything else
+        assert_eq!(compute_health_status(&config, 0, 0.9, 95.0), "unhealthy");
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoint_requires_token_and_redacts_it() {
+        let mut config = ServerConfig::default();
+        config.server.port = 7123;
+        config.security.auth_token = Some("s3cret-token".to_string());
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let app = Router::new()
+            .route("/config", get(handle_get_config))
+            .with_state(server);
+        let test_server = TestServer::new(app).unwrap();
+
+        assert_eq!(test_server.get("/config").await.status_code(), 401);
+        let wrong = test_server
+            .get("/config")
+            .add_header(axum::http::header::AUTHORIZATION, axum::http::HeaderValue::from_static("Bearer nope"))
+            .await;
+        assert_eq!(wrong.status_code(), 401);
+
+        let response = test_server
+            .get("/config")
+            .add_header(axum::http::header::AUTHORIZATION, axum::http::HeaderValue::from_static("Bearer s3cret-token"))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(!response.text().contains("s3cret-token"));
+        let body: Value = response.json();
+        assert_eq!(body["server"]["port"], 7123);
+        assert_eq!(body["connections"]["wire_format"], "json");
+        assert_eq!(body["security"]["auth_token"], "[redacted]");
+    }
+
+    #[tokio::test]
+    async fn test_sessions_keep_separate_active_tabs() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        for tab_id in [1, 2] {
+            server.connection_pool.attach_mock_browser(Some(tab_id), move |_| async move {
+                Ok(serde_json::json!({ "tab": tab_id }))
+            });
+        }
+
+        let app = Router::new()
+            .route("/mcp", post(handle_mcp_request).delete(handle_end_session))
+            .with_state(server.clone());
+        let test_server = TestServer::new(app).unwrap();
+        let header = axum::http::HeaderName::from_static(SESSION_HEADER);
+
+        let mut sessions = Vec::new();
+        for id in [1, 2] {
+            let response = test_server
+                .post("/mcp")
+                .json(&serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": "initialize", "params": {} }))
+                .await;
+            sessions.push(response.header(SESSION_HEADER));
+        }
+        assert_ne!(sessions[0], sessions[1]);
+
+        let call = |session: axum::http::HeaderValue, arguments: Value| {
+            test_server
+                .post("/mcp")
+                .add_header(header.clone(), session)
+                .json(&serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 3,
+                    "method": "tools/call",
+                    "params": { "name": "get_performance_metrics", "arguments": arguments }
+                }))
+        };
+        let tab_in = |response: axum_test::TestResponse| {
+            let body: Value = response.json();
+            result_text(&body["result"])["tab"].clone()
+        };
+
+        // Each session selects a tab, then omits tabId and stays on its own tab
+        assert_eq!(tab_in(call(sessions[0].clone(), serde_json::json!({ "tabId": 1 })).await), 1);
+        assert_eq!(tab_in(call(sessions[1].clone(), serde_json::json!({ "tabId": 2 })).await), 2);
+        assert_eq!(tab_in(call(sessions[0].clone(), serde_json::json!({})).await), 1);
+        assert_eq!(tab_in(call(sessions[1].clone(), serde_json::json!({})).await), 2);
+
+        // A JavaScript context belongs to the session that created it
+        let first = sessions[0].to_str().unwrap();
+        server.sessions.add_js_context(first, "ctx-1".to_string());
+        let other_session = handle_session_tool_call(
+            server.clone(),
+            Some(sessions[1].to_str().unwrap()),
+            &serde_json::json!({ "name": "execute_javascript", "arguments": { "code": "1", "contextId": "ctx-1" } }),
+        )
+        .await;
+        assert!(other_session.unwrap_err().contains("Unknown JavaScript context"));
+
+        // Ended sessions are rejected until the client initializes again
+        let ended = test_server.delete("/mcp").add_header(header.clone(), sessions[0].clone()).await;
+        assert_eq!(ended.status_code(), 204);
+        let response = call(sessions[0].clone(), serde_json::json!({})).await;
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(server.sessions.active_tab(sessions[1].to_str().unwrap()), Some(2));
+    }
+
+    async fn evaluate_xpath_with(response: Value) -> Value {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(3), move |request| {
+            let response = response.clone();
+            async move {
+                assert_eq!(request["action"], "evaluateXPath");
+                assert_eq!(request["expression"], "//li");
+                Ok(response)
+            }
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "evaluate_xpath", "arguments": { "tabId": 3, "expression": "//li" } }),
+        )
+        .await
+        .unwrap();
+        result_text(&result)
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_xpath_returns_matched_nodes() {
+        let node = |text: &str, xpath: &str| {
+            serde_json::json!({
+                "node_type": "element",
+                "tag_name": "li",
+                "text_content": text,
+                "attributes": { "class": "item" },
+                "computed_styles": null,
+                "children": [],
+                "xpath": xpath,
+                "selector": null
+            })
+        };
+        let body = evaluate_xpath_with(serde_json::json!({
+            "result": { "type": "nodes", "value": [node("One", "/html/body/ul/li[1]"), node("Two", "/html/body/ul/li[2]")] }
+        }))
+        .await;
+
+        assert_eq!(body["type"], "nodes");
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["value"][1]["text_content"], "Two");
+        assert_eq!(body["value"][1]["xpath"], "/html/body/ul/li[2]");
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_xpath_returns_scalars() {
+        let body = evaluate_xpath_with(serde_json::json!({ "result": { "type": "number", "value": 4.0 } })).await;
+        assert_eq!(body, serde_json::json!({ "type": "number", "value": 4.0 }));
+
+        let body = evaluate_xpath_with(serde_json::json!({ "result": { "type": "boolean", "value": true } })).await;
+        assert_eq!(body["value"], true);
+
+        let body = evaluate_xpath_with(serde_json::json!({ "result": { "type": "string", "value": "Menu" } })).await;
+        assert_eq!(body["type"], "string");
+        assert_eq!(body["value"], "Menu");
+    }
+
+    #[tokio::test]
+    async fn test_observe_mutations_dispatches_options_and_parses_records() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(8), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request;
+                async {
+                    Ok(serde_json::json!({
+                        "records": [
+                            {
+                                "type": "childList",
+                                "target": "/html[1]/body[1]/div[2]",
+                                "added_nodes": ["div.toast"],
+                                "offset_ms": 120.5
+                            },
+                            {
+                                "type": "attributes",
+                                "target": "/html[1]/body[1]/div[2]/div[1]",
+                                "attribute_name": "class",
+                                "old_value": "toast",
+                                "offset_ms": 480.0
+                            },
+                            {
+                                "type": "childList",
+                                "target": "/html[1]/body[1]/div[2]",
+                                "removed_nodes": ["div.toast.fading"],
+                                "offset_ms": 900.0
+                            }
+                        ],
+                        "truncated": false
+                    }))
+                }
+            }
+        });
+
+        let result = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({
+                "name": "observe_mutations",
+                "arguments": { "tabId": 8, "selector": "#notifications", "durationMs": 1000, "subtree": false }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "observeMutations");
+        assert_eq!(request["selector"], "#notifications");
+        assert_eq!(request["durationMs"], 1000);
+        assert_eq!(request["attributes"], true);
+        assert_eq!(request["childList"], true);
+        assert_eq!(request["subtree"], false);
+
+        let body = result_text(&result);
+        assert_eq!(body["count"], 3);
+        assert_eq!(body["truncated"], false);
+        assert_eq!(body["records"][0]["added_nodes"][0], "div.toast");
+        assert_eq!(body["records"][1]["attribute_name"], "class");
+        assert_eq!(body["records"][2]["removed_nodes"][0], "div.toast.fading");
+
+        let error = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "observe_mutations", "arguments": { "tabId": 8, "durationMs": 120000 } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("durationMs must be between"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_get_navigation_history_is_fetched_live() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        server.connection_pool.attach_mock_browser(Some(6), {
+            let calls = calls.clone();
+            move |request| {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    assert_eq!(request["action"], "getNavigationHistory");
+                    assert_eq!(request["tabId"], 6);
+                    Ok(serde_json::json!({
+                        "current_index": 1,
+                        "entries": [
+                            { "url": "https://example.com/", "title": "Home" },
+                            { "url": "https://example.com/search?q=rust", "title": "Search" },
+                            { "url": "https://example.com/item/7", "title": "Item 7" }
+                        ]
+                    }))
+                }
+            }
+        });
+
+        let params = serde_json::json!({ "name": "get_navigation_history", "arguments": { "tabId": 6 } });
+        let result = handle_tool_call(server.clone(), &params).await.unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["tabId"], 6);
+        assert_eq!(body["count"], 3);
+        assert_eq!(body["current_index"], 1);
+        assert_eq!(body["entries"][1]["title"], "Search");
+        assert_eq!(body["entries"][2]["url"], "https://example.com/item/7");
+
+        // Not served from the response cache
+        handle_tool_call(server, &params).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_set_page_content_replaces_document() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(5), |request| async move {
+            assert_eq!(request["action"], "setPageContent");
+            assert_eq!(request["tabId"], 5);
+            assert_eq!(request["html"], "<title>Fixture</title><p>Hi</p>");
+            Ok(serde_json::json!({ "title": "Fixture" }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "set_page_content",
+                "arguments": { "tabId": 5, "html": "<title>Fixture</title><p>Hi</p>" }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["tabId"], 5);
+        assert_eq!(body["replaced"], true);
+        assert_eq!(body["length"], 31);
+        assert_eq!(body["title"], "Fixture");
+    }
+
+    #[tokio::test]
+    async fn test_set_page_content_is_blocked_without_javascript_execution() {
+        let mut config = ServerConfig::default();
+        config.security.allow_javascript_execution = false;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        server.connection_pool.attach_mock_browser(Some(5), {
+            let sent = sent.clone();
+            move |_| {
+                sent.store(true, std::sync::atomic::Ordering::SeqCst);
+                async { Ok(serde_json::json!({})) }
+            }
+        });
+
+        let err = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "set_page_content", "arguments": { "tabId": 5, "html": "<p>x</p>" } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("set_page_content is disabled by security.allow_javascript_execution"), "{}", err);
+
+        let err = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "execute_javascript", "arguments": { "tabId": 5, "code": "1" } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("execute_javascript is disabled"), "{}", err);
+        assert!(!sent.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_clear_browsing_data_sends_selected_categories() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(4, true).await;
+        let sent = recording_mock_browser(&server, 4, |_| async { Ok(serde_json::json!({ "origin": "https://example.com" })) });
+
+        let err = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "clear_browsing_data", "arguments": { "tabId": 4, "cache": false } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Select at least one of cache, cookies or localStorage"), "{}", err);
+        assert!(sent.lock().is_empty());
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "clear_browsing_data",
+                "arguments": { "tabId": 4, "cookies": true, "localStorage": true }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "clearBrowsingData");
+        assert_eq!(request["tabId"], 4);
+        assert_eq!(request["cache"], false);
+        assert_eq!(request["cookies"], true);
+        assert_eq!(request["localStorage"], true);
+
+        let body = result_text(&result);
+        assert_eq!(body["cleared"], serde_json::json!(["cookies", "localStorage"]));
+        assert_eq!(body["origin"], "https://example.com");
+    }
+
+    #[tokio::test]
+    async fn test_get_accessibility_issues_audits_cached_snapshot() {
+        use crate::types::browser::{DomNode, DomSnapshot};
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let request = serde_json::json!({ "name": "get_accessibility_issues", "arguments": { "tabId": 6 } });
+        let call = || handle_tool_call(server.clone(), &request);
+        let err = call().await.unwrap_err();
+        assert!(err.contains("No DOM snapshot or accessibility tree is cached for tab 6"), "{}", err);
+
+        let node = |tag: &str, attributes: serde_json::Value, children: Vec<Value>| {
+            serde_json::json!({
+                "node_type": "element", "tag_name": tag, "text_content": null, "attributes": attributes,
+                "computed_styles": null, "children": children, "xpath": null, "selector": null
+            })
+        };
+        let root: DomNode = serde_json::from_value(node(
+            "html",
+            serde_json::json!({}),
+            vec![node("body", serde_json::json!({}), vec![
+                node("img", serde_json::json!({ "src": "/hero.jpg" }), vec![]),
+                node("input", serde_json::json!({ "type": "text" }), vec![]),
+            ])],
+        ))
+        .unwrap();
+        server.data_cache.update_dom_snapshot(6, DomSnapshot {
+            root,
+            node_count: 4,
+            max_depth: 2,
+            include_styles: false,
+            timestamp: std::time::SystemTime::now(),
+        }).await;
+
+        let result = call().await.unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["sources"], serde_json::json!({ "domSnapshot": true, "accessibilityTree": false }));
+        assert_eq!(body["issueCount"], 2);
+        assert_eq!(body["issues"][0]["rule"], "missing-alt-text");
+        assert_eq!(body["issues"][0]["severity"], "critical");
+        assert_eq!(body["issues"][0]["node"], "/html/body/img");
+        assert_eq!(body["issues"][1]["rule"], "missing-form-label");
+    }
+
+    #[tokio::test]
+    async fn test_get_accessibility_issues_audits_extension_replies() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        // Shaped like content.js getDOMSnapshot / getAccessibilityTree replies
+        server.connection_pool.attach_mock_browser(Some(6), |request| async move {
+            match request["action"].as_str().unwrap() {
+                "getDOMSnapshot" => Ok(serde_json::json!({
+                    "nodeCount": 4,
+                    "structure": {
+                        "tagName": "HTML", "attributes": {}, "children": [{
+                            "tagName": "BODY", "attributes": {}, "children": [
+                                { "tagName": "IMG", "attributes": { "src": "/hero.jpg" }, "children": [] },
+                                { "tagName": "INPUT", "attributes": { "type": "text" }, "children": [] }
+                            ]
+                        }]
+                    },
+                    "forms": [], "links": []
+                })),
+                "getAccessibilityTree" => Ok(serde_json::json!({
+                    "tree": {
+                        "tagName": "body", "role": "body", "name": "", "visible": true,
+                        "ariaAttributes": {}, "semanticInfo": {}, "accessibilityIssues": [], "children": [
+                            { "tagName": "h1", "role": "h1", "name": "Shop", "visible": true, "ariaAttributes": {},
+                              "semanticInfo": { "headingLevel": 1 }, "accessibilityIssues": [], "children": [] },
+                            { "tagName": "h3", "role": "h3", "name": "Deals", "visible": true, "ariaAttributes": {},
+                              "semanticInfo": { "headingLevel": 3 }, "accessibilityIssues": [], "children": [] }
+                        ]
+                    },
+                    "summary": { "totalNodes": 3 }
+                })),
+                action => Err(format!("unexpected action {}", action)),
+            }
+        });
+        let call = |name: &str| {
+            let params = serde_json::json!({ "name": name, "arguments": { "tabId": 6 } });
+            let server = server.clone();
+            async move { call_tool(server, &params).await }
+        };
+
+        call("get_dom_snapshot").await.unwrap();
+        call("get_accessibility_tree").await.unwrap();
+        let snapshot = server.data_cache.get_dom_snapshot(6).await.unwrap();
+        assert_eq!(snapshot.node_count, 4);
+        assert_eq!(server.data_cache.get_accessibility_tree(6).await.unwrap().node_count, 3);
+
+        let body = call("get_accessibility_issues").await.unwrap();
+        assert_eq!(body["sources"], serde_json::json!({ "domSnapshot": true, "accessibilityTree": true }));
+        let rules: Vec<_> = body["issues"].as_array().unwrap().iter().map(|issue| issue["rule"].clone()).collect();
+        assert_eq!(rules, ["missing-alt-text", "missing-form-label", "heading-order"]);
+        assert_eq!(body["issues"][0]["node"], "/html/body/img");
+    }
+
+    #[tokio::test]
+    async fn test_get_multiple_page_contents_reports_per_tab_errors() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        for (tab, title) in [(1u32, "Docs"), (2, "Pricing")] {
+            server.connection_pool.attach_mock_browser(Some(tab), move |request| async move {
+                assert_eq!(request["action"], "getPageContent");
+                Ok(serde_json::json!({
+                    "url": format!("https://example.com/{}", title.to_lowercase()),
+                    "title": title,
+                    "text": format!("{} page", title)
+                }))
+            });
+        }
+        server.connection_pool.attach_mock_browser(Some(3), |_| async {
+            Err("Cannot access contents of the page".to_string())
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({
+                "name": "get_multiple_page_contents",
+                "arguments": { "tabIds": [1, 2, 3], "maxTextLength": 4 }
+            }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["succeeded"], 2);
+        assert_eq!(body["failed"], 1);
+        assert_eq!(body["pages"]["1"]["title"], "Docs");
+        assert!(body["pages"]["1"]["text"].as_str().unwrap().starts_with("Docs\n... [TRUNCATED"));
+        assert_eq!(body["pages"]["1"]["textTruncated"], true);
+        assert_eq!(body["pages"]["2"]["url"], "https://example.com/pricing");
+        assert!(body["pages"]["3"]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Cannot access contents of the page"));
+    }
+
+    #[tokio::test]
+    async fn test_capture_mhtml_returns_multipart_resource() {
+        use base64::Engine;
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(8, true).await;
+        let mhtml = "From: <Saved by Blink>\r\nContent-Type: multipart/related;\r\n\r\n------MultipartBoundary--\r\n";
+        server.connection_pool.attach_mock_browser(Some(8), move |request| async move {
+            assert_eq!(request["action"], "captureMhtml");
+            assert_eq!(request["tabId"], 8);
+            Ok(serde_json::json!({ "data": mhtml }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "capture_mhtml", "arguments": { "tabId": 8 } }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["mimeType"], "multipart/related");
+        assert_eq!(body["size"], mhtml.len());
+        let resource = &result["content"][1]["resource"];
+        assert_eq!(resource["uri"], "browser://tab/8/mhtml");
+        assert_eq!(resource["mimeType"], "multipart/related");
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(resource["blob"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(bytes, mhtml.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_get_layout_metrics_dispatches_to_debugger() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        server.connection_pool.attach_mock_browser(Some(6), |request| async move {
+            assert_eq!(request["action"], "getLayoutMetrics");
+            assert_eq!(request["tabId"], 6);
+            Ok(serde_json::json!({
+                "scrollX": 0,
+                "scrollY": 1200,
+                "scrollWidth": 1280,
+                "scrollHeight": 6000,
+                "viewportWidth": 1280,
+                "viewportHeight": 800,
+                "devicePixelRatio": 2
+            }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_layout_metrics", "arguments": { "tabId": 6 } }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["tabId"], 6);
+        let metrics = &body["layoutMetrics"];
+        assert_eq!(metrics["scrollY"], 1200.0);
+        assert_eq!(metrics["scrollHeight"], 6000.0);
+        assert_eq!(metrics["viewportHeight"], 800.0);
+        assert_eq!(metrics["devicePixelRatio"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_get_capabilities_returns_structured_map() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(6), |request| async move {
+            assert_eq!(request["action"], "getCapabilities");
+            assert_eq!(request["tabId"], 6);
+            Ok(serde_json::json!({
+                "debugger": { "available": true },
+                "screenshots": { "available": false, "reason": "Only the visible tab in a window can be captured" },
+                "cookies": { "available": false, "reason": "The extension lacks the cookies permission" }
+            }))
+        });
+
+        let result = handle_tool_call(
+            server,
+            &serde_json::json!({ "name": "get_capabilities", "arguments": { "tabId": 6 } }),
+        )
+        .await
+        .unwrap();
+
+        let body = result_text(&result);
+        assert_eq!(body["tabId"], 6);
+        assert_eq!(body["capabilities"]["debugger"], serde_json::json!({ "available": true }));
+        assert_eq!(body["capabilities"]["screenshots"]["available"], false);
+        assert_eq!(body["capabilities"]["cookies"]["reason"], "The extension lacks the cookies permission");
+    }
+
+    #[tokio::test]
+    async fn test_go_back_forward_moves_through_history() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(2, true).await;
+        // Fake extension history: three pages, currently on the last one
+        let urls = ["https://example.com/", "https://example.com/a", "https://example.com/b"];
+        let index = Arc::new(parking_lot::Mutex::new(2_i64));
+        server.connection_pool.attach_mock_browser(Some(2), {
+            let index = index.clone();
+            move |request| {
+                assert_eq!(request["action"], "navigateHistory");
+                let mut index = index.lock();
+                let target = *index + request["delta"].as_i64().unwrap();
+                let result = if (0..urls.len() as i64).contains(&target) {
+                    *index = target;
+                    Ok(serde_json::json!({ "url": urls[target as usize], "current_index": target }))
+                } else {
+                    Err(format!("delta {} is out of range", request["delta"]))
+                };
+                async move { result }
+            }
+        });
+        let go = |delta: i64| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "go_back_forward", "arguments": { "tabId": 2, "delta": delta } });
+            async move { handle_tool_call(server, &params).await }
+        };
+
+        let back = result_text(&go(-2).await.unwrap());
+        assert_eq!(back["url"], "https://example.com/");
+        assert_eq!(back["delta"], -2);
+
+        let forward = result_text(&go(1).await.unwrap());
+        assert_eq!(forward["url"], "https://example.com/a");
+        assert_eq!(forward["current_index"], 1);
+
+        let error = go(5).await.unwrap_err();
+        assert!(error.contains("out of range"), "{}", error);
+        assert_eq!(*index.lock(), 1);
+
+        assert!(go(0).await.unwrap_err().contains("delta must be non-zero"));
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_last_connection_error() {
+        use futures_util::SinkExt;
+        use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
+
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        assert!(server.get_health_status().await.last_connection_error.is_none());
+
+        let app = Router::new()
+            .route("/ws", get(handle_websocket_upgrade))
+            .route("/health", get(handle_health_check))
+            .with_state(server.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+
+        let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        for _ in 0..50 {
+            if server.get_health_status().await.active_connections > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.get_health_status().await.status, "healthy");
+        socket.send(TungsteniteMessage::Text("{not json".to_string())).await.unwrap();
+
+        for _ in 0..50 {
+            if server.get_health_status().await.last_connection_error.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let health: Value = reqwest::get(format!("http://{}/health", addr)).await.unwrap().json().await.unwrap();
+        assert!(health["last_connection_error"].as_str().unwrap().contains("Invalid JSON"));
+        assert_eq!(health["last_connection_error_age_secs"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_press_key_dispatches_normalized_modifiers() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(8, true).await;
+        let sent = recording_mock_browser(&server, 8, |_| async { Ok(serde_json::json!({ "status": "success" })) });
+        let call = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "press_key", "arguments": arguments });
+            async move { handle_tool_call(server, &params).await }
+        };
+
+        let result = call(serde_json::json!({
+            "tabId": 8, "key": "Enter", "selector": "#search", "modifiers": ["ctrl", "Shift"]
+        }))
+        .await
+        .unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["success"], true);
+
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "dispatchKey");
+        assert_eq!(request["key"], "Enter");
+        assert_eq!(request["selector"], "#search");
+        assert_eq!(request["modifiers"], serde_json::json!(["Control", "Shift"]));
+
+        let result = call(serde_json::json!({ "tabId": 8, "key": "a", "modifiers": ["Hyper"] })).await;
+        assert!(result.unwrap_err().contains("Unknown key modifier 'Hyper'"));
+        assert_eq!(sent.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tool_arguments_are_validated_against_input_schema() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 4, |_| async { Ok(serde_json::json!({ "origin": "https://example.com" })) });
+        let call = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "clear_browsing_data", "arguments": arguments });
+            async move { handle_tool_call(server, &params).await }
+        };
+
+        let err = call(serde_json::json!({ "cookies": true })).await.unwrap_err();
+        assert_eq!(err, "Invalid parameters: clear_browsing_data: missing required field `tabId`");
+
+        let err = call(serde_json::json!({ "tabId": "abc", "cookies": "yes" })).await.unwrap_err();
+        assert!(err.starts_with("Invalid parameters: clear_browsing_data: "), "{}", err);
+        assert!(err.contains("field `tabId`: \"abc\" is not of type \"number\""), "{}", err);
+        assert!(err.contains("field `cookies`: \"yes\" is not of type \"boolean\""), "{}", err);
+        assert!(sent.lock().is_empty());
+
+        // Tools without a schema entry, and valid arguments, pass through
+        assert!(validate_tool_arguments("not_a_tool", &serde_json::json!({ "x": 1 })).is_ok());
+        assert!(validate_tool_arguments("clear_browsing_data", &serde_json::json!({ "tabId": 4, "cache": true })).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tab_label_is_offered_and_resolved_before_validation() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+        let tools = handle_tools_list().await.unwrap();
+        let clear = tools["tools"].as_array().unwrap().iter().find(|t| t["name"] == "clear_browsing_data").unwrap();
+        assert_eq!(clear["inputSchema"]["properties"]["tabLabel"]["type"], "string");
+        assert_eq!(clear["inputSchema"]["anyOf"][1]["required"], serde_json::json!(["tabLabel"]));
+
+        let call = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "clear_browsing_data", "arguments": arguments });
+            async move { handle_tool_call(server, &params).await }
+        };
+        let err = call(serde_json::json!({ "tabLabel": "checkout-page", "cache": true })).await.unwrap_err();
+        assert_eq!(err, "Invalid parameters: No connected tab is labelled 'checkout-page'");
+        let err = call(serde_json::json!({ "tabId": 4, "tabLabel": "checkout-page" })).await.unwrap_err();
+        assert!(err.contains("either tabId or tabLabel"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_execute_javascript_await_promise_returns_resolved_value() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |_| {
+            // What Runtime.evaluate hands back once the Promise settles
+            async { Ok(serde_json::json!({ "status": 200, "ok": true })) }
+        });
+
+        let result = call_tool(
+            server,
+            &serde_json::json!({
+                "name": "execute_javascript",
+                "arguments": { "tabId": 6, "code": "fetch('/api').then(r => ({ status: r.status, ok: r.ok }))", "awaitPromise": true }
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["result"], serde_json::json!({ "status": 200, "ok": true }));
+
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "executeScript");
+        assert_eq!(request["awaitPromise"], true);
+        assert_eq!(request["tabId"], 6);
+    }
+
+    #[tokio::test]
+    async fn test_fill_form_reports_each_field() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 7, |request| {
+            let fields: serde_json::Map<String, Value> = request["fields"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|selector| {
+                    let status = if selector == "#nickname" { "not_found" } else { "filled" };
+                    (selector.clone(), serde_json::json!({ "status": status }))
+                })
+                .collect();
+            async move { Ok(serde_json::json!({ "fields": fields })) }
+        });
+
+        let result = call_tool(
+            server,
+            &serde_json::json!({
+                "name": "fill_form",
+                "arguments": {
+                    "tabId": 7,
+                    "fields": { "#email": "ada@example.com", "#age": 36, "#terms": true, "#nickname": "ada" }
+                }
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result["filled"], 3);
+        assert_eq!(result["failed"], 1);
+        assert_eq!(result["notFound"], serde_json::json!(["#nickname"]));
+        assert_eq!(result["fields"]["#email"]["success"], true);
+        assert_eq!(result["fields"]["#nickname"], serde_json::json!({ "success": false, "error": "not found" }));
+
+        // All fields go to the page in one request
+        let requests = sent.lock();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["action"], "fillForm");
+        assert_eq!(requests[0]["fields"]["#terms"], true);
+        assert_eq!(requests[0]["fields"]["#age"], 36);
+    }
+
+    #[tokio::test]
+    async fn test_get_current_location_uses_cached_tab_state() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 2, |_| async {
+            Ok(serde_json::json!([
+                { "id": 2, "url": "https://example.com/cart", "title": "Cart", "active": false },
+                { "id": 3, "url": "https://example.com/", "title": "Home", "active": true }
+            ]))
+        });
+        let location = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "get_current_location", "arguments": arguments });
+            async move { call_tool(server, &params).await.unwrap() }
+        };
+
+        server.data_cache.set_tab_url(2, "https://example.com/checkout");
+        server
+            .data_cache
+            .update_page_content(2, PageContent {
+                url: "https://example.com/checkout".to_string(),
+                title: "Checkout".to_string(),
+                text: String::new(),
+                html: String::new(),
+                metadata: HashMap::new(),
+                last_updated: std::time::SystemTime::now(),
+            })
+            .await;
+        let result = location(serde_json::json!({ "tabId": 2 })).await;
+        assert_eq!(result, serde_json::json!({ "url": "https://example.com/checkout", "title": "Checkout" }));
+        assert!(sent.lock().is_empty());
+
+        // Nothing cached: the tab list is asked, never the page
+        let result = location(serde_json::json!({})).await;
+        assert_eq!(result, serde_json::json!({ "url": "https://example.com/", "title": "Home" }));
+        assert_eq!(sent.lock().len(), 1);
+        assert_eq!(sent.lock()[0]["action"], "getAllTabs");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_console_error_resolves_with_matching_error() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_debugger_attached(9, true).await;
+        let sent = Arc::new(parking_lot::Mutex::new(Value::Null));
+        server.connection_pool.attach_mock_browser(Some(9), {
+            let sent = sent.clone();
+            move |request| {
+                *sent.lock() = request;
+                async {
+                    // The page logs the error a little after the wait starts
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    Ok(serde_json::json!({
+                        "message": { "level": "error", "text": "TypeError: cart is undefined", "source": "exception" },
+                        "waitedMs": 50
+                    }))
+                }
+            }
+        });
+
+        let result = call_tool(
+            server.clone(),
+            &serde_json::json!({
+                "name": "wait_for_console_error",
+                "arguments": { "tabId": 9, "timeoutMs": 3000, "pattern": "TypeError" }
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["message"]["text"], "TypeError: cart is undefined");
+
+        let request = sent.lock().clone();
+        assert_eq!(request["action"], "waitForConsoleError");
+        assert_eq!(request["pattern"], "TypeError");
+        assert_eq!(request["timeoutMs"], 3000);
+
+        // The request outlives the browser's own window so its timeout can be reported
+        let wait = crate::types::messages::BrowserRequest::WaitForConsoleError { timeout_ms: 30_000, pattern: None };
+        assert_eq!(
+            server.connection_pool.timeout_for_request(&wait, None),
+            std::time::Duration::from_secs(32)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_structured_data_parses_cached_html() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 2, |_| async {
+            Ok(serde_json::json!({
+                "url": "https://example.com/about",
+                "html": "<head><meta property=\"og:title\" content=\"About\"></head>"
+            }))
+        });
+        let structured_data = || {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "get_structured_data", "arguments": { "tabId": 2 } });
+            async move { call_tool(server, &params).await.unwrap() }
+        };
+
+        server.data_cache.set_tab_url(2, "https://example.com/mug");
+        server
+            .data_cache
+            .update_page_content(2, PageContent {
+                url: "https://example.com/mug".to_string(),
+                title: "Mug".to_string(),
+                text: String::new(),
+                html: r#"<head>
+                    <meta name="description" content="A mug">
+                    <script type="application/ld+json">{"@type": "Product", "name": "Mug"}</script>
+                </head>"#
+                    .to_string(),
+                metadata: HashMap::new(),
+                last_updated: std::time::SystemTime::now(),
+            })
+            .await;
+        let result = structured_data().await;
+        assert_eq!(result["source"], "cache");
+        assert_eq!(result["jsonLd"], serde_json::json!([{ "@type": "Product", "name": "Mug" }]));
+        assert_eq!(result["meta"], serde_json::json!([{ "name": "description", "content": "A mug" }]));
+        assert!(sent.lock().is_empty());
+
+        // The tab moved on, so the cached HTML no longer describes it
+        server.data_cache.set_tab_url(2, "https://example.com/about");
+        let result = structured_data().await;
+        assert_eq!(result["source"], "live");
+        assert_eq!(result["url"], "https://example.com/about");
+        assert_eq!(result["meta"], serde_json::json!([{ "property": "og:title", "content": "About" }]));
+        assert_eq!(sent.lock().len(), 1);
+        assert_eq!(sent.lock()[0]["action"], "getPageContent");
+
+        // The live reply was cached, so asking again makes no round trip
+        let result = structured_data().await;
+        assert_eq!(result["source"], "cache");
+        assert_eq!(result["meta"], serde_json::json!([{ "property": "og:title", "content": "About" }]));
+        assert_eq!(sent.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_element_rect_dispatches_selector() {
+        let config = ServerConfig::default();
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(4), |request| async move {
+            assert_eq!(request["action"], "getElementRect");
+            assert_eq!(request["tabId"], 4);
+            assert_eq!(request["selector"], "#checkout");
+            Ok(serde_json::json!({
+                "boundingBox": { "x": 40.0, "y": 920.5, "width": 120.0, "height": 32.0 },
+                "isVisible": true,
+                "isInViewport": false
+            }))
+        });
+
+        let result = call_tool(
+            server,
+            &serde_json::json!({ "name": "get_element_rect", "arguments": { "tabId": 4, "selector": "#checkout" } }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["selector"], "#checkout");
+        assert_eq!(result["boundingBox"]["y"], 920.5);
+        assert_eq!(result["isVisible"], true);
+        assert_eq!(result["isInViewport"], false);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_validates_without_contacting_browser() {
+        let mut config = ServerConfig::default();
+        config.server.dry_run = true;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let sent = recording_mock_browser(&server, 3, |_| async { Ok(serde_json::json!({ "url": "https://example.com" })) });
+
+        let result = handle_tool_call(
+            server.clone(),
+            &serde_json::json!({ "name": "execute_javascript", "arguments": { "tabId": 3, "code": "1 + 1" } }),
+        )
+        .await
+        .unwrap();
+        let body = result_text(&result);
+        assert_eq!(body["dryRun"], true);
+        assert_eq!(body["tool"], "execute_javascript");
+        assert_eq!(body["arguments"], serde_json::json!({ "tabId": 3, "code": "1 + 1" }));
+
+        // Validation still applies, and unknown tools are still unknown
+        let error = call_tool(server.clone(), &serde_json::json!({ "name": "execute_javascript", "arguments": { "tabId": 3 } }))
+            .await
+            .unwrap_err();
+        assert!(error.contains("missing required field `code`"), "{}", error);
+        let error = call_tool(server.clone(), &serde_json::json!({ "name": "no_such_tool", "arguments": {} }))
+            .await
+            .unwrap_err();
+        assert_eq!(error, "Unknown tool: no_such_tool");
+        assert!(sent.lock().is_empty());
+
+        // A call can opt back in to the browser
+        let params = serde_json::json!({ "name": "get_page_content", "arguments": { "tabId": 3, "_dryRun": false } });
+        let result = call_tool(server.clone(), &params).await.unwrap();
+        assert!(result.get("dryRun").is_none());
+        assert_eq!(sent.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_flag_on_single_call() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(3), |_| async {
+            panic!("dry-run calls must not reach the browser")
+        });
+
+        let params = serde_json::json!({ "name": "press_key", "arguments": { "tabId": 3, "key": "Enter", "_dryRun": true } });
+        let result = call_tool(server, &params).await.unwrap();
+        assert_eq!(result["dryRun"], true);
+        assert_eq!(result["arguments"], serde_json::json!({ "tabId": 3, "key": "Enter" }));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_returns_each_tool_result_shape() {
+        let mut config = ServerConfig::default();
+        config.server.dry_run = true;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(3), |_| async {
+            panic!("dry-run calls must not reach the browser")
+        });
+        let page = call_tool(
+            server.clone(),
+            &tool_params("get_page_content", serde_json::json!({ "tabId": 3 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(page["url"], "");
+        assert_eq!(page["text"], "");
+        assert_eq!(page["textTruncated"], false);
+        assert_eq!(page["originalTextSize"], 0);
+
+        let screenshot = call_tool(
+            server.clone(),
+            &tool_params("capture_screenshot", serde_json::json!({ "tabId": 3, "format": "jpeg" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(screenshot["format"], "jpeg");
+        assert_eq!(screenshot["mimeType"], "image/jpeg");
+        assert_eq!(screenshot["dataLength"], 0);
+        assert_eq!(screenshot["scale"], 1.0);
+
+        let console = call_tool(
+            server.clone(),
+            &tool_params("get_console_messages", serde_json::json!({ "tabId": 3, "searchTerm": "failed" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(console["messages"], serde_json::json!([]));
+        assert_eq!(console["hasMore"], false);
+        assert_eq!(console["filters"]["searchTerm"], "failed");
+
+        let network = call_tool(
+            server.clone(),
+            &tool_params("get_network_requests", serde_json::json!({ "tabId": 3 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(network["requests"], serde_json::json!([]));
+        assert_eq!(network["total"], 0);
+
+        let script = call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 3, "code": "1 + 1" })),
+        )
+        .await
+        .unwrap();
+        assert!(script.get("result").is_some_and(Value::is_null));
+
+        let breakdown = call_tool(
+            server.clone(),
+            &tool_params("get_resource_breakdown", serde_json::json!({ "tabId": 3 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(breakdown["byType"], serde_json::json!([]));
+        assert_eq!(breakdown["totalCount"], 0);
+        assert_eq!(breakdown["tabId"], 3);
+
+        let rect = call_tool(
+            server.clone(),
+            &tool_params("get_element_rect", serde_json::json!({ "tabId": 3, "selector": "#buy" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(rect["selector"], "#buy");
+        assert_eq!(rect["boundingBox"]["width"], 0.0);
+        assert_eq!(rect["isVisible"], false);
+
+        let history = call_tool(
+            server.clone(),
+            &tool_params("get_navigation_history", serde_json::json!({ "tabId": 3 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(history["entries"], serde_json::json!([]));
+        assert_eq!(history["count"], 0);
+
+        // Every stub still carries the dry-run envelope
+        for result in [&page, &screenshot, &console, &network, &script, &breakdown, &rect, &history] {
+            assert_eq!(result["dryRun"], true);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_console_tail_streams_matching_messages() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let query = ConsoleTailQuery { tab_id: Some(3), level: Some("error, warn".to_string()) };
+        let mut tail = Box::pin(console_tail(server.clone(), query));
+        let message = |level: &str, text: &str| crate::types::browser::ConsoleMessage {
+            level: level.to_string(),
+            message: text.to_string(),
+            timestamp: chrono::Utc::now(),
+            source: None,
+            line_number: None,
+            column_number: None,
+            stack_trace: None,
+        };
+
+        server.data_cache.add_console_message(3, message("log", "rendered")).await;
+        server.data_cache.add_console_message(4, message("error", "other tab")).await;
+        server.data_cache.add_console_message(3, message("error", "checkout failed")).await;
+
+        let pushed = tokio::time::timeout(Duration::from_secs(1), tail.next()).await.unwrap().unwrap();
+        assert_eq!(pushed["tabId"], 3);
+        assert_eq!(pushed["message"]["level"], "error");
+        assert_eq!(pushed["message"]["message"], "checkout failed");
+    }
+
+    #[tokio::test]
+    async fn test_get_dom_snapshot_flat_matches_nested_tree() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(2), |_| async {
+            Ok(serde_json::json!({
+                "nodeCount": 5,
+                "root": {
+                    "tag": "html",
+                    "children": [
+                        { "tag": "head" },
+                        {
+                            "tag": "body",
+                            "children": [
+                                { "tag": "h1", "text": "Orders" },
+                                { "tag": "p", "attributes": { "class": "empty" } }
+                            ]
+                        }
+                    ]
+                }
+            }))
+        });
+        let snapshot = |flat: bool| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "get_dom_snapshot", "arguments": { "tabId": 2, "flat": flat } });
+            async move { call_tool(server, &params).await.unwrap() }
+        };
+
+        let nested = snapshot(false).await;
+        let flat = snapshot(true).await;
+        assert!(flat.get("root").is_none());
+        assert_eq!(flat["nodeCount"], nested["nodeCount"]);
+
+        // Walking the nested tree in pre-order visits the same nodes with the same parents
+        fn walk(node: &Value, parent: Option<usize>, out: &mut Vec<(Value, Option<usize>)>) {
+            let index = out.len();
+            let mut bare = node.clone();
+            bare.as_object_mut().unwrap().remove("children");
+            out.push((bare, parent));
+            for child in node["children"].as_array().into_iter().flatten() {
+                walk(child, Some(index), out);
+            }
+        }
+        let mut expected = Vec::new();
+        walk(&nested["root"], None, &mut expected);
+
+        let nodes = flat["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 5);
+        for (index, (node, (bare, parent))) in nodes.iter().zip(&expected).enumerate() {
+            assert_eq!(node["node_index"], index);
+            assert_eq!(node["parent_index"], serde_json::json!(parent));
+            let mut node = node.clone();
+            node.as_object_mut().unwrap().retain(|key, _| key != "node_index" && key != "parent_index");
+            assert_eq!(&node, bare);
+        }
+        assert_eq!(nodes[4]["tag"], "p");
+        assert_eq!(nodes[4]["parent_index"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_tool_invocations_are_recorded_per_tab() {
+        let mut config = ServerConfig::default();
+        config.cache.activity_log_entries = 2;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(5), |request| async move {
+            match request["action"].as_str() {
+                Some("evaluateXPath") => Err("Invalid XPath expression".to_string()),
+                _ => Ok(serde_json::json!({ "title": "Cart", "url": "https://example.com/cart" })),
+            }
+        });
+        call_tool(server.clone(), &tool_params("get_page_content", serde_json::json!({ "tabId": 5 }))).await.unwrap();
+        call_tool(
+            server.clone(),
+            &tool_params("press_key", serde_json::json!({ "tabId": 5, "key": "Enter" })),
+        )
+        .await
+        .unwrap();
+        call_tool(
+            server.clone(),
+            &tool_params("evaluate_xpath", serde_json::json!({ "tabId": 5, "expression": "//li[" })),
+        )
+        .await
+        .unwrap_err();
+        call_tool(server.clone(), &tool_params("get_page_content", serde_json::json!({ "tabId": 6 }))).await.unwrap();
+
+        // Bounded to the last two calls, and reading the log isn't itself logged
+        call_tool(server.clone(), &tool_params("get_tab_activity", serde_json::json!({ "tabId": 5 }))).await.unwrap();
+        let activity = call_tool(
+            server.clone(),
+            &tool_params("get_tab_activity", serde_json::json!({ "tabId": 5 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(activity["count"], 2);
+        let invocations = activity["invocations"].as_array().unwrap();
+        assert_eq!(invocations[0]["tool"], "press_key");
+        assert_eq!(invocations[0]["arguments"], serde_json::json!(["key", "tabId"]));
+        assert_eq!(invocations[0]["success"], true);
+        assert_eq!(invocations[1]["tool"], "evaluate_xpath");
+        assert_eq!(invocations[1]["success"], false);
+        assert!(invocations[1]["error"].as_str().unwrap().contains("Invalid XPath expression"));
+
+        let other_tab = call_tool(
+            server.clone(),
+            &tool_params("get_tab_activity", serde_json::json!({ "tabId": 6, "limit": 5 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(other_tab["count"], 1);
+        assert_eq!(other_tab["invocations"][0]["tool"], "get_page_content");
+    }
+
+    #[tokio::test]
+    async fn test_tab_activity_keeps_argument_names_but_not_values() {
+        let mut config = ServerConfig::default();
+        config.cache.activity_log_entries = 5;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(7), |_| async move {
+            Ok(serde_json::json!({ "fields": { "#password": { "status": "filled" } } }))
+        });
+        let params = serde_json::json!({
+            "name": "fill_form",
+            "arguments": { "tabId": 7, "fields": { "#password": "hunter2-secret" } }
+        });
+        call_tool(server.clone(), &params).await.unwrap();
+
+        let activity = call_tool(
+            server,
+            &serde_json::json!({ "name": "get_tab_activity", "arguments": { "tabId": 7 } }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(activity["invocations"][0]["tool"], "fill_form");
+        assert_eq!(activity["invocations"][0]["arguments"], serde_json::json!(["fields", "tabId"]));
+        assert!(!activity.to_string().contains("hunter2-secret"));
+    }
+
+    #[tokio::test]
+    async fn test_pause_execution_blocks_conflicting_tools_until_resumed() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(7, true).await;
+        let sent = recording_mock_browser(&server, 7, |_| async { Ok(serde_json::json!({ "result": { "value": 2 } })) });
+        let paused = call_tool(
+            server.clone(),
+            &tool_params("pause_execution", serde_json::json!({ "tabId": 7 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(paused, serde_json::json!({ "tabId": 7, "state": "paused" }));
+        assert!(server.data_cache.is_execution_paused(7));
+
+        let error = call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 7, "code": "1 + 1" })),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.starts_with("Precondition failed: tab 7 is paused"), "{}", error);
+        assert_eq!(BrowserMcpError::category_of_message(&error), "precondition_failed");
+        assert_eq!(sent_actions(&sent), vec!["pauseExecution"]);
+
+        let resumed = call_tool(
+            server.clone(),
+            &tool_params("resume_execution", serde_json::json!({ "tabId": 7 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resumed, serde_json::json!({ "tabId": 7, "state": "running" }));
+        call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 7, "code": "1 + 1" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[1]["action"], "resumeExecution");
+        assert!(!server.data_cache.is_execution_paused(7));
+    }
+
+    #[tokio::test]
+    async fn test_resume_execution_clears_stale_pause() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(7, true).await;
+        server.data_cache.set_execution_paused(7, true);
+        // The page already runs again, so the debugger refuses to resume it
+        server.connection_pool.attach_mock_browser(Some(7), |request| async move {
+            match request["action"].as_str() {
+                Some("resumeExecution") => Err("{\"code\":-32000,\"message\":\"Can only perform operation while paused.\"}".to_string()),
+                _ => Ok(serde_json::json!({ "result": { "value": 2 } })),
+            }
+        });
+        let resumed = call_tool(
+            server.clone(),
+            &tool_params("resume_execution", serde_json::json!({ "tabId": 7 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resumed["state"], "running");
+        assert!(!server.data_cache.is_execution_paused(7));
+        call_tool(
+            server.clone(),
+            &tool_params("execute_javascript", serde_json::json!({ "tabId": 7, "code": "1 + 1" })),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pretty_json_controls_tool_result_formatting() {
+        let params = serde_json::json!({ "name": "get_page_content", "arguments": { "tabId": 1, "_dryRun": true } });
+        for pretty in [false, true] {
+            let mut config = ServerConfig::default();
+            config.monitoring.pretty_json = pretty;
+            let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+
+            let result = handle_tool_call(server, &params).await.unwrap();
+            let text = result["content"][0]["text"].as_str().unwrap();
+            let parsed: Value = serde_json::from_str(text).unwrap();
+            assert_eq!(parsed["dryRun"], true);
+            if pretty {
+                assert_eq!(text, serde_json::to_string_pretty(&parsed).unwrap());
+            } else {
+                assert!(!text.contains('\n'), "{}", text);
+                assert_eq!(text, serde_json::to_string(&parsed).unwrap());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_dom_snapshot_pierce_reaches_shadow_tree() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let sent = recording_mock_browser(&server, 2, |_| async {
+            Ok(serde_json::json!({
+                "nodeCount": 3,
+                "root": {
+                    "tag": "user-card",
+                    "children": [{
+                        "nodeType": "shadow-root",
+                        "shadowRoot": true,
+                        "children": [{ "tag": "span", "attributes": { "id": "name" }, "text": "Ada" }]
+                    }]
+                }
+            }))
+        });
+
+        let params = serde_json::json!({
+            "name": "get_dom_snapshot",
+            "arguments": { "tabId": 2, "selector": "#name", "pierce": true }
+        });
+        let result = call_tool(server.clone(), &params).await.unwrap();
+        assert_eq!(sent.lock()[0]["action"], "getDOMSnapshot");
+        assert_eq!(sent.lock()[0]["pierce"], true);
+        assert_eq!(result["root"]["text"], "Ada");
+        assert_eq!(result["filters"]["pierce"], true);
+
+        // The boundary survives into the typed node the cache keeps
+        let host: crate::types::browser::DomNode = serde_json::from_value(serde_json::json!({
+            "node_type": "element",
+            "tag_name": "user-card",
+            "text_content": null,
+            "attributes": {},
+            "computed_styles": null,
+            "children": [{
+                "node_type": "shadow-root",
+                "tag_name": null,
+                "text_content": null,
+                "attributes": {},
+                "computed_styles": null,
+                "children": [],
+                "xpath": null,
+                "selector": null,
+                "shadowRoot": true
+            }],
+            "xpath": null,
+            "selector": null
+        }))
+        .unwrap();
+        assert!(!host.shadow_root);
+        assert!(host.children[0].shadow_root);
+
+        let params = serde_json::json!({ "name": "get_dom_snapshot", "arguments": { "tabId": 2 } });
+        call_tool(server, &params).await.unwrap();
+        assert_eq!(sent.lock()[1]["pierce"], false);
+    }
+
+    #[tokio::test]
+    async fn test_capture_screenshot_clips_to_selector() {
+        use base64::Engine;
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(8, true).await;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(120, 40, image::Rgba([0, 128, 255, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let data_url = format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+        );
+        let sent = recording_mock_browser(&server, 8, move |_| {
+            let data_url = data_url.clone();
+            async move {
+                Ok(serde_json::json!({
+                    "dataUrl": data_url,
+                    "clip": { "x": 10.0, "y": 20.5, "width": 120.0, "height": 40.0 }
+                }))
+            }
+        });
+
+        let params = serde_json::json!({
+            "name": "capture_screenshot",
+            "arguments": {
+                "tabId": 8,
+                "captureSelector": "#checkout",
+                "clip": { "x": 0, "y": 0, "width": 5, "height": 5 }
+            }
+        });
+        let result = call_tool(server.clone(), &params).await.unwrap();
+
+        // The selector wins over the explicit clip, which isn't sent
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "captureScreenshot");
+        assert_eq!(request["captureSelector"], "#checkout");
+        assert!(request.get("clip").is_none());
+
+        assert_eq!(result["clip"], serde_json::json!({ "x": 10.0, "y": 20.5, "width": 120.0, "height": 40.0 }));
+        assert_eq!((result["width"].as_u64(), result["height"].as_u64()), (Some(120), Some(40)));
+        let cached = server.data_cache.get_tab_data(8).await.unwrap().screenshot_data.read().clone().unwrap();
+        assert_eq!(cached.clip.as_ref().map(|clip| clip.y), Some(20.5));
+    }
+
+    #[tokio::test]
+    async fn test_capture_screenshot_converts_to_requested_format() {
+        use base64::Engine;
+
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(8, true).await;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(64, 32, image::Rgba([200, 40, 40, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let data_url = format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+        );
+        server.connection_pool.attach_mock_browser(Some(8), move |_| {
+            let data_url = data_url.clone();
+            async move { Ok(Value::String(data_url)) }
+        });
+        let capture = |arguments: Value| {
+            let server = server.clone();
+            let params = serde_json::json!({ "name": "capture_screenshot", "arguments": arguments });
+            async move { call_tool(server, &params).await.unwrap() }
+        };
+
+        let result = capture(serde_json::json!({ "tabId": 8, "format": "jpeg", "quality": 80 })).await;
+        assert_eq!(result["format"], "jpeg");
+        assert_eq!(result["mimeType"], "image/jpeg");
+        assert_eq!(result["convertedFrom"], "png");
+        assert_eq!((result["width"].as_u64(), result["height"].as_u64()), (Some(64), Some(32)));
+
+        let cached = server.data_cache.get_tab_data(8).await.unwrap().screenshot_data.read().clone().unwrap();
+        assert_eq!(cached.format, "jpeg");
+        assert_eq!(image::guess_format(&cached.data).unwrap(), image::ImageFormat::Jpeg);
+        let decoded = image::load_from_memory(&cached.data).unwrap().to_rgb8();
+        let pixel = decoded.get_pixel(10, 10);
+        assert!(pixel[0] > 180 && pixel[1] < 70, "{:?}", pixel);
+
+        // Matching formats pass through untouched
+        let result = capture(serde_json::json!({ "tabId": 8, "format": "png" })).await;
+        assert_eq!(result["mimeType"], "image/png");
+        assert!(result.get("convertedFrom").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_windows_groups_tabs_by_window() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(1), |_| async {
+            Ok(serde_json::json!([
+                { "id": 12, "url": "https://b.test/", "title": "B", "active": false, "windowId": 7, "windowFocused": true, "index": 1 },
+                { "id": 30, "url": "https://c.test/", "title": "C", "active": true, "windowId": 9, "windowFocused": false, "index": 0 },
+                { "id": 11, "url": "https://a.test/", "title": "A", "active": true, "windowId": 7, "windowFocused": true, "index": 0 }
+            ]))
+        });
+
+        let params = serde_json::json!({ "name": "get_windows", "arguments": {} });
+        let result = call_tool(server, &params).await.unwrap();
+        assert_eq!(result["windowCount"], 2);
+        assert_eq!(result["tabCount"], 3);
+
+        let windows = result["windows"].as_array().unwrap();
+        assert_eq!(windows[0]["window_id"], 7);
+        assert_eq!(windows[0]["focused"], true);
+        let ids: Vec<_> = windows[0]["tabs"].as_array().unwrap().iter().map(|tab| tab["id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![11, 12]);
+
+        assert_eq!(windows[1]["window_id"], 9);
+        assert_eq!(windows[1]["focused"], false);
+        assert_eq!(windows[1]["tabs"][0]["title"], "C");
+    }
+
+    #[tokio::test]
+    async fn test_init_scripts_are_added_tracked_and_removed() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |request| async move {
+            if request["action"] == "addInitScript" {
+                Ok(serde_json::json!({ "identifier": format!("{}", 10 + request["source"].as_str().unwrap().len()) }))
+            } else {
+                Ok(serde_json::json!({ "status": "success" }))
+            }
+        });
+        let added = call_tool(
+            server.clone(),
+            &tool_params("add_init_script", serde_json::json!({ "tabId": 6, "source": "window.a = 1" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[0]["action"], "addInitScript");
+        assert_eq!(sent.lock()[0]["tabId"], 6);
+        assert_eq!(sent.lock()[0]["source"], "window.a = 1");
+        assert_eq!(added["scriptId"], "22");
+        call_tool(
+            server.clone(),
+            &tool_params("add_init_script", serde_json::json!({ "tabId": 6, "source": "window.b = 2;" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(server.data_cache.get_init_scripts(6).len(), 2);
 
-    let caps = re.captures(uri)
-        .ok_or_else(|| format!("Invalid resource URI: {}", uri))?;
+        let removed = call_tool(
+            server.clone(),
+            &tool_params("remove_init_script", serde_json::json!({ "tabId": 6, "scriptId": "22" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[2]["action"], "removeInitScript");
+        assert_eq!(sent.lock()[2]["scriptId"], "22");
+        assert_eq!(removed["remaining"], 1);
+        assert_eq!(server.data_cache.get_init_scripts(6)[0].source, "window.b = 2;");
 
-    let tab_id: u32 = caps.get(1).unwrap().as_str().parse()
-        .map_err(|_| "Invalid tab ID".to_string())?;
-    let resource_type = caps.get(2).unwrap().as_str();
+        // Unknown ids are refused without bothering the browser
+        let err = call_tool(
+            server.clone(),
+            &tool_params("remove_init_script", serde_json::json!({ "tabId": 6, "scriptId": "22" })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("no init script '22'"), "{}", err);
+        assert_eq!(sent.lock().len(), 3);
 
-    let tab_data = server.data_cache.get_tab_data(tab_id).await
-        .ok_or_else(|| format!("No data available for tab {}", tab_id))?;
+        // Detaching the debugger drops the scripts with its session
+        server.data_cache.set_debugger_attached(6, false).await;
+        assert!(server.data_cache.get_init_scripts(6).is_empty());
+    }
 
-    match resource_type {
-        "content" => {
-            let html = tab_data.page_content.as_ref()
-                .map(|pc| pc.html.as_str())
-                .unwrap_or("");
-            let (truncated_html, _) = truncation::truncate_string(html, truncation::MAX_HTML_SIZE);
+    #[tokio::test]
+    async fn test_add_init_script_is_blocked_without_javascript_execution() {
+        let mut config = ServerConfig::default();
+        config.security.allow_javascript_execution = false;
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(6), |_| async { Ok(serde_json::json!({ "identifier": "1" })) });
 
-            Ok(serde_json::json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "text/html",
-                    "text": truncated_html
-                }]
-            }))
-        }
-        "dom" => {
-            let dom_text = if let Some(dom) = &tab_data.dom_snapshot {
-                let dom_value = serde_json::to_value(dom.as_ref())
-                    .unwrap_or(Value::Null);
+        let params = serde_json::json!({ "name": "add_init_script", "arguments": { "tabId": 6, "source": "window.a = 1" } });
+        let err = call_tool(server.clone(), &params).await.unwrap_err();
+        assert!(err.contains("add_init_script is disabled by security.allow_javascript_execution"), "{}", err);
+        assert!(server.data_cache.get_init_scripts(6).is_empty());
+    }
 
-                // Truncate DOM tree
-                if let Some(root) = dom_value.get("root") {
-                    let mut count = 0;
-                    let truncated_root = crate::utils::dom::truncate_dom_tree(
-                        root, truncation::MAX_DOM_NODES, &mut count
-                    );
-                    let mut result = dom_value.clone();
-                    result["root"] = truncated_root;
-                    result["truncated"] = Value::Bool(count >= truncation::MAX_DOM_NODES);
-                    result["returnedNodeCount"] = Value::Number(count.into());
-                    serde_json::to_string_pretty(&result).unwrap_or_default()
-                } else {
-                    serde_json::to_string_pretty(&dom_value).unwrap_or_default()
-                }
-            } else {
-                "null".to_string()
+    #[tokio::test]
+    async fn test_get_accessibility_node_dispatches_selector() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(6), |request| async move {
+            assert_eq!(request["action"], "getAccessibilityNode");
+            assert_eq!(request["tabId"], 6);
+            let node = match request["selector"].as_str() {
+                Some("#submit") => serde_json::json!({
+                    "role": "button",
+                    "name": "Place order",
+                    "description": null,
+                    "value": null,
+                    "properties": { "focusable": true, "aria-disabled": "false" },
+                    "children": [],
+                    "bounds": { "x": 10.0, "y": 20.0, "width": 90.0, "height": 30.0 }
+                }),
+                _ => Value::Null,
             };
+            Ok(serde_json::json!({ "node": node }))
+        });
 
-            Ok(serde_json::json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": dom_text
-                }]
-            }))
-        }
-        "console" => {
-            let console_data = if let Some(console_logs) = &tab_data.console_logs {
-                let logs = console_logs.read();
-                let total = logs.len();
-                let limited = total > 100;
-                let messages: Vec<_> = if limited {
-                    logs.iter().skip(total - 100).cloned().collect()
-                } else {
-                    logs.iter().cloned().collect()
-                };
-                serde_json::json!({
-                    "messages": messages,
-                    "count": messages.len(),
-                    "limited": limited
-                })
-            } else {
-                serde_json::json!({ "messages": [], "count": 0, "limited": false })
-            };
+        let result = call_tool(
+            server.clone(),
+            &serde_json::json!({ "name": "get_accessibility_node", "arguments": { "tabId": 6, "selector": "#submit" } }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["selector"], "#submit");
+        assert_eq!(result["role"], "button");
+        assert_eq!(result["name"], "Place order");
+        assert_eq!(result["properties"]["aria-disabled"], "false");
+        assert_eq!(result["bounds"]["width"], 90.0);
 
-            Ok(serde_json::json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": serde_json::to_string_pretty(&console_data).unwrap_or_default()
-                }]
-            }))
-        }
-        _ => Err(format!("Unknown resource type: {}", resource_type)),
+        let err = call_tool(
+            server,
+            &serde_json::json!({ "name": "get_accessibility_node", "arguments": { "tabId": 6, "selector": "#missing" } }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Resource not found: element matching #missing"), "{}", err);
     }
-}
 
-async fn handle_tool_call(server: Arc<SimpleBrowserMcpServer>, params: &Value) -> Result<Value, String> {
-    let tool_name = params.get("name")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing tool name")?;
+    #[tokio::test]
+    async fn test_duplicate_in_flight_id_is_rejected_per_session() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.connection_pool.attach_mock_browser(Some(5), |_| async {
+            std::future::pending::<()>().await;
+            Ok(serde_json::json!({}))
+        });
+        let session = server.sessions.create();
+        let mut headers = HeaderMap::new();
+        headers.insert(SESSION_HEADER, session.parse().unwrap());
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": { "name": "get_page_content", "arguments": { "tabId": 5 } }
+        });
+        let post = |headers: HeaderMap, request: Value| {
+            let server = server.clone();
+            async move {
+                let response = handle_mcp_request(State(server), headers, Json(request)).await;
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<Value>(&body).unwrap()
+            }
+        };
 
-    let empty_args = Value::Object(serde_json::Map::new());
-    let args = params.get("arguments").unwrap_or(&empty_args);
+        // Call the handler directly: axum-test serializes requests, so it can't overlap them
+        let first = tokio::spawn(post(headers.clone(), request.clone()));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
 
-    let result = match tool_name {
-        "get_page_content" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let include_metadata = args.get("includeMetadata").and_then(|v| v.as_bool()).unwrap_or(true);
-            let include_html = args.get("includeHtml").and_then(|v| v.as_bool()).unwrap_or(false);
-            let max_text_length = args.get("maxTextLength").and_then(|v| v.as_u64()).unwrap_or(30000) as usize;
+        let body = post(headers.clone(), request.clone()).await;
+        assert_eq!(body["id"], 7);
+        assert_eq!(body["error"]["code"], -32600);
+        assert_eq!(body["error"]["data"], "Request id 7 is already in flight in this session");
 
-            server.handle_get_page_content(tab_id, include_metadata, include_html, max_text_length).await
-                .map_err(|e| format!("Failed to get page content: {}", e))?
-        }
-        "get_dom_snapshot" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let selector = args.get("selector").and_then(|v| v.as_str());
-            let max_nodes = args.get("maxNodes").and_then(|v| v.as_u64()).unwrap_or(500) as usize;
-            let include_styles = args.get("includeStyles").and_then(|v| v.as_bool()).unwrap_or(false);
-            let exclude_scripts = args.get("excludeScripts").and_then(|v| v.as_bool()).unwrap_or(true);
-            let exclude_styles = args.get("excludeStyles").and_then(|v| v.as_bool()).unwrap_or(true);
+        // Other ids, and the same id from another session, are unaffected
+        let body = post(headers.clone(), serde_json::json!({ "jsonrpc": "2.0", "id": 8, "method": "tools/list" })).await;
+        assert!(body.get("error").is_none());
+        let mut other = HeaderMap::new();
+        other.insert(SESSION_HEADER, server.sessions.create().parse().unwrap());
+        let body = post(other, serde_json::json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/list" })).await;
+        assert!(body.get("error").is_none());
 
-            server.handle_get_dom_snapshot(tab_id, selector, max_nodes, include_styles, exclude_scripts, exclude_styles).await
-                .map_err(|e| format!("Failed to get DOM snapshot: {}", e))?
-        }
-        "execute_javascript" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let code = args.get("code").and_then(|v| v.as_str()).ok_or("Missing JavaScript code")?;
+        // Once the first request is gone its id can be used again
+        first.abort();
+        let _ = first.await;
+        let body = post(headers, serde_json::json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/list" })).await;
+        assert!(body.get("error").is_none());
+    }
 
-            server.handle_execute_javascript(tab_id, code.to_string()).await
-                .map_err(|e| format!("Failed to execute JavaScript: {}", e))?
-        }
-        "get_console_messages" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let log_levels = args.get("logLevels").and_then(|v| v.as_array()).map(|arr| {
-                arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>()
-            });
-            let search_term = args.get("searchTerm").and_then(|v| v.as_str());
-            let since = args.get("since").and_then(|v| v.as_f64());
-            let page_size = args.get("pageSize").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
-            let cursor = args.get("cursor").and_then(|v| v.as_str());
+    #[tokio::test]
+    async fn test_user_agent_override_is_dispatched_and_reported() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |request| async move {
+            if request["action"] == "getUserAgent" {
+                Ok(serde_json::json!({ "userAgent": "Mozilla/5.0 (iPhone)", "language": "de-DE" }))
+            } else {
+                Ok(serde_json::json!({ "status": "success" }))
+            }
+        });
+        let result = call_tool(server.clone(), &tool_params(
+            "set_user_agent",
+            serde_json::json!({ "tabId": 6, "userAgent": "Mozilla/5.0 (iPhone)", "acceptLanguage": "de-DE" }),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(result["userAgent"], "Mozilla/5.0 (iPhone)");
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "setUserAgent");
+        assert_eq!(request["tabId"], 6);
+        assert_eq!(request["userAgent"], "Mozilla/5.0 (iPhone)");
+        assert_eq!(request["acceptLanguage"], "de-DE");
 
-            server.handle_get_console_messages(tab_id, log_levels, search_term, since, page_size, cursor).await
-                .map_err(|e| format!("Failed to get console messages: {}", e))?
-        }
-        "get_network_requests" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let method = args.get("method").and_then(|v| v.as_str());
-            let status = args.get("status");
-            let resource_type = args.get("resourceType").and_then(|v| v.as_str());
-            let domain = args.get("domain").and_then(|v| v.as_str());
-            let failed_only = args.get("failedOnly").and_then(|v| v.as_bool()).unwrap_or(false);
-            let page_size = args.get("pageSize").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
-            let cursor = args.get("cursor").and_then(|v| v.as_str());
-            let include_response_bodies = args.get("includeResponseBodies").and_then(|v| v.as_bool()).unwrap_or(false);
-            let include_request_bodies = args.get("includeRequestBodies").and_then(|v| v.as_bool()).unwrap_or(false);
+        let result = call_tool(
+            server.clone(),
+            &tool_params("get_user_agent", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[1]["action"], "getUserAgent");
+        assert_eq!(result["userAgent"], "Mozilla/5.0 (iPhone)");
+        assert_eq!(result["language"], "de-DE");
+        assert_eq!(result["overridden"], true);
+        assert_eq!(result["override"]["acceptLanguage"], "de-DE");
 
-            server.handle_get_network_requests(
-                tab_id, method, status, resource_type, domain, failed_only,
-                page_size, cursor, include_response_bodies, include_request_bodies
-            ).await
-                .map_err(|e| format!("Failed to get network requests: {}", e))?
-        }
-        "capture_screenshot" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let format = args.get("format").and_then(|v| v.as_str()).unwrap_or("png");
-            let quality = args.get("quality").and_then(|v| v.as_f64()).unwrap_or(90.0) as f32;
+        // A blank user agent is rejected before it reaches the browser
+        let err = call_tool(
+            server.clone(),
+            &tool_params("set_user_agent", serde_json::json!({ "tabId": 6, "userAgent": "  " })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("userAgent must not be empty"), "{}", err);
+        assert_eq!(sent.lock().len(), 2);
 
-            server.handle_capture_screenshot(tab_id, format, quality).await
-                .map_err(|e| format!("Failed to capture screenshot: {}", e))?
-        }
-        "get_performance_metrics" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
+        // The override ends with the debugger session
+        server.data_cache.set_debugger_attached(6, false).await;
+        assert!(server.data_cache.get_user_agent(6).is_none());
 
-            server.handle_get_performance_metrics(tab_id).await
-                .map_err(|e| format!("Failed to get performance metrics: {}", e))?
-        }
-        "get_accessibility_tree" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64()).map(|v| v as u32);
-            let timeout = args.get("timeout").and_then(|v| v.as_u64());
+        // Setting it again re-attaches the debugger before overriding
+        call_tool(
+            server.clone(),
+            &tool_params("set_user_agent", serde_json::json!({ "tabId": 6, "userAgent": "Mozilla/5.0 (iPad)" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[2]["action"], "attachDebugger");
+        assert_eq!(sent.lock()[3]["action"], "setUserAgent");
+        assert!(server.data_cache.get_user_agent(6).is_some());
+    }
 
-            server.handle_get_accessibility_tree(tab_id, timeout).await
-                .map_err(|e| format!("Failed to get accessibility tree: {}", e))?
-        }
-        "get_browser_tabs" => {
-            server.handle_get_browser_tabs().await
-                .map_err(|e| format!("Failed to get browser tabs: {}", e))?
+    #[tokio::test]
+    async fn test_running_scripts_are_listed_and_terminated() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(5, true).await;
+        let terminated = Arc::new(tokio::sync::Notify::new());
+        let sent = recording_mock_browser(&server, 5, {
+            let terminated = terminated.clone();
+            move |request| {
+                let terminated = terminated.clone();
+                async move {
+                    if request["action"] == "executeScript" {
+                        // Spins until the debugger interrupts it
+                        terminated.notified().await;
+                        return Err("Uncaught Error: Execution was terminated".to_string());
+                    }
+                    if request["action"] == "terminateExecution" {
+                        terminated.notify_one();
+                    }
+                    Ok(serde_json::json!({ "status": "success" }))
+                }
+            }
+        });
+        let script = spawn_tool_call(&server, "execute_javascript", serde_json::json!({ "tabId": 5, "code": "while (true) {}" }));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
         }
-        "attach_debugger" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
-                .ok_or("tabId is required for debugger operations")? as u32;
 
-            server.handle_attach_debugger(tab_id).await
-                .map_err(|e| format!("Failed to attach debugger: {}", e))?
-        }
-        "detach_debugger" => {
-            let tab_id = args.get("tabId").and_then(|v| v.as_u64())
-                .ok_or("tabId is required for debugger operations")? as u32;
+        let listed = call_tool(
+            server.clone(),
+            &tool_params("list_running_scripts", serde_json::json!({})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(listed["count"], 1);
+        assert_eq!(listed["scripts"][0]["tabId"], 5);
+        assert_eq!(listed["scripts"][0]["code"], "while (true) {}");
+        let script_id = listed["scripts"][0]["scriptId"].as_str().unwrap().to_string();
+        let other_tab = call_tool(
+            server.clone(),
+            &tool_params("list_running_scripts", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(other_tab["count"], 0);
 
-            server.handle_detach_debugger(tab_id).await
-                .map_err(|e| format!("Failed to detach debugger: {}", e))?
-        }
-        _ => return Err(format!("Unknown tool: {}", tool_name)),
-    };
+        let result = call_tool(
+            server.clone(),
+            &tool_params("terminate_script", serde_json::json!({ "scriptId": script_id })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["terminated"], true);
+        assert_eq!(result["tabId"], 5);
+        let request = sent.lock()[1].clone();
+        assert_eq!(request["action"], "terminateExecution");
+        assert_eq!(request["tabId"], 5);
 
-    // Wrap result in MCP tool response format
-    Ok(serde_json::json!({
-        "content": [{
-            "type": "text",
-            "text": serde_json::to_string_pretty(&result).unwrap_or_default()
-        }]
-    }))
-}
+        let err = tokio::time::timeout(Duration::from_secs(2), script).await.unwrap().unwrap().unwrap_err();
+        assert!(err.contains("Execution was terminated"), "{}", err);
+        let listed = call_tool(
+            server.clone(),
+            &tool_params("list_running_scripts", serde_json::json!({})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(listed["count"], 0);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::ServerConfig;
-    use axum_test::TestServer;
+        // The script is gone, so there is nothing left to terminate
+        let err = call_tool(
+            server.clone(),
+            &tool_params("terminate_script", serde_json::json!({ "scriptId": script_id })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("No running script"), "{}", err);
+    }
 
     #[tokio::test]
-    async fn test_combined_server_creation() {
-        let config = ServerConfig::default();
+    async fn test_terminate_script_skips_the_serialized_tab_queue() {
+        let mut config = ServerConfig::default();
+        config.connections.serialize_per_tab = true;
         let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let terminated = Arc::new(tokio::sync::Notify::new());
+        server.connection_pool.attach_mock_browser(Some(5), {
+            let terminated = terminated.clone();
+            move |request| {
+                let terminated = terminated.clone();
+                async move {
+                    if request["action"] == "executeScript" {
+                        terminated.notified().await;
+                        return Err("Uncaught Error: Execution was terminated".to_string());
+                    }
+                    if request["action"] == "terminateExecution" {
+                        terminated.notify_one();
+                    }
+                    Ok(serde_json::json!({ "status": "success" }))
+                }
+            }
+        });
 
-        let app = Router::new()
-            .route("/mcp", post(handle_mcp_request))
-            .route("/ws", get(handle_websocket_upgrade))
-            .route("/health", get(handle_health_check))
-            .route("/cleanup-connections", post(handle_cleanup_connections))
-            .layer(CorsLayer::permissive())
-            .with_state(server);
+        let script = spawn_tool_call(&server, "execute_javascript", serde_json::json!({ "tabId": 5, "code": "while (true) {}" }));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let listed = call_tool(
+            server.clone(),
+            &tool_params("list_running_scripts", serde_json::json!({})),
+        )
+        .await
+        .unwrap();
+        let script_id = listed["scripts"][0]["scriptId"].as_str().unwrap().to_string();
 
-        let test_server = TestServer::new(app).unwrap();
+        // The script holds tab 5's queue; neither attaching the debugger nor terminating
+        // the script may wait behind it
+        let result = tokio::time::timeout(
+            Duration::from_secs(2),
+            call_tool(server.clone(), &tool_params("terminate_script", serde_json::json!({ "scriptId": script_id }))),
+        )
+        .await
+        .expect("terminate_script queued behind the script it was stopping")
+        .unwrap();
+        assert_eq!(result["terminated"], true);
+        let err = tokio::time::timeout(Duration::from_secs(2), script).await.unwrap().unwrap().unwrap_err();
+        assert!(err.contains("Execution was terminated"), "{}", err);
+    }
 
-        // Test health endpoint
-        let response = test_server.get("/health").await;
-        assert_eq!(response.status_code(), 200);
+    #[tokio::test]
+    async fn test_terminate_script_checks_tab_access() {
+        let mut config = ServerConfig::default();
+        config.security.blocked_url_patterns = vec!["^https://bank\\.example/".to_string()];
+        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        server.data_cache.set_tab_url(5, "https://shop.example/");
+        let sent = recording_mock_browser(&server, 5, |request| async move {
+            if request["action"] == "executeScript" {
+                // Never finishes on its own
+                std::future::pending::<()>().await;
+            }
+            Ok(serde_json::json!({ "status": "success" }))
+        });
+        let script = spawn_tool_call(&server, "execute_javascript", serde_json::json!({ "tabId": 5, "code": "while (true) {}" }));
+        while server.connection_pool.pending_request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let listed = call_tool(
+            server.clone(),
+            &tool_params("list_running_scripts", serde_json::json!({})),
+        )
+        .await
+        .unwrap();
+        let script_id = listed["scripts"][0]["scriptId"].as_str().unwrap().to_string();
+
+        // The tab has since navigated somewhere this client may not touch
+        server.data_cache.set_tab_url(5, "https://bank.example/account");
+        let err = call_tool(
+            server.clone(),
+            &tool_params("terminate_script", serde_json::json!({ "scriptId": script_id })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Permission denied"), "{}", err);
+        assert_eq!(sent.lock().len(), 1);
+        script.abort();
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_mcp_initialize() {
-        let config = ServerConfig::default();
+    async fn test_mcp_is_served_over_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.sock");
+        let mut config = ServerConfig::default();
+        config.server.unix_socket_path = Some(path.clone());
+        config.validate().unwrap();
         let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        let serving = tokio::spawn({
+            let server = server.clone();
+            let path = path.clone();
+            async move { serve_mcp_unix_socket(server, &path).await }
+        });
+        while !path.exists() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
 
-        let app = Router::new()
-            .route("/mcp", post(handle_mcp_request))
-            .with_state(server);
+        let body = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }).to_string();
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST /mcp HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert!(!body["result"]["tools"].as_array().unwrap().is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        // The private directory the socket was bound in is gone
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
 
-        let test_server = TestServer::new(app).unwrap();
+        // The TCP port no longer answers MCP requests
+        let tcp = TestServer::new(combined_router(server)).unwrap();
+        let response = tcp
+            .post("/mcp")
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+        serving.abort();
 
-        let request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {}
+        let mut config = ServerConfig::default();
+        config.server.unix_socket_path = Some(std::path::PathBuf::new());
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_request_reissues_cached_request_without_browser_headers() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        let mut original = network_request("202.7", None);
+        original.method = "POST".to_string();
+        original.url = "https://example.com/api/cart".to_string();
+        original.request_body = Some(r#"{"sku":"A1"}"#.to_string());
+        // Only the response was cut short, the request body is intact
+        original.body_truncated = true;
+        original.request_headers = std::collections::HashMap::from([
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("Cookie".to_string(), "session=abc".to_string()),
+            ("X-Csrf-Token".to_string(), "t0k".to_string()),
+        ]);
+        server.data_cache.add_network_request(5, original).await;
+        server.connection_pool.attach_mock_browser(Some(5), |request| async move {
+            assert_eq!(request["action"], "replayRequest");
+            assert_eq!(request["method"], "POST");
+            assert_eq!(request["url"], "https://example.com/api/cart");
+            assert_eq!(request["body"], r#"{"sku":"A1"}"#);
+            assert_eq!(request["headers"]["X-Csrf-Token"], "t0k");
+            assert!(request["headers"].get("Cookie").is_none());
+            Ok(serde_json::json!({ "status": 201, "body": "created", "bodyTruncated": false }))
         });
 
-        let response = test_server.post("/mcp").json(&request).await;
-        assert_eq!(response.status_code(), 200);
+        let params = serde_json::json!({ "name": "replay_request", "arguments": { "tabId": 5, "requestId": "202.7" } });
+        let result = call_tool(server.clone(), &params).await.unwrap();
+        assert_eq!(result["originalRequestId"], "202.7");
+        assert_eq!(result["request"]["droppedHeaders"], serde_json::json!(["Cookie"]));
+        assert_eq!(result["response"]["status"], 201);
 
-        let body: Value = response.json();
-        assert_eq!(body["jsonrpc"], "2.0");
-        assert_eq!(body["id"], 1);
-        assert!(body["result"].is_object());
+        let missing = serde_json::json!({ "name": "replay_request", "arguments": { "tabId": 5, "requestId": "nope" } });
+        let err = call_tool(server, &missing).await.unwrap_err();
+        assert!(err.contains("No cached network request nope"), "unexpected error: {}", err);
+
+        let mut config = ServerConfig::default();
+        config.security.allow_javascript_execution = false;
+        let locked = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+        locked.data_cache.add_network_request(5, network_request("202.7", None)).await;
+        let err = call_tool(locked, &params).await.unwrap_err();
+        assert!(err.contains("replay_request is disabled by security.allow_javascript_execution"), "{}", err);
     }
 
     #[tokio::test]
-    async fn test_tools_list_returns_11_tools() {
-        let config = ServerConfig::default();
-        let server = Arc::new(SimpleBrowserMcpServer::new(config).await.unwrap());
+    async fn test_emulated_media_forces_color_scheme_and_is_tracked_per_tab() {
+        let server = Arc::new(SimpleBrowserMcpServer::new(ServerConfig::default()).await.unwrap());
+        server.data_cache.set_debugger_attached(6, true).await;
+        let sent = recording_mock_browser(&server, 6, |_| async { Ok(serde_json::json!({ "status": "success" })) });
+        let dark = serde_json::json!([{ "name": "prefers-color-scheme", "value": "dark" }]);
+        let result = call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "features": dark })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["overridden"], true);
+        assert_eq!(result["media"], Value::Null);
+        let request = sent.lock()[0].clone();
+        assert_eq!(request["action"], "setEmulatedMedia");
+        assert_eq!(request["tabId"], 6);
+        assert_eq!(request["media"], "");
+        assert_eq!(request["features"], dark);
 
-        let app = Router::new()
-            .route("/mcp", post(handle_mcp_request))
-            .with_state(server);
+        call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "media": "print", "features": dark })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.lock()[1]["media"], "print");
+        let result = call_tool(
+            server.clone(),
+            &tool_params("get_emulated_media", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["media"], "print");
+        assert_eq!(result["features"], dark);
+        let other_tab = call_tool(
+            server.clone(),
+            &tool_params("get_emulated_media", serde_json::json!({ "tabId": 7 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(other_tab["overridden"], false);
 
-        let test_server = TestServer::new(app).unwrap();
+        // Typos are caught before they reach the browser
+        let err = call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "media": "tv" })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("field `media`: \"tv\" is not one of"), "{}", err);
+        let bad = serde_json::json!([{ "name": "prefers-colour-scheme", "value": "dark" }]);
+        let err = call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "features": bad })),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Unsupported media feature \"prefers-colour-scheme\""), "{}", err);
+        assert_eq!(sent.lock().len(), 2);
 
-        let request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/list"
-        });
+        // An empty call clears the emulation
+        let result = call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6 })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["overridden"], false);
+        assert!(server.data_cache.get_emulated_media(6).is_none());
 
-        let response = test_server.post("/mcp").json(&request).await;
-        let body: Value = response.json();
-        let tools = body["result"]["tools"].as_array().unwrap();
-        assert_eq!(tools.len(), 11, "Expected 11 tools, got {}", tools.len());
+        call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "media": "screen" })),
+        )
+        .await
+        .unwrap();
+        server.data_cache.set_debugger_attached(6, false).await;
+        assert!(server.data_cache.get_emulated_media(6).is_none());
+
+        // Emulating again re-attaches the debugger first
+        call_tool(
+            server.clone(),
+            &tool_params("set_emulated_media", serde_json::json!({ "tabId": 6, "media": "print" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent_actions(&sent)[4..], ["attachDebugger", "setEmulatedMedia"]);
     }
 }
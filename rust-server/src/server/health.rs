@@ -1,5 +1,6 @@
+use crate::transport::ConnectionPool;
 use crate::types::mcp::{HealthStatus, PerformanceStats};
-use axum::{http::StatusCode, response::Json, routing::get, Router};
+use axum::{extract::State, http::StatusCode, response::Json, routing::get, Router};
 use metrics_exporter_prometheus::PrometheusHandle;
 use std::sync::Arc;
 
@@ -38,12 +39,21 @@ impl Default for HealthMonitor {
     }
 }
 
-pub fn create_health_router() -> Router {
+/// What readiness is judged against: the live browser connections, optionally
+/// narrowed to one tab that must be connected.
+#[derive(Clone)]
+pub struct ReadinessProbe {
+    pub connection_pool: Arc<ConnectionPool>,
+    pub required_tab: Option<u32>,
+}
+
+pub fn create_health_router(probe: ReadinessProbe) -> Router {
     Router::new()
         .route("/health", get(health_check))
         .route("/health/live", get(liveness_check))
         .route("/health/ready", get(readiness_check))
         .route("/metrics", get(metrics_endpoint))
+        .with_state(probe)
 }
 
 async fn health_check() -> Json<HealthStatus> {
@@ -55,12 +65,21 @@ async fn health_check() -> Json<HealthStatus> {
         active_connections: 0,
         cached_tabs: 0,
         memory_usage_mb: 0.0,
+        in_flight_requests: 0,
+        interned_strings: 0,
+        interner_cache_hits: 0,
+        last_connection_error: None,
+        last_connection_error_age_secs: None,
+        compression_ratio: 1.0,
+        bytes_saved: 0,
+        messages_per_second: 0.0,
         performance_stats: PerformanceStats {
             requests_per_second: 0.0,
             average_response_time_ms: 0.0,
             cache_hit_rate: 0.0,
             error_rate: 0.0,
             active_websocket_connections: 0,
+            errors_by_category: Default::default(),
         },
     })
 }
@@ -70,16 +89,23 @@ async fn liveness_check() -> StatusCode {
     StatusCode::OK
 }
 
-async fn readiness_check() -> Result<StatusCode, StatusCode> {
-    // More comprehensive readiness check
-    // Check if all critical services are available
+async fn readiness_check(State(probe): State<ReadinessProbe>) -> StatusCode {
+    browser_readiness(&probe.connection_pool, probe.required_tab).await
+}
 
-    // For now, just return OK
-    // In a real implementation, this would check:
-    // - Database connectivity
-    // - External service dependencies
-    // - Resource availability
-    Ok(StatusCode::OK)
+/// Ready means a browser extension is connected (to `required_tab`, if set), so
+/// MCP traffic isn't routed to an instance that can't reach a browser.
+pub async fn browser_readiness(connection_pool: &ConnectionPool, required_tab: Option<u32>) -> StatusCode {
+    let connected = match required_tab {
+        Some(tab_id) => !connection_pool.get_connections_for_tab(tab_id).await.is_empty(),
+        None => !connection_pool.get_active_connections().await.is_empty(),
+    };
+
+    if connected {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
 }
 
 async fn metrics_endpoint() -> Result<String, StatusCode> {
@@ -112,9 +138,21 @@ mod tests {
     use super::*;
     use axum_test::TestServer;
 
+    fn probe(required_tab: Option<u32>) -> ReadinessProbe {
+        ReadinessProbe {
+            connection_pool: Arc::new(ConnectionPool::new(
+                std::time::Duration::from_secs(30),
+                std::time::Duration::from_secs(300),
+            )),
+            required_tab,
+        }
+    }
+
     #[tokio::test]
     async fn test_health_endpoints() {
-        let app = create_health_router();
+        let probe = probe(None);
+        probe.connection_pool.attach_mock_browser(None, |_| async { Ok(serde_json::json!({})) });
+        let app = create_health_router(probe);
         let server = TestServer::new(app).unwrap();
 
         // Test health check
@@ -136,6 +174,24 @@ mod tests {
         assert!(metrics.contains("browser_mcp_server_info"));
     }
 
+    #[tokio::test]
+    async fn test_readiness_reflects_browser_connectivity() {
+        let probe = probe(None);
+        let server = TestServer::new(create_health_router(probe.clone())).unwrap();
+        assert_eq!(server.get("/health/ready").await.status_code(), 503);
+
+        probe.connection_pool.attach_mock_browser(Some(1), |_| async { Ok(serde_json::json!({})) });
+        assert_eq!(server.get("/health/ready").await.status_code(), 200);
+
+        // A required tab must itself be connected
+        let probe = ReadinessProbe { required_tab: Some(2), ..probe };
+        let server = TestServer::new(create_health_router(probe.clone())).unwrap();
+        assert_eq!(server.get("/health/ready").await.status_code(), 503);
+
+        probe.connection_pool.attach_mock_browser(Some(2), |_| async { Ok(serde_json::json!({})) });
+        assert_eq!(server.get("/health/ready").await.status_code(), 200);
+    }
+
     #[test]
     fn test_health_monitor() {
         let monitor = HealthMonitor::new();
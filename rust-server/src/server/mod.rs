@@ -1,11 +1,13 @@
 pub mod combined;
 pub mod health;
 // pub mod mcp_server;  // Will be enabled after fixing rmcp API compatibility
+pub mod session;
 pub mod simple;
 pub mod websocket;
 
 pub use combined::*;
 pub use health::*;
 // pub use mcp_server::*;
+pub use session::*;
 pub use simple::*;
 pub use websocket::*;
\ No newline at end of file
@@ -0,0 +1,200 @@
+use dashmap::DashMap;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Header carrying the session id issued by `initialize`.
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+pub type SessionId = String;
+
+/// State that belongs to one MCP client rather than the whole server.
+#[derive(Debug)]
+pub struct SessionState {
+    /// Tab used by tool calls that don't name one: the last tab this session named.
+    pub active_tab: Option<u32>,
+    /// Persistent JavaScript contexts created by this session.
+    pub js_contexts: HashSet<String>,
+    /// JSON-RPC ids of this session's requests that haven't been answered yet.
+    pub in_flight: HashSet<String>,
+    pub created_at: Instant,
+    /// When the client last sent a request in this session.
+    pub last_active: Instant,
+}
+
+/// MCP client sessions keyed by `Mcp-Session-Id`. Clients that don't send the
+/// header keep the old behaviour of sharing one global view of the browser.
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: DashMap<SessionId, SessionState>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&self) -> SessionId {
+        let id = Uuid::new_v4().to_string();
+        self.sessions.insert(
+            id.clone(),
+            SessionState {
+                active_tab: None,
+                js_contexts: HashSet::new(),
+                in_flight: HashSet::new(),
+                created_at: Instant::now(),
+                last_active: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.sessions.contains_key(id)
+    }
+
+    /// Mark session `id` as used now, returning false if it doesn't exist.
+    pub fn touch(&self, id: &str) -> bool {
+        match self.sessions.get_mut(id) {
+            Some(mut session) => {
+                session.last_active = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// End sessions that have been idle for longer than `idle_ttl` and have no request
+    /// running, returning how many were removed. Clients that vanish without sending
+    /// `DELETE` would otherwise keep their session forever.
+    pub fn expire_idle(&self, idle_ttl: Duration) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|id, session| {
+            let keep = !session.in_flight.is_empty() || session.last_active.elapsed() < idle_ttl;
+            if !keep {
+                tracing::info!(
+                    "Expiring MCP session {} idle for {}s (started {}s ago)",
+                    id,
+                    session.last_active.elapsed().as_secs(),
+                    session.created_at.elapsed().as_secs()
+                );
+            }
+            keep
+        });
+        before.saturating_sub(self.sessions.len())
+    }
+
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.remove(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn active_tab(&self, id: &str) -> Option<u32> {
+        self.sessions.get(id).and_then(|session| session.active_tab)
+    }
+
+    pub fn set_active_tab(&self, id: &str, tab_id: u32) {
+        if let Some(mut session) = self.sessions.get_mut(id) {
+            session.active_tab = Some(tab_id);
+        }
+    }
+
+    pub fn add_js_context(&self, id: &str, context_id: String) {
+        if let Some(mut session) = self.sessions.get_mut(id) {
+            session.js_contexts.insert(context_id);
+        }
+    }
+
+    pub fn owns_js_context(&self, id: &str, context_id: &str) -> bool {
+        self.sessions
+            .get(id)
+            .is_some_and(|session| session.js_contexts.contains(context_id))
+    }
+
+    pub fn remove_js_context(&self, id: &str, context_id: &str) {
+        if let Some(mut session) = self.sessions.get_mut(id) {
+            session.js_contexts.remove(context_id);
+        }
+    }
+
+    /// Claim `rpc_id` for a request in session `id` until the returned guard drops.
+    /// `None` means a request with that id is still running, so a response could
+    /// not be told apart from the earlier one's.
+    pub fn begin_request(&self, id: &str, rpc_id: String) -> Option<InFlightRequest<'_>> {
+        if let Some(mut session) = self.sessions.get_mut(id) {
+            if !session.in_flight.insert(rpc_id.clone()) {
+                return None;
+            }
+        }
+        Some(InFlightRequest {
+            store: self,
+            session: id.to_string(),
+            rpc_id,
+        })
+    }
+}
+
+/// Releases a request id claimed with [`SessionStore::begin_request`].
+pub struct InFlightRequest<'a> {
+    store: &'a SessionStore,
+    session: SessionId,
+    rpc_id: String,
+}
+
+impl Drop for InFlightRequest<'_> {
+    fn drop(&mut self) {
+        if let Some(mut session) = self.store.sessions.get_mut(&self.session) {
+            session.in_flight.remove(&self.rpc_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_are_isolated() {
+        let store = SessionStore::new();
+        let first = store.create();
+        let second = store.create();
+        assert_ne!(first, second);
+
+        store.set_active_tab(&first, 3);
+        store.add_js_context(&first, "ctx-1".to_string());
+        assert_eq!(store.active_tab(&first), Some(3));
+        assert_eq!(store.active_tab(&second), None);
+        assert!(store.owns_js_context(&first, "ctx-1"));
+        assert!(!store.owns_js_context(&second, "ctx-1"));
+
+        assert!(store.remove(&first));
+        assert!(!store.contains(&first));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_idle_sessions_expire() {
+        let store = SessionStore::new();
+        let idle = store.create();
+        let busy = store.create();
+        let recent = store.create();
+        for id in [&idle, &busy] {
+            store.sessions.get_mut(id.as_str()).unwrap().last_active = Instant::now() - Duration::from_secs(120);
+        }
+        let _request = store.begin_request(&busy, "1".to_string()).unwrap();
+
+        assert_eq!(store.expire_idle(Duration::from_secs(60)), 1);
+        assert!(!store.touch(&idle));
+        assert!(store.contains(&busy));
+        assert!(store.contains(&recent));
+    }
+}
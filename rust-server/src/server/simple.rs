@@ -1,11 +1,57 @@
 use crate::{
     cache::BrowserDataCache,
     config::ServerConfig,
-    transport::ConnectionPool,
-    types::{errors::*, messages::*},
-    utils::{self, pagination::PaginationCursors, truncation},
+    server::SessionStore,
+    transport::{BatchRequest, CircuitBreaker, ConnectionPool, RequestHandler},
+    types::{
+        browser::{
+            AccessibilityNode, AccessibilityTree, BoundingBox, BrowserTab, DomSnapshot, ElementRect, InitScript, InterceptAction, InterceptRule, MutationRecord, NavigationHistory, PageContent, PageLink, PerformanceMetrics,
+            ResourceBreakdown, ResourceUsage, LayoutMetrics, ScreenshotData, TabCapabilities, TabSummary, UserAgentOverride, EmulatedMedia, MediaFeature, WindowInfo, XPathResult,
+        },
+        errors::*,
+        messages::*,
+    },
+    utils::{self, pagination::PaginationCursors, truncation, LogFilter, UrlAccessPolicy},
 };
-use std::{sync::Arc, time::Duration};
+use dashmap::DashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Filters for `handle_get_dom_snapshot`, mirroring the get_dom_snapshot arguments.
+#[derive(Debug, Clone, Copy)]
+pub struct DomSnapshotOptions<'a> {
+    pub selector: Option<&'a str>,
+    pub max_nodes: usize,
+    pub include_styles: bool,
+    pub exclude_scripts: bool,
+    pub exclude_styles: bool,
+    /// Include open shadow roots, so the selector can match inside web components.
+    pub pierce: bool,
+}
+
+/// How much of a running script's source list_running_scripts shows.
+const RUNNING_SCRIPT_PREVIEW_CHARS: usize = 200;
+
+/// An `execute_javascript` call still waiting on the browser.
+struct RunningScript {
+    tab_id: Option<u32>,
+    code: String,
+    started_at: std::time::Instant,
+}
+
+/// Removes a script from `running_scripts` however its call ends, including cancellation.
+struct RunningScriptGuard<'a> {
+    scripts: &'a DashMap<Uuid, RunningScript>,
+    script_id: Uuid,
+}
+
+impl Drop for RunningScriptGuard<'_> {
+    fn drop(&mut self) {
+        self.scripts.remove(&self.script_id);
+    }
+}
 
 /// Simplified server implementation for compatibility testing
 pub struct SimpleBrowserMcpServer {
@@ -13,32 +59,243 @@ pub struct SimpleBrowserMcpServer {
     pub connection_pool: Arc<ConnectionPool>,
     pub config: ServerConfig,
     pub pagination_cursors: Arc<PaginationCursors>,
+    pub request_handler: Arc<RequestHandler>,
+    pub sessions: SessionStore,
+    /// Keyed by MCP session and serialized JSON-RPC id, since every session numbers its own requests.
+    in_flight_calls: DashMap<(Option<String>, String), CancellationToken>,
+    running_scripts: DashMap<Uuid, RunningScript>,
+    url_policy: UrlAccessPolicy,
+    request_slots: Arc<Semaphore>,
+    log_filter: Option<LogFilter>,
     start_time: std::time::Instant,
 }
 
+/// Map a modifier name, or a common alias like "Ctrl" or "Cmd", to the name the
+/// debugger's key events use.
+fn normalize_key_modifier(name: &str) -> Result<String> {
+    let canonical = match name.to_ascii_lowercase().as_str() {
+        "alt" | "option" => "Alt",
+        "control" | "ctrl" => "Control",
+        "meta" | "cmd" | "command" => "Meta",
+        "shift" => "Shift",
+        _ => {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("Unknown key modifier '{}'; expected Alt, Control, Meta or Shift", name),
+            })
+        }
+    };
+    Ok(canonical.to_string())
+}
+
+/// "unhealthy" with no browser to talk to, "degraded" when requests are failing or the
+/// cache is close to its size limit, "healthy" otherwise.
+pub(crate) fn compute_health_status(
+    config: &ServerConfig,
+    active_connections: usize,
+    error_rate: f64,
+    memory_usage_mb: f64,
+) -> &'static str {
+    let memory_limit_mb = config.cache.max_size_mb as f64;
+    if active_connections == 0 {
+        "unhealthy"
+    } else if error_rate > config.monitoring.degraded_error_rate
+        || memory_usage_mb > memory_limit_mb * config.monitoring.degraded_memory_ratio
+    {
+        "degraded"
+    } else {
+        "healthy"
+    }
+}
+
+/// Mime type for downloaded bytes: the declared Content-Type, or a guess from
+/// well-known file signatures when the server sent none or a generic one.
+fn detect_mime_type(bytes: &[u8], declared: Option<&str>) -> String {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+
+    let declared = declared
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| !value.is_empty() && value != "application/octet-stream");
+    if let Some(declared) = declared {
+        return declared;
+    }
+
+    match SIGNATURES.iter().find(|(signature, _)| bytes.starts_with(signature)) {
+        Some((_, mime_type)) => mime_type.to_string(),
+        None if std::str::from_utf8(bytes).is_ok() => "text/plain".to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+/// Longest window `observe_mutations` may watch a page for.
+const MAX_OBSERVE_DURATION_MS: u64 = 60_000;
+
+/// Longest delay a rule may hold a request for, so a typo cannot hang a page.
+const MAX_INTERCEPT_DELAY_MS: u64 = 60_000;
+
+/// Media features `Emulation.setEmulatedMedia` understands. Chrome ignores other
+/// names, so they are rejected instead of silently doing nothing.
+const EMULATED_MEDIA_FEATURES: &[&str] = &[
+    "prefers-color-scheme",
+    "prefers-reduced-motion",
+    "prefers-reduced-transparency",
+    "prefers-reduced-data",
+    "prefers-contrast",
+    "forced-colors",
+    "color-gamut",
+];
+
+/// Request headers the browser sets itself and `fetch` silently drops, so
+/// `replay_request` leaves them out and says so.
+fn is_unreplayable_header(name: &str) -> bool {
+    const BROWSER_CONTROLLED: &[&str] = &[
+        "accept-encoding",
+        "connection",
+        "content-length",
+        "cookie",
+        "date",
+        "expect",
+        "host",
+        "keep-alive",
+        "origin",
+        "referer",
+        "te",
+        "trailer",
+        "transfer-encoding",
+        "upgrade",
+        "via",
+    ];
+    let name = name.to_ascii_lowercase();
+    BROWSER_CONTROLLED.contains(&name.as_str()) || name.starts_with("sec-") || name.starts_with("proxy-")
+}
+
+/// Sorted names of a tab's extra headers. Their values are never echoed back, since
+/// they are usually credentials such as `Authorization` or `Cookie`.
+fn header_names(headers: &HashMap<String, String>) -> Vec<String> {
+    let mut names: Vec<String> = headers.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+/// Check a rule before it reaches the browser. Patterns use the Fetch domain's
+/// wildcard syntax, where a backslash escapes the next character.
+fn validate_intercept_rule(rule: &InterceptRule) -> Result<()> {
+    let invalid = |message: String| BrowserMcpError::InvalidParameters { message };
+    let pattern = &rule.url_pattern;
+
+    if pattern.is_empty() {
+        return Err(invalid("Intercept rule urlPattern must not be empty".to_string()));
+    }
+    if pattern.chars().any(char::is_whitespace) {
+        return Err(invalid(format!("Intercept rule urlPattern '{}' contains whitespace", pattern)));
+    }
+    let trailing_backslashes = pattern.chars().rev().take_while(|&c| c == '\\').count();
+    if trailing_backslashes % 2 == 1 {
+        return Err(invalid(format!("Intercept rule urlPattern '{}' ends with a dangling escape", pattern)));
+    }
+
+    match &rule.action {
+        InterceptAction::Block => {}
+        InterceptAction::Delay { delay_ms } => {
+            if *delay_ms > MAX_INTERCEPT_DELAY_MS {
+                return Err(invalid(format!(
+                    "Intercept delay of {}ms exceeds the maximum of {}ms",
+                    delay_ms, MAX_INTERCEPT_DELAY_MS
+                )));
+            }
+        }
+        InterceptAction::RespondWith { status, headers, .. } => {
+            if !(100..=599).contains(status) {
+                return Err(invalid(format!("Intercept response status {} is not a valid HTTP status", status)));
+            }
+            if headers.keys().any(|name| name.is_empty()) {
+                return Err(invalid("Intercept response header names must not be empty".to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
 impl SimpleBrowserMcpServer {
     pub async fn new(config: ServerConfig) -> crate::types::errors::Result<Self> {
-        let data_cache = Arc::new(BrowserDataCache::new(
+        let mut data_cache = BrowserDataCache::new(
             config.cache.max_size_mb * 1024 * 1024, // Convert to bytes
             Duration::from_secs(config.cache.data_ttl_secs),
-        ));
+        );
+        data_cache.set_response_cache(
+            config.cache.response_cache_entries,
+            Duration::from_secs(config.cache.response_cache_ttl_secs),
+        );
+        data_cache.set_max_retained_body_bytes(config.cache.max_retained_body_bytes);
+        data_cache.set_activity_log_entries(config.cache.activity_log_entries);
+        let data_cache = Arc::new(data_cache);
 
         let mut connection_pool = ConnectionPool::new(
             Duration::from_secs(config.connections.health_check_interval_secs),
             Duration::from_secs(config.connections.websocket_timeout_secs),
         );
         connection_pool.set_data_cache(data_cache.clone());
+        connection_pool.set_serialize_per_tab(config.connections.serialize_per_tab);
+        connection_pool.set_outbound_queue(
+            config.connections.outbound_queue_size,
+            config.connections.outbound_overflow_policy,
+        );
+        connection_pool.set_wire_format(config.connections.wire_format);
+        connection_pool.set_request_retries(
+            config.connections.request_retry_attempts,
+            Duration::from_millis(config.connections.max_retry_backoff_ms),
+        );
+        connection_pool.set_circuit_breaker(CircuitBreaker::new(
+            config.connections.breaker_failure_threshold,
+            Duration::from_secs(config.connections.breaker_window_secs),
+            Duration::from_secs(config.connections.breaker_cooldown_secs),
+        ));
+        connection_pool.set_tab_rate_limit(config.connections.max_requests_per_tab_per_sec);
+        connection_pool.set_request_timeouts(
+            Duration::from_secs(config.server.request_timeout_secs),
+            config
+                .timeouts
+                .iter()
+                .map(|(tool, secs)| (tool.clone(), Duration::from_secs(*secs)))
+                .collect(),
+        );
         let connection_pool = Arc::new(connection_pool);
+        let request_slots = Arc::new(Semaphore::new(config.server.max_concurrent_requests));
+        let url_policy = UrlAccessPolicy::from_settings(&config.security)?;
+        let request_handler = Arc::new(RequestHandler::with_duration_sampling(
+            1000,
+            config.monitoring.duration_sampling_threshold,
+            config.monitoring.duration_sample_rate,
+        ));
 
         Ok(Self {
             data_cache,
             connection_pool,
             config,
             pagination_cursors: Arc::new(PaginationCursors::new()),
+            request_handler,
+            sessions: SessionStore::new(),
+            in_flight_calls: DashMap::new(),
+            running_scripts: DashMap::new(),
+            url_policy,
+            request_slots,
+            log_filter: None,
             start_time: std::time::Instant::now(),
         })
     }
 
+    /// How long ago cached data was captured; zero if the timestamp is in the future.
+    fn age_of(timestamp: chrono::DateTime<chrono::Utc>) -> Duration {
+        (chrono::Utc::now() - timestamp).to_std().unwrap_or_default()
+    }
+
     /// Extract the raw JSON data from a BrowserResponse, handling both RawJson and typed variants.
     fn extract_response_data(response: BrowserResponse) -> Result<serde_json::Value> {
         match response {
@@ -46,9 +303,7 @@ impl SimpleBrowserMcpServer {
             BrowserResponse::Error { message } => Err(BrowserMcpError::BrowserExtensionError { message }),
             other => {
                 // Serialize typed responses to JSON value
-                serde_json::to_value(&other).map_err(|e| BrowserMcpError::JsonError {
-                    message: e.to_string(),
-                })
+                utils::to_json_value(&other)
             }
         }
     }
@@ -70,7 +325,66 @@ impl SimpleBrowserMcpServer {
         };
 
         let page_content = Self::extract_response_data(response)?;
+        Ok(Self::shape_page_content(&page_content, include_metadata, include_html, max_text_length))
+    }
+
+    /// Pages fetched at once by `get_multiple_page_contents`.
+    const MAX_PARALLEL_PAGE_CONTENTS: usize = 4;
+
+    /// Fetch several tabs' content concurrently. A tab that is refused or fails gets an
+    /// `error` entry instead of failing the whole call.
+    pub async fn handle_get_multiple_page_contents(
+        &self,
+        tab_ids: &[u32],
+        include_metadata: bool,
+        include_html: bool,
+        max_text_length: usize,
+    ) -> Result<serde_json::Value> {
+        if tab_ids.is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "tabIds must list at least one tab".to_string(),
+            });
+        }
+
+        let request = BrowserRequest::GetPageContent { include_metadata };
+        let mut batch = BatchRequest::new(
+            self.connection_pool.timeout_for_request(&request, None),
+            Self::MAX_PARALLEL_PAGE_CONTENTS,
+        );
+        let mut pages = serde_json::Map::new();
+        for &tab_id in tab_ids {
+            match self.check_tab_access(Some(tab_id)) {
+                Ok(()) => batch.add_request(tab_id, request.clone()),
+                Err(e) => {
+                    pages.insert(tab_id.to_string(), serde_json::json!({ "error": e.to_string() }));
+                }
+            }
+        }
+
+        let refused = pages.len();
+        let batch = self.connection_pool.send_batch(batch).await;
+        for (tab_id, response) in batch.responses {
+            let page = response
+                .and_then(Self::extract_response_data)
+                .map(|content| Self::shape_page_content(&content, include_metadata, include_html, max_text_length))
+                .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }));
+            pages.insert(tab_id.to_string(), page);
+        }
+
+        Ok(serde_json::json!({
+            "pages": pages,
+            "succeeded": batch.completed,
+            "failed": batch.failed + refused,
+            "elapsedMs": batch.elapsed.as_millis() as u64
+        }))
+    }
 
+    fn shape_page_content(
+        page_content: &serde_json::Value,
+        include_metadata: bool,
+        include_html: bool,
+        max_text_length: usize,
+    ) -> serde_json::Value {
         // Truncate text content
         let text = page_content.get("text").and_then(|v| v.as_str()).unwrap_or("");
         let original_text_size = text.len();
@@ -106,7 +420,7 @@ impl SimpleBrowserMcpServer {
             }
         }
 
-        Ok(result)
+        result
     }
 
     // ─── get_dom_snapshot ─────────────────────────────────────────────────
@@ -114,21 +428,29 @@ impl SimpleBrowserMcpServer {
     pub async fn handle_get_dom_snapshot(
         &self,
         tab_id: Option<u32>,
-        selector: Option<&str>,
-        max_nodes: usize,
-        include_styles: bool,
-        exclude_scripts: bool,
-        exclude_styles: bool,
+        options: DomSnapshotOptions<'_>,
     ) -> Result<serde_json::Value> {
+        let DomSnapshotOptions { selector, max_nodes, include_styles, exclude_scripts, exclude_styles, pierce } = options;
+        let max_depth = 10;
         let request = BrowserRequest::GetDomSnapshot {
-            max_depth: 10,
+            max_depth,
             include_styles,
+            pierce,
         };
         let response = if let Some(tid) = tab_id {
             self.connection_pool.send_request(tid, request).await?
         } else {
             self.connection_pool.send_request_any(request).await?
         };
+        // Extensions reply with raw JSON, so build the typed snapshot from it for the cache
+        let snapshot = match &response {
+            BrowserResponse::DomSnapshot(snapshot) => Some(snapshot.clone()),
+            BrowserResponse::RawJson(data) => DomSnapshot::from_extension(data, max_depth, include_styles),
+            _ => None,
+        };
+        if let (Some(tid), Some(snapshot)) = (tab_id, snapshot) {
+            self.data_cache.update_dom_snapshot(tid, snapshot).await;
+        }
 
         let dom_data = Self::extract_response_data(response)?;
 
@@ -186,7 +508,8 @@ impl SimpleBrowserMcpServer {
                 "selector": selector,
                 "maxNodes": effective_max,
                 "excludeScripts": exclude_scripts,
-                "excludeStyles": exclude_styles
+                "excludeStyles": exclude_styles,
+                "pierce": pierce
             },
             "message": message
         }))
@@ -194,10 +517,46 @@ impl SimpleBrowserMcpServer {
 
     // ─── execute_javascript ───────────────────────────────────────────────
 
-    pub async fn handle_execute_javascript(&self, tab_id: Option<u32>, code: String) -> Result<serde_json::Value> {
+    pub async fn handle_execute_javascript(
+        &self,
+        tab_id: Option<u32>,
+        code: String,
+        context_id: Option<String>,
+        await_promise: bool,
+    ) -> Result<serde_json::Value> {
+        self.require_javascript_allowed("execute_javascript")?;
+        if let Some(id) = &context_id {
+            let tid = tab_id.ok_or_else(|| BrowserMcpError::InvalidParameters {
+                message: "tabId is required when using a contextId".to_string(),
+            })?;
+            if !self.data_cache.has_js_context(tid, id) {
+                return Err(BrowserMcpError::InvalidParameters {
+                    message: format!("Unknown JavaScript context {} for tab {}", id, tid),
+                });
+            }
+        }
+
+        // Promises are resolved with Runtime.evaluate, which needs the debugger
+        if await_promise && context_id.is_none() {
+            self.require_debugger(tab_id).await?;
+        }
+
+        let script_id = Uuid::new_v4();
+        self.running_scripts.insert(
+            script_id,
+            RunningScript {
+                tab_id,
+                code: code.chars().take(RUNNING_SCRIPT_PREVIEW_CHARS).collect(),
+                started_at: std::time::Instant::now(),
+            },
+        );
+        let _running = RunningScriptGuard { scripts: &self.running_scripts, script_id };
+
         let request = BrowserRequest::ExecuteJavaScript {
             code,
             return_by_value: true,
+            context_id,
+            await_promise,
         };
 
         let response = if let Some(tid) = tab_id {
@@ -210,6 +569,125 @@ impl SimpleBrowserMcpServer {
         Ok(serde_json::json!({ "result": data }))
     }
 
+    // ─── list_running_scripts / terminate_script ──────────────────────────
+
+    /// `execute_javascript` calls still waiting on the browser, longest-running first.
+    pub fn handle_list_running_scripts(&self, tab_id: Option<u32>) -> serde_json::Value {
+        let mut scripts: Vec<(Uuid, Option<u32>, String, Duration)> = self
+            .running_scripts
+            .iter()
+            .filter(|entry| tab_id.is_none() || entry.tab_id == tab_id)
+            .map(|entry| (*entry.key(), entry.tab_id, entry.code.clone(), entry.started_at.elapsed()))
+            .collect();
+        scripts.sort_by_key(|script| std::cmp::Reverse(script.3));
+
+        let scripts: Vec<serde_json::Value> = scripts
+            .into_iter()
+            .map(|(script_id, tab_id, code, elapsed)| {
+                serde_json::json!({
+                    "scriptId": script_id.to_string(),
+                    "tabId": tab_id,
+                    "elapsedMs": elapsed.as_millis() as u64,
+                    "code": code
+                })
+            })
+            .collect();
+        serde_json::json!({ "count": scripts.len(), "scripts": scripts })
+    }
+
+    /// Abort a running script. `Runtime.terminateExecution` stops whatever JavaScript
+    /// the tab is executing, and the script's own call then fails with the browser's error.
+    pub async fn handle_terminate_script(&self, script_id: &str) -> Result<serde_json::Value> {
+        let unknown = || BrowserMcpError::InvalidParameters {
+            message: format!("No running script {}; list them with list_running_scripts", script_id),
+        };
+        let id = Uuid::parse_str(script_id).map_err(|_| unknown())?;
+        let (tab_id, elapsed) = self
+            .running_scripts
+            .get(&id)
+            .map(|script| (script.tab_id, script.started_at.elapsed()))
+            .ok_or_else(unknown)?;
+        // terminate_script takes no tabId, so the dispatcher can't check the tab for us
+        self.check_tab_access(tab_id)?;
+        self.require_debugger(tab_id).await?;
+
+        let response = match tab_id {
+            Some(tid) => self.connection_pool.send_request(tid, BrowserRequest::TerminateExecution).await?,
+            None => self.connection_pool.send_request_any(BrowserRequest::TerminateExecution).await?,
+        };
+        Self::extract_response_data(response)?;
+        tracing::info!("Terminated script {} after {:?}", script_id, elapsed);
+        Ok(serde_json::json!({
+            "scriptId": script_id,
+            "tabId": tab_id,
+            "elapsedMs": elapsed.as_millis() as u64,
+            "terminated": true
+        }))
+    }
+
+    // ─── set_page_content ─────────────────────────────────────────────────
+
+    /// Scripts in the new document run, so this is gated like `execute_javascript`.
+    pub async fn handle_set_page_content(&self, tab_id: u32, html: String) -> Result<serde_json::Value> {
+        self.require_javascript_allowed("set_page_content")?;
+        let length = html.len();
+        let request = BrowserRequest::SetPageContent { html, tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+        let data = Self::extract_response_data(response)?;
+
+        // Anything cached about the old document is stale now
+        self.data_cache.invalidate_responses(tab_id);
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "replaced": true,
+            "length": length,
+            "title": data.get("title").cloned().unwrap_or(serde_json::Value::Null)
+        }))
+    }
+
+    // ─── create_js_context / dispose_js_context ───────────────────────────
+
+    pub async fn handle_create_js_context(&self, tab_id: u32) -> Result<serde_json::Value> {
+        self.require_debugger(Some(tab_id)).await?;
+        let response = self
+            .connection_pool
+            .send_request(tab_id, BrowserRequest::CreateJsContext)
+            .await?;
+
+        let context_id = match response {
+            BrowserResponse::JsContextCreated { context_id } => context_id,
+            other => Self::extract_response_data(other)?
+                .get("contextId")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+                    message: "Extension did not return a contextId".to_string(),
+                })?,
+        };
+
+        self.data_cache.add_js_context(tab_id, context_id.clone());
+        Ok(serde_json::json!({
+            "contextId": context_id,
+            "tabId": tab_id,
+            "message": format!("Created JavaScript context {} in tab {}", context_id, tab_id)
+        }))
+    }
+
+    pub async fn handle_dispose_js_context(&self, tab_id: u32, context_id: String) -> Result<serde_json::Value> {
+        if !self.data_cache.remove_js_context(tab_id, &context_id) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("Unknown JavaScript context {} for tab {}", context_id, tab_id),
+            });
+        }
+
+        let request = BrowserRequest::DisposeJsContext { context_id: context_id.clone() };
+        self.connection_pool.send_request(tab_id, request).await?;
+        Ok(serde_json::json!({
+            "message": format!("Disposed JavaScript context {} in tab {}", context_id, tab_id),
+            "tabId": tab_id
+        }))
+    }
+
     // ─── get_console_messages ─────────────────────────────────────────────
 
     pub async fn handle_get_console_messages(
@@ -382,18 +860,208 @@ impl SimpleBrowserMcpServer {
         }))
     }
 
+    // ─── replay_request ───────────────────────────────────────────────────
+
+    /// Send a cached request again from inside the tab, with the page's cookies, and
+    /// return the new response. Gated like `execute_javascript` because replaying a
+    /// POST can change server state.
+    pub async fn handle_replay_request(&self, tab_id: u32, request_id: &str) -> Result<serde_json::Value> {
+        self.require_javascript_allowed("replay_request")?;
+        let original = self
+            .data_cache
+            .get_network_request(tab_id, request_id)
+            .await
+            .ok_or_else(|| BrowserMcpError::InvalidParameters {
+                message: format!(
+                    "No cached network request {} in tab {}; list them with get_network_requests",
+                    request_id, tab_id
+                ),
+            })?;
+        if original.request_body_truncated {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!(
+                    "Request {} can't be replayed: its cached body may be cut short by cache.max_retained_body_bytes",
+                    request_id
+                ),
+            });
+        }
+        self.url_policy.check(tab_id, Some(&original.url))?;
+
+        let (dropped, headers): (HashMap<String, String>, HashMap<String, String>) = original
+            .request_headers
+            .into_iter()
+            .partition(|(name, _)| is_unreplayable_header(name));
+        let mut dropped_headers: Vec<String> = dropped.into_keys().collect();
+        dropped_headers.sort();
+
+        let request = BrowserRequest::ReplayRequest {
+            method: original.method.clone(),
+            url: original.url.clone(),
+            headers: headers.clone(),
+            body: original.request_body,
+            tab_id,
+            max_bytes: self.config.server.max_body_bytes,
+        };
+        let response = Self::extract_response_data(self.connection_pool.send_request(tab_id, request).await?)?;
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "originalRequestId": request_id,
+            "request": {
+                "method": original.method,
+                "url": original.url,
+                "headers": headers,
+                "droppedHeaders": dropped_headers
+            },
+            "response": response
+        }))
+    }
+
+    // ─── get_network_request_body ─────────────────────────────────────────
+
+    pub async fn handle_get_network_request_body(
+        &self,
+        tab_id: Option<u32>,
+        request_id: &str,
+    ) -> Result<serde_json::Value> {
+        // Serve retained bodies from the cache, otherwise the extension looks the
+        // request up and replays it if the body wasn't kept
+        let cached = match tab_id {
+            Some(tid) => self.data_cache.get_network_request(tid, request_id).await,
+            None => None,
+        };
+        let (url, method, request_body, response_body, retained_truncated, source) = match cached {
+            Some(req) if req.request_body.is_some() || req.response_body.is_some() => {
+                (req.url, req.method, req.request_body, req.response_body, req.body_truncated, "cache")
+            }
+            _ => {
+                let request = BrowserRequest::GetNetworkRequestBody {
+                    request_id: request_id.to_string(),
+                };
+                let response = if let Some(tid) = tab_id {
+                    self.connection_pool.send_request(tid, request).await?
+                } else {
+                    self.connection_pool.send_request_any(request).await?
+                };
+
+                let data = Self::extract_response_data(response)?;
+                let body_text = |key: &str| match data.get(key) {
+                    None | Some(serde_json::Value::Null) => None,
+                    Some(serde_json::Value::String(s)) => Some(s.clone()),
+                    Some(other) => Some(other.to_string()),
+                };
+                let source = if data.get("replayed").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    "replay"
+                } else {
+                    "browser"
+                };
+                (
+                    data.get("url").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    data.get("method").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    body_text("requestBody"),
+                    body_text("responseBody"),
+                    false,
+                    source,
+                )
+            }
+        };
+
+        let truncate = |body: Option<String>| {
+            body.map(|b| truncation::truncate_string(&b, truncation::MAX_RESPONSE_SIZE))
+        };
+        let request_body = truncate(request_body);
+        let response_body = truncate(response_body);
+
+        Ok(serde_json::json!({
+            "requestId": request_id,
+            "url": url,
+            "method": method,
+            "requestBody": request_body.as_ref().map(|(b, _)| b),
+            "responseBody": response_body.as_ref().map(|(b, _)| b),
+            "truncated": retained_truncated
+                || request_body.is_some_and(|(_, t)| t)
+                || response_body.is_some_and(|(_, t)| t),
+            "source": source
+        }))
+    }
+
+    // ─── download_resource ────────────────────────────────────────────────
+
+    /// Unlike get_network_request_body this fetches the URL now, from the page, rather
+    /// than reading traffic that was already captured.
+    pub async fn handle_download_resource(&self, tab_id: u32, url: &str) -> Result<serde_json::Value> {
+        use base64::Engine;
+
+        if url.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "url must not be empty".to_string(),
+            });
+        }
+
+        // The fetch carries the page's cookies, so the target must pass the URL policy too.
+        // Relative URLs resolve against the tab's page, as the extension will resolve them.
+        let target = self
+            .data_cache
+            .get_tab_url(tab_id)
+            .and_then(|page| reqwest::Url::parse(&page).ok())
+            .and_then(|page| page.join(url).ok())
+            .map_or_else(|| url.to_string(), String::from);
+        self.url_policy.check(tab_id, Some(&target))?;
+
+        let max_bytes = self.config.server.max_body_bytes;
+        let request = BrowserRequest::DownloadResource { url: url.to_string(), tab_id, max_bytes };
+        let data = Self::extract_response_data(self.connection_pool.send_request(tab_id, request).await?)?;
+
+        let encoded = data.get("data").and_then(|v| v.as_str()).ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+            message: format!("Download of {} returned no data", url),
+        })?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| BrowserMcpError::BrowserExtensionError {
+                message: format!("Download of {} is not valid base64: {}", url, e),
+            })?;
+        if bytes.len() > max_bytes {
+            return Err(BrowserMcpError::InvalidRequest {
+                message: format!("{} is {} bytes, over the {}-byte download limit", url, bytes.len(), max_bytes),
+            });
+        }
+
+        // Redirects may have moved us; report where the bytes came from
+        let final_url = data.get("url").and_then(|v| v.as_str()).unwrap_or(url);
+        let mime_type = detect_mime_type(&bytes, data.get("contentType").and_then(|v| v.as_str()));
+        Ok(serde_json::json!({
+            "url": final_url,
+            "status": data.get("status"),
+            "mimeType": mime_type,
+            "size": bytes.len(),
+            "_resource": {
+                "uri": final_url,
+                "mimeType": mime_type,
+                "blob": encoded
+            }
+        }))
+    }
+
     // ─── capture_screenshot ───────────────────────────────────────────────
 
+    /// Captures larger than `max_size` (width, height; 0 for no limit) are downscaled,
+    /// keeping their aspect ratio, before they are cached and returned. Limits not given
+    /// fall back to `server.max_screenshot_width`/`max_screenshot_height`.
+    /// `capture_selector` clips to that element's bounding box, taking precedence over `clip`.
     pub async fn handle_capture_screenshot(
         &self,
         tab_id: Option<u32>,
         format: &str,
         quality: f32,
+        max_size: (Option<u32>, Option<u32>),
+        clip: Option<BoundingBox>,
+        capture_selector: Option<String>,
     ) -> Result<serde_json::Value> {
+        self.require_debugger(tab_id).await?;
         let request = BrowserRequest::CaptureScreenshot {
             format: format.to_string(),
             quality: Some(quality),
-            clip: None,
+            clip,
+            capture_selector,
         };
         let response = if let Some(tid) = tab_id {
             self.connection_pool.send_request(tid, request).await?
@@ -401,26 +1069,222 @@ impl SimpleBrowserMcpServer {
             self.connection_pool.send_request_any(request).await?
         };
 
-        let data = Self::extract_response_data(response)?;
+        // Binary frames arrive as raw image bytes rather than a data URL
+        let shot = match response {
+            BrowserResponse::Screenshot(shot) => shot,
+            other => {
+                let data = Self::extract_response_data(other)?;
+                match Self::screenshot_data_url(&data) {
+                    Some(data_url) => Self::decode_screenshot_data_url(data_url, data.get("clip"))?,
+                    None => {
+                        // Return text description with truncated data preview
+                        let data_str = data.to_string();
+                        let preview = if data_str.len() > 100 {
+                            format!("{}...", &data_str[..100])
+                        } else {
+                            data_str.clone()
+                        };
+                        return Ok(serde_json::json!({
+                            "message": format!("Screenshot captured in {} format. Data URL: {}", format, preview),
+                            "format": format,
+                            "dataLength": data_str.len()
+                        }));
+                    }
+                }
+            }
+        };
 
-        // Return text description with truncated data URL preview
-        let data_str = if let Some(s) = data.as_str() {
-            s.to_string()
-        } else {
-            serde_json::to_string(&data).unwrap_or_default()
+        // Extensions that can only capture PNG still hand the client the format it asked for
+        let requested_format = if format == "jpg" { "jpeg" } else { format };
+        let captured_format = if shot.format == "jpg" { "jpeg" } else { shot.format.as_str() };
+        let converted_from = (captured_format != requested_format).then(|| shot.format.clone());
+        let shot = match &converted_from {
+            Some(from) => {
+                tracing::debug!("Converting {} screenshot to {}", from, requested_format);
+                let data = utils::convert_image(&shot.data, requested_format, quality.clamp(1.0, 100.0) as u8)
+                    .map_err(|message| BrowserMcpError::InternalError { message })?;
+                ScreenshotData {
+                    data,
+                    format: requested_format.to_string(),
+                    ..shot
+                }
+            }
+            None => shot,
         };
 
-        let preview = if data_str.len() > 100 {
-            format!("{}...", &data_str[..100])
-        } else {
-            data_str.clone()
+        let max_width = max_size.0.unwrap_or(self.config.server.max_screenshot_width);
+        let max_height = max_size.1.unwrap_or(self.config.server.max_screenshot_height);
+        let original_size = (shot.width, shot.height);
+        let scaled = utils::downscale_image(
+            &shot.data,
+            &shot.format,
+            original_size,
+            (max_width, max_height),
+            quality.clamp(1.0, 100.0) as u8,
+        )
+        .map_err(|message| BrowserMcpError::InternalError { message })?;
+        let (shot, scale) = match scaled {
+            Some(scaled) => {
+                tracing::debug!(
+                    "Downscaled {}x{} screenshot to {}x{}",
+                    original_size.0,
+                    original_size.1,
+                    scaled.width,
+                    scaled.height
+                );
+                let shot = ScreenshotData {
+                    data: scaled.data,
+                    width: scaled.width,
+                    height: scaled.height,
+                    ..shot
+                };
+                (shot, scaled.scale)
+            }
+            None => (shot, 1.0),
         };
 
-        Ok(serde_json::json!({
-            "message": format!("Screenshot captured in {} format. Data URL: {}", format, preview),
-            "format": format,
-            "dataLength": data_str.len()
-        }))
+        use base64::Engine;
+        let prefix = &shot.data[..shot.data.len().min(75)];
+        let preview = format!(
+            "data:image/{};base64,{}...",
+            shot.format,
+            base64::engine::general_purpose::STANDARD.encode(prefix)
+        );
+        let mut result = serde_json::json!({
+            "message": format!("Screenshot captured in {} format. Data URL: {}", shot.format, preview),
+            "format": shot.format,
+            "mimeType": format!("image/{}", shot.format),
+            "width": shot.width,
+            "height": shot.height,
+            "dataLength": shot.data.len(),
+            "scale": scale
+        });
+        if let Some(from) = converted_from {
+            result["convertedFrom"] = serde_json::Value::String(from);
+        }
+        if scale < 1.0 {
+            result["originalWidth"] = serde_json::json!(original_size.0);
+            result["originalHeight"] = serde_json::json!(original_size.1);
+        }
+        if let Some(clip) = &shot.clip {
+            result["clip"] = utils::to_json_value(clip)?;
+        }
+        if let Some(tid) = tab_id {
+            self.data_cache.update_screenshot(tid, shot).await;
+        }
+        Ok(result)
+    }
+
+    /// The data URL in a text screenshot response: the data itself, or its `dataUrl`
+    /// when a clipped capture also reports its `clip`.
+    fn screenshot_data_url(data: &serde_json::Value) -> Option<&str> {
+        data.as_str()
+            .or_else(|| data.get("dataUrl").and_then(|v| v.as_str()))
+            .filter(|s| s.starts_with("data:image/"))
+    }
+
+    /// Decode the `data:image/...;base64,` URL the extension falls back to when it
+    /// can't send a binary frame.
+    fn decode_screenshot_data_url(data_url: &str, clip: Option<&serde_json::Value>) -> Result<ScreenshotData> {
+        let (header, encoded) = data_url.split_once(";base64,").ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+            message: "Screenshot response is not a base64 data URL".to_string(),
+        })?;
+        use base64::Engine;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| BrowserMcpError::BrowserExtensionError {
+                message: format!("Screenshot data is not valid base64: {}", e),
+            })?;
+        let (width, height) =
+            utils::image_dimensions(&bytes).map_err(|message| BrowserMcpError::BrowserExtensionError { message })?;
+        Ok(ScreenshotData {
+            format: header.trim_start_matches("data:image/").to_string(),
+            data: bytes,
+            width,
+            height,
+            timestamp: std::time::SystemTime::now(),
+            clip: clip.and_then(|clip| serde_json::from_value(clip.clone()).ok()),
+        })
+    }
+
+    // ─── set_baseline_screenshot / compare_screenshot ─────────────────────
+
+    /// Capture the tab as a PNG, whether the extension sends raw bytes or a data URL.
+    async fn capture_screenshot_data(&self, tab_id: u32) -> Result<ScreenshotData> {
+        self.require_debugger(Some(tab_id)).await?;
+        let request = BrowserRequest::CaptureScreenshot {
+            format: "png".to_string(),
+            quality: None,
+            clip: None,
+            capture_selector: None,
+        };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+        if let BrowserResponse::Screenshot(shot) = response {
+            return Ok(shot);
+        }
+
+        let data = Self::extract_response_data(response)?;
+        let data_url = Self::screenshot_data_url(&data).ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+            message: "Screenshot response did not contain a data URL".to_string(),
+        })?;
+        Self::decode_screenshot_data_url(data_url, None)
+    }
+
+    pub async fn handle_set_baseline_screenshot(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let shot = self.capture_screenshot_data(tab_id).await?;
+        let result = serde_json::json!({
+            "tabId": tab_id,
+            "format": shot.format,
+            "width": shot.width,
+            "height": shot.height,
+            "dataLength": shot.data.len()
+        });
+        self.data_cache.set_baseline_screenshot(tab_id, shot);
+        Ok(result)
+    }
+
+    /// Diff a fresh capture against the tab's baseline. `threshold` is the percentage of
+    /// differing pixels tolerated, defaulting to `server.screenshot_diff_threshold`.
+    pub async fn handle_compare_screenshot(
+        &self,
+        tab_id: u32,
+        threshold: Option<f64>,
+        include_diff_image: bool,
+    ) -> Result<serde_json::Value> {
+        let threshold = threshold.unwrap_or(self.config.server.screenshot_diff_threshold);
+        if !(0.0..=100.0).contains(&threshold) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("Threshold {} must be a percentage between 0 and 100", threshold),
+            });
+        }
+        let baseline = self.data_cache.get_baseline_screenshot(tab_id).ok_or_else(|| {
+            BrowserMcpError::InvalidParameters {
+                message: format!("No baseline screenshot for tab {}; call set_baseline_screenshot first", tab_id),
+            }
+        })?;
+
+        let current = self.capture_screenshot_data(tab_id).await?;
+        let diff = utils::diff_screenshots(&baseline.data, &current.data, include_diff_image)
+            .map_err(|message| BrowserMcpError::InvalidParameters { message })?;
+
+        let mut result = serde_json::json!({
+            "tabId": tab_id,
+            "width": diff.width,
+            "height": diff.height,
+            "differingPixels": diff.differing_pixels,
+            "differencePercent": diff.difference_percent,
+            "threshold": threshold,
+            "verdict": if diff.difference_percent <= threshold { "match" } else { "mismatch" }
+        });
+        if let Some(png) = diff.diff_image {
+            use base64::Engine;
+            result["_resource"] = serde_json::json!({
+                "uri": format!("browser://tab/{}/screenshot-diff", tab_id),
+                "mimeType": "image/png",
+                "blob": base64::engine::general_purpose::STANDARD.encode(png)
+            });
+        }
+        Ok(result)
     }
 
     // ─── get_performance_metrics ──────────────────────────────────────────
@@ -429,6 +1293,7 @@ impl SimpleBrowserMcpServer {
         &self,
         tab_id: Option<u32>,
     ) -> Result<serde_json::Value> {
+        self.require_debugger(tab_id).await?;
         let request = BrowserRequest::GetPerformanceMetrics;
         let response = if let Some(tid) = tab_id {
             self.connection_pool.send_request(tid, request).await?
@@ -436,42 +1301,875 @@ impl SimpleBrowserMcpServer {
             self.connection_pool.send_request_any(request).await?
         };
 
+        // Keep typed metrics around for get_web_vitals_report; extensions reply with raw JSON
+        let metrics = match &response {
+            BrowserResponse::PerformanceMetrics(metrics) => Some(metrics.clone()),
+            BrowserResponse::RawJson(data) => PerformanceMetrics::from_extension(data),
+            _ => None,
+        };
+        if let (Some(tid), Some(metrics)) = (tab_id, metrics) {
+            self.data_cache.update_performance_metrics(tid, metrics).await;
+        }
         Self::extract_response_data(response)
     }
 
-    // ─── get_accessibility_tree ───────────────────────────────────────────
+    // ─── get_web_vitals_report ────────────────────────────────────────────
+
+    /// Built from cached metrics; returns the report with the age of those metrics.
+    pub async fn handle_get_web_vitals_report(&self, tab_id: u32) -> Result<(serde_json::Value, Duration)> {
+        let metrics = self
+            .data_cache
+            .get_performance_metrics(tab_id)
+            .await
+            .ok_or_else(|| BrowserMcpError::CacheError {
+                message: format!("No performance metrics cached for tab {}", tab_id),
+            })?;
+
+        let mut report = utils::to_json_value(&metrics.core_web_vitals.report())?;
+        report["tabId"] = tab_id.into();
+        report["measuredAt"] = utils::to_json_value(&metrics.timestamp)?;
+        Ok((report, Self::age_of(metrics.timestamp)))
+    }
 
-    pub async fn handle_get_accessibility_tree(
+    // ─── get_resource_breakdown ───────────────────────────────────────────
+
+    /// Total the tab's cached network requests and resource timings by type, without
+    /// asking the browser for anything. Returns it with the age of the newest capture.
+    pub async fn handle_get_resource_breakdown(&self, tab_id: u32) -> Result<(serde_json::Value, Duration)> {
+        let requests = self.data_cache.get_network_requests(tab_id).await.unwrap_or_default();
+        let metrics = self.data_cache.get_performance_metrics(tab_id).await;
+        let timings = metrics.as_ref().map(|metrics| metrics.resource_timing.as_slice()).unwrap_or_default();
+        if requests.is_empty() && timings.is_empty() {
+            return Err(BrowserMcpError::CacheError {
+                message: format!(
+                    "No network requests or resource timings cached for tab {}; capture them with get_network_requests or get_performance_metrics first",
+                    tab_id
+                ),
+            });
+        }
+
+        let newest = requests
+            .iter()
+            .map(|request| request.timestamp)
+            .chain(metrics.as_ref().map(|metrics| metrics.timestamp))
+            .max()
+            .unwrap_or_else(chrono::Utc::now);
+
+        let mut value = utils::to_json_value(&ResourceBreakdown::new(&requests, timings))?;
+        value["tabId"] = tab_id.into();
+        Ok((value, Self::age_of(newest)))
+    }
+
+    // ─── get_resource_usage ───────────────────────────────────────────────
+
+    pub async fn handle_get_resource_usage(
         &self,
         tab_id: Option<u32>,
-        timeout_ms: Option<u64>,
     ) -> Result<serde_json::Value> {
-        let request = BrowserRequest::GetAccessibilityTree { max_depth: None };
-        let custom_timeout = timeout_ms.map(Duration::from_millis);
+        let request = BrowserRequest::GetResourceUsage;
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
 
-        let response = self.connection_pool.send_request_with_timeout(
-            tab_id,
-            request,
-            custom_timeout,
-        ).await?;
+        let usage = match response {
+            BrowserResponse::ResourceUsage(usage) => usage,
+            other => serde_json::from_value::<ResourceUsage>(Self::extract_response_data(other)?)?,
+        };
+        utils::to_json_value(&usage)
+    }
+
+    // ─── wait_for_network_idle ────────────────────────────────────────────
+
+    pub async fn handle_wait_for_network_idle(
+        &self,
+        tab_id: Option<u32>,
+        idle_ms: u64,
+        timeout_ms: u64,
+        max_inflight: usize,
+    ) -> Result<serde_json::Value> {
+        if max_inflight == 0 {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "maxInflight must be at least 1".to_string(),
+            });
+        }
 
+        let request = BrowserRequest::WaitForNetworkIdle { idle_ms, timeout_ms, max_inflight };
+        let response = self.connection_pool.send_request_with_timeout(tab_id, request, None).await?;
         Self::extract_response_data(response)
     }
 
-    // ─── get_browser_tabs ─────────────────────────────────────────────────
+    // ─── wait_for_console_error ───────────────────────────────────────────
 
-    pub async fn handle_get_browser_tabs(&self) -> Result<serde_json::Value> {
-        let connections = self.connection_pool.get_active_connections().await;
-        if connections.is_empty() {
-            return Ok(serde_json::json!({
-                "tabs": [],
-                "message": "No active browser connections"
-            }));
+    pub async fn handle_wait_for_console_error(
+        &self,
+        tab_id: Option<u32>,
+        timeout_ms: u64,
+        pattern: Option<String>,
+    ) -> Result<serde_json::Value> {
+        if timeout_ms == 0 {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "timeoutMs must be greater than 0".to_string(),
+            });
         }
+        // Errors are observed through Runtime events, which need the debugger
+        self.require_debugger(tab_id).await?;
 
-        let request = BrowserRequest::GetBrowserTabs;
-        match self.connection_pool.send_request_any(request).await {
-            Ok(response) => {
+        let request = BrowserRequest::WaitForConsoleError { timeout_ms, pattern };
+        let response = self.connection_pool.send_request_with_timeout(tab_id, request, None).await?;
+        Self::extract_response_data(response)
+    }
+
+    // ─── get_computed_style ───────────────────────────────────────────────
+
+    pub async fn handle_get_computed_style(
+        &self,
+        tab_id: Option<u32>,
+        selector: String,
+        properties: Option<Vec<String>>,
+    ) -> Result<serde_json::Value> {
+        let request = BrowserRequest::GetComputedStyle {
+            selector: selector.clone(),
+            properties,
+        };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        let styles = match response {
+            BrowserResponse::ComputedStyle(styles) => styles,
+            other => serde_json::from_value::<HashMap<String, String>>(Self::extract_response_data(other)?)?,
+        };
+        // Sort so the output is stable for diffing
+        let styles: std::collections::BTreeMap<_, _> = styles.into_iter().collect();
+        Ok(serde_json::json!({
+            "selector": selector,
+            "styles": styles
+        }))
+    }
+
+    // ─── get_links ────────────────────────────────────────────────────────
+
+    pub async fn handle_get_links(
+        &self,
+        tab_id: Option<u32>,
+        same_origin_only: bool,
+        include_text: bool,
+    ) -> Result<serde_json::Value> {
+        let request = BrowserRequest::GetLinks { same_origin_only, include_text };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        let links = match response {
+            BrowserResponse::Links(links) => links,
+            other => {
+                let data = Self::extract_response_data(other)?;
+                let data = data.get("links").cloned().unwrap_or(data);
+                serde_json::from_value::<Vec<PageLink>>(data)?
+            }
+        };
+        Ok(serde_json::json!({
+            "count": links.len(),
+            "links": links
+        }))
+    }
+
+    // ─── press_key ────────────────────────────────────────────────────────
+
+    pub async fn handle_press_key(
+        &self,
+        tab_id: Option<u32>,
+        selector: Option<String>,
+        key: String,
+        modifiers: Vec<String>,
+    ) -> Result<serde_json::Value> {
+        if key.is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "key must not be empty".to_string(),
+            });
+        }
+        let modifiers = modifiers
+            .iter()
+            .map(|name| normalize_key_modifier(name))
+            .collect::<Result<Vec<_>>>()?;
+        self.require_debugger(tab_id).await?;
+
+        let request = BrowserRequest::DispatchKey {
+            selector: selector.clone(),
+            key: key.clone(),
+            modifiers: modifiers.clone(),
+        };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        Self::extract_response_data(response)?;
+        Ok(serde_json::json!({
+            "success": true,
+            "key": key,
+            "modifiers": modifiers,
+            "selector": selector
+        }))
+    }
+
+    // ─── fill_form ────────────────────────────────────────────────────────
+
+    /// The extension reports each field as `filled`, `not_found` or `error` with a
+    /// message; one missing field doesn't stop the rest from being filled.
+    pub async fn handle_fill_form(
+        &self,
+        tab_id: Option<u32>,
+        fields: HashMap<String, serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        if fields.is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "fields must name at least one selector".to_string(),
+            });
+        }
+        if let Some((selector, _)) = fields.iter().find(|(_, value)| !(value.is_string() || value.is_number() || value.is_boolean())) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("Value for '{}' must be a string, number or boolean", selector),
+            });
+        }
+
+        let request = BrowserRequest::FillForm { fields: fields.clone() };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+        let data = Self::extract_response_data(response)?;
+        if let Some(tid) = tab_id {
+            self.data_cache.invalidate_responses(tid);
+        }
+
+        let reported = data.get("fields").cloned().unwrap_or_default();
+        let mut results = serde_json::Map::new();
+        let (mut filled, mut not_found) = (0, Vec::new());
+        for selector in fields.keys() {
+            let status = reported[selector].get("status").and_then(|s| s.as_str()).unwrap_or("error");
+            let result = match status {
+                "filled" => {
+                    filled += 1;
+                    serde_json::json!({ "success": true })
+                }
+                "not_found" => {
+                    not_found.push(selector.clone());
+                    serde_json::json!({ "success": false, "error": "not found" })
+                }
+                _ => {
+                    let error = reported[selector].get("error").and_then(|e| e.as_str()).unwrap_or("no result from the page");
+                    serde_json::json!({ "success": false, "error": error })
+                }
+            };
+            results.insert(selector.clone(), result);
+        }
+        not_found.sort();
+
+        Ok(serde_json::json!({
+            "fields": results,
+            "filled": filled,
+            "failed": fields.len() - filled,
+            "notFound": not_found
+        }))
+    }
+
+    // ─── evaluate_xpath ───────────────────────────────────────────────────
+
+    pub async fn handle_evaluate_xpath(&self, tab_id: Option<u32>, expression: String) -> Result<serde_json::Value> {
+        if expression.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "XPath expression must not be empty".to_string(),
+            });
+        }
+
+        let request = BrowserRequest::EvaluateXPath { expression };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        let result = match response {
+            BrowserResponse::XPathResult(result) => result,
+            other => {
+                let data = Self::extract_response_data(other)?;
+                let data = data.get("result").cloned().unwrap_or(data);
+                serde_json::from_value::<XPathResult>(data)?
+            }
+        };
+        let mut value = utils::to_json_value(&result)?;
+        if let XPathResult::Nodes(nodes) = &result {
+            value["count"] = nodes.len().into();
+        }
+        Ok(value)
+    }
+
+    // ─── get_element_rect ─────────────────────────────────────────────────
+
+    pub async fn handle_get_element_rect(&self, tab_id: Option<u32>, selector: String) -> Result<serde_json::Value> {
+        if selector.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "selector must not be empty".to_string(),
+            });
+        }
+
+        let request = BrowserRequest::GetElementRect { selector: selector.clone() };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        let rect = match response {
+            BrowserResponse::ElementRect(rect) => rect,
+            other => serde_json::from_value::<ElementRect>(Self::extract_response_data(other)?)?,
+        };
+        let mut value = utils::to_json_value(&rect)?;
+        value["selector"] = selector.into();
+        Ok(value)
+    }
+
+    // ─── observe_mutations ────────────────────────────────────────────────
+
+    pub async fn handle_observe_mutations(
+        &self,
+        tab_id: Option<u32>,
+        selector: Option<String>,
+        duration_ms: u64,
+        attributes: bool,
+        child_list: bool,
+        subtree: bool,
+    ) -> Result<serde_json::Value> {
+        if duration_ms == 0 || duration_ms > MAX_OBSERVE_DURATION_MS {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("durationMs must be between 1 and {}", MAX_OBSERVE_DURATION_MS),
+            });
+        }
+        if !attributes && !child_list {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "At least one of attributes or childList must be observed".to_string(),
+            });
+        }
+
+        let request = BrowserRequest::ObserveMutations { selector, duration_ms, attributes, child_list, subtree };
+        let response = self.connection_pool.send_request_with_timeout(tab_id, request, None).await?;
+
+        let (records, truncated) = match response {
+            BrowserResponse::MutationRecords(records) => (records, false),
+            other => {
+                let data = Self::extract_response_data(other)?;
+                let truncated = data.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false);
+                let records = data.get("records").cloned().unwrap_or(data);
+                (serde_json::from_value::<Vec<MutationRecord>>(records)?, truncated)
+            }
+        };
+        Ok(serde_json::json!({
+            "count": records.len(),
+            "records": records,
+            "truncated": truncated,
+            "durationMs": duration_ms
+        }))
+    }
+
+    // ─── get_navigation_history ───────────────────────────────────────────
+
+    /// Always asks the browser: history changes with every navigation, so it is never cached.
+    pub async fn handle_get_navigation_history(&self, tab_id: u32) -> Result<serde_json::Value> {
+        self.require_debugger(Some(tab_id)).await?;
+        let request = BrowserRequest::GetNavigationHistory { tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+
+        let history = match response {
+            BrowserResponse::NavigationHistory(history) => history,
+            other => serde_json::from_value::<NavigationHistory>(Self::extract_response_data(other)?)?,
+        };
+        let mut value = utils::to_json_value(&history)?;
+        value["tabId"] = tab_id.into();
+        value["count"] = history.entries.len().into();
+        Ok(value)
+    }
+
+    // ─── clear_browsing_data ──────────────────────────────────────────────
+
+    pub async fn handle_clear_browsing_data(
+        &self,
+        tab_id: u32,
+        cache: bool,
+        cookies: bool,
+        local_storage: bool,
+    ) -> Result<serde_json::Value> {
+        if !(cache || cookies || local_storage) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "Select at least one of cache, cookies or localStorage to clear".to_string(),
+            });
+        }
+        self.require_debugger(Some(tab_id)).await?;
+        let request = BrowserRequest::ClearBrowsingData {
+            cache,
+            cookies,
+            local_storage,
+            tab_id,
+        };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+        let data = Self::extract_response_data(response)?;
+
+        self.data_cache.invalidate_responses(tab_id);
+        let cleared: Vec<&str> = [(cache, "cache"), (cookies, "cookies"), (local_storage, "localStorage")]
+            .into_iter()
+            .filter_map(|(selected, name)| selected.then_some(name))
+            .collect();
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "cleared": cleared,
+            "origin": data.get("origin").cloned().unwrap_or(serde_json::Value::Null)
+        }))
+    }
+
+    // ─── capture_mhtml ────────────────────────────────────────────────────
+
+    pub async fn handle_capture_mhtml(&self, tab_id: u32) -> Result<serde_json::Value> {
+        use base64::Engine;
+
+        self.require_debugger(Some(tab_id)).await?;
+        let request = BrowserRequest::CaptureMhtml { tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+
+        let mhtml = match response {
+            BrowserResponse::Mhtml { data } => data,
+            other => Self::extract_response_data(other)?
+                .get("data")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+                    message: "MHTML capture returned no data".to_string(),
+                })?,
+        };
+        let url = self.data_cache.get_tab_url(tab_id);
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "url": url,
+            "mimeType": "multipart/related",
+            "size": mhtml.len(),
+            "_resource": {
+                "uri": format!("browser://tab/{}/mhtml", tab_id),
+                "mimeType": "multipart/related",
+                "blob": base64::engine::general_purpose::STANDARD.encode(mhtml.as_bytes())
+            }
+        }))
+    }
+
+    // ─── get_layout_metrics ───────────────────────────────────────────────
+
+    pub async fn handle_get_layout_metrics(&self, tab_id: u32) -> Result<serde_json::Value> {
+        self.require_debugger(Some(tab_id)).await?;
+        let request = BrowserRequest::GetLayoutMetrics { tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+
+        let metrics = match response {
+            BrowserResponse::LayoutMetrics(metrics) => metrics,
+            other => serde_json::from_value::<LayoutMetrics>(Self::extract_response_data(other)?)?,
+        };
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "layoutMetrics": metrics
+        }))
+    }
+
+    // ─── get_capabilities ─────────────────────────────────────────────────
+
+    /// Always asks the extension: permissions and attached debuggers change underneath us.
+    pub async fn handle_get_capabilities(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let request = BrowserRequest::GetCapabilities { tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+
+        let capabilities = match response {
+            BrowserResponse::Capabilities(capabilities) => capabilities,
+            other => serde_json::from_value::<TabCapabilities>(Self::extract_response_data(other)?)?,
+        };
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "capabilities": capabilities
+        }))
+    }
+
+    // ─── go_back_forward ──────────────────────────────────────────────────
+
+    /// The extension rejects deltas that run past either end of the history.
+    pub async fn handle_go_back_forward(&self, tab_id: Option<u32>, delta: i32) -> Result<serde_json::Value> {
+        if delta == 0 {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "delta must be non-zero (negative goes back, positive goes forward)".to_string(),
+            });
+        }
+        self.require_debugger(tab_id).await?;
+
+        let request = BrowserRequest::NavigateHistory { delta };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+        if let Some(tid) = tab_id {
+            self.data_cache.invalidate_responses(tid);
+        }
+
+        let mut data = Self::extract_response_data(response)?;
+        data["delta"] = delta.into();
+        Ok(data)
+    }
+
+    // ─── set_intercept_rules / clear_intercept_rules ──────────────────────
+
+    /// Replace the tab's interception rules. An empty list turns interception off.
+    pub async fn handle_set_intercept_rules(&self, tab_id: u32, rules: Vec<InterceptRule>) -> Result<serde_json::Value> {
+        for rule in &rules {
+            validate_intercept_rule(rule)?;
+        }
+        if !rules.is_empty() {
+            self.require_debugger(Some(tab_id)).await?;
+        }
+
+        let request = BrowserRequest::SetInterceptRules { rules: rules.clone() };
+        self.connection_pool.send_request(tab_id, request).await?;
+        self.data_cache.set_intercept_rules(tab_id, rules.clone());
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "count": rules.len(),
+            "rules": rules
+        }))
+    }
+
+    pub async fn handle_clear_intercept_rules(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let cleared = self.data_cache.get_intercept_rules(tab_id).len();
+        self.connection_pool
+            .send_request(tab_id, BrowserRequest::SetInterceptRules { rules: Vec::new() })
+            .await?;
+        self.data_cache.set_intercept_rules(tab_id, Vec::new());
+        Ok(serde_json::json!({
+            "message": format!("Cleared {} intercept rules in tab {}", cleared, tab_id),
+            "tabId": tab_id,
+            "cleared": cleared
+        }))
+    }
+
+    // ─── set_extra_headers / clear_extra_headers ──────────────────────────
+
+    /// Replace the headers added to the tab's requests. Use clear to remove them.
+    pub async fn handle_set_extra_headers(
+        &self,
+        tab_id: u32,
+        headers: HashMap<String, String>,
+    ) -> Result<serde_json::Value> {
+        if headers.is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "headers must not be empty; use clear_extra_headers to remove them".to_string(),
+            });
+        }
+        for (name, value) in &headers {
+            axum::http::HeaderName::from_bytes(name.as_bytes()).map_err(|_| BrowserMcpError::InvalidParameters {
+                message: format!("Invalid header name '{}'", name),
+            })?;
+            axum::http::HeaderValue::from_str(value).map_err(|_| BrowserMcpError::InvalidParameters {
+                message: format!("Invalid value for header '{}'", name),
+            })?;
+        }
+        self.require_debugger(Some(tab_id)).await?;
+
+        let request = BrowserRequest::SetExtraHeaders { headers: headers.clone(), tab_id };
+        self.connection_pool.send_request(tab_id, request).await?;
+        let names = header_names(&headers);
+        self.data_cache.set_extra_headers(tab_id, headers);
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "count": names.len(),
+            "headers": names
+        }))
+    }
+
+    pub async fn handle_clear_extra_headers(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let cleared = self.data_cache.get_extra_headers(tab_id).len();
+        // Headers live on the debugger session, so a detached tab has none left to clear
+        if self.data_cache.is_debugger_attached(tab_id).await {
+            self.connection_pool
+                .send_request(tab_id, BrowserRequest::SetExtraHeaders { headers: HashMap::new(), tab_id })
+                .await?;
+        }
+        self.data_cache.set_extra_headers(tab_id, HashMap::new());
+        Ok(serde_json::json!({
+            "message": format!("Cleared {} extra headers in tab {}", cleared, tab_id),
+            "tabId": tab_id,
+            "cleared": cleared
+        }))
+    }
+
+    // ─── get_user_agent / set_user_agent ──────────────────────────────────
+
+    /// The user agent and language the tab's pages see, which reflect any override.
+    pub async fn handle_get_user_agent(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let response = self
+            .connection_pool
+            .send_request(tab_id, BrowserRequest::GetUserAgent { tab_id })
+            .await?;
+        let data = Self::extract_response_data(response)?;
+        let user_agent_override = self.data_cache.get_user_agent(tab_id);
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "userAgent": data.get("userAgent").cloned().unwrap_or(serde_json::Value::Null),
+            "language": data.get("language").cloned().unwrap_or(serde_json::Value::Null),
+            "overridden": user_agent_override.is_some(),
+            "override": user_agent_override
+        }))
+    }
+
+    /// Make the tab report `user_agent` until the debugger detaches.
+    pub async fn handle_set_user_agent(
+        &self,
+        tab_id: u32,
+        user_agent: String,
+        accept_language: Option<String>,
+    ) -> Result<serde_json::Value> {
+        if user_agent.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "userAgent must not be empty".to_string(),
+            });
+        }
+        let accept_language = accept_language.filter(|language| !language.trim().is_empty());
+        self.require_debugger(Some(tab_id)).await?;
+
+        let request = BrowserRequest::SetUserAgent {
+            user_agent: user_agent.clone(),
+            accept_language: accept_language.clone(),
+            tab_id,
+        };
+        self.connection_pool.send_request(tab_id, request).await?;
+        let user_agent_override = UserAgentOverride { user_agent, accept_language };
+        self.data_cache.set_user_agent(tab_id, user_agent_override.clone());
+        let mut value = utils::to_json_value(&user_agent_override)?;
+        value["tabId"] = tab_id.into();
+        Ok(value)
+    }
+
+    // ─── get_emulated_media / set_emulated_media ──────────────────────────
+
+    /// The media emulation set on the tab, if any. Answered from the cache, since the
+    /// debugger has no way to read it back.
+    pub fn handle_get_emulated_media(&self, tab_id: u32) -> serde_json::Value {
+        let media = self.data_cache.get_emulated_media(tab_id);
+        serde_json::json!({
+            "tabId": tab_id,
+            "overridden": media.is_some(),
+            "media": media.as_ref().and_then(|media| media.media.clone()),
+            "features": media.map(|media| media.features).unwrap_or_default()
+        })
+    }
+
+    /// Replace the tab's media emulation until the debugger detaches. No media and no
+    /// features clears it.
+    pub async fn handle_set_emulated_media(
+        &self,
+        tab_id: u32,
+        media: Option<String>,
+        features: Vec<MediaFeature>,
+    ) -> Result<serde_json::Value> {
+        let media = media.filter(|media| !media.is_empty());
+        if let Some(media) = media.as_deref().filter(|media| !matches!(*media, "print" | "screen")) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("media must be \"print\" or \"screen\", got \"{}\"", media),
+            });
+        }
+        for feature in &features {
+            if !EMULATED_MEDIA_FEATURES.contains(&feature.name.as_str()) {
+                return Err(BrowserMcpError::InvalidParameters {
+                    message: format!(
+                        "Unsupported media feature \"{}\"; expected one of {}",
+                        feature.name,
+                        EMULATED_MEDIA_FEATURES.join(", ")
+                    ),
+                });
+            }
+            if feature.value.trim().is_empty() {
+                return Err(BrowserMcpError::InvalidParameters {
+                    message: format!("Media feature \"{}\" needs a value", feature.name),
+                });
+            }
+        }
+        self.require_debugger(Some(tab_id)).await?;
+
+        let request = BrowserRequest::SetEmulatedMedia {
+            media: media.clone(),
+            features: features.clone(),
+            tab_id,
+        };
+        self.connection_pool.send_request(tab_id, request).await?;
+        self.data_cache.set_emulated_media(tab_id, EmulatedMedia { media, features });
+        Ok(self.handle_get_emulated_media(tab_id))
+    }
+
+    // ─── add_init_script / remove_init_script ─────────────────────────────
+
+    /// Run `source` in every document the tab loads from now on, before the page's own scripts.
+    pub async fn handle_add_init_script(&self, tab_id: u32, source: String) -> Result<serde_json::Value> {
+        self.require_javascript_allowed("add_init_script")?;
+        if source.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "source must not be empty".to_string(),
+            });
+        }
+        self.require_debugger(Some(tab_id)).await?;
+
+        let request = BrowserRequest::AddInitScript { source: source.clone(), tab_id };
+        let response = self.connection_pool.send_request(tab_id, request).await?;
+        let data = Self::extract_response_data(response)?;
+        let script_id = data
+            .get("identifier")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| BrowserMcpError::BrowserExtensionError {
+                message: "Response did not include the script identifier".to_string(),
+            })?
+            .to_string();
+
+        self.data_cache.add_init_script(tab_id, InitScript { script_id: script_id.clone(), source });
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "scriptId": script_id,
+            "count": self.data_cache.get_init_scripts(tab_id).len()
+        }))
+    }
+
+    pub async fn handle_remove_init_script(&self, tab_id: u32, script_id: &str) -> Result<serde_json::Value> {
+        if !self.data_cache.get_init_scripts(tab_id).iter().any(|script| script.script_id == script_id) {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!("Tab {} has no init script '{}'", tab_id, script_id),
+            });
+        }
+
+        let request = BrowserRequest::RemoveInitScript { script_id: script_id.to_string(), tab_id };
+        self.connection_pool.send_request(tab_id, request).await?;
+        self.data_cache.remove_init_script(tab_id, script_id);
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "scriptId": script_id,
+            "remaining": self.data_cache.get_init_scripts(tab_id).len()
+        }))
+    }
+
+    // ─── get_accessibility_tree ───────────────────────────────────────────
+
+    pub async fn handle_get_accessibility_tree(
+        &self,
+        tab_id: Option<u32>,
+        timeout_ms: Option<u64>,
+    ) -> Result<serde_json::Value> {
+        let request = BrowserRequest::GetAccessibilityTree { max_depth: None };
+        let custom_timeout = timeout_ms.map(Duration::from_millis);
+
+        let response = self.connection_pool.send_request_with_timeout(
+            tab_id,
+            request,
+            custom_timeout,
+        ).await?;
+        let tree = match &response {
+            BrowserResponse::AccessibilityTree(tree) => Some(tree.clone()),
+            BrowserResponse::RawJson(data) => AccessibilityTree::from_extension(data),
+            _ => None,
+        };
+        if let (Some(tid), Some(tree)) = (tab_id, tree) {
+            self.data_cache.update_accessibility_tree(tid, tree).await;
+        }
+
+        Self::extract_response_data(response)
+    }
+
+    // ─── get_accessibility_node ───────────────────────────────────────────
+
+    pub async fn handle_get_accessibility_node(&self, tab_id: Option<u32>, selector: String) -> Result<serde_json::Value> {
+        if selector.trim().is_empty() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "selector must not be empty".to_string(),
+            });
+        }
+
+        let request = BrowserRequest::GetAccessibilityNode { selector: selector.clone() };
+        let response = if let Some(tid) = tab_id {
+            self.connection_pool.send_request(tid, request).await?
+        } else {
+            self.connection_pool.send_request_any(request).await?
+        };
+
+        let node = match response {
+            BrowserResponse::AccessibilityNode(node) => node,
+            other => {
+                let data = Self::extract_response_data(other)?;
+                // The extension answers `{ node: null }` when nothing matches
+                match data.get("node").cloned().unwrap_or(data) {
+                    serde_json::Value::Null => {
+                        return Err(BrowserMcpError::ResourceNotFound {
+                            uri: format!("element matching {}", selector),
+                        })
+                    }
+                    node => serde_json::from_value::<AccessibilityNode>(node)?,
+                }
+            }
+        };
+        let mut value = utils::to_json_value(&node)?;
+        value["selector"] = selector.into();
+        Ok(value)
+    }
+
+    // ─── get_accessibility_issues ─────────────────────────────────────────
+
+    /// Audit the tab's cached DOM snapshot and accessibility tree without asking the
+    /// browser for anything.
+    pub async fn handle_get_accessibility_issues(&self, tab_id: u32) -> Result<serde_json::Value> {
+        let dom = self.data_cache.get_dom_snapshot(tab_id).await;
+        let tree = self.data_cache.get_accessibility_tree(tab_id).await;
+        if dom.is_none() && tree.is_none() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: format!(
+                    "No DOM snapshot or accessibility tree is cached for tab {}; capture one with get_dom_snapshot or get_accessibility_tree first",
+                    tab_id
+                ),
+            });
+        }
+
+        let issues = utils::find_accessibility_issues(
+            dom.as_ref().map(|snapshot| &snapshot.root),
+            tree.as_ref().map(|tree| &tree.root),
+        );
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "sources": {
+                "domSnapshot": dom.is_some(),
+                "accessibilityTree": tree.is_some()
+            },
+            "issueCount": issues.len(),
+            "issues": issues
+        }))
+    }
+
+    // ─── get_browser_tabs ─────────────────────────────────────────────────
+
+    pub async fn handle_get_browser_tabs(&self) -> Result<serde_json::Value> {
+        let connections = self.connection_pool.get_active_connections().await;
+        if connections.is_empty() {
+            return Ok(serde_json::json!({
+                "tabs": [],
+                "message": "No active browser connections"
+            }));
+        }
+
+        let request = BrowserRequest::GetBrowserTabs;
+        match self.connection_pool.send_request_any(request).await {
+            Ok(response) => {
                 let data = Self::extract_response_data(response)?;
                 Ok(data)
             }
@@ -485,6 +2183,173 @@ impl SimpleBrowserMcpServer {
         }
     }
 
+    // ─── get_windows ──────────────────────────────────────────────────────
+
+    /// The tab list grouped by window. Windows are ordered by id and their tabs by index.
+    pub async fn handle_get_windows(&self) -> Result<serde_json::Value> {
+        let connections = self.connection_pool.get_active_connections().await;
+        if connections.is_empty() {
+            return Ok(serde_json::json!({
+                "windows": [],
+                "message": "No active browser connections"
+            }));
+        }
+
+        let response = self.connection_pool.send_request_any(BrowserRequest::GetBrowserTabs).await?;
+        let data = Self::extract_response_data(response)?;
+        let tabs = data.get("tabs").cloned().unwrap_or(data);
+        let tabs: Vec<BrowserTab> = serde_json::from_value(tabs).map_err(|e| BrowserMcpError::BrowserExtensionError {
+            message: format!("Unexpected tab list: {}", e),
+        })?;
+        let tab_count = tabs.len();
+        let windows = Self::group_tabs_by_window(tabs);
+
+        Ok(serde_json::json!({
+            "windows": utils::to_json_value(&windows)?,
+            "windowCount": windows.len(),
+            "tabCount": tab_count
+        }))
+    }
+
+    fn group_tabs_by_window(tabs: Vec<BrowserTab>) -> Vec<WindowInfo> {
+        let mut windows: std::collections::BTreeMap<Option<u32>, WindowInfo> = std::collections::BTreeMap::new();
+        for tab in tabs {
+            let window = windows.entry(tab.window_id).or_insert_with(|| WindowInfo {
+                window_id: tab.window_id,
+                focused: false,
+                tabs: Vec::new(),
+            });
+            window.focused |= tab.window_focused;
+            window.tabs.push(tab);
+        }
+        windows
+            .into_values()
+            .map(|mut window| {
+                window.tabs.sort_by_key(|tab| tab.index);
+                window
+            })
+            .collect()
+    }
+
+    // ─── get_current_location ─────────────────────────────────────────────
+
+    /// Answered from what tab events have already told us when possible. The browser
+    /// is only asked (for its tab list, not the page) when nothing is known about the
+    /// tab, or when no tab is given and the active one has to be found.
+    pub async fn handle_get_current_location(&self, tab_id: Option<u32>) -> Result<serde_json::Value> {
+        if let Some(tid) = tab_id {
+            if let Some((url, title)) = self.cached_location(tid).await {
+                return Ok(serde_json::json!({ "url": url, "title": title }));
+            }
+        }
+
+        let response = self.connection_pool.send_request_any(BrowserRequest::GetBrowserTabs).await?;
+        let data = Self::extract_response_data(response)?;
+        let tabs = data.as_array().or_else(|| data.get("tabs").and_then(|t| t.as_array()));
+        let tab = tabs
+            .into_iter()
+            .flatten()
+            .find(|tab| match tab_id {
+                Some(tid) => tab.get("id").and_then(|id| id.as_u64()) == Some(tid as u64),
+                None => tab.get("active").and_then(|a| a.as_bool()).unwrap_or(false),
+            })
+            .ok_or_else(|| match tab_id {
+                Some(tid) => BrowserMcpError::TabNotFound { tab_id: tid },
+                None => BrowserMcpError::BrowserExtensionError {
+                    message: "No active tab found".to_string(),
+                },
+            })?;
+
+        let url = tab.get("url").and_then(|u| u.as_str()).unwrap_or_default();
+        if let Some(tid) = tab.get("id").and_then(|id| id.as_u64()) {
+            self.data_cache.set_tab_url(tid as u32, url);
+        }
+        Ok(serde_json::json!({
+            "url": url,
+            "title": tab.get("title").and_then(|t| t.as_str()).unwrap_or_default()
+        }))
+    }
+
+    /// The tab's last known URL, with a title only if it was seen for that same URL.
+    async fn cached_location(&self, tab_id: u32) -> Option<(String, String)> {
+        let reported = self.connection_pool.tab_location(tab_id);
+        let Some(url) = self.data_cache.get_tab_url(tab_id) else {
+            return reported;
+        };
+        if let Some((_, title)) = reported.filter(|(reported_url, _)| *reported_url == url) {
+            return Some((url, title));
+        }
+        let content = self.data_cache.get_page_content(tab_id).await?;
+        (content.url == url).then(|| (url, content.title.clone()))
+    }
+
+    // ─── get_structured_data ──────────────────────────────────────────────
+
+    /// How old cached page HTML may be before `get_structured_data` fetches it again.
+    const STRUCTURED_DATA_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(30);
+
+    /// JSON-LD blocks and meta tags, parsed from the cached HTML when it is fresh and
+    /// still for the tab's current URL.
+    /// Returns the data with the age of the cached page it was read from, `None` when
+    /// the page was fetched live.
+    pub async fn handle_get_structured_data(&self, tab_id: u32) -> Result<(serde_json::Value, Option<Duration>)> {
+        let current_url = self.data_cache.get_tab_url(tab_id);
+        let cached = self.data_cache.get_page_content(tab_id).await.filter(|content| {
+            !content.html.is_empty()
+                && content.is_fresh(Self::STRUCTURED_DATA_MAX_AGE)
+                && current_url.as_ref().is_none_or(|url| *url == content.url)
+        });
+
+        let (url, html, age) = match cached {
+            Some(content) => {
+                let age = std::time::SystemTime::now().duration_since(content.last_updated).unwrap_or_default();
+                (content.url.clone(), content.html.clone(), Some(age))
+            }
+            None => {
+                let request = BrowserRequest::GetPageContent { include_metadata: false };
+                let response = self.connection_pool.send_request(tab_id, request).await?;
+                // Extensions reply with raw JSON; cache it so the next call can skip the round trip
+                let content = match response {
+                    BrowserResponse::PageContent(content) => content,
+                    other => PageContent::from_extension(&Self::extract_response_data(other)?),
+                };
+                let (url, html) = (content.url.clone(), content.html.clone());
+                self.data_cache.update_page_content(tab_id, content).await;
+                (url, html, None)
+            }
+        };
+
+        let data = utils::extract_structured_data(&html);
+        let value = serde_json::json!({
+            "url": url,
+            "jsonLd": data.json_ld,
+            "invalidJsonLd": data.invalid_json_ld,
+            "meta": data.meta,
+            "source": if age.is_some() { "cache" } else { "live" }
+        });
+        Ok((value, age))
+    }
+
+    // ─── get_tab_activity ─────────────────────────────────────────────────
+
+    /// The most recent `limit` tool calls made against the tab, oldest first.
+    pub fn handle_get_tab_activity(&self, tab_id: u32, limit: Option<usize>) -> Result<serde_json::Value> {
+        if !self.data_cache.activity_log_enabled() {
+            return Err(BrowserMcpError::InvalidParameters {
+                message: "Tool activity is not being recorded; set cache.activity_log_entries to enable it".to_string(),
+            });
+        }
+        let mut invocations = self.data_cache.get_tab_activity(tab_id);
+        if let Some(limit) = limit {
+            invocations.drain(..invocations.len().saturating_sub(limit));
+        }
+        Ok(serde_json::json!({
+            "tabId": tab_id,
+            "count": invocations.len(),
+            "invocations": invocations
+        }))
+    }
+
     // ─── attach_debugger ──────────────────────────────────────────────────
 
     pub async fn handle_attach_debugger(&self, tab_id: u32) -> Result<serde_json::Value> {
@@ -509,6 +2374,237 @@ impl SimpleBrowserMcpServer {
         }))
     }
 
+    // ─── pause_execution / resume_execution ───────────────────────────────
+
+    pub async fn handle_pause_execution(&self, tab_id: u32) -> Result<serde_json::Value> {
+        if !self.data_cache.is_execution_paused(tab_id) {
+            self.require_debugger(Some(tab_id)).await?;
+            self.connection_pool.send_request(tab_id, BrowserRequest::PauseExecution).await?;
+            self.data_cache.set_execution_paused(tab_id, true);
+        }
+        Ok(serde_json::json!({ "tabId": tab_id, "state": "paused" }))
+    }
+
+    pub async fn handle_resume_execution(&self, tab_id: u32) -> Result<serde_json::Value> {
+        if self.data_cache.is_execution_paused(tab_id) {
+            match self.connection_pool.send_request(tab_id, BrowserRequest::ResumeExecution).await {
+                Ok(_) => {}
+                // Already running, e.g. the debugger was detached from outside the server;
+                // the flag is stale, so clear it rather than leave the tab stuck
+                Err(BrowserMcpError::BrowserExtensionError { message }) if message.contains("only perform operation while paused") => {
+                    tracing::debug!("Tab {} was no longer paused: {}", tab_id, message);
+                }
+                Err(e) => return Err(e),
+            }
+            self.data_cache.set_execution_paused(tab_id, false);
+        }
+        Ok(serde_json::json!({ "tabId": tab_id, "state": "running" }))
+    }
+
+    /// Tools that need the page to run can't while its JavaScript is paused.
+    pub fn check_not_paused(&self, tool: &str, tab_id: Option<u32>) -> Result<()> {
+        match tab_id {
+            Some(tab_id) if self.data_cache.is_execution_paused(tab_id) => Err(BrowserMcpError::PreconditionFailed {
+                message: format!(
+                    "tab {} is paused in the debugger, so {} can't run; call resume_execution first",
+                    tab_id, tool
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Make sure the debugger is attached before a tool that depends on it runs,
+    /// attaching it when `auto_attach_debugger` allows. Without a tabId the extension
+    /// picks the active tab itself, so there is no cached state to check.
+    async fn require_debugger(&self, tab_id: Option<u32>) -> Result<()> {
+        let Some(tab_id) = tab_id else {
+            return Ok(());
+        };
+        if self.data_cache.is_debugger_attached(tab_id).await {
+            return Ok(());
+        }
+        if !self.config.connections.auto_attach_debugger {
+            return Err(BrowserMcpError::PermissionDenied {
+                message: format!(
+                    "The debugger is not attached to tab {}; call attach_debugger first",
+                    tab_id
+                ),
+            });
+        }
+        tracing::debug!("Auto-attaching debugger to tab {}", tab_id);
+        self.handle_attach_debugger(tab_id).await.map(|_| ())
+    }
+
+    fn require_javascript_allowed(&self, tool: &str) -> Result<()> {
+        if self.config.security.allow_javascript_execution {
+            return Ok(());
+        }
+        Err(BrowserMcpError::PermissionDenied {
+            message: format!("{} is disabled by security.allow_javascript_execution", tool),
+        })
+    }
+
+    // ─── concurrency limit ────────────────────────────────────────────────
+
+    /// Claim one of the `max_concurrent_requests` slots without waiting. The slot is
+    /// released when the permit is dropped.
+    pub fn try_acquire_request_slot(&self) -> Option<OwnedSemaphorePermit> {
+        self.request_slots.clone().try_acquire_owned().ok()
+    }
+
+    pub fn in_flight_requests(&self) -> usize {
+        self.config.server.max_concurrent_requests - self.request_slots.available_permits()
+    }
+
+    // ─── cleanup ──────────────────────────────────────────────────────────
+
+    /// Evict stale cached tabs, and have the connection pool forget them too so a
+    /// later `tab_updated` repeating their last URL still restores it.
+    pub async fn cleanup_stale_data(&self) {
+        for tab_id in self.data_cache.cleanup_stale_data().await {
+            self.connection_pool.forget_tab(tab_id);
+        }
+    }
+
+    // ─── sessions ─────────────────────────────────────────────────────────
+
+    /// End MCP sessions idle past `server.session_idle_timeout_secs`.
+    pub fn expire_idle_sessions(&self) {
+        let idle_timeout = self.config.server.session_idle_timeout_secs;
+        if idle_timeout == 0 {
+            return;
+        }
+        let expired = self.sessions.expire_idle(Duration::from_secs(idle_timeout));
+        if expired > 0 {
+            tracing::info!("Expired {} idle MCP sessions ({} active)", expired, self.sessions.len());
+        }
+    }
+
+    // ─── access control ───────────────────────────────────────────────────
+
+    /// Refuse tools against tabs matched by `[security]`. Without a tabId the target
+    /// tab can't be checked, so one is required once a policy is configured.
+    pub fn check_tab_access(&self, tab_id: Option<u32>) -> Result<()> {
+        if !self.url_policy.is_enabled() {
+            return Ok(());
+        }
+        let tab_id = tab_id.ok_or_else(|| BrowserMcpError::PermissionDenied {
+            message: "tabId is required when URL access control is configured".to_string(),
+        })?;
+        self.url_policy.check(tab_id, self.data_cache.get_tab_url(tab_id).as_deref())
+    }
+
+    // ─── cancellation ─────────────────────────────────────────────────────
+
+    /// Register an in-flight JSON-RPC call so `notifications/cancelled` from the same
+    /// session can abort it. `id` is the serialized JSON-RPC id, so `1` and `"1"` stay distinct.
+    pub fn begin_cancellable_call(&self, session: Option<&str>, id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.in_flight_calls.insert((session.map(str::to_string), id.to_string()), token.clone());
+        token
+    }
+
+    pub fn finish_cancellable_call(&self, session: Option<&str>, id: &str) {
+        self.in_flight_calls.remove(&(session.map(str::to_string), id.to_string()));
+    }
+
+    /// Returns false if the session has no call with that id in flight (already
+    /// finished or unknown). Calls in other sessions are never touched.
+    pub fn cancel_call(&self, session: Option<&str>, id: &str) -> bool {
+        match self.in_flight_calls.remove(&(session.map(str::to_string), id.to_string())) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // ─── tabs/describe ────────────────────────────────────────────────────
+
+    pub async fn describe_tabs(&self) -> Vec<TabSummary> {
+        let mut tabs: Vec<TabSummary> = self
+            .data_cache
+            .get_all_tabs()
+            .await
+            .iter()
+            .map(|tab| TabSummary {
+                intercept_rules: self.data_cache.get_intercept_rules(tab.tab_id),
+                extra_headers: header_names(&self.data_cache.get_extra_headers(tab.tab_id)),
+                ..tab.summary()
+            })
+            .collect();
+        tabs.sort_by_key(|tab| tab.tab_id);
+        tabs
+    }
+
+    // ─── metrics ──────────────────────────────────────────────────────────
+
+    pub async fn metrics_snapshot(&self) -> serde_json::Value {
+        let metrics = self.request_handler.get_metrics();
+        let (hits, misses, hit_rate) = self.data_cache.get_cache_stats().await;
+        let min_response_time = if metrics.successful_requests > 0 {
+            metrics.min_response_time
+        } else {
+            Duration::ZERO
+        };
+
+        serde_json::json!({
+            "requests": {
+                "total": metrics.total_requests,
+                "successful": metrics.successful_requests,
+                "failed": metrics.failed_requests,
+                "timeouts": metrics.timeout_requests,
+                "retries": metrics.retry_requests,
+                "lateResponses": self.connection_pool.late_response_count(),
+                "averageResponseTimeMs": metrics.average_response_time.as_secs_f64() * 1000.0,
+                "maxResponseTimeMs": metrics.max_response_time.as_secs_f64() * 1000.0,
+                "minResponseTimeMs": min_response_time.as_secs_f64() * 1000.0
+            },
+            "cache": {
+                "hits": hits,
+                "misses": misses,
+                "hitRate": hit_rate
+            }
+        })
+    }
+
+    /// Zero the request and cache counters, returning the values they held beforehand.
+    pub async fn reset_metrics(&self) -> serde_json::Value {
+        let snapshot = self.metrics_snapshot().await;
+        self.request_handler.reset_metrics();
+        self.connection_pool.reset_late_response_count();
+        self.data_cache.reset_cache_stats().await;
+        snapshot
+    }
+
+    // ─── logging/setLevel ─────────────────────────────────────────────────
+
+    /// Hand the server the process's reloadable log filter so clients can adjust it.
+    pub fn set_log_filter(&mut self, log_filter: LogFilter) {
+        self.log_filter = Some(log_filter);
+    }
+
+    /// Apply an MCP log level (`debug`, `info`, `warning`, `error`, ...) to the live filter.
+    pub fn set_log_level(&self, level: &str) -> Result<()> {
+        let tracing_level = utils::mcp_log_level(level).ok_or_else(|| BrowserMcpError::InvalidParameters {
+            message: format!("Unknown log level '{}'", level),
+        })?;
+        let log_filter = self.log_filter.as_ref().ok_or_else(|| BrowserMcpError::InternalError {
+            message: "Log level cannot be changed: no reloadable filter is installed".to_string(),
+        })?;
+        log_filter
+            .set_level(tracing_level)
+            .map_err(|message| BrowserMcpError::InternalError { message })?;
+        tracing::info!("Log level set to {} by client", level);
+        Ok(())
+    }
+
+    pub fn supports_log_level_changes(&self) -> bool {
+        self.log_filter.is_some()
+    }
+
     // ─── health ───────────────────────────────────────────────────────────
 
     pub async fn get_health_status(&self) -> crate::types::mcp::HealthStatus {
@@ -516,25 +2612,38 @@ impl SimpleBrowserMcpServer {
         let cache_stats = self.data_cache.get_cache_stats().await;
         let connection_stats = self.connection_pool.get_stats();
         let memory_usage = self.data_cache.get_memory_usage().await;
+        let request_metrics = self.request_handler.get_metrics();
+        let (interned_strings, interner_cache_hits) = self.data_cache.get_interner_stats();
+        let last_error = connection_stats.last_error.read().clone();
+        let active_connections = connection_stats
+            .active_connections
+            .load(std::sync::atomic::Ordering::Relaxed) as usize;
+        let memory_usage_mb = memory_usage as f64 / (1024.0 * 1024.0);
+        let error_rate = self.request_handler.get_error_rate();
 
         crate::types::mcp::HealthStatus {
-            status: "healthy".to_string(),
+            status: compute_health_status(&self.config, active_connections, error_rate, memory_usage_mb).to_string(),
             timestamp: chrono::Utc::now(),
             version: "1.0.0".to_string(),
             uptime_seconds: uptime.as_secs(),
-            active_connections: connection_stats
-                .active_connections
-                .load(std::sync::atomic::Ordering::Relaxed) as usize,
+            active_connections,
             cached_tabs: self.data_cache.get_all_tabs().await.len(),
-            memory_usage_mb: memory_usage as f64 / (1024.0 * 1024.0),
+            memory_usage_mb,
+            in_flight_requests: self.in_flight_requests(),
+            interned_strings,
+            interner_cache_hits,
+            last_connection_error_age_secs: last_error.as_ref().map(|(at, _)| at.elapsed().as_secs()),
+            last_connection_error: last_error.map(|(_, message)| message),
+            compression_ratio: connection_stats.compression_ratio(),
+            bytes_saved: connection_stats.bytes_saved(),
+            messages_per_second: connection_stats.messages_per_second(),
             performance_stats: crate::types::mcp::PerformanceStats {
                 requests_per_second: 0.0,
-                average_response_time_ms: 0.0,
+                average_response_time_ms: request_metrics.average_response_time.as_secs_f64() * 1000.0,
                 cache_hit_rate: cache_stats.2,
-                error_rate: 0.0,
-                active_websocket_connections: connection_stats
-                    .active_connections
-                    .load(std::sync::atomic::Ordering::Relaxed) as usize,
+                error_rate,
+                active_websocket_connections: active_connections,
+                errors_by_category: self.request_handler.get_error_breakdown(),
             },
         }
     }
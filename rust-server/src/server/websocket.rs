@@ -1,9 +1,11 @@
 use crate::server::SimpleBrowserMcpServer;
+use crate::transport::{reject_unsupported_protocol, ProtocolNegotiation};
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
         ConnectInfo, State,
     },
+    http::HeaderMap,
     response::IntoResponse,
     routing::get,
     Router,
@@ -41,20 +43,36 @@ async fn handle_websocket_upgrade(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(server): State<Arc<SimpleBrowserMcpServer>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     tracing::info!("WebSocket upgrade request from {}", addr);
-    ws.on_upgrade(move |socket| handle_websocket_connection(socket, addr, server))
+    let max_message_bytes = server.config.connections.max_message_bytes;
+    let negotiation = ProtocolNegotiation::from_headers(&headers);
+    let ws = negotiation
+        .apply(ws)
+        .max_message_size(max_message_bytes)
+        .max_frame_size(max_message_bytes);
+    match negotiation {
+        ProtocolNegotiation::Unsupported(offered) => {
+            ws.on_upgrade(move |socket| reject_unsupported_protocol(socket, offered))
+        }
+        negotiation => {
+            let protocol = negotiation.protocol();
+            ws.on_upgrade(move |socket| handle_websocket_connection(socket, addr, protocol, server))
+        }
+    }
 }
 
 async fn handle_websocket_connection(
     socket: WebSocket,
     addr: SocketAddr,
+    protocol: Option<&'static str>,
     server: Arc<SimpleBrowserMcpServer>,
 ) {
     tracing::info!("New WebSocket connection from {}", addr);
     server
         .connection_pool
-        .handle_connection(socket, Some(addr))
+        .handle_connection(socket, Some(addr), protocol)
         .await;
 }
 
@@ -104,7 +122,8 @@ mod tests {
 
         assert_eq!(response.status_code(), 200);
         let health_status: serde_json::Value = response.json();
-        assert_eq!(health_status["status"], "healthy");
+        // No browser extension is connected
+        assert_eq!(health_status["status"], "unhealthy");
         assert!(health_status["version"].is_string());
     }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use crate::types::{
+    browser::{BoundingBox, ScreenshotData},
+    errors::*,
+    messages::BrowserResponse,
+};
+use serde::Deserialize;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Header of a binary response frame. On the wire a frame is a big-endian `u16`
+/// header length, the header as JSON, then the raw payload bytes. This lets the
+/// extension send screenshots without base64-encoding them into a text frame.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinaryFrameHeader {
+    request_id: Uuid,
+    #[serde(rename = "type")]
+    response_type: String,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    /// Region a clipped screenshot covers.
+    #[serde(default)]
+    clip: Option<BoundingBox>,
+}
+
+/// Split a binary frame into the request it answers and its response.
+pub fn parse_binary_frame(frame: &[u8]) -> Result<(Uuid, BrowserResponse)> {
+    let invalid = |message: String| BrowserMcpError::InvalidRequest { message };
+
+    let header_len = frame
+        .get(..2)
+        .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+        .ok_or_else(|| invalid("Binary frame too short for header length".to_string()))?;
+    let header_bytes = frame
+        .get(2..2 + header_len)
+        .ok_or_else(|| invalid(format!("Binary frame truncated, expected {} header bytes", header_len)))?;
+    let header: BinaryFrameHeader = serde_json::from_slice(header_bytes)
+        .map_err(|e| invalid(format!("Invalid binary frame header: {}", e)))?;
+    let payload = &frame[2 + header_len..];
+
+    let response = match header.response_type.as_str() {
+        "screenshot" => BrowserResponse::Screenshot(ScreenshotData {
+            data: payload.to_vec(),
+            format: header.format.unwrap_or_else(|| "png".to_string()),
+            width: header.width,
+            height: header.height,
+            timestamp: SystemTime::now(),
+            clip: header.clip,
+        }),
+        other => return Err(invalid(format!("Unsupported binary response type '{}'", other))),
+    };
+
+    Ok((header.request_id, response))
+}
+
+/// Build a binary frame as the extension does.
+pub fn encode_binary_frame(header: &serde_json::Value, payload: &[u8]) -> Vec<u8> {
+    let header = header.to_string();
+    let mut frame = Vec::with_capacity(2 + header.len() + payload.len());
+    frame.extend_from_slice(&(header.len() as u16).to_be_bytes());
+    frame.extend_from_slice(header.as_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_screenshot_frame() {
+        let request_id = Uuid::new_v4();
+        let frame = encode_binary_frame(
+            &serde_json::json!({ "requestId": request_id, "type": "screenshot", "format": "jpeg", "width": 2, "height": 1 }),
+            &[0xff, 0xd8, 0x00, 0x01],
+        );
+
+        let (parsed_id, response) = parse_binary_frame(&frame).unwrap();
+        assert_eq!(parsed_id, request_id);
+        let BrowserResponse::Screenshot(shot) = response else {
+            panic!("expected a screenshot response");
+        };
+        assert_eq!(shot.data, vec![0xff, 0xd8, 0x00, 0x01]);
+        assert_eq!(shot.format, "jpeg");
+        assert_eq!((shot.width, shot.height), (2, 1));
+    }
+
+    #[test]
+    fn test_malformed_frames_are_rejected() {
+        assert!(parse_binary_frame(&[0x00]).is_err());
+        assert!(parse_binary_frame(&[0x00, 0x40, b'{']).is_err());
+
+        let frame = encode_binary_frame(&serde_json::json!({ "requestId": Uuid::new_v4(), "type": "video" }), &[1]);
+        assert!(matches!(
+            parse_binary_frame(&frame),
+            Err(BrowserMcpError::InvalidRequest { .. })
+        ));
+    }
+}
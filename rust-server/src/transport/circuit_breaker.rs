@@ -0,0 +1,264 @@
+use crate::types::errors::*;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Per-tab circuit breaker that fast-fails requests to tabs whose extension keeps
+/// timing out, instead of making every caller wait out the full request timeout.
+pub struct CircuitBreaker {
+    states: DashMap<u32, BreakerState>,
+    failure_threshold: usize,
+    failure_window: Duration,
+    cooldown: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakerState {
+    pub consecutive_failures: usize,
+    pub first_failure_at: Instant,
+    pub opened_at: Option<Instant>,
+    pub probe_in_flight: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerStatus {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakerSnapshot {
+    pub tab_id: u32,
+    pub status: BreakerStatus,
+    pub consecutive_failures: usize,
+    pub retry_after_ms: Option<u64>,
+}
+
+impl CircuitBreaker {
+    /// A `failure_threshold` of 0 disables the breaker.
+    pub fn new(failure_threshold: usize, failure_window: Duration, cooldown: Duration) -> Self {
+        Self {
+            states: DashMap::new(),
+            failure_threshold,
+            failure_window,
+            cooldown,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::ZERO, Duration::ZERO)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.failure_threshold > 0
+    }
+
+    /// Check whether a request to `tab_id` may proceed. Once the cooldown has elapsed
+    /// a single probe is let through (half-open); everything else fails fast.
+    /// Returns `true` when the caller holds that probe and must resolve it with
+    /// `record_success`, `record_failure` or `release_probe`.
+    pub fn try_acquire(&self, tab_id: u32) -> Result<bool> {
+        if !self.is_enabled() {
+            return Ok(false);
+        }
+
+        let Some(mut state) = self.states.get_mut(&tab_id) else {
+            return Ok(false);
+        };
+        let Some(opened_at) = state.opened_at else {
+            return Ok(false);
+        };
+
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.cooldown {
+            return Err(BrowserMcpError::ServiceUnavailable {
+                message: format!(
+                    "Circuit open for tab {} after {} consecutive failures, retry in {}ms",
+                    tab_id,
+                    state.consecutive_failures,
+                    (self.cooldown - elapsed).as_millis()
+                ),
+            });
+        }
+
+        if state.probe_in_flight {
+            return Err(BrowserMcpError::ServiceUnavailable {
+                message: format!("Circuit half-open for tab {}, probe request in flight", tab_id),
+            });
+        }
+
+        state.probe_in_flight = true;
+        Ok(true)
+    }
+
+    /// Give up a half-open probe that never produced an outcome (e.g. the request was
+    /// cancelled), so the next request can probe instead of the tab failing fast forever.
+    pub fn release_probe(&self, tab_id: u32) {
+        if let Some(mut state) = self.states.get_mut(&tab_id) {
+            state.probe_in_flight = false;
+        }
+    }
+
+    /// Drop the state of a tab that no longer exists.
+    pub fn forget(&self, tab_id: u32) {
+        self.states.remove(&tab_id);
+    }
+
+    pub fn record_success(&self, tab_id: u32) {
+        if self.is_enabled() {
+            self.states.remove(&tab_id);
+        }
+    }
+
+    pub fn record_failure(&self, tab_id: u32) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut state = self.states.entry(tab_id).or_insert_with(|| BreakerState {
+            consecutive_failures: 0,
+            first_failure_at: now,
+            opened_at: None,
+            probe_in_flight: false,
+        });
+
+        if state.probe_in_flight {
+            // Failed probe: reopen for another cooldown
+            state.probe_in_flight = false;
+            state.consecutive_failures += 1;
+            state.opened_at = Some(now);
+            tracing::warn!("Circuit breaker probe failed for tab {}, reopening", tab_id);
+            return;
+        }
+
+        if state.opened_at.is_none() && now.duration_since(state.first_failure_at) > self.failure_window {
+            state.consecutive_failures = 0;
+            state.first_failure_at = now;
+        }
+
+        state.consecutive_failures += 1;
+        if state.opened_at.is_none() && state.consecutive_failures >= self.failure_threshold {
+            state.opened_at = Some(now);
+            tracing::warn!(
+                "Circuit breaker opened for tab {} after {} consecutive failures",
+                tab_id,
+                state.consecutive_failures
+            );
+        }
+    }
+
+    pub fn status(&self, tab_id: u32) -> BreakerStatus {
+        self.states
+            .get(&tab_id)
+            .map(|state| self.status_of(&state))
+            .unwrap_or(BreakerStatus::Closed)
+    }
+
+    fn status_of(&self, state: &BreakerState) -> BreakerStatus {
+        match state.opened_at {
+            None => BreakerStatus::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => BreakerStatus::Open,
+            Some(_) => BreakerStatus::HalfOpen,
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<BreakerSnapshot> {
+        let mut snapshots: Vec<BreakerSnapshot> = self
+            .states
+            .iter()
+            .map(|entry| {
+                let (tab_id, state) = entry.pair();
+                let status = self.status_of(state);
+                let retry_after_ms = state
+                    .opened_at
+                    .filter(|_| status == BreakerStatus::Open)
+                    .map(|opened_at| self.cooldown.saturating_sub(opened_at.elapsed()).as_millis() as u64);
+                BreakerSnapshot {
+                    tab_id: *tab_id,
+                    status,
+                    consecutive_failures: state.consecutive_failures,
+                    retry_after_ms,
+                }
+            })
+            .collect();
+        snapshots.sort_by_key(|snapshot| snapshot.tab_id);
+        snapshots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_trips_and_recovers() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60), Duration::from_millis(50));
+
+        for _ in 0..2 {
+            breaker.record_failure(1);
+        }
+        assert!(breaker.try_acquire(1).is_ok());
+        assert_eq!(breaker.status(1), BreakerStatus::Closed);
+
+        breaker.record_failure(1);
+        assert_eq!(breaker.status(1), BreakerStatus::Open);
+        assert!(matches!(
+            breaker.try_acquire(1),
+            Err(BrowserMcpError::ServiceUnavailable { .. })
+        ));
+
+        // Other tabs are unaffected
+        assert!(breaker.try_acquire(2).is_ok());
+
+        // After the cooldown one probe is allowed through, the rest still fail fast
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.status(1), BreakerStatus::HalfOpen);
+        assert!(breaker.try_acquire(1).is_ok());
+        assert!(breaker.try_acquire(1).is_err());
+
+        breaker.record_success(1);
+        assert_eq!(breaker.status(1), BreakerStatus::Closed);
+        assert!(breaker.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_failed_probe_reopens_breaker() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60), Duration::from_millis(20));
+
+        breaker.record_failure(7);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire(7).is_ok());
+
+        breaker.record_failure(7);
+        assert_eq!(breaker.status(7), BreakerStatus::Open);
+        assert_eq!(breaker.snapshot()[0].consecutive_failures, 2);
+    }
+
+    #[test]
+    fn test_released_probe_lets_next_request_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60), Duration::from_millis(20));
+
+        breaker.record_failure(3);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire(3).unwrap());
+        assert!(breaker.try_acquire(3).is_err());
+
+        breaker.release_probe(3);
+        assert_eq!(breaker.status(3), BreakerStatus::HalfOpen);
+        assert!(breaker.try_acquire(3).unwrap());
+    }
+
+    #[test]
+    fn test_disabled_breaker_never_trips() {
+        let breaker = CircuitBreaker::disabled();
+        for _ in 0..10 {
+            breaker.record_failure(1);
+        }
+        assert!(breaker.try_acquire(1).is_ok());
+        assert!(breaker.snapshot().is_empty());
+    }
+}
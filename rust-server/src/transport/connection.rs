@@ -1,17 +1,32 @@
+use super::binary::parse_binary_frame;
+use super::circuit_breaker::{BreakerSnapshot, CircuitBreaker};
+use super::outbound::OutboundQueue;
+use super::rate_limit::TabRateLimiter;
+use super::request::{BatchRequest, BatchResponse, RequestTracker, ToolCallOrigin, TOOL_CALL_ORIGIN};
+use super::wire::{decode_msgpack, encode_message, is_msgpack_frame};
 use crate::cache::BrowserDataCache;
+use crate::config::{OutboundOverflowPolicy, WireFormat};
 use crate::types::{errors::*, messages::*};
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use dashmap::DashMap;
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{future::BoxFuture, SinkExt, StreamExt};
 use parking_lot::RwLock;
 use std::{
-    collections::HashSet,
-    sync::Arc,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::oneshot;
 use uuid::Uuid;
 
+/// Answers MCP JSON-RPC messages sent over `/ws` instead of `/mcp`. Returns `None`
+/// for notifications, which get no reply.
+pub type McpMessageHandler =
+    Arc<dyn Fn(serde_json::Value) -> BoxFuture<'static, Option<serde_json::Value>> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct ConnectionPool {
     connections: Arc<DashMap<Uuid, WebSocketConnection>>,
@@ -19,17 +34,64 @@ pub struct ConnectionPool {
     message_router: Arc<MessageRouter>,
     stats: Arc<ConnectionStats>,
     data_cache: Option<Arc<BrowserDataCache>>,
+    tab_locks: Arc<DashMap<u32, Arc<tokio::sync::Mutex<()>>>>,
+    /// Last URL and title reported for each tab, so repeated `tab_updated` events are ignored.
+    tab_states: Arc<DashMap<u32, (String, String)>>,
+    /// Tab each extension-supplied label names. One socket serves every tab, so a label
+    /// is bound to the tab it was given for rather than following the connection.
+    tab_labels: Arc<DashMap<String, u32>>,
+    /// Associations of recently closed connections, keyed by their resume token.
+    resumable_sessions: Arc<DashMap<Uuid, ResumableSession>>,
+    serialize_per_tab: bool,
+    circuit_breaker: Arc<CircuitBreaker>,
+    rate_limiter: Arc<TabRateLimiter>,
+    default_timeout: Duration,
+    tool_timeouts: Arc<HashMap<String, Duration>>,
+    outbound_queue_size: usize,
+    outbound_policy: OutboundOverflowPolicy,
+    wire_format: WireFormat,
+    retry_attempts: usize,
+    max_retry_backoff: Duration,
 }
 
 pub struct WebSocketConnection {
     pub id: Uuid,
-    pub sender: mpsc::UnboundedSender<Message>,
+    pub sender: OutboundQueue,
+    /// Encoding agreed during the handshake; JSON until the extension offers more.
+    pub wire_format: WireFormat,
+    /// `Sec-WebSocket-Protocol` version agreed on the upgrade; `None` for clients that
+    /// offered none.
+    pub protocol: Option<&'static str>,
     pub tab_id: Option<u32>,
+    /// Name the extension gave the first tab announced on this connection, resolvable
+    /// with `resolve_tab_label`.
+    pub label: Option<String>,
+    /// Token issued in reply to `connection_established`; presenting it on the next
+    /// connection restores this one's tab and label.
+    pub resume_token: Option<Uuid>,
     pub connected_at: Instant,
     pub last_activity: Arc<RwLock<Instant>>,
+    /// Heartbeat pings sent since the last pong. Reset by any pong.
+    pub outstanding_pings: Arc<AtomicU64>,
     pub remote_addr: Option<std::net::SocketAddr>,
 }
 
+/// How long a closed connection's tab association can be resumed by a reconnecting extension.
+const RESUME_TOKEN_TTL: Duration = Duration::from_secs(30);
+
+/// Span the messages-per-second gauge averages over.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Heartbeat pings a connection may leave unanswered before it is treated as half-open
+/// and closed, without waiting for the activity timeout.
+const MAX_MISSED_PONGS: u64 = 2;
+
+struct ResumableSession {
+    tab_id: u32,
+    label: Option<String>,
+    expires_at: Instant,
+}
+
 #[derive(Default)]
 pub struct ConnectionStats {
     pub total_connections: std::sync::atomic::AtomicU64,
@@ -37,6 +99,71 @@ pub struct ConnectionStats {
     pub messages_sent: std::sync::atomic::AtomicU64,
     pub messages_received: std::sync::atomic::AtomicU64,
     pub connection_errors: std::sync::atomic::AtomicU64,
+    /// When the most recent connection error happened and what it was.
+    pub last_error: Arc<RwLock<Option<(Instant, String)>>>,
+    /// Bytes sent messages would have taken uncompressed, and bytes actually sent.
+    pub bytes_uncompressed: std::sync::atomic::AtomicU64,
+    pub bytes_sent: std::sync::atomic::AtomicU64,
+    /// Message totals sampled by `sample_throughput`, oldest first.
+    throughput_samples: Arc<parking_lot::Mutex<VecDeque<(Instant, u64)>>>,
+    /// Messages sent plus received per second over the last sampled window.
+    messages_per_second: Arc<RwLock<f64>>,
+}
+
+impl ConnectionStats {
+    pub fn record_sent(&self, uncompressed: usize, sent: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_uncompressed.fetch_add(uncompressed as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(sent as u64, Ordering::Relaxed);
+        metrics::counter!("browser_mcp_ws_bytes_uncompressed_total", uncompressed as u64);
+        metrics::counter!("browser_mcp_ws_bytes_sent_total", sent as u64);
+        metrics::gauge!("browser_mcp_ws_compression_ratio", self.compression_ratio());
+    }
+
+    /// Sent bytes over uncompressed bytes; 1.0 until something has been sent.
+    pub fn compression_ratio(&self) -> f64 {
+        let uncompressed = self.bytes_uncompressed.load(Ordering::Relaxed);
+        if uncompressed == 0 {
+            return 1.0;
+        }
+        self.bytes_sent.load(Ordering::Relaxed) as f64 / uncompressed as f64
+    }
+
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_uncompressed
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.bytes_sent.load(Ordering::Relaxed))
+    }
+
+    /// Sample the message totals and update the messages-per-second gauge from the
+    /// change since the oldest sample still covering `window`.
+    pub fn sample_throughput(&self, window: Duration) -> f64 {
+        let now = Instant::now();
+        let total = self.messages_sent.load(Ordering::Relaxed) + self.messages_received.load(Ordering::Relaxed);
+        let mut samples = self.throughput_samples.lock();
+        samples.push_back((now, total));
+        // Keep one sample at least `window` old as the baseline
+        while samples.len() > 2 && now.duration_since(samples[1].0) >= window {
+            samples.pop_front();
+        }
+
+        let (oldest_at, oldest_total) = samples[0];
+        let elapsed = now.duration_since(oldest_at).as_secs_f64();
+        let rate = if elapsed > 0.0 { total.saturating_sub(oldest_total) as f64 / elapsed } else { 0.0 };
+        *self.messages_per_second.write() = rate;
+        metrics::gauge!("browser_mcp_ws_messages_per_second", rate);
+        rate
+    }
+
+    pub fn messages_per_second(&self) -> f64 {
+        *self.messages_per_second.read()
+    }
+
+    pub fn record_error(&self, message: String) {
+        self.connection_errors
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        *self.last_error.write() = Some((Instant::now(), message));
+    }
 }
 
 pub struct HealthMonitor {
@@ -46,8 +173,25 @@ pub struct HealthMonitor {
 }
 
 pub struct MessageRouter {
-    pending_requests: Arc<DashMap<Uuid, oneshot::Sender<BrowserResponse>>>,
+    pending_requests: Arc<DashMap<Uuid, PendingRequest>>,
     request_timeout: Duration,
+    sweeper_started: std::sync::Once,
+    // Recently timed-out requests and when they gave up, to recognise late responses
+    timed_out: parking_lot::Mutex<VecDeque<(Uuid, Instant)>>,
+    late_responses: AtomicU64,
+}
+
+/// How many timed-out request ids are remembered for late-response logging.
+const TIMED_OUT_HISTORY: usize = 256;
+
+/// How often pending requests past their deadline are resolved with a timeout error.
+const PENDING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+struct PendingRequest {
+    sender: oneshot::Sender<BrowserResponse>,
+    deadline: tokio::time::Instant,
+    // Set once the request is queued on a browser connection
+    connection_id: Option<Uuid>,
 }
 
 impl ConnectionPool {
@@ -58,6 +202,20 @@ impl ConnectionPool {
             message_router: Arc::new(MessageRouter::new(Duration::from_secs(30))),
             stats: Arc::new(ConnectionStats::default()),
             data_cache: None,
+            tab_locks: Arc::new(DashMap::new()),
+            tab_states: Arc::new(DashMap::new()),
+            tab_labels: Arc::new(DashMap::new()),
+            resumable_sessions: Arc::new(DashMap::new()),
+            serialize_per_tab: false,
+            circuit_breaker: Arc::new(CircuitBreaker::disabled()),
+            rate_limiter: Arc::new(TabRateLimiter::disabled()),
+            default_timeout: Duration::from_secs(10),
+            tool_timeouts: Arc::new(HashMap::new()),
+            outbound_queue_size: 1024,
+            outbound_policy: OutboundOverflowPolicy::DropOldest,
+            wire_format: WireFormat::Json,
+            retry_attempts: 0,
+            max_retry_backoff: Duration::from_secs(2),
         }
     }
 
@@ -65,18 +223,94 @@ impl ConnectionPool {
         self.data_cache = Some(cache);
     }
 
+    /// When enabled, requests targeting the same tab wait for the previous one to complete.
+    /// tokio's mutex is FIFO, so queued requests are dispatched in submission order.
+    pub fn set_serialize_per_tab(&mut self, enabled: bool) {
+        self.serialize_per_tab = enabled;
+    }
+
+    /// `default_timeout` applies to requests with no per-tool or built-in timeout.
+    pub fn set_request_timeouts(&mut self, default_timeout: Duration, tool_timeouts: HashMap<String, Duration>) {
+        self.default_timeout = default_timeout;
+        self.tool_timeouts = Arc::new(tool_timeouts);
+    }
+
+    /// Bound each connection's outbound queue; `policy` decides what gives when it fills.
+    pub fn set_outbound_queue(&mut self, size: usize, policy: OutboundOverflowPolicy) {
+        self.outbound_queue_size = size;
+        self.outbound_policy = policy;
+    }
+
+    /// Preferred encoding, offered to extensions that announce support for it.
+    pub fn set_wire_format(&mut self, format: WireFormat) {
+        self.wire_format = format;
+    }
+
+    /// Re-send requests that time out or lose their connection up to `attempts` times,
+    /// backing off between attempts by no more than `max_backoff`.
+    pub fn set_request_retries(&mut self, attempts: usize, max_backoff: Duration) {
+        self.retry_attempts = attempts;
+        self.max_retry_backoff = max_backoff;
+    }
+
+    pub fn set_circuit_breaker(&mut self, breaker: CircuitBreaker) {
+        self.circuit_breaker = Arc::new(breaker);
+    }
+
+    /// Refuse requests to a tab beyond `requests_per_sec`; 0 leaves tabs unlimited.
+    pub fn set_tab_rate_limit(&mut self, requests_per_sec: u32) {
+        self.rate_limiter = Arc::new(TabRateLimiter::new(requests_per_sec));
+    }
+
+    pub fn get_breaker_states(&self) -> Vec<BreakerSnapshot> {
+        self.circuit_breaker.snapshot()
+    }
+
     // Efficient connection handling with minimal allocations
-    pub async fn handle_connection(&self, socket: WebSocket, addr: Option<std::net::SocketAddr>) {
+    pub async fn handle_connection(
+        &self,
+        socket: WebSocket,
+        addr: Option<std::net::SocketAddr>,
+        protocol: Option<&'static str>,
+    ) {
+        self.serve_connection(socket, addr, protocol, None).await
+    }
+
+    /// Like `handle_connection`, but a socket whose first JSON-RPC message shows it is an
+    /// MCP client rather than an extension is detached from browser routing and has its
+    /// messages answered by `mcp_handler`.
+    pub async fn handle_connection_with_mcp(
+        &self,
+        socket: WebSocket,
+        addr: Option<std::net::SocketAddr>,
+        protocol: Option<&'static str>,
+        mcp_handler: McpMessageHandler,
+    ) {
+        self.serve_connection(socket, addr, protocol, Some(mcp_handler)).await
+    }
+
+    async fn serve_connection(
+        &self,
+        socket: WebSocket,
+        addr: Option<std::net::SocketAddr>,
+        protocol: Option<&'static str>,
+        mcp_handler: Option<McpMessageHandler>,
+    ) {
         let (sender, mut receiver) = socket.split();
-        let (tx, mut rx) = mpsc::unbounded_channel();
+        let outbound = OutboundQueue::new(self.outbound_queue_size, self.outbound_policy);
 
         let connection_id = Uuid::new_v4();
         let connection = WebSocketConnection {
             id: connection_id,
-            sender: tx,
+            sender: outbound.clone(),
+            wire_format: WireFormat::Json,
+            protocol,
             tab_id: None,
+            label: None,
+            resume_token: None,
             connected_at: Instant::now(),
             last_activity: Arc::new(RwLock::new(Instant::now())),
+            outstanding_pings: Arc::new(AtomicU64::new(0)),
             remote_addr: addr,
         };
 
@@ -89,32 +323,37 @@ impl ConnectionPool {
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
         tracing::info!(
-            "WebSocket connection established: {} from {:?}",
+            "WebSocket connection established: {} from {:?} (protocol {})",
             connection_id,
-            addr
+            addr,
+            protocol.unwrap_or("unversioned")
         );
 
         // Spawn sender task (outbound messages)
         let sender_task = {
             let connection_id = connection_id;
             let stats = self.stats.clone();
+            let outbound = outbound.clone();
             tokio::spawn(async move {
                 let mut sender = sender;
-                while let Some(msg) = rx.recv().await {
-                    if sender.send(msg).await.is_err() {
-                        tracing::warn!("Failed to send message to {}", connection_id);
+                while let Some(encoded) = outbound.recv_encoded().await {
+                    let (uncompressed, sent) = encoded.sizes();
+                    if let Err(e) = sender.send(encoded.message).await {
+                        tracing::warn!("Failed to send message to {}: {}", connection_id, e);
+                        stats.record_error(format!("Send to {} failed: {}", connection_id, e));
                         break;
                     }
-                    stats
-                        .messages_sent
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    stats.record_sent(uncompressed, sent);
                 }
             })
         };
 
         // Spawn receiver task (inbound messages)
+        let mcp_client = Arc::new(AtomicBool::new(false));
         let receiver_task = {
             let pool = self.clone();
+            let outbound = outbound.clone();
+            let mcp_client = mcp_client.clone();
             tokio::spawn(async move {
                 while let Some(msg_result) = receiver.next().await {
                     match msg_result {
@@ -123,23 +362,34 @@ impl ConnectionPool {
                                 .messages_received
                                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
+                            if let Some(handler) = &mcp_handler {
+                                if mcp_client.load(Ordering::Relaxed) || is_mcp_message(&msg) {
+                                    if !mcp_client.swap(true, Ordering::Relaxed) {
+                                        pool.detach_mcp_client(connection_id);
+                                    }
+                                    if !handle_mcp_message(msg, handler, &outbound) {
+                                        break;
+                                    }
+                                    continue;
+                                }
+                            }
+
                             if let Err(e) = pool.handle_message(connection_id, msg).await {
                                 tracing::error!(
                                     "Error handling message from {}: {}",
                                     connection_id,
                                     e
                                 );
-                                pool.stats
-                                    .connection_errors
-                                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                                pool.stats.record_error(format!("Message from {}: {}", connection_id, e));
                                 break;
                             }
                         }
                         Err(e) => {
                             tracing::error!("WebSocket error for {}: {}", connection_id, e);
-                            pool.stats
-                                .connection_errors
-                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            pool.stats.record_error(format!("WebSocket error for {}: {}", connection_id, e));
+                            if is_message_too_large(&e) {
+                                pool.close_with_policy_violation(connection_id, "Message exceeds maximum size");
+                            }
                             break;
                         }
                     }
@@ -153,14 +403,39 @@ impl ConnectionPool {
             _ = receiver_task => {},
         }
 
-        // Cleanup
+        // Cleanup; the sender task keeps running until it has flushed any close frame
+        outbound.close();
         self.remove_connection(connection_id).await;
-        self.stats
-            .active_connections
-            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+        if !mcp_client.load(Ordering::Relaxed) {
+            self.stats
+                .active_connections
+                .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+        }
         tracing::info!("WebSocket connection closed: {}", connection_id);
     }
 
+    /// Stop treating a socket as a browser connection once it turns out to be an MCP
+    /// client, so browser requests are never routed to it. Its outbound queue stays open
+    /// for replies.
+    fn detach_mcp_client(&self, connection_id: Uuid) {
+        if self.connections.remove(&connection_id).is_some() {
+            self.stats
+                .active_connections
+                .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            tracing::info!("WebSocket connection {} is an MCP client", connection_id);
+        }
+    }
+
+    /// Queue a policy-violation close frame; the sender task flushes it before shutting down.
+    fn close_with_policy_violation(&self, connection_id: Uuid, reason: &'static str) {
+        if let Some(connection) = self.connections.get(&connection_id) {
+            let _ = connection.sender.send_critical(Message::Close(Some(CloseFrame {
+                code: close_code::POLICY,
+                reason: reason.into(),
+            })));
+        }
+    }
+
     async fn handle_message(&self, connection_id: Uuid, message: Message) -> Result<()> {
         // Update last activity
         if let Some(connection) = self.connections.get(&connection_id) {
@@ -192,9 +467,27 @@ impl ConnectionPool {
                     }
                 }
             }
-            Message::Binary(_) => {
-                tracing::warn!("Received unexpected binary message from {}", connection_id);
+            Message::Binary(frame) if self.uses_msgpack(connection_id) && is_msgpack_frame(&frame) => {
+                let value = decode_msgpack(&frame)?;
+                match serde_json::from_value::<BrowserMessage>(value.clone()) {
+                    Ok(browser_message) => {
+                        self.process_browser_message(connection_id, browser_message)
+                            .await?;
+                    }
+                    Err(_) => self.process_flexible_message(connection_id, value).await?,
+                }
             }
+            Message::Binary(frame) => match parse_binary_frame(&frame) {
+                Ok((request_id, response)) => {
+                    tracing::debug!("Received {} byte binary response for request {}", frame.len(), request_id);
+                    self.message_router
+                        .handle_response(request_id, Ok(response))
+                        .await?;
+                }
+                Err(e) => {
+                    tracing::warn!("Dropping invalid binary message from {}: {}", connection_id, e);
+                }
+            },
             Message::Ping(data) => {
                 if let Some(connection) = self.connections.get(&connection_id) {
                     let _ = connection.sender.send(Message::Pong(data));
@@ -202,6 +495,9 @@ impl ConnectionPool {
             }
             Message::Pong(_) => {
                 // Pong received, connection is alive
+                if let Some(connection) = self.connections.get(&connection_id) {
+                    connection.outstanding_pings.store(0, Ordering::Relaxed);
+                }
             }
             Message::Close(_) => {
                 tracing::info!("Received close message from {}", connection_id);
@@ -212,6 +508,31 @@ impl ConnectionPool {
         Ok(())
     }
 
+    fn uses_msgpack(&self, connection_id: Uuid) -> bool {
+        self.connections
+            .get(&connection_id)
+            .is_some_and(|connection| connection.wire_format == WireFormat::Msgpack)
+    }
+
+    /// Answer the extension's list of supported encodings with the one to use from
+    /// now on. The reply itself is always JSON so older extensions can read it.
+    fn negotiate_wire_format(&self, connection_id: Uuid, offered: &[serde_json::Value]) {
+        let supports_msgpack = offered.iter().any(|format| format.as_str() == Some("msgpack"));
+        let format = if self.wire_format == WireFormat::Msgpack && supports_msgpack {
+            WireFormat::Msgpack
+        } else {
+            WireFormat::Json
+        };
+
+        if let Some(mut connection) = self.connections.get_mut(&connection_id) {
+            let reply = serde_json::json!({ "type": "wire_format", "format": format });
+            if connection.sender.send_critical(Message::Text(reply.to_string())).is_ok() {
+                connection.wire_format = format;
+                tracing::info!("Connection {} using {:?} wire format", connection_id, format);
+            }
+        }
+    }
+
     async fn process_browser_message(
         &self,
         connection_id: Uuid,
@@ -219,6 +540,16 @@ impl ConnectionPool {
     ) -> Result<()> {
         match message {
             BrowserMessage::Response { request_id, result } => {
+                // Fail the request now rather than leaving it to time out
+                let result = match result {
+                    Ok(BrowserResponse::Unknown) => {
+                        tracing::warn!("Connection {} sent an unknown response type for request {}", connection_id, request_id);
+                        Err("Browser sent a response type this server does not understand; \
+                             the extension may be newer than the server"
+                            .to_string())
+                    }
+                    other => other,
+                };
                 self.message_router
                     .handle_response(request_id, result)
                     .await?;
@@ -242,10 +573,16 @@ impl ConnectionPool {
 
     async fn handle_browser_event(&self, connection_id: Uuid, event: BrowserEvent) -> Result<()> {
         match event {
-            BrowserEvent::ConnectionEstablished { tab_id } => {
+            BrowserEvent::ConnectionEstablished { tab_id, label, resume_token } => {
+                if let Some(token) = resume_token {
+                    self.resume_session(connection_id, &token);
+                }
                 self.associate_tab_with_connection(connection_id, tab_id)
                     .await;
-                tracing::info!("Connection {} associated with tab {}", connection_id, tab_id);
+                if let Some(label) = label {
+                    self.set_connection_label(connection_id, label);
+                }
+                self.issue_resume_token(connection_id);
             }
             BrowserEvent::ConnectionLost { tab_id } => {
                 self.disassociate_tab_from_connection(connection_id, tab_id)
@@ -256,6 +593,23 @@ impl ConnectionPool {
                     tab_id
                 );
             }
+            BrowserEvent::PageLoaded { tab_id, url } => {
+                self.invalidate_cached_responses(tab_id);
+                if let Some(cache) = &self.data_cache {
+                    cache.clear_js_contexts(tab_id);
+                    cache.set_tab_url(tab_id, &url);
+                }
+            }
+            BrowserEvent::TabUpdated { tab } => {
+                if !self.record_tab_state(tab.id, &tab.url, &tab.title) {
+                    return Ok(());
+                }
+                self.invalidate_cached_responses(tab.id);
+                if let Some(cache) = &self.data_cache {
+                    cache.set_tab_url(tab.id, &tab.url);
+                }
+            }
+            BrowserEvent::TabRemoved { tab_id } => self.handle_tab_removed(tab_id).await,
             _ => {
                 // Other events can be logged or processed as needed
                 tracing::debug!("Received browser event: {:?}", event);
@@ -282,9 +636,52 @@ impl ConnectionPool {
                 if let Some(event) = message.get("event") {
                     tracing::debug!("Received notification event from {}: {}", connection_id, event);
 
+                    if event.get("type").and_then(|t| t.as_str()) == Some("connection_established") {
+                        if let Some(offered) = event.get("wireFormats").and_then(|f| f.as_array()) {
+                            self.negotiate_wire_format(connection_id, offered);
+                        }
+                        if let Some(token) = event.get("resumeToken").and_then(|t| t.as_str()) {
+                            self.resume_session(connection_id, token);
+                        }
+                        if let Some(label) = event.get("label").and_then(|l| l.as_str()) {
+                            self.set_connection_label(connection_id, label.to_string());
+                        }
+                        self.issue_resume_token(connection_id);
+                    }
+
                     // Extract tab_id if available for connection association
                     if let Some(tab_id) = event.get("tabId").and_then(|t| t.as_u64()) {
+                        if event.get("type").and_then(|t| t.as_str()) == Some("tab_removed") {
+                            self.handle_tab_removed(tab_id as u32).await;
+                            return Ok(());
+                        }
                         self.associate_tab_with_connection(connection_id, tab_id as u32).await;
+
+                        let event_type = event.get("type").and_then(|t| t.as_str());
+                        let url = event.get("url").and_then(|u| u.as_str());
+                        if event_type == Some("tab_updated") {
+                            let title = event.get("title").and_then(|t| t.as_str()).unwrap_or_default();
+                            if !self.record_tab_state(tab_id as u32, url.unwrap_or_default(), title) {
+                                return Ok(());
+                            }
+                        }
+                        if matches!(event_type, Some("page_loaded") | Some("tab_updated")) {
+                            self.invalidate_cached_responses(tab_id as u32);
+                        }
+                        if let (Some("page_loaded"), Some(cache)) = (event_type, &self.data_cache) {
+                            cache.clear_js_contexts(tab_id as u32);
+                        }
+                        if let (Some("debugger_detached"), Some(cache)) = (event_type, &self.data_cache) {
+                            tracing::info!(
+                                "Debugger detached from tab {} outside the server ({})",
+                                tab_id,
+                                event.get("reason").and_then(|r| r.as_str()).unwrap_or("unknown reason")
+                            );
+                            cache.set_debugger_attached(tab_id as u32, false).await;
+                        }
+                        if let (Some(url), Some(cache)) = (url, &self.data_cache) {
+                            cache.set_tab_url(tab_id as u32, url);
+                        }
                     }
                 }
             }
@@ -297,7 +694,9 @@ impl ConnectionPool {
                         "timestamp": chrono::Utc::now().timestamp_millis(),
                         "originalTimestamp": message.get("timestamp")
                     });
-                    let _ = connection.sender.send(Message::Text(pong_response.to_string()));
+                    if let Ok(pong) = encode_message(connection.wire_format, &pong_response) {
+                        let _ = connection.sender.send(pong);
+                    }
                 }
             }
             "response" => {
@@ -372,14 +771,7 @@ impl ConnectionPool {
                         if let Some(data) = message.get("data") {
                             // Store page content if available
                             if let Some(page_content) = data.get("pageContent") {
-                                let content = crate::types::browser::PageContent {
-                                    url: page_content.get("url").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                                    title: page_content.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                                    text: page_content.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                                    html: page_content.get("html").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                                    metadata: std::collections::HashMap::new(),
-                                    last_updated: std::time::SystemTime::now(),
-                                };
+                                let content = crate::types::browser::PageContent::from_extension(page_content);
                                 cache.update_page_content(tab_id, content).await;
                             }
                         }
@@ -395,12 +787,133 @@ impl ConnectionPool {
         }
     }
 
-    async fn associate_tab_with_connection(&self, connection_id: Uuid, tab_id: u32) {
+    fn invalidate_cached_responses(&self, tab_id: u32) {
+        if let Some(cache) = &self.data_cache {
+            cache.invalidate_responses(tab_id);
+        }
+    }
+
+    /// Returns whether the association changed. Extensions resend it with most events,
+    /// so the common case only takes a read lock.
+    async fn associate_tab_with_connection(&self, connection_id: Uuid, tab_id: u32) -> bool {
+        match self.connections.get(&connection_id) {
+            Some(connection) if connection.tab_id == Some(tab_id) => return false,
+            Some(_) => {}
+            None => return false,
+        }
+        let Some(mut connection) = self.connections.get_mut(&connection_id) else {
+            return false;
+        };
+        // A label sent before any tab was announced names the first one
+        if let (None, Some(label)) = (connection.tab_id, &connection.label) {
+            self.bind_tab_label(label, tab_id);
+        }
+        connection.tab_id = Some(tab_id);
+        tracing::info!("Connection {} associated with tab {}", connection_id, tab_id);
+        true
+    }
+
+    fn set_connection_label(&self, connection_id: Uuid, label: String) {
         if let Some(mut connection) = self.connections.get_mut(&connection_id) {
-            connection.tab_id = Some(tab_id);
+            if connection.label.as_deref() == Some(label.as_str()) {
+                return;
+            }
+            tracing::info!("Connection {} labelled '{}'", connection_id, label);
+            if let Some(tab_id) = connection.tab_id {
+                self.bind_tab_label(&label, tab_id);
+            }
+            connection.label = Some(label);
         }
     }
 
+    fn bind_tab_label(&self, label: &str, tab_id: u32) {
+        tracing::info!("Tab {} labelled '{}'", tab_id, label);
+        self.tab_labels.insert(label.to_string(), tab_id);
+    }
+
+    /// Give the connection a fresh resume token, replacing any earlier one.
+    fn issue_resume_token(&self, connection_id: Uuid) {
+        if let Some(mut connection) = self.connections.get_mut(&connection_id) {
+            let token = Uuid::new_v4();
+            let reply = serde_json::json!({ "type": "resume_token", "token": token.to_string() });
+            if connection.sender.send_critical(Message::Text(reply.to_string())).is_ok() {
+                connection.resume_token = Some(token);
+            }
+        }
+    }
+
+    /// Restore the tab and label of the closed connection `token` was issued to, so a
+    /// reconnecting extension is routable before it re-announces its tabs. Tokens are
+    /// single use; returns whether one was accepted.
+    fn resume_session(&self, connection_id: Uuid, token: &str) -> bool {
+        let Some((_, session)) = Uuid::parse_str(token)
+            .ok()
+            .and_then(|token| self.resumable_sessions.remove(&token))
+        else {
+            tracing::debug!("Connection {} presented an unknown resume token", connection_id);
+            return false;
+        };
+        if session.expires_at <= Instant::now() {
+            tracing::debug!("Connection {} presented an expired resume token", connection_id);
+            return false;
+        }
+        let Some(mut connection) = self.connections.get_mut(&connection_id) else {
+            return false;
+        };
+        connection.tab_id = Some(session.tab_id);
+        if connection.label.is_none() {
+            if let Some(label) = &session.label {
+                self.bind_tab_label(label, session.tab_id);
+            }
+            connection.label = session.label;
+        }
+        tracing::info!("Connection {} resumed tab {}", connection_id, session.tab_id);
+        true
+    }
+
+    /// Drop everything kept for a tab the browser closed.
+    async fn handle_tab_removed(&self, tab_id: u32) {
+        tracing::debug!("Tab {} closed", tab_id);
+        self.forget_tab(tab_id);
+        self.tab_labels.retain(|_, labelled| *labelled != tab_id);
+        if let Some(cache) = &self.data_cache {
+            cache.remove_tab_data(tab_id).await;
+        }
+    }
+
+    /// Forget the last reported state, request budget, breaker state and request lock
+    /// of a tab that closed or whose cached data was evicted, so its next `tab_updated`
+    /// is applied rather than dropped as a repeat.
+    pub fn forget_tab(&self, tab_id: u32) {
+        self.tab_states.remove(&tab_id);
+        self.rate_limiter.remove_tab(tab_id);
+        self.circuit_breaker.forget(tab_id);
+        // Keep a lock a request still holds or waits on, so the tab's requests stay serialized
+        self.tab_locks.remove_if(&tab_id, |_, lock| Arc::strong_count(lock) == 1);
+    }
+
+    /// URL and title from the tab's last `tab_updated` event.
+    pub fn tab_location(&self, tab_id: u32) -> Option<(String, String)> {
+        self.tab_states.get(&tab_id).map(|state| state.clone())
+    }
+
+    /// Remember a tab's URL and title, returning false if they are what was last reported.
+    fn record_tab_state(&self, tab_id: u32, url: &str, title: &str) -> bool {
+        if let Some(state) = self.tab_states.get(&tab_id) {
+            if state.0 == url && state.1 == title {
+                return false;
+            }
+        }
+        self.tab_states.insert(tab_id, (url.to_string(), title.to_string()));
+        true
+    }
+
+    /// The tab the extension labelled `label`. When a label is given again the most
+    /// recent binding wins.
+    pub fn resolve_tab_label(&self, label: &str) -> Option<u32> {
+        self.tab_labels.get(label).map(|tab_id| *tab_id)
+    }
+
     async fn disassociate_tab_from_connection(&self, connection_id: Uuid, tab_id: u32) {
         if let Some(mut connection) = self.connections.get_mut(&connection_id) {
             if connection.tab_id == Some(tab_id) {
@@ -411,15 +924,13 @@ impl ConnectionPool {
 
     // Zero-allocation message broadcasting
     pub async fn broadcast_to_tab(&self, tab_id: u32, message: &BrowserMessage) -> Result<usize> {
-        let serialized = serde_json::to_string(message)?;
-        let ws_message = Message::Text(serialized);
-
         let mut sent_count = 0;
 
         for entry in self.connections.iter() {
             let connection = entry.value();
             if connection.tab_id == Some(tab_id) {
-                if connection.sender.send(ws_message.clone()).is_ok() {
+                let ws_message = encode_message(connection.wire_format, message)?;
+                if connection.sender.send(ws_message).is_ok() {
                     sent_count += 1;
                 } else {
                     // Connection is dead, will be cleaned up by health monitor
@@ -438,11 +949,22 @@ impl ConnectionPool {
             BrowserRequest::GetPageContent { include_metadata } => {
                 serde_json::json!({ "action": "getPageContent", "includeMetadata": include_metadata })
             }
-            BrowserRequest::GetDomSnapshot { max_depth, include_styles } => {
-                serde_json::json!({ "action": "getDOMSnapshot", "maxDepth": max_depth, "includeStyles": include_styles })
+            BrowserRequest::GetDomSnapshot { max_depth, include_styles, pierce } => {
+                serde_json::json!({ "action": "getDOMSnapshot", "maxDepth": max_depth, "includeStyles": include_styles, "pierce": pierce })
+            }
+            BrowserRequest::ExecuteJavaScript { code, context_id, await_promise, .. } => {
+                let mut m = serde_json::json!({ "action": "executeScript", "script": code, "awaitPromise": await_promise });
+                if let Some(id) = context_id { m["contextId"] = serde_json::Value::String(id.clone()); }
+                m
             }
-            BrowserRequest::ExecuteJavaScript { code, .. } => {
-                serde_json::json!({ "action": "executeScript", "script": code })
+            BrowserRequest::SetPageContent { html, tab_id } => {
+                serde_json::json!({ "action": "setPageContent", "html": html, "tabId": tab_id })
+            }
+            BrowserRequest::CreateJsContext => {
+                serde_json::json!({ "action": "createJsContext" })
+            }
+            BrowserRequest::DisposeJsContext { context_id } => {
+                serde_json::json!({ "action": "disposeJsContext", "contextId": context_id })
             }
             BrowserRequest::GetConsoleMessages { level_filter, limit } => {
                 let mut m = serde_json::json!({ "action": "getConsoleMessages" });
@@ -455,19 +977,164 @@ impl ConnectionPool {
                 if let Some(l) = limit { m["limit"] = serde_json::json!(l); }
                 m
             }
-            BrowserRequest::CaptureScreenshot { format, quality, .. } => {
+            // `requestId` is taken by the bridge's own correlation id
+            BrowserRequest::GetNetworkRequestBody { request_id } => {
+                serde_json::json!({ "action": "getNetworkRequestBody", "networkRequestId": request_id })
+            }
+            BrowserRequest::ReplayRequest { method, url, headers, body, tab_id, max_bytes } => {
+                serde_json::json!({
+                    "action": "replayRequest",
+                    "method": method,
+                    "url": url,
+                    "headers": headers,
+                    "body": body,
+                    "tabId": tab_id,
+                    "maxBytes": max_bytes
+                })
+            }
+            BrowserRequest::DownloadResource { url, tab_id, max_bytes } => {
+                serde_json::json!({ "action": "downloadResource", "url": url, "tabId": tab_id, "maxBytes": max_bytes })
+            }
+            BrowserRequest::CaptureScreenshot { format, quality, clip, capture_selector } => {
                 let mut m = serde_json::json!({ "action": "captureScreenshot", "format": format });
                 if let Some(q) = quality { m["quality"] = serde_json::json!(q); }
+                if let Some(selector) = capture_selector {
+                    m["captureSelector"] = serde_json::Value::String(selector.clone());
+                } else if let Some(clip) = clip {
+                    m["clip"] = serde_json::json!(clip);
+                }
                 m
             }
             BrowserRequest::GetPerformanceMetrics => {
                 serde_json::json!({ "action": "getPerformanceMetrics" })
             }
+            BrowserRequest::GetResourceUsage => {
+                serde_json::json!({ "action": "getResourceUsage" })
+            }
+            BrowserRequest::WaitForNetworkIdle { idle_ms, timeout_ms, max_inflight } => {
+                serde_json::json!({
+                    "action": "waitForNetworkIdle",
+                    "idleMs": idle_ms,
+                    "timeoutMs": timeout_ms,
+                    "maxInflight": max_inflight
+                })
+            }
+            BrowserRequest::WaitForConsoleError { timeout_ms, pattern } => {
+                serde_json::json!({
+                    "action": "waitForConsoleError",
+                    "timeoutMs": timeout_ms,
+                    "pattern": pattern
+                })
+            }
+            BrowserRequest::GetComputedStyle { selector, properties } => {
+                let mut m = serde_json::json!({ "action": "getComputedStyle", "selector": selector });
+                if let Some(p) = properties { m["properties"] = serde_json::json!(p); }
+                m
+            }
+            BrowserRequest::GetLinks { same_origin_only, include_text } => {
+                serde_json::json!({
+                    "action": "getLinks",
+                    "sameOriginOnly": same_origin_only,
+                    "includeText": include_text
+                })
+            }
+            BrowserRequest::DispatchKey { selector, key, modifiers } => {
+                let mut m = serde_json::json!({
+                    "action": "dispatchKey",
+                    "key": key,
+                    "modifiers": modifiers
+                });
+                if let Some(s) = selector {
+                    m["selector"] = serde_json::json!(s);
+                }
+                m
+            }
+            BrowserRequest::FillForm { fields } => {
+                serde_json::json!({ "action": "fillForm", "fields": fields })
+            }
+            BrowserRequest::EvaluateXPath { expression } => {
+                serde_json::json!({ "action": "evaluateXPath", "expression": expression })
+            }
+            BrowserRequest::GetElementRect { selector } => {
+                serde_json::json!({ "action": "getElementRect", "selector": selector })
+            }
+            BrowserRequest::ObserveMutations { selector, duration_ms, attributes, child_list, subtree } => {
+                let mut m = serde_json::json!({
+                    "action": "observeMutations",
+                    "durationMs": duration_ms,
+                    "attributes": attributes,
+                    "childList": child_list,
+                    "subtree": subtree
+                });
+                if let Some(s) = selector {
+                    m["selector"] = serde_json::json!(s);
+                }
+                m
+            }
+            BrowserRequest::GetNavigationHistory { tab_id } => {
+                serde_json::json!({ "action": "getNavigationHistory", "tabId": tab_id })
+            }
+            BrowserRequest::NavigateHistory { delta } => {
+                serde_json::json!({ "action": "navigateHistory", "delta": delta })
+            }
+            BrowserRequest::SetInterceptRules { rules } => {
+                serde_json::json!({ "action": "setInterceptRules", "rules": rules })
+            }
+            BrowserRequest::ClearBrowsingData { cache, cookies, local_storage, tab_id } => {
+                serde_json::json!({
+                    "action": "clearBrowsingData",
+                    "cache": cache,
+                    "cookies": cookies,
+                    "localStorage": local_storage,
+                    "tabId": tab_id
+                })
+            }
+            BrowserRequest::CaptureMhtml { tab_id } => {
+                serde_json::json!({ "action": "captureMhtml", "tabId": tab_id })
+            }
+            BrowserRequest::GetLayoutMetrics { tab_id } => {
+                serde_json::json!({ "action": "getLayoutMetrics", "tabId": tab_id })
+            }
+            BrowserRequest::GetCapabilities { tab_id } => {
+                serde_json::json!({ "action": "getCapabilities", "tabId": tab_id })
+            }
+            BrowserRequest::SetExtraHeaders { headers, tab_id } => {
+                serde_json::json!({ "action": "setExtraHeaders", "headers": headers, "tabId": tab_id })
+            }
+            BrowserRequest::GetUserAgent { tab_id } => {
+                serde_json::json!({ "action": "getUserAgent", "tabId": tab_id })
+            }
+            BrowserRequest::SetUserAgent { user_agent, accept_language, tab_id } => {
+                let mut m = serde_json::json!({ "action": "setUserAgent", "userAgent": user_agent, "tabId": tab_id });
+                if let Some(language) = accept_language { m["acceptLanguage"] = serde_json::json!(language); }
+                m
+            }
+            BrowserRequest::TerminateExecution => {
+                serde_json::json!({ "action": "terminateExecution" })
+            }
+            BrowserRequest::SetEmulatedMedia { media, features, tab_id } => {
+                // The debugger takes an empty media type to mean "no override"
+                serde_json::json!({
+                    "action": "setEmulatedMedia",
+                    "media": media.as_deref().unwrap_or(""),
+                    "features": features,
+                    "tabId": tab_id
+                })
+            }
+            BrowserRequest::AddInitScript { source, tab_id } => {
+                serde_json::json!({ "action": "addInitScript", "source": source, "tabId": tab_id })
+            }
+            BrowserRequest::RemoveInitScript { script_id, tab_id } => {
+                serde_json::json!({ "action": "removeInitScript", "scriptId": script_id, "tabId": tab_id })
+            }
             BrowserRequest::GetAccessibilityTree { max_depth } => {
                 let mut m = serde_json::json!({ "action": "getAccessibilityTree" });
                 if let Some(d) = max_depth { m["maxDepth"] = serde_json::json!(d); }
                 m
             }
+            BrowserRequest::GetAccessibilityNode { selector } => {
+                serde_json::json!({ "action": "getAccessibilityNode", "selector": selector })
+            }
             BrowserRequest::GetBrowserTabs => {
                 serde_json::json!({ "action": "getAllTabs" })
             }
@@ -477,6 +1144,12 @@ impl ConnectionPool {
             BrowserRequest::DetachDebugger => {
                 serde_json::json!({ "action": "detachDebugger" })
             }
+            BrowserRequest::PauseExecution => {
+                serde_json::json!({ "action": "pauseExecution" })
+            }
+            BrowserRequest::ResumeExecution => {
+                serde_json::json!({ "action": "resumeExecution" })
+            }
         };
 
         msg["requestId"] = serde_json::Value::String(request_id.to_string());
@@ -486,15 +1159,30 @@ impl ConnectionPool {
         msg
     }
 
-    /// Get timeout duration based on the action type
-    fn timeout_for_request(request: &BrowserRequest, custom_timeout: Option<Duration>) -> Duration {
+    /// Get timeout duration for a request: an explicit per-call timeout, then the configured
+    /// `[timeouts]` entry for the tool, then the built-in per-action default, then the global one.
+    pub fn timeout_for_request(&self, request: &BrowserRequest, custom_timeout: Option<Duration>) -> Duration {
         if let Some(t) = custom_timeout {
             return t;
         }
+        if let Some(t) = self.tool_timeouts.get(request.name()) {
+            return *t;
+        }
         match request {
+            // The extension gives up at timeout_ms; leave it time to report that
+            BrowserRequest::WaitForNetworkIdle { timeout_ms, .. } => {
+                Duration::from_millis(*timeout_ms) + Duration::from_secs(2)
+            }
+            BrowserRequest::WaitForConsoleError { timeout_ms, .. } => {
+                Duration::from_millis(*timeout_ms) + Duration::from_secs(2)
+            }
+            // Records come back only once the observation window closes
+            BrowserRequest::ObserveMutations { duration_ms, .. } => {
+                Duration::from_millis(*duration_ms) + Duration::from_secs(2)
+            }
             BrowserRequest::GetAccessibilityTree { .. } => Duration::from_secs(30),
             BrowserRequest::GetDomSnapshot { .. } => Duration::from_secs(20),
-            _ => Duration::from_secs(10),
+            _ => self.default_timeout,
         }
     }
 
@@ -508,6 +1196,33 @@ impl ConnectionPool {
         self.send_request_with_timeout(None, request, None).await
     }
 
+    /// Send every request in `batch`, at most `max_parallel` at a time, each bounded by
+    /// `batch.timeout`. Responses are in the order the requests were added; one failing
+    /// doesn't stop the others. Errors the extension reports count as failures.
+    pub async fn send_batch(&self, batch: BatchRequest) -> BatchResponse {
+        let started = Instant::now();
+        let timeout = batch.timeout;
+        let responses: Vec<(u32, Result<BrowserResponse>)> = futures_util::stream::iter(batch.requests)
+            .map(|(tab_id, request)| async move {
+                let response = match self.send_request_with_timeout(Some(tab_id), request, Some(timeout)).await {
+                    Ok(BrowserResponse::Error { message }) => Err(BrowserMcpError::BrowserExtensionError { message }),
+                    other => other,
+                };
+                (tab_id, response)
+            })
+            .buffered(batch.max_parallel.max(1))
+            .collect()
+            .await;
+
+        let failed = responses.iter().filter(|(_, response)| response.is_err()).count();
+        BatchResponse {
+            completed: responses.len() - failed,
+            failed,
+            responses,
+            elapsed: started.elapsed(),
+        }
+    }
+
     /// Send request with optional tab targeting and custom timeout
     pub async fn send_request_with_timeout(
         &self,
@@ -515,16 +1230,92 @@ impl ConnectionPool {
         request: BrowserRequest,
         custom_timeout: Option<Duration>,
     ) -> Result<BrowserResponse> {
+        if self.retry_attempts == 0 {
+            return self.send_attempt(tab_id, request, custom_timeout).await;
+        }
+
+        let timeout = self.timeout_for_request(&request, custom_timeout);
+        let mut tracker = RequestTracker::new(timeout, self.retry_attempts);
+        loop {
+            match self.send_attempt(tab_id, request.clone(), custom_timeout).await {
+                Err(BrowserMcpError::RequestTimeout { .. }) | Err(BrowserMcpError::ConnectionClosed)
+                    if tracker.retry() =>
+                {
+                    let delay = tracker.backoff_delay(self.max_retry_backoff);
+                    tracing::debug!(
+                        "Retrying {} (attempt {} of {}) in {:?}",
+                        request.name(),
+                        tracker.retry_count,
+                        tracker.max_retries,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_attempt(
+        &self,
+        tab_id: Option<u32>,
+        request: BrowserRequest,
+        custom_timeout: Option<Duration>,
+    ) -> Result<BrowserResponse> {
+        let Some(tid) = tab_id else {
+            return self.dispatch_request(None, request, custom_timeout).await;
+        };
+
+        // Refuse bursts before they reach the tab's single-threaded message loop
+        self.rate_limiter.try_acquire(tid)?;
+        // Fail fast while the tab's breaker is open rather than waiting out another timeout
+        let is_probe = self.circuit_breaker.try_acquire(tid)?;
+        let _probe_guard = is_probe.then(|| ProbeGuard {
+            breaker: &self.circuit_breaker,
+            tab_id: tid,
+        });
+
+        let result = self.dispatch_request(Some(tid), request, custom_timeout).await;
+        match &result {
+            Err(BrowserMcpError::RequestTimeout { .. }) | Err(BrowserMcpError::ConnectionClosed) => {
+                self.circuit_breaker.record_failure(tid);
+            }
+            _ => self.circuit_breaker.record_success(tid),
+        }
+        result
+    }
+
+    async fn dispatch_request(
+        &self,
+        tab_id: Option<u32>,
+        request: BrowserRequest,
+        custom_timeout: Option<Duration>,
+    ) -> Result<BrowserResponse> {
+        // Hold the tab's lock until the response arrives so operations never interleave
+        let _tab_guard = match tab_id {
+            Some(tid) if self.serialize_per_tab && !request.bypasses_tab_queue() => {
+                let lock = self.tab_locks.entry(tid).or_default().clone();
+                Some(lock.lock_owned().await)
+            }
+            _ => None,
+        };
+
         let request_id = Uuid::new_v4();
-        let timeout = Self::timeout_for_request(&request, custom_timeout);
+        let timeout = self.timeout_for_request(&request, custom_timeout);
 
         // Create response channel
         let (response_tx, response_rx) = oneshot::channel();
 
-        // Register pending request
+        // Register pending request. The guard unregisters it if this future is dropped
+        // (timeout or client cancellation) and tells the browser to stop working on it.
         self.message_router
-            .register_pending_request(request_id, response_tx)
+            .register_pending_request(request_id, response_tx, timeout)
             .await;
+        let mut pending_guard = PendingRequestGuard {
+            pending_requests: self.message_router.pending_requests.clone(),
+            request_id,
+            browser: None,
+        };
 
         // Find connection: either for specific tab or most recently active
         let connection = if let Some(tid) = tab_id {
@@ -540,16 +1331,32 @@ impl ConnectionPool {
 
         // Build flat camelCase JSON message
         let msg = Self::build_request_json(&request_id, &request, tab_id);
-        let serialized = serde_json::to_string(&msg)?;
-
-        tracing::debug!("Sending request {} for action: {}", request_id, msg.get("action").and_then(|v| v.as_str()).unwrap_or("unknown"));
-        connection.sender.send(Message::Text(serialized))?;
+        let encoded = encode_message(connection.wire_format, &msg)?;
+
+        let action = msg.get("action").and_then(|v| v.as_str()).unwrap_or("unknown");
+        match TOOL_CALL_ORIGIN.try_with(ToolCallOrigin::clone) {
+            Ok(origin) => tracing::info!(
+                rpc_id = %origin.rpc_id,
+                tool = %origin.tool,
+                request_id = %request_id,
+                tab_id,
+                action,
+                "Sending browser request for tool call"
+            ),
+            Err(_) => tracing::debug!("Sending request {} for action: {}", request_id, action),
+        }
+        self.message_router.assign_connection(request_id, connection.id);
+        connection.sender.send_critical(encoded)?;
+        pending_guard.browser = Some((connection.sender.clone(), connection.wire_format));
 
         // Wait for response with timeout
-        tokio::time::timeout(timeout, response_rx)
-            .await
-            .map_err(|_| BrowserMcpError::RequestTimeout { timeout })?
-            .map_err(|_| BrowserMcpError::ConnectionClosed)
+        match tokio::time::timeout(timeout, response_rx).await {
+            Ok(response) => response.map_err(|_| BrowserMcpError::ConnectionClosed),
+            Err(_) => {
+                self.message_router.record_timeout(request_id);
+                Err(BrowserMcpError::RequestTimeout { timeout })
+            }
+        }
     }
 
     pub fn find_connection_for_tab(&self, tab_id: u32) -> Option<WebSocketConnection> {
@@ -559,9 +1366,14 @@ impl ConnectionPool {
                 return Some(WebSocketConnection {
                     id: connection.id,
                     sender: connection.sender.clone(),
+                    wire_format: connection.wire_format,
+                    protocol: connection.protocol,
                     tab_id: connection.tab_id,
+                    label: connection.label.clone(),
+                    resume_token: connection.resume_token,
                     connected_at: connection.connected_at,
                     last_activity: connection.last_activity.clone(),
+                    outstanding_pings: connection.outstanding_pings.clone(),
                     remote_addr: connection.remote_addr,
                 });
             }
@@ -582,9 +1394,14 @@ impl ConnectionPool {
                 WebSocketConnection {
                     id: connection.id,
                     sender: connection.sender.clone(),
+                    wire_format: connection.wire_format,
+                    protocol: connection.protocol,
                     tab_id: connection.tab_id,
+                    label: connection.label.clone(),
+                    resume_token: connection.resume_token,
                     connected_at: connection.connected_at,
                     last_activity: connection.last_activity.clone(),
+                    outstanding_pings: connection.outstanding_pings.clone(),
                     remote_addr: connection.remote_addr,
                 }
             })
@@ -594,6 +1411,33 @@ impl ConnectionPool {
         self.connections.iter().map(|entry| *entry.key()).collect()
     }
 
+    /// Per-connection summary for the introspection endpoint.
+    pub fn describe_connections(&self) -> Vec<serde_json::Value> {
+        let mut connections: Vec<_> = self
+            .connections
+            .iter()
+            .map(|entry| {
+                let connection = entry.value();
+                (
+                    connection.connected_at,
+                    serde_json::json!({
+                        "id": connection.id.to_string(),
+                        "tabId": connection.tab_id,
+                        "label": connection.label,
+                        "protocol": connection.protocol,
+                        "remoteAddr": connection.remote_addr.map(|addr| addr.to_string()),
+                        "connectedSecs": connection.connected_at.elapsed().as_secs(),
+                        "idleSecs": connection.last_activity.read().elapsed().as_secs(),
+                        "outboundQueued": connection.sender.len(),
+                        "outboundDropped": connection.sender.dropped_count(),
+                    }),
+                )
+            })
+            .collect();
+        connections.sort_by_key(|(connected_at, _)| *connected_at);
+        connections.into_iter().map(|(_, info)| info).collect()
+    }
+
     pub async fn get_connections_for_tab(&self, tab_id: u32) -> Vec<Uuid> {
         self.connections
             .iter()
@@ -609,15 +1453,47 @@ impl ConnectionPool {
     }
 
     pub async fn remove_connection(&self, connection_id: Uuid) {
-        self.connections.remove(&connection_id);
+        if let Some((_, connection)) = self.connections.remove(&connection_id) {
+            connection.sender.close();
+            if let (Some(token), Some(tab_id)) = (connection.resume_token, connection.tab_id) {
+                self.resumable_sessions.insert(token, ResumableSession {
+                    tab_id,
+                    label: connection.label,
+                    expires_at: Instant::now() + RESUME_TOKEN_TTL,
+                });
+            }
+        }
         self.health_monitor
             .unhealthy_connections
             .remove(&connection_id);
         self.message_router.cleanup_connection(connection_id).await;
     }
 
+    /// Ping every connection, first closing those that left the last `MAX_MISSED_PONGS`
+    /// pings unanswered. A peer that vanished without closing the TCP connection sends
+    /// nothing, so this catches it long before `cleanup_stale_connections` would.
+    pub async fn send_heartbeats(&self) {
+        let mut unresponsive = Vec::new();
+        for entry in self.connections.iter() {
+            let connection = entry.value();
+            if connection.outstanding_pings.load(Ordering::Relaxed) >= MAX_MISSED_PONGS {
+                unresponsive.push(connection.id);
+                continue;
+            }
+            connection.outstanding_pings.fetch_add(1, Ordering::Relaxed);
+            let _ = connection.sender.send(Message::Ping(Vec::new()));
+        }
+
+        for connection_id in unresponsive {
+            tracing::warn!("Closing connection {} after {} missed pongs", connection_id, MAX_MISSED_PONGS);
+            self.remove_connection(connection_id).await;
+        }
+    }
+
     pub async fn cleanup_stale_connections(&self) {
+        self.stats.sample_throughput(THROUGHPUT_WINDOW);
         let now = Instant::now();
+        self.resumable_sessions.retain(|_, session| session.expires_at > now);
         let timeout_threshold = self.health_monitor.timeout_threshold;
 
         let stale_connections: Vec<Uuid> = self
@@ -640,6 +1516,18 @@ impl ConnectionPool {
         }
     }
 
+    pub fn pending_request_count(&self) -> usize {
+        self.message_router.pending_count()
+    }
+
+    pub fn late_response_count(&self) -> u64 {
+        self.message_router.late_response_count()
+    }
+
+    pub fn reset_late_response_count(&self) {
+        self.message_router.reset_late_response_count();
+    }
+
     pub fn get_stats(&self) -> ConnectionStats {
         ConnectionStats {
             total_connections: std::sync::atomic::AtomicU64::new(
@@ -667,8 +1555,83 @@ impl ConnectionPool {
                     .connection_errors
                     .load(std::sync::atomic::Ordering::Relaxed),
             ),
+            last_error: self.stats.last_error.clone(),
+            bytes_uncompressed: std::sync::atomic::AtomicU64::new(
+                self.stats
+                    .bytes_uncompressed
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            ),
+            bytes_sent: std::sync::atomic::AtomicU64::new(
+                self.stats
+                    .bytes_sent
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            ),
+            throughput_samples: self.stats.throughput_samples.clone(),
+            messages_per_second: self.stats.messages_per_second.clone(),
+        }
+    }
+}
+
+/// JSON-RPC requests and notifications carry `jsonrpc` and `method`; extension
+/// messages never do.
+fn is_mcp_message(message: &Message) -> bool {
+    let Message::Text(text) = message else {
+        return false;
+    };
+    text.contains("\"jsonrpc\"")
+        && serde_json::from_str::<serde_json::Value>(text)
+            .is_ok_and(|value| value.get("jsonrpc").is_some() && value.get("method").is_some())
+}
+
+/// Answer a frame from an MCP client socket. Returns false once the client closes.
+fn handle_mcp_message(message: Message, handler: &McpMessageHandler, outbound: &OutboundQueue) -> bool {
+    let text = match message {
+        Message::Text(text) => text,
+        Message::Close(_) => return false,
+        _ => return true,
+    };
+    let request = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": "Parse error", "data": e.to_string() }
+            });
+            if let Err(e) = outbound.send_critical(Message::Text(error.to_string())) {
+                tracing::warn!("Failed to queue JSON-RPC parse error: {}", e);
+            }
+            return true;
+        }
+    };
+
+    // Answered concurrently so a slow tool call doesn't hold up a cancellation behind it
+    let handler = handler.clone();
+    let outbound = outbound.clone();
+    tokio::spawn(async move {
+        // A client is waiting on every reply, so pings and broadcasts give way to it
+        if let Some(reply) = handler(request).await {
+            if let Err(e) = outbound.send_critical(Message::Text(reply.to_string())) {
+                tracing::warn!("Failed to queue JSON-RPC reply: {}", e);
+            }
+        }
+    });
+    true
+}
+
+/// Whether a WebSocket read error was caused by a frame exceeding the configured
+/// `max_message_size`/`max_frame_size`. axum wraps the tungstenite error, so match on
+/// the capacity error text anywhere in the source chain.
+fn is_message_too_large(error: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(err) = source {
+        let text = err.to_string();
+        if text.contains("Space limit exceeded") || text.contains("too long") || text.contains("too big") {
+            return true;
         }
+        source = err.source();
     }
+    false
 }
 
 impl HealthMonitor {
@@ -693,48 +1656,952 @@ impl HealthMonitor {
     }
 }
 
+/// Removes a pending request when its caller stops waiting for it. If the request was
+/// already dispatched and never answered, the browser is sent a `cancelRequest`.
+struct PendingRequestGuard {
+    pending_requests: Arc<DashMap<Uuid, PendingRequest>>,
+    request_id: Uuid,
+    browser: Option<(OutboundQueue, WireFormat)>,
+}
+
+impl Drop for PendingRequestGuard {
+    fn drop(&mut self) {
+        if self.pending_requests.remove(&self.request_id).is_none() {
+            return;
+        }
+        if let Some((browser, format)) = &self.browser {
+            tracing::debug!("Cancelling browser request {}", self.request_id);
+            let cancel = serde_json::json!({ "action": "cancelRequest", "requestId": self.request_id.to_string() });
+            if let Ok(message) = encode_message(*format, &cancel) {
+                let _ = browser.send(message);
+            }
+        }
+    }
+}
+
+/// Releases a half-open breaker probe if its request future is dropped before the
+/// outcome is recorded. A no-op once `record_success`/`record_failure` has run.
+struct ProbeGuard<'a> {
+    breaker: &'a CircuitBreaker,
+    tab_id: u32,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        self.breaker.release_probe(self.tab_id);
+    }
+}
+
 impl MessageRouter {
     pub fn new(request_timeout: Duration) -> Self {
         Self {
             pending_requests: Arc::new(DashMap::new()),
             request_timeout,
+            sweeper_started: std::sync::Once::new(),
+            timed_out: parking_lot::Mutex::new(VecDeque::with_capacity(TIMED_OUT_HISTORY)),
+            late_responses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_timeout(&self, request_id: Uuid) {
+        let mut timed_out = self.timed_out.lock();
+        if timed_out.len() == TIMED_OUT_HISTORY {
+            timed_out.pop_front();
         }
+        timed_out.push_back((request_id, Instant::now()));
     }
 
     pub async fn register_pending_request(
         &self,
         request_id: Uuid,
         sender: oneshot::Sender<BrowserResponse>,
+        timeout: Duration,
     ) {
-        self.pending_requests.insert(request_id, sender);
+        // Expire it never before the request's own timeout
+        let deadline = tokio::time::Instant::now() + timeout.max(self.request_timeout);
+        self.pending_requests.insert(request_id, PendingRequest {
+            sender,
+            deadline,
+            connection_id: None,
+        });
 
-        // Set up timeout cleanup
-        let pending_requests = self.pending_requests.clone();
-        let timeout = self.request_timeout;
-        tokio::spawn(async move {
-            tokio::time::sleep(timeout).await;
-            if let Some((_, sender)) = pending_requests.remove(&request_id) {
-                let _ = sender.send(BrowserResponse::Error {
-                    message: "Request timeout".to_string(),
-                });
-            }
+        // One sweeper per router, which stops once the router is dropped
+        self.sweeper_started.call_once(|| {
+            let pending_requests = Arc::downgrade(&self.pending_requests);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(PENDING_SWEEP_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                loop {
+                    interval.tick().await;
+                    let Some(pending_requests) = pending_requests.upgrade() else {
+                        break;
+                    };
+                    sweep_expired_requests(&pending_requests);
+                }
+            });
         });
     }
 
+    /// Resolve every pending request past its deadline with a timeout error, returning
+    /// how many were swept.
+    pub fn sweep_expired(&self) -> usize {
+        sweep_expired_requests(&self.pending_requests)
+    }
+
     pub async fn handle_response(
         &self,
         request_id: Uuid,
         result: std::result::Result<BrowserResponse, String>,
     ) -> Result<()> {
-        if let Some((_, sender)) = self.pending_requests.remove(&request_id) {
+        if let Some((_, pending)) = self.pending_requests.remove(&request_id) {
             let response = result.unwrap_or_else(|error| BrowserResponse::Error { message: error });
-            sender.send(response).map_err(|_| BrowserMcpError::ConnectionClosed)?;
+            pending.sender.send(response).map_err(|_| BrowserMcpError::ConnectionClosed)?;
+        } else {
+            let timed_out_at = {
+                let mut timed_out = self.timed_out.lock();
+                timed_out
+                    .iter()
+                    .position(|(id, _)| *id == request_id)
+                    .and_then(|index| timed_out.remove(index))
+                    .map(|(_, at)| at)
+            };
+            if let Some(timed_out_at) = timed_out_at {
+                self.late_responses.fetch_add(1, Ordering::Relaxed);
+                metrics::increment_counter!("browser_mcp_late_responses_total");
+                tracing::debug!(
+                    request_id = %request_id,
+                    late_by_ms = timed_out_at.elapsed().as_millis() as u64,
+                    "Dropping response that arrived after its request timed out"
+                );
+            }
         }
         Ok(())
     }
 
-    pub async fn cleanup_connection(&self, _connection_id: Uuid) {
-        // Clean up any pending requests for this connection if needed
-        // For now, we let them timeout naturally
+    pub fn pending_count(&self) -> usize {
+        self.pending_requests.len()
+    }
+
+    /// Responses that arrived after their request had already timed out.
+    pub fn late_response_count(&self) -> u64 {
+        self.late_responses.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_late_response_count(&self) {
+        self.late_responses.store(0, Ordering::Relaxed);
+    }
+
+    /// Record which browser connection a pending request was queued on.
+    pub fn assign_connection(&self, request_id: Uuid, connection_id: Uuid) {
+        if let Some(mut pending) = self.pending_requests.get_mut(&request_id) {
+            pending.connection_id = Some(connection_id);
+        }
+    }
+
+    /// Fail every request still waiting on a closed connection. Its reply can no longer
+    /// arrive (and a full outbound queue may have discarded the request unsent), so
+    /// dropping the response channel surfaces `ConnectionClosed` instead of a timeout.
+    pub async fn cleanup_connection(&self, connection_id: Uuid) {
+        let before = self.pending_requests.len();
+        self.pending_requests
+            .retain(|_, pending| pending.connection_id != Some(connection_id));
+        let failed = before.saturating_sub(self.pending_requests.len());
+        if failed > 0 {
+            tracing::debug!("Failed {} pending requests on closed connection {}", failed, connection_id);
+        }
+    }
+}
+
+fn sweep_expired_requests(pending_requests: &DashMap<Uuid, PendingRequest>) -> usize {
+    let now = tokio::time::Instant::now();
+    let expired: Vec<Uuid> = pending_requests
+        .iter()
+        .filter(|entry| entry.deadline <= now)
+        .map(|entry| *entry.key())
+        .collect();
+    let mut swept = 0;
+    for request_id in expired {
+        // Re-check under the removal, a response may have raced in
+        if let Some((_, pending)) = pending_requests.remove_if(&request_id, |_, pending| pending.deadline <= now) {
+            tracing::debug!("Sweeping pending request {} past its deadline", request_id);
+            let _ = pending.sender.send(BrowserResponse::Error {
+                message: "Request timeout".to_string(),
+            });
+            swept += 1;
+        }
+    }
+    swept
+}
+
+#[cfg(test)]
+impl ConnectionPool {
+    /// Register an in-memory browser connection for tests. Every request the pool sends
+    /// to it is passed to `respond` (as the JSON the extension would receive), and the
+    /// returned value resolves the pending request like a real extension response.
+    pub(crate) fn attach_mock_browser<F, Fut>(&self, tab_id: Option<u32>, respond: F) -> Uuid
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = std::result::Result<serde_json::Value, String>> + Send + 'static,
+    {
+        let outbound = OutboundQueue::new(self.outbound_queue_size, self.outbound_policy);
+        let connection_id = Uuid::new_v4();
+        self.connections.insert(
+            connection_id,
+            WebSocketConnection {
+                id: connection_id,
+                sender: outbound.clone(),
+                wire_format: WireFormat::Json,
+                protocol: None,
+                tab_id,
+                label: None,
+                resume_token: None,
+                connected_at: Instant::now(),
+                last_activity: Arc::new(RwLock::new(Instant::now())),
+                outstanding_pings: Arc::new(AtomicU64::new(0)),
+                remote_addr: None,
+            },
+        );
+
+        let router = self.message_router.clone();
+        let respond = Arc::new(respond);
+        tokio::spawn(async move {
+            while let Some(msg) = outbound.recv().await {
+                let Message::Text(text) = msg else { continue };
+                let Ok(request) = serde_json::from_str::<serde_json::Value>(&text) else { continue };
+                let Some(request_id) = request
+                    .get("requestId")
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| Uuid::parse_str(v).ok())
+                else {
+                    continue;
+                };
+
+                let router = router.clone();
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    let result = respond(request).await.map(BrowserResponse::RawJson);
+                    let _ = router.handle_response(request_id, result).await;
+                });
+            }
+        });
+
+        connection_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::BreakerStatus;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_serialize_per_tab_dispatches_in_order() {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_serialize_per_tab(true);
+
+        let in_flight = Arc::new(AtomicBool::new(false));
+        let overlapped = Arc::new(AtomicBool::new(false));
+        let dispatched = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        pool.attach_mock_browser(Some(1), {
+            let in_flight = in_flight.clone();
+            let overlapped = overlapped.clone();
+            let dispatched = dispatched.clone();
+            move |request| {
+                let in_flight = in_flight.clone();
+                let overlapped = overlapped.clone();
+                let dispatched = dispatched.clone();
+                async move {
+                    if in_flight.swap(true, Ordering::SeqCst) {
+                        overlapped.store(true, Ordering::SeqCst);
+                    }
+                    dispatched.lock().push(request["action"].as_str().unwrap_or_default().to_string());
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    in_flight.store(false, Ordering::SeqCst);
+                    Ok(serde_json::json!({ "ok": true }))
+                }
+            }
+        });
+
+        let (first, second) = tokio::join!(
+            pool.send_request(1, BrowserRequest::GetPageContent { include_metadata: false }),
+            pool.send_request(1, BrowserRequest::GetPerformanceMetrics),
+        );
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert!(!overlapped.load(Ordering::SeqCst), "requests to one tab overlapped");
+        assert_eq!(*dispatched.lock(), vec!["getPageContent", "getPerformanceMetrics"]);
+    }
+
+    #[test]
+    fn test_wait_for_network_idle_timeout_follows_timeout_ms() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        let request = BrowserRequest::WaitForNetworkIdle { idle_ms: 500, timeout_ms: 45_000, max_inflight: 1 };
+        assert_eq!(pool.timeout_for_request(&request, None), Duration::from_secs(47));
+        let request = BrowserRequest::ObserveMutations {
+            selector: None,
+            duration_ms: 15_000,
+            attributes: true,
+            child_list: true,
+            subtree: true,
+        };
+        assert_eq!(pool.timeout_for_request(&request, None), Duration::from_secs(17));
+        assert_eq!(
+            pool.timeout_for_request(&request, Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_per_tool_timeout_overrides_defaults() {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_request_timeouts(
+            Duration::from_secs(45),
+            HashMap::from([
+                ("get_performance_metrics".to_string(), Duration::from_secs(3)),
+                ("get_accessibility_tree".to_string(), Duration::from_secs(90)),
+            ]),
+        );
+
+        assert_eq!(pool.timeout_for_request(&BrowserRequest::GetPerformanceMetrics, None), Duration::from_secs(3));
+        assert_eq!(
+            pool.timeout_for_request(&BrowserRequest::GetAccessibilityTree { max_depth: None }, None),
+            Duration::from_secs(90)
+        );
+        // Built-in per-action default, then the global fallback
+        assert_eq!(
+            pool.timeout_for_request(&BrowserRequest::GetDomSnapshot { max_depth: 5, include_styles: false, pierce: false }, None),
+            Duration::from_secs(20)
+        );
+        assert_eq!(pool.timeout_for_request(&BrowserRequest::GetBrowserTabs, None), Duration::from_secs(45));
+        // An explicit per-call timeout still wins
+        assert_eq!(
+            pool.timeout_for_request(&BrowserRequest::GetPerformanceMetrics, Some(Duration::from_secs(8))),
+            Duration::from_secs(8)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_page_loaded_notification_invalidates_cached_responses() {
+        let mut cache = BrowserDataCache::new(1024, Duration::from_secs(60));
+        cache.set_response_cache(16, Duration::from_secs(60));
+        let cache = Arc::new(cache);
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_data_cache(cache.clone());
+
+        let key = |tab_id| crate::cache::ResponseCacheKey::new(tab_id, "get_page_content", &serde_json::json!({}));
+        cache.cache_response(key(4), serde_json::json!("old page"));
+        cache.cache_response(key(5), serde_json::json!("other tab"));
+
+        let notification = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "page_loaded", "tabId": 4, "url": "https://example.com" }
+        });
+        pool.process_flexible_message(Uuid::new_v4(), notification).await.unwrap();
+
+        assert!(cache.get_cached_response(&key(4)).is_none());
+        assert!(cache.get_cached_response(&key(5)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_debugger_detached_notification_clears_debugger_state() {
+        let cache = Arc::new(BrowserDataCache::new(1024, Duration::from_secs(60)));
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_data_cache(cache.clone());
+        cache.set_debugger_attached(4, true).await;
+        cache.set_execution_paused(4, true);
+
+        let notification = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "debugger_detached", "tabId": 4, "reason": "canceled_by_user" }
+        });
+        pool.process_flexible_message(Uuid::new_v4(), notification).await.unwrap();
+
+        assert!(!cache.is_debugger_attached(4).await);
+        assert!(!cache.is_execution_paused(4));
+    }
+
+    #[test]
+    fn test_compression_ratio_tracks_bytes_saved() {
+        let stats = ConnectionStats::default();
+        assert_eq!(stats.compression_ratio(), 1.0);
+
+        let plain = encode_message(WireFormat::Json, &serde_json::json!({ "action": "ping" })).unwrap();
+        let (uncompressed, sent) = plain.sizes();
+        stats.record_sent(uncompressed, sent);
+        assert_eq!(stats.compression_ratio(), 1.0);
+        assert_eq!(stats.bytes_saved(), 0);
+
+        // Repetitive numeric data packs much smaller than its JSON text
+        let payload = serde_json::json!({ "action": "replay", "samples": vec![1_000_000u32; 200] });
+        let packed = encode_message(WireFormat::Msgpack, &payload).unwrap();
+        let (uncompressed, sent) = packed.sizes();
+        assert_eq!(uncompressed, payload.to_string().len());
+        stats.record_sent(uncompressed, sent);
+
+        assert!(stats.compression_ratio() < 0.8, "{}", stats.compression_ratio());
+        assert_eq!(stats.bytes_saved(), (uncompressed - sent) as u64);
+        assert_eq!(stats.messages_sent.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sweeper_resolves_stale_pending_requests_with_timeout() {
+        let router = MessageRouter::new(Duration::from_millis(100));
+        let (stale_tx, stale_rx) = oneshot::channel();
+        let (fresh_tx, mut fresh_rx) = oneshot::channel();
+        router.register_pending_request(Uuid::new_v4(), stale_tx, Duration::from_millis(100)).await;
+        router.register_pending_request(Uuid::new_v4(), fresh_tx, Duration::from_secs(60)).await;
+        assert_eq!(router.sweep_expired(), 0);
+
+        // The periodic sweeper picks it up without anything waiting on the request
+        let response = tokio::time::timeout(Duration::from_secs(5), stale_rx).await.unwrap().unwrap();
+        let BrowserResponse::Error { message } = response else {
+            panic!("expected a timeout error, got {:?}", response);
+        };
+        assert_eq!(message, "Request timeout");
+        assert_eq!(router.pending_count(), 1);
+        assert!(fresh_rx.try_recv().is_err());
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        router.sweep_expired();
+        assert!(matches!(fresh_rx.await, Ok(BrowserResponse::Error { .. })));
+        assert_eq!(router.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_closing_a_connection_fails_its_pending_requests() {
+        let router = MessageRouter::new(Duration::from_secs(30));
+        let (closed_id, other_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let (closed_tx, closed_rx) = oneshot::channel();
+        let (other_tx, mut other_rx) = oneshot::channel();
+        let (closed_request, other_request) = (Uuid::new_v4(), Uuid::new_v4());
+        router.register_pending_request(closed_request, closed_tx, Duration::from_secs(30)).await;
+        router.register_pending_request(other_request, other_tx, Duration::from_secs(30)).await;
+        router.assign_connection(closed_request, closed_id);
+        router.assign_connection(other_request, other_id);
+
+        router.cleanup_connection(closed_id).await;
+        assert!(closed_rx.await.is_err());
+        assert!(other_rx.try_recv().is_err());
+        assert_eq!(router.pending_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_connection_events_are_no_ops() {
+        let mut cache = BrowserDataCache::new(1024, Duration::from_secs(60));
+        cache.set_response_cache(16, Duration::from_secs(60));
+        let cache = Arc::new(cache);
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_data_cache(cache.clone());
+        let connection_id = pool.attach_mock_browser(None, |_| async { Ok(serde_json::Value::Null) });
+
+        let established = || BrowserEvent::ConnectionEstablished { tab_id: 5, label: None, resume_token: None };
+        pool.handle_browser_event(connection_id, established()).await.unwrap();
+        assert_eq!(pool.get_connections_for_tab(5).await, vec![connection_id]);
+        pool.handle_browser_event(connection_id, established()).await.unwrap();
+        assert_eq!(pool.get_connections_for_tab(5).await, vec![connection_id]);
+        assert!(!pool.associate_tab_with_connection(connection_id, 5).await);
+        assert!(pool.associate_tab_with_connection(connection_id, 6).await);
+
+        // A resent tab_updated with nothing new keeps cached responses
+        let key = crate::cache::ResponseCacheKey::new(5, "get_page_content", &serde_json::json!({}));
+        let updated = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "tab_updated", "tabId": 5, "url": "https://example.com", "title": "Shop" }
+        });
+        pool.process_flexible_message(connection_id, updated.clone()).await.unwrap();
+        cache.cache_response(key.clone(), serde_json::json!("page"));
+        pool.process_flexible_message(connection_id, updated.clone()).await.unwrap();
+        assert!(cache.get_cached_response(&key).is_some());
+
+        let mut retitled = updated;
+        retitled["event"]["title"] = serde_json::json!("Cart");
+        pool.process_flexible_message(connection_id, retitled).await.unwrap();
+        assert!(cache.get_cached_response(&key).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tab_state_is_forgotten_on_eviction_and_removal() {
+        let cache = Arc::new(BrowserDataCache::new(1024, Duration::ZERO));
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_data_cache(cache.clone());
+        pool.set_serialize_per_tab(true);
+        pool.set_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(60)));
+        let extension = pool.attach_mock_browser(None, |_| async { Ok(serde_json::Value::Null) });
+        let updated = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "tab_updated", "tabId": 5, "url": "https://example.com", "title": "Shop" }
+        });
+        pool.process_flexible_message(extension, updated.clone()).await.unwrap();
+        cache.add_console_message(5, crate::types::browser::ConsoleMessage {
+            level: "log".to_string(),
+            message: "hello".to_string(),
+            timestamp: chrono::Utc::now(),
+            source: None,
+            line_number: None,
+            column_number: None,
+            stack_trace: None,
+        }).await;
+
+        // Eviction drops the cached URL; the next identical update must restore it
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        for tab_id in cache.cleanup_stale_data().await {
+            pool.forget_tab(tab_id);
+        }
+        assert_eq!(cache.get_tab_url(5), None);
+        pool.process_flexible_message(extension, updated).await.unwrap();
+        assert_eq!(cache.get_tab_url(5).as_deref(), Some("https://example.com"));
+
+        // Requests leave a per-tab lock and, after a failure, breaker state behind
+        pool.send_request(5, BrowserRequest::GetPerformanceMetrics).await.unwrap();
+        pool.circuit_breaker.record_failure(5);
+        assert!(pool.tab_locks.contains_key(&5));
+        assert_eq!(pool.get_breaker_states().len(), 1);
+
+        let removed = serde_json::json!({ "type": "notification", "event": { "type": "tab_removed", "tabId": 5 } });
+        pool.process_flexible_message(extension, removed).await.unwrap();
+        assert_eq!(pool.tab_location(5), None);
+        assert_eq!(cache.get_tab_url(5), None);
+        assert!(!pool.tab_locks.contains_key(&5));
+        assert!(pool.get_breaker_states().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fast_fails_wedged_tab_then_recovers() {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_millis(50)));
+
+        let wedged = Arc::new(AtomicBool::new(true));
+        let dispatches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        pool.attach_mock_browser(Some(1), {
+            let wedged = wedged.clone();
+            let dispatches = dispatches.clone();
+            move |request| {
+                let wedged = wedged.load(Ordering::SeqCst);
+                if request["action"] != "cancelRequest" {
+                    dispatches.fetch_add(1, Ordering::SeqCst);
+                }
+                async move {
+                    if wedged {
+                        std::future::pending::<()>().await;
+                    }
+                    Ok(serde_json::json!({ "ok": true }))
+                }
+            }
+        });
+
+        let short = Some(Duration::from_millis(20));
+        for _ in 0..2 {
+            let result = pool
+                .send_request_with_timeout(Some(1), BrowserRequest::GetPerformanceMetrics, short)
+                .await;
+            assert!(matches!(result, Err(BrowserMcpError::RequestTimeout { .. })));
+        }
+
+        let result = pool
+            .send_request_with_timeout(Some(1), BrowserRequest::GetPerformanceMetrics, short)
+            .await;
+        assert!(matches!(result, Err(BrowserMcpError::ServiceUnavailable { .. })));
+        assert_eq!(dispatches.load(Ordering::SeqCst), 2, "open breaker still dispatched");
+        assert_eq!(pool.get_breaker_states()[0].status, BreakerStatus::Open);
+
+        wedged.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        let result = pool
+            .send_request_with_timeout(Some(1), BrowserRequest::GetPerformanceMetrics, short)
+            .await;
+        assert!(result.is_ok());
+        assert!(pool.get_breaker_states().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_requests_are_retried_with_growing_backoff() {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_request_retries(3, Duration::from_secs(1));
+
+        let sent_at = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        pool.attach_mock_browser(Some(1), {
+            let sent_at = sent_at.clone();
+            move |request| {
+                let attempt = if request["action"] == "cancelRequest" {
+                    None
+                } else {
+                    let mut sent_at = sent_at.lock();
+                    sent_at.push(Instant::now());
+                    Some(sent_at.len())
+                };
+                async move {
+                    // Slow for the first three attempts, then answers
+                    if attempt.is_none_or(|n| n < 4) {
+                        std::future::pending::<()>().await;
+                    }
+                    Ok(serde_json::json!({ "ok": true }))
+                }
+            }
+        });
+
+        let timeout = Duration::from_millis(20);
+        let result = pool
+            .send_request_with_timeout(Some(1), BrowserRequest::GetPerformanceMetrics, Some(timeout))
+            .await;
+        assert!(result.is_ok());
+
+        // Gaps are the timeout plus backoffs of 25-50ms, 50-100ms and 100-200ms
+        let sent_at = sent_at.lock();
+        assert_eq!(sent_at.len(), 4);
+        let gaps: Vec<Duration> = sent_at.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        for (retry, gap) in gaps.iter().enumerate() {
+            let ceiling = crate::transport::INITIAL_RETRY_BACKOFF * 2u32.pow(retry as u32);
+            assert!(*gap >= timeout + ceiling / 2, "retry {} came after only {:?}", retry + 1, gap);
+        }
+        assert!(gaps[2] > gaps[0]);
+    }
+
+    #[tokio::test]
+    async fn test_late_response_after_timeout_is_counted() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.attach_mock_browser(Some(1), |request| async move {
+            if request["action"] == "cancelRequest" {
+                std::future::pending::<()>().await;
+            }
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            Ok(serde_json::json!({ "ok": true }))
+        });
+
+        let result = pool
+            .send_request_with_timeout(Some(1), BrowserRequest::GetPerformanceMetrics, Some(Duration::from_millis(20)))
+            .await;
+        assert!(matches!(result, Err(BrowserMcpError::RequestTimeout { .. })));
+        assert_eq!(pool.late_response_count(), 0);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(pool.late_response_count(), 1);
+        assert_eq!(pool.pending_request_count(), 0);
+
+        // Responses to requests that never timed out are not counted
+        pool.message_router
+            .handle_response(Uuid::new_v4(), Ok(BrowserResponse::RawJson(serde_json::json!({}))))
+            .await
+            .unwrap();
+        assert_eq!(pool.late_response_count(), 1);
+
+        pool.reset_late_response_count();
+        assert_eq!(pool.late_response_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_binary_screenshot_frame_resolves_pending_request() {
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300)));
+        let (id_tx, mut id_rx) = tokio::sync::mpsc::unbounded_channel();
+        let connection_id = pool.attach_mock_browser(Some(2), move |request| {
+            let _ = id_tx.send(request["requestId"].as_str().unwrap_or_default().to_string());
+            async move {
+                // The answer arrives as a binary frame instead
+                std::future::pending::<()>().await;
+                Ok(serde_json::Value::Null)
+            }
+        });
+
+        let pending = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                let request = BrowserRequest::CaptureScreenshot {
+                    format: "png".to_string(),
+                    quality: None,
+                    clip: None,
+                    capture_selector: None,
+                };
+                pool.send_request(2, request).await
+            }
+        });
+        let request_id = id_rx.recv().await.unwrap();
+
+        let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let frame = crate::transport::encode_binary_frame(
+            &serde_json::json!({ "requestId": request_id, "type": "screenshot", "format": "png", "width": 800, "height": 600 }),
+            &png,
+        );
+        pool.handle_message(connection_id, Message::Binary(frame)).await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(1), pending).await.unwrap().unwrap().unwrap();
+        let BrowserResponse::Screenshot(shot) = response else {
+            panic!("expected a screenshot response, got {:?}", response);
+        };
+        assert_eq!(shot.data, png);
+        assert_eq!((shot.width, shot.height), (800, 600));
+        assert_eq!(pool.pending_request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_mcp_replies_are_not_dropped_for_best_effort_frames() {
+        let outbound = OutboundQueue::new(1, OutboundOverflowPolicy::DropOldest);
+        let handler: McpMessageHandler = Arc::new(|request: serde_json::Value| {
+            Box::pin(async move { Some(serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": {} })) })
+        });
+
+        let request = serde_json::json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/list" });
+        assert!(handle_mcp_message(Message::Text(request.to_string()), &handler, &outbound));
+        while outbound.is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        // A full queue drops the pong rather than the reply
+        outbound.send(Message::Pong(Vec::new())).unwrap();
+        let Some(Message::Text(reply)) = outbound.recv().await else {
+            panic!("expected the JSON-RPC reply");
+        };
+        assert!(reply.contains("\"id\":7"), "{}", reply);
+        assert!(outbound.is_empty());
+    }
+
+    async fn round_trip_through_wire_format(offered: &[&str]) -> (Message, serde_json::Value) {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_wire_format(WireFormat::Msgpack);
+        let pool = Arc::new(pool);
+
+        let outbound = OutboundQueue::new(16, OutboundOverflowPolicy::DropOldest);
+        let connection_id = Uuid::new_v4();
+        pool.connections.insert(
+            connection_id,
+            WebSocketConnection {
+                id: connection_id,
+                sender: outbound.clone(),
+                wire_format: WireFormat::Json,
+                protocol: None,
+                tab_id: None,
+                label: None,
+                resume_token: None,
+                connected_at: Instant::now(),
+                last_activity: Arc::new(RwLock::new(Instant::now())),
+                outstanding_pings: Arc::new(AtomicU64::new(0)),
+                remote_addr: None,
+            },
+        );
+
+        let handshake = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "connection_established", "wireFormats": offered },
+        });
+        pool.handle_message(connection_id, Message::Text(handshake.to_string())).await.unwrap();
+        let Some(Message::Text(reply)) = outbound.recv().await else {
+            panic!("expected a JSON wire_format reply");
+        };
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["type"], "wire_format");
+        let format: WireFormat = serde_json::from_value(reply["format"].clone()).unwrap();
+        let Some(Message::Text(reply)) = outbound.recv().await else {
+            panic!("expected a JSON resume_token reply");
+        };
+        assert!(reply.contains("\"resume_token\""));
+
+        let pending = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.send_request_any(BrowserRequest::GetPerformanceMetrics).await }
+        });
+        let request_frame = outbound.recv().await.unwrap();
+        let request = match (&request_frame, format) {
+            (Message::Text(text), WireFormat::Json) => serde_json::from_str::<serde_json::Value>(text).unwrap(),
+            (Message::Binary(frame), WireFormat::Msgpack) => decode_msgpack(frame).unwrap(),
+            (other, _) => panic!("request sent as {:?} with {:?} negotiated", other, format),
+        };
+        assert_eq!(request["action"], "getPerformanceMetrics");
+
+        let response = serde_json::json!({ "type": "response", "requestId": request["requestId"], "data": { "ok": true } });
+        pool.handle_message(connection_id, encode_message(format, &response).unwrap().message).await.unwrap();
+
+        let BrowserResponse::RawJson(data) = tokio::time::timeout(Duration::from_secs(1), pending).await.unwrap().unwrap().unwrap() else {
+            panic!("expected a raw JSON response");
+        };
+        (request_frame, data)
+    }
+
+    #[tokio::test]
+    async fn test_wire_format_round_trip() {
+        let (request, data) = round_trip_through_wire_format(&["msgpack", "json"]).await;
+        assert!(matches!(request, Message::Binary(_)));
+        assert_eq!(data, serde_json::json!({ "ok": true }));
+
+        // Extensions that only speak JSON stay on JSON
+        let (request, data) = round_trip_through_wire_format(&["json"]).await;
+        assert!(matches!(request, Message::Text(_)));
+        assert_eq!(data, serde_json::json!({ "ok": true }));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_response_type_fails_pending_request() {
+        let pool = Arc::new(ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300)));
+        let (id_tx, mut id_rx) = tokio::sync::mpsc::unbounded_channel();
+        let connection_id = pool.attach_mock_browser(Some(4), move |request| {
+            let _ = id_tx.send(request["requestId"].as_str().unwrap_or_default().to_string());
+            async move {
+                // The answer arrives as a typed frame instead
+                std::future::pending::<()>().await;
+                Ok(serde_json::Value::Null)
+            }
+        });
+
+        let pending = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.send_request(4, BrowserRequest::GetPerformanceMetrics).await }
+        });
+        let request_id = id_rx.recv().await.unwrap();
+
+        let frame = serde_json::json!({
+            "type": "response",
+            "request_id": request_id,
+            "result": { "Ok": { "type": "hologram", "data": { "frames": 3 } } }
+        });
+        pool.handle_message(connection_id, Message::Text(frame.to_string())).await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(1), pending).await.unwrap().unwrap().unwrap();
+        let BrowserResponse::Error { message } = response else {
+            panic!("expected an error response, got {:?}", response);
+        };
+        assert!(message.contains("does not understand"), "{}", message);
+        assert_eq!(pool.pending_request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tab_label_resolves_to_labelled_connection() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.attach_mock_browser(Some(3), |_| async { Ok(serde_json::json!({ "from": 3 })) });
+        let labelled = pool.attach_mock_browser(None, |_| async { Ok(serde_json::json!({ "from": 5 })) });
+        assert_eq!(pool.resolve_tab_label("checkout-page"), None);
+
+        let established = serde_json::json!({
+            "type": "notification",
+            "event": { "type": "connection_established", "tabId": 5, "label": "checkout-page" }
+        });
+        pool.handle_message(labelled, Message::Text(established.to_string())).await.unwrap();
+
+        let tab_id = pool.resolve_tab_label("checkout-page").unwrap();
+        assert_eq!(tab_id, 5);
+        assert_eq!(pool.resolve_tab_label("cart"), None);
+        let BrowserResponse::RawJson(data) = pool.send_request(tab_id, BrowserRequest::GetPerformanceMetrics).await.unwrap() else {
+            panic!("expected a raw response");
+        };
+        assert_eq!(data["from"], 5);
+        assert_eq!(pool.describe_connections()[1]["label"], "checkout-page");
+    }
+
+    #[tokio::test]
+    async fn test_tab_label_stays_on_its_tab_when_other_tabs_report() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        let extension = pool.attach_mock_browser(None, |_| async { Ok(serde_json::Value::Null) });
+        let notify = |event: serde_json::Value| serde_json::json!({ "type": "notification", "event": event });
+
+        // The extension connects with a label before any tab has reported in
+        pool.process_flexible_message(extension, notify(serde_json::json!({ "type": "connection_established", "label": "checkout" })))
+            .await
+            .unwrap();
+        assert_eq!(pool.resolve_tab_label("checkout"), None);
+
+        for tab_id in [5, 9] {
+            let update = serde_json::json!({ "type": "tab_updated", "tabId": tab_id, "url": "https://example.com", "title": "" });
+            pool.process_flexible_message(extension, notify(update)).await.unwrap();
+        }
+        assert_eq!(pool.resolve_tab_label("checkout"), Some(5));
+
+        pool.handle_browser_event(extension, BrowserEvent::TabRemoved { tab_id: 5 }).await.unwrap();
+        assert_eq!(pool.resolve_tab_label("checkout"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resume_token_restores_tab_association_on_reconnect() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        let established = |resume_token: Option<String>| {
+            serde_json::json!({
+                "type": "notification",
+                "event": { "type": "connection_established", "label": "checkout", "resumeToken": resume_token }
+            })
+        };
+
+        let first = pool.attach_mock_browser(Some(5), |_| async { Ok(serde_json::Value::Null) });
+        pool.process_flexible_message(first, established(None)).await.unwrap();
+        let token = pool.connections.get(&first).unwrap().resume_token.unwrap();
+        pool.remove_connection(first).await;
+
+        // The reconnected extension hasn't announced any tab yet
+        let second = pool.attach_mock_browser(None, |_| async { Ok(serde_json::json!("resumed")) });
+        pool.process_flexible_message(second, established(Some(token.to_string()))).await.unwrap();
+        assert_eq!(pool.get_connections_for_tab(5).await, vec![second]);
+        assert_eq!(pool.resolve_tab_label("checkout"), Some(5));
+        let response = pool.send_request(5, BrowserRequest::GetBrowserTabs).await.unwrap();
+        assert!(matches!(response, BrowserResponse::RawJson(value) if value == "resumed"));
+
+        // Each connection gets its own token, and a token works once
+        let reissued = pool.connections.get(&second).unwrap().resume_token.unwrap();
+        assert_ne!(reissued, token);
+        let third = pool.attach_mock_browser(None, |_| async { Ok(serde_json::Value::Null) });
+        assert!(!pool.resume_session(third, &token.to_string()));
+        assert!(!pool.resume_session(third, "not-a-token"));
+        assert_eq!(pool.connections.get(&third).unwrap().tab_id, None);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_tab_rate_limit_leaves_other_tabs_alone() {
+        let mut pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.set_tab_rate_limit(3);
+        pool.attach_mock_browser(Some(1), |_| async { Ok(serde_json::json!({ "from": 1 })) });
+        pool.attach_mock_browser(Some(2), |_| async { Ok(serde_json::json!({ "from": 2 })) });
+
+        for _ in 0..3 {
+            pool.send_request(1, BrowserRequest::GetPerformanceMetrics).await.unwrap();
+        }
+        let refused = pool.send_request(1, BrowserRequest::GetPerformanceMetrics).await;
+        assert!(matches!(refused, Err(BrowserMcpError::RateLimitExceeded)));
+
+        let response = pool.send_request(2, BrowserRequest::GetPerformanceMetrics).await.unwrap();
+        assert!(matches!(response, BrowserResponse::RawJson(value) if value["from"] == 2));
+    }
+
+    #[tokio::test]
+    async fn test_missed_pongs_close_connection() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        // The mock browser ignores pings, like a peer that went away without a FIN
+        let silent = pool.attach_mock_browser(Some(1), |_| async { Ok(serde_json::Value::Null) });
+        let answering = pool.attach_mock_browser(Some(2), |_| async { Ok(serde_json::Value::Null) });
+        let silent_sender = pool.connections.get(&silent).unwrap().sender.clone();
+
+        for _ in 0..2 {
+            pool.send_heartbeats().await;
+            pool.handle_message(answering, Message::Pong(Vec::new())).await.unwrap();
+        }
+        assert_eq!(pool.connections.get(&silent).unwrap().outstanding_pings.load(Ordering::Relaxed), 2);
+        assert!(!silent_sender.is_closed());
+
+        // The next heartbeat closes it without waiting for the activity timeout
+        pool.send_heartbeats().await;
+        assert!(pool.connections.get(&silent).is_none());
+        assert!(silent_sender.is_closed());
+        assert_eq!(pool.get_connections_for_tab(1).await, Vec::<Uuid>::new());
+
+        assert_eq!(pool.connections.get(&answering).unwrap().outstanding_pings.load(Ordering::Relaxed), 1);
+        assert_eq!(pool.get_connections_for_tab(2).await, vec![answering]);
+    }
+
+    #[tokio::test]
+    async fn test_throughput_gauge_follows_recent_traffic() {
+        let pool = ConnectionPool::new(Duration::from_secs(30), Duration::from_secs(300));
+        pool.cleanup_stale_connections().await;
+        assert_eq!(pool.get_stats().messages_per_second(), 0.0);
+
+        for _ in 0..10 {
+            pool.stats.record_sent(100, 100);
+            pool.stats.messages_received.fetch_add(1, Ordering::Relaxed);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        pool.cleanup_stale_connections().await;
+        let rate = pool.get_stats().messages_per_second();
+        assert!(rate > 0.0, "{}", rate);
+        // 20 messages in a little over 50ms
+        assert!(rate <= 400.0, "{}", rate);
+
+        // Once the window has moved past the burst the rate falls back to zero
+        let quiet = pool.stats.sample_throughput(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(quiet > 0.0);
+        assert_eq!(pool.stats.sample_throughput(Duration::from_millis(10)), 0.0);
+    }
+}
@@ -1,7 +1,19 @@
+pub mod binary;
 pub mod browser;
+pub mod circuit_breaker;
 pub mod connection;
+pub mod outbound;
+pub mod protocol;
+pub mod rate_limit;
 pub mod request;
+pub mod wire;
 
+pub use binary::*;
 pub use browser::*;
+pub use circuit_breaker::*;
 pub use connection::*;
-pub use request::*;
\ No newline at end of file
+pub use outbound::*;
+pub use protocol::*;
+pub use rate_limit::*;
+pub use request::*;
+pub use wire::*;
\ No newline at end of file
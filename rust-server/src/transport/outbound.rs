@@ -0,0 +1,222 @@
+use super::wire::EncodedMessage;
+use crate::config::OutboundOverflowPolicy;
+use crate::types::errors::*;
+use axum::extract::ws::{close_code, CloseFrame, Message};
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Notify;
+
+/// Bounded queue of messages waiting to be written to one browser connection.
+///
+/// Critical messages (requests that a caller is waiting on, close frames) are never
+/// dropped: if there is no room for one the send fails so the caller sees an error.
+/// Everything else (pongs, broadcasts, cancellations) is best effort and is what the
+/// `DropOldest` policy discards when the browser falls behind.
+#[derive(Clone)]
+pub struct OutboundQueue {
+    inner: Arc<QueueInner>,
+}
+
+struct QueueInner {
+    messages: Mutex<VecDeque<QueuedMessage>>,
+    notify: Notify,
+    capacity: usize,
+    policy: OutboundOverflowPolicy,
+    closed: AtomicBool,
+    dropped: AtomicU64,
+}
+
+struct QueuedMessage {
+    message: EncodedMessage,
+    critical: bool,
+}
+
+impl OutboundQueue {
+    pub fn new(capacity: usize, policy: OutboundOverflowPolicy) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                messages: Mutex::new(VecDeque::new()),
+                notify: Notify::new(),
+                capacity: capacity.max(1),
+                policy,
+                closed: AtomicBool::new(false),
+                dropped: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Queue a best-effort message.
+    pub fn send(&self, message: impl Into<EncodedMessage>) -> Result<()> {
+        self.push(message.into(), false)
+    }
+
+    /// Queue a message that must reach the browser or fail loudly.
+    pub fn send_critical(&self, message: impl Into<EncodedMessage>) -> Result<()> {
+        self.push(message.into(), true)
+    }
+
+    fn push(&self, message: EncodedMessage, critical: bool) -> Result<()> {
+        if self.is_closed() {
+            return Err(BrowserMcpError::ConnectionClosed);
+        }
+
+        let mut messages = self.inner.messages.lock();
+        if messages.len() >= self.inner.capacity {
+            match self.inner.policy {
+                OutboundOverflowPolicy::Close => {
+                    tracing::warn!(
+                        "Outbound queue full ({} messages), closing slow browser connection",
+                        self.inner.capacity
+                    );
+                    // Skip the backlog and tell the browser why it is being dropped. Requests
+                    // in it are failed when the pool removes the closed connection.
+                    messages.clear();
+                    messages.push_back(QueuedMessage {
+                        message: Message::Close(Some(CloseFrame {
+                            code: close_code::AGAIN,
+                            reason: "Outbound queue full".into(),
+                        }))
+                        .into(),
+                        critical: true,
+                    });
+                    drop(messages);
+                    self.close();
+                    return Err(BrowserMcpError::ConnectionClosed);
+                }
+                OutboundOverflowPolicy::DropOldest => {
+                    match messages.iter().position(|queued| !queued.critical) {
+                        Some(oldest) => {
+                            messages.remove(oldest);
+                        }
+                        None if critical => {
+                            return Err(BrowserMcpError::ServiceUnavailable {
+                                message: format!(
+                                    "Outbound queue full with {} pending requests",
+                                    self.inner.capacity
+                                ),
+                            });
+                        }
+                        // Nothing older can give way, so the new message is the one dropped
+                        None => {
+                            self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+                            metrics::increment_counter!("browser_mcp_outbound_dropped_total");
+                            return Ok(());
+                        }
+                    }
+                    self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+                    metrics::increment_counter!("browser_mcp_outbound_dropped_total");
+                }
+            }
+        }
+
+        messages.push_back(QueuedMessage { message, critical });
+        drop(messages);
+        self.inner.notify.notify_one();
+        Ok(())
+    }
+
+    /// Next message to write, or `None` once the queue is closed and drained.
+    pub async fn recv(&self) -> Option<Message> {
+        self.recv_encoded().await.map(|encoded| encoded.message)
+    }
+
+    /// Like `recv`, keeping the JSON size recorded when the message was encoded.
+    pub async fn recv_encoded(&self) -> Option<EncodedMessage> {
+        loop {
+            if let Some(queued) = self.inner.messages.lock().pop_front() {
+                return Some(queued.message);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            self.inner.notify.notified().await;
+        }
+    }
+
+    /// Stop accepting messages and wake the writer, which flushes what is already
+    /// queued and then shuts down.
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::Relaxed);
+        self.inner.notify.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.messages.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Best-effort messages discarded because the queue was full.
+    pub fn dropped_count(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(body: &str) -> Message {
+        Message::Text(body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_critical_messages() {
+        let queue = OutboundQueue::new(3, OutboundOverflowPolicy::DropOldest);
+        queue.send_critical(text("request-1")).unwrap();
+        queue.send(text("pong-1")).unwrap();
+        queue.send(text("pong-2")).unwrap();
+
+        // Full: the oldest best-effort message makes room
+        queue.send_critical(text("request-2")).unwrap();
+        queue.send(text("pong-3")).unwrap();
+        assert_eq!(queue.dropped_count(), 2);
+
+        let mut drained = Vec::new();
+        while !queue.is_empty() {
+            if let Some(Message::Text(body)) = queue.recv().await {
+                drained.push(body);
+            }
+        }
+        assert_eq!(drained, vec!["request-1", "request-2", "pong-3"]);
+
+        // With only requests queued, another request is refused rather than lost
+        for i in 0..3 {
+            queue.send_critical(text(&format!("request-{}", i))).unwrap();
+        }
+        assert!(matches!(
+            queue.send_critical(text("request-3")),
+            Err(BrowserMcpError::ServiceUnavailable { .. })
+        ));
+        queue.send(text("pong-4")).unwrap();
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.dropped_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_close_policy_closes_when_full() {
+        let queue = OutboundQueue::new(2, OutboundOverflowPolicy::Close);
+        queue.send(text("a")).unwrap();
+        queue.send_critical(text("b")).unwrap();
+
+        assert!(matches!(queue.send(text("c")), Err(BrowserMcpError::ConnectionClosed)));
+        assert!(queue.is_closed());
+        assert!(queue.send_critical(text("d")).is_err());
+
+        // Only the close frame is left to flush
+        assert!(matches!(queue.recv().await, Some(Message::Close(Some(frame))) if frame.code == close_code::AGAIN));
+        assert!(queue.recv().await.is_none());
+    }
+}
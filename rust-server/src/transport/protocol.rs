@@ -0,0 +1,106 @@
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderMap},
+};
+
+/// Protocol versions the server speaks over `/ws`, most preferred first.
+pub const SUPPORTED_PROTOCOLS: &[&str] = &["browser-mcp.v1"];
+
+/// Result of matching a client's `Sec-WebSocket-Protocol` offer against
+/// `SUPPORTED_PROTOCOLS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolNegotiation {
+    /// The client offered no subprotocol. Extensions predating versioning and MCP
+    /// clients connect this way, so they are still accepted.
+    Unversioned,
+    Agreed(&'static str),
+    /// Everything the client offered, none of which the server speaks.
+    Unsupported(Vec<String>),
+}
+
+impl ProtocolNegotiation {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let offered: Vec<String> = headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|protocol| protocol.trim().to_string())
+            .filter(|protocol| !protocol.is_empty())
+            .collect();
+        Self::from_offer(offered)
+    }
+
+    fn from_offer(offered: Vec<String>) -> Self {
+        if offered.is_empty() {
+            return Self::Unversioned;
+        }
+        SUPPORTED_PROTOCOLS
+            .iter()
+            .find(|supported| offered.iter().any(|protocol| protocol == *supported))
+            .map_or(Self::Unsupported(offered), |supported| Self::Agreed(supported))
+    }
+
+    /// The negotiated version, if any.
+    pub fn protocol(&self) -> Option<&'static str> {
+        match self {
+            Self::Agreed(protocol) => Some(protocol),
+            _ => None,
+        }
+    }
+
+    /// Apply the negotiated protocol to the upgrade response. An unsupported offer is
+    /// echoed back so the handshake completes and the client can read the close frame
+    /// from `reject_unsupported_protocol`; browsers fail the connection without a
+    /// reason otherwise.
+    pub fn apply(&self, upgrade: WebSocketUpgrade) -> WebSocketUpgrade {
+        match self {
+            Self::Unversioned => upgrade,
+            Self::Agreed(protocol) => upgrade.protocols([*protocol]),
+            Self::Unsupported(offered) => upgrade.protocols([offered[0].clone()]),
+        }
+    }
+}
+
+/// Close a connection whose client only offered protocol versions the server doesn't speak.
+pub async fn reject_unsupported_protocol(mut socket: WebSocket, offered: Vec<String>) {
+    tracing::warn!("Rejecting WebSocket connection offering unsupported protocol {:?}", offered);
+    let reason = format!(
+        "Unsupported protocol {}; server speaks {}",
+        offered.join(", "),
+        SUPPORTED_PROTOCOLS.join(", ")
+    );
+    // Close reasons are limited to 123 bytes
+    let reason = match reason.char_indices().nth(123) {
+        Some(_) => format!("Unsupported protocol; server speaks {}", SUPPORTED_PROTOCOLS.join(", ")),
+        None => reason,
+    };
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::PROTOCOL,
+            reason: reason.into(),
+        })))
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn offer(value: &str) -> ProtocolNegotiation {
+        let mut headers = HeaderMap::new();
+        headers.insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_str(value).unwrap());
+        ProtocolNegotiation::from_headers(&headers)
+    }
+
+    #[test]
+    fn test_negotiation_picks_supported_version_from_offer() {
+        assert_eq!(ProtocolNegotiation::from_headers(&HeaderMap::new()), ProtocolNegotiation::Unversioned);
+        assert_eq!(offer("browser-mcp.v2, browser-mcp.v1"), ProtocolNegotiation::Agreed("browser-mcp.v1"));
+        assert_eq!(
+            offer("browser-mcp.v2"),
+            ProtocolNegotiation::Unsupported(vec!["browser-mcp.v2".to_string()])
+        );
+    }
+}
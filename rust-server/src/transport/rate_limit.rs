@@ -0,0 +1,90 @@
+use crate::types::errors::*;
+use dashmap::DashMap;
+use std::time::Instant;
+
+/// Per-tab token bucket. Each tab's extension handles messages one at a time, so a
+/// burst aimed at one tab is refused here instead of piling up in its message loop,
+/// while requests to other tabs keep flowing.
+pub struct TabRateLimiter {
+    buckets: DashMap<u32, TokenBucket>,
+    requests_per_sec: u32,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TabRateLimiter {
+    /// A `requests_per_sec` of 0 disables the limit. Buckets hold one second's worth of
+    /// tokens, so a tab that has been idle can take that many requests at once.
+    pub fn new(requests_per_sec: u32) -> Self {
+        Self {
+            buckets: DashMap::new(),
+            requests_per_sec,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.requests_per_sec > 0
+    }
+
+    /// Take a token from `tab_id`'s bucket, or fail with `RateLimitExceeded` if it is empty.
+    pub fn try_acquire(&self, tab_id: u32) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let capacity = f64::from(self.requests_per_sec);
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry(tab_id).or_insert_with(|| TokenBucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            tracing::debug!("Tab {} exceeded {} requests/sec", tab_id, self.requests_per_sec);
+            return Err(BrowserMcpError::RateLimitExceeded);
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Forget a tab's bucket, e.g. once its connection is gone.
+    pub fn remove_tab(&self, tab_id: u32) {
+        self.buckets.remove(&tab_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let limiter = TabRateLimiter::new(20);
+        for _ in 0..20 {
+            assert!(limiter.try_acquire(1).is_ok());
+        }
+        assert!(matches!(limiter.try_acquire(1), Err(BrowserMcpError::RateLimitExceeded)));
+
+        // 20 per second is one token every 50ms
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+
+        let unlimited = TabRateLimiter::disabled();
+        for _ in 0..1000 {
+            assert!(unlimited.try_acquire(1).is_ok());
+        }
+    }
+}
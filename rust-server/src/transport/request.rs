@@ -1,7 +1,26 @@
-use crate::types::{errors::*, messages::*};
-use std::time::{Duration, Instant};
+use crate::types::{errors::*, mcp::ErrorCategoryStats, messages::*};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
+/// Delay before the first retry; each further retry doubles it up to the configured cap.
+pub const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// The MCP tool call a browser request is made for, so logs can tie the two together.
+#[derive(Debug, Clone)]
+pub struct ToolCallOrigin {
+    /// JSON-RPC `id` of the `tools/call`, as sent by the client.
+    pub rpc_id: String,
+    pub tool: String,
+}
+
+tokio::task_local! {
+    /// Set for the duration of a tool call; browser requests read it when they are sent.
+    pub static TOOL_CALL_ORIGIN: ToolCallOrigin;
+}
+
 #[derive(Debug)]
 pub struct RequestTracker {
     pub request_id: Uuid,
@@ -43,6 +62,18 @@ impl RequestTracker {
     pub fn elapsed(&self) -> Duration {
         self.start_time.elapsed()
     }
+
+    /// How long to wait before sending the current retry: exponential in `retry_count`,
+    /// capped at `max_backoff`, with the upper half jittered so retries from many
+    /// requests don't land on the browser together.
+    pub fn backoff_delay(&self, max_backoff: Duration) -> Duration {
+        let exponent = self.retry_count.saturating_sub(1).min(31) as u32;
+        let ceiling = INITIAL_RETRY_BACKOFF.saturating_mul(1 << exponent).min(max_backoff);
+        let half = ceiling / 2;
+        // The v4 request id is random, and retry() makes a new one per attempt
+        let jitter = (self.request_id.as_u128() as u64) % (half.as_micros() as u64 + 1);
+        half + Duration::from_micros(jitter)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,6 +83,8 @@ pub struct RequestMetrics {
     pub failed_requests: u64,
     pub timeout_requests: u64,
     pub retry_requests: u64,
+    /// Failed requests keyed by `BrowserMcpError::category`.
+    pub errors_by_category: HashMap<&'static str, u64>,
     pub average_response_time: Duration,
     pub max_response_time: Duration,
     pub min_response_time: Duration,
@@ -65,6 +98,7 @@ impl Default for RequestMetrics {
             failed_requests: 0,
             timeout_requests: 0,
             retry_requests: 0,
+            errors_by_category: HashMap::new(),
             average_response_time: Duration::ZERO,
             max_response_time: Duration::ZERO,
             min_response_time: Duration::MAX,
@@ -76,25 +110,61 @@ pub struct RequestHandler {
     metrics: parking_lot::RwLock<RequestMetrics>,
     response_times: parking_lot::RwLock<Vec<Duration>>,
     max_history: usize,
+    /// Start of each tool's current one-second window and the calls seen in it.
+    tool_rates: dashmap::DashMap<String, (Instant, u64)>,
+    sampling_threshold: u64,
+    sample_rate: u64,
 }
 
 impl RequestHandler {
     pub fn new(max_history: usize) -> Self {
+        Self::with_duration_sampling(max_history, u64::MAX, 1)
+    }
+
+    /// Once a tool makes more than `sampling_threshold` calls in a second, only one in
+    /// `sample_rate` of its durations goes into the response time history for the rest
+    /// of that second. Counts and min/max still see every call.
+    pub fn with_duration_sampling(max_history: usize, sampling_threshold: u64, sample_rate: u64) -> Self {
         Self {
             metrics: parking_lot::RwLock::new(RequestMetrics::default()),
             response_times: parking_lot::RwLock::new(Vec::new()),
             max_history,
+            tool_rates: dashmap::DashMap::new(),
+            sampling_threshold,
+            sample_rate: sample_rate.max(1),
         }
     }
 
     pub fn record_request_start(&self) -> Instant {
         let mut metrics = self.metrics.write();
         metrics.total_requests += 1;
+        metrics::increment_counter!("browser_mcp_requests_total");
         Instant::now()
     }
 
     pub fn record_request_success(&self, start_time: Instant) {
+        self.record_success(start_time.elapsed(), true);
+    }
+
+    /// Like `record_request_success`, sampling the duration if `tool` is busy.
+    pub fn record_tool_success(&self, tool: &str, start_time: Instant) {
         let duration = start_time.elapsed();
+        self.record_success(duration, self.should_record_duration(tool));
+    }
+
+    fn should_record_duration(&self, tool: &str) -> bool {
+        let now = Instant::now();
+        let mut rate = self.tool_rates.entry(tool.to_string()).or_insert((now, 0));
+        let (window_start, in_window) = &mut *rate;
+        if now.duration_since(*window_start) >= Duration::from_secs(1) {
+            *window_start = now;
+            *in_window = 0;
+        }
+        *in_window += 1;
+        *in_window <= self.sampling_threshold || *in_window % self.sample_rate == 0
+    }
+
+    fn record_success(&self, duration: Duration, keep_duration: bool) {
         let mut metrics = self.metrics.write();
         let mut response_times = self.response_times.write();
 
@@ -109,6 +179,9 @@ impl RequestHandler {
         }
 
         // Maintain response time history
+        if !keep_duration {
+            return;
+        }
         response_times.push(duration);
         if response_times.len() > self.max_history {
             response_times.remove(0);
@@ -121,16 +194,20 @@ impl RequestHandler {
         }
     }
 
-    pub fn record_request_failure(&self, _start_time: Instant, error: &BrowserMcpError) {
+    pub fn record_request_failure(&self, start_time: Instant, error: &BrowserMcpError) {
+        self.record_request_failure_category(start_time, error.category());
+    }
+
+    /// Like `record_request_failure`, for callers that only have the error's category.
+    pub fn record_request_failure_category(&self, _start_time: Instant, category: &'static str) {
         let mut metrics = self.metrics.write();
         metrics.failed_requests += 1;
-
-        match error {
-            BrowserMcpError::RequestTimeout { .. } => {
-                metrics.timeout_requests += 1;
-            }
-            _ => {}
+        *metrics.errors_by_category.entry(category).or_insert(0) += 1;
+        if category == "request_timeout" {
+            metrics.timeout_requests += 1;
         }
+        // Divide by browser_mcp_requests_total for the rate
+        metrics::increment_counter!("browser_mcp_request_errors_total", "category" => category);
     }
 
     pub fn record_request_retry(&self) {
@@ -160,9 +237,27 @@ impl RequestHandler {
         }
     }
 
+    /// Count and share of all requests for each category that has failed at least once.
+    pub fn get_error_breakdown(&self) -> BTreeMap<String, ErrorCategoryStats> {
+        let metrics = self.metrics.read();
+        metrics
+            .errors_by_category
+            .iter()
+            .map(|(category, &count)| {
+                let rate = if metrics.total_requests == 0 {
+                    0.0
+                } else {
+                    count as f64 / metrics.total_requests as f64
+                };
+                (category.to_string(), ErrorCategoryStats { count, rate })
+            })
+            .collect()
+    }
+
     pub fn reset_metrics(&self) {
         *self.metrics.write() = RequestMetrics::default();
         self.response_times.write().clear();
+        self.tool_rates.clear();
     }
 }
 
@@ -236,6 +331,35 @@ mod tests {
         assert!(!tracker.can_retry());
     }
 
+    #[test]
+    fn test_backoff_grows_with_jitter_within_bounds() {
+        let max_backoff = Duration::from_millis(300);
+        let mut tracker = RequestTracker::new(Duration::from_secs(5), 6);
+        let (mut previous_delay, mut previous_ceiling) = (Duration::ZERO, Duration::ZERO);
+
+        for attempt in 1..=6u32 {
+            assert!(tracker.retry());
+            let ceiling = (INITIAL_RETRY_BACKOFF * 2u32.pow(attempt - 1)).min(max_backoff);
+            let delay = tracker.backoff_delay(max_backoff);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "retry {}: {:?} outside {:?}", attempt, delay, ceiling);
+            // Until the cap, each window starts where the previous one ended
+            if ceiling == previous_ceiling * 2 {
+                assert!(delay >= previous_delay, "retry {} waited less than the one before", attempt);
+            }
+            (previous_delay, previous_ceiling) = (delay, ceiling);
+        }
+
+        // Jitter spreads the delay over the top half of the window
+        let delays: std::collections::HashSet<Duration> = (0..20)
+            .map(|_| {
+                let mut tracker = RequestTracker::new(Duration::from_secs(5), 1);
+                tracker.retry();
+                tracker.backoff_delay(max_backoff)
+            })
+            .collect();
+        assert!(delays.len() > 1);
+    }
+
     #[test]
     fn test_request_handler_metrics() {
         let handler = RequestHandler::new(100);
@@ -253,6 +377,67 @@ mod tests {
         assert_eq!(handler.get_error_rate(), 0.0);
     }
 
+    #[test]
+    fn test_tool_durations_are_sampled_above_rate_threshold() {
+        let handler = RequestHandler::with_duration_sampling(1000, 10, 5);
+        for _ in 0..60 {
+            let started = handler.record_request_start();
+            handler.record_tool_success("get_page_content", started);
+        }
+        let started = handler.record_request_start();
+        handler.record_tool_success("click_element", started);
+
+        // The first 10 in the window are all kept, then every 5th call; other tools
+        // have their own window
+        assert_eq!(handler.response_times.read().len(), 10 + 10 + 1);
+        assert_eq!(handler.get_metrics().successful_requests, 61);
+    }
+
+    #[test]
+    fn test_failures_are_broken_down_by_category() {
+        let handler = RequestHandler::new(100);
+        let failures = [
+            BrowserMcpError::RequestTimeout { timeout: Duration::from_secs(30) },
+            BrowserMcpError::RequestTimeout { timeout: Duration::from_secs(5) },
+            BrowserMcpError::ConnectionNotAvailable { tab_id: 4 },
+            BrowserMcpError::BrowserExtensionError { message: "No tab with id: 4".to_string() },
+            BrowserMcpError::InvalidParameters { message: "selector must not be empty".to_string() },
+        ];
+        for error in &failures {
+            let started = handler.record_request_start();
+            handler.record_request_failure(started, error);
+        }
+        for _ in 0..4 {
+            let started = handler.record_request_start();
+            handler.record_request_success(started);
+        }
+        // Tool call errors only carry a message
+        let started = handler.record_request_start();
+        handler.record_request_failure_category(
+            started,
+            BrowserMcpError::category_of_message("Failed to click element: Request timeout after 10s"),
+        );
+
+        let breakdown = handler.get_error_breakdown();
+        let stats = |category: &str| breakdown[category];
+        assert_eq!(breakdown.len(), 4);
+        assert_eq!(stats("request_timeout"), ErrorCategoryStats { count: 3, rate: 0.3 });
+        assert_eq!(stats("connection_not_available"), ErrorCategoryStats { count: 1, rate: 0.1 });
+        assert_eq!(stats("browser_extension_error").count, 1);
+        assert_eq!(stats("invalid_parameters").count, 1);
+        assert_eq!(handler.get_metrics().timeout_requests, 3);
+        assert_eq!(handler.get_error_rate(), 0.6);
+
+        assert_eq!(
+            BrowserMcpError::category_of_message("Failed to get page content: Connection not available for tab 2"),
+            "connection_not_available"
+        );
+        assert_eq!(BrowserMcpError::category_of_message("tabId is required for click_element"), "invalid_parameters");
+
+        handler.reset_metrics();
+        assert!(handler.get_error_breakdown().is_empty());
+    }
+
     #[test]
     fn test_batch_request() {
         let mut batch = BatchRequest::new(Duration::from_secs(30), 5);
@@ -260,7 +445,7 @@ mod tests {
         assert!(batch.is_empty());
 
         batch.add_request(1, BrowserRequest::GetPageContent { include_metadata: true });
-        batch.add_request(2, BrowserRequest::GetDomSnapshot { max_depth: 10, include_styles: false });
+        batch.add_request(2, BrowserRequest::GetDomSnapshot { max_depth: 10, include_styles: false, pierce: false });
 
         assert_eq!(batch.len(), 2);
         assert!(!batch.is_empty());
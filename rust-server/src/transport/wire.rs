@@ -0,0 +1,103 @@
+use crate::config::WireFormat;
+use crate::types::errors::*;
+use axum::extract::ws::Message;
+use serde::Serialize;
+
+/// An outbound frame along with the bytes its payload would take as plain JSON, so
+/// compression stats don't have to decode MessagePack frames again to measure them.
+#[derive(Debug)]
+pub struct EncodedMessage {
+    pub message: Message,
+    pub json_len: usize,
+}
+
+impl EncodedMessage {
+    /// Bytes the frame would take as plain JSON, and bytes it actually takes.
+    pub fn sizes(&self) -> (usize, usize) {
+        (self.json_len, frame_len(&self.message))
+    }
+}
+
+/// Frames built by hand (text replies, pings, close frames) count as their own size.
+impl From<Message> for EncodedMessage {
+    fn from(message: Message) -> Self {
+        let json_len = frame_len(&message);
+        Self { message, json_len }
+    }
+}
+
+/// Encode an outbound message for a connection. JSON goes out as text frames,
+/// MessagePack as binary frames.
+pub fn encode_message<T: Serialize>(format: WireFormat, value: &T) -> Result<EncodedMessage> {
+    match format {
+        WireFormat::Json => Ok(Message::Text(serde_json::to_string(value)?).into()),
+        WireFormat::Msgpack => {
+            let frame = rmp_serde::to_vec_named(value).map_err(|e| BrowserMcpError::JsonError {
+                message: format!("MessagePack encoding failed: {}", e),
+            })?;
+            // Count the JSON it replaces without building it
+            let mut json_len = ByteCounter(0);
+            serde_json::to_writer(&mut json_len, value)?;
+            Ok(EncodedMessage {
+                message: Message::Binary(frame),
+                json_len: json_len.0,
+            })
+        }
+    }
+}
+
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn decode_msgpack(frame: &[u8]) -> Result<serde_json::Value> {
+    rmp_serde::from_slice(frame).map_err(|e| BrowserMcpError::InvalidRequest {
+        message: format!("Invalid MessagePack: {}", e),
+    })
+}
+
+fn frame_len(message: &Message) -> usize {
+    match message {
+        Message::Text(text) => text.len(),
+        Message::Binary(frame) => frame.len(),
+        _ => 0,
+    }
+}
+
+/// Binary frames also carry raw screenshots, which start with a big-endian u16
+/// header length. Messages are always maps, and a MessagePack map starts with a
+/// byte no realistic header length does.
+pub fn is_msgpack_frame(frame: &[u8]) -> bool {
+    matches!(frame.first(), Some(0x80..=0x8f | 0xde | 0xdf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::encode_binary_frame;
+
+    #[test]
+    fn test_msgpack_frames_are_distinguished_from_screenshots() {
+        let value = serde_json::json!({ "type": "response", "requestId": "abc", "data": { "ok": true, "n": 3 } });
+        let encoded = encode_message(WireFormat::Msgpack, &value).unwrap();
+        assert_eq!(encoded.json_len, value.to_string().len());
+        let Message::Binary(frame) = encoded.message else {
+            panic!("expected a binary frame");
+        };
+        assert!(is_msgpack_frame(&frame));
+        assert_eq!(decode_msgpack(&frame).unwrap(), value);
+
+        let screenshot = encode_binary_frame(&serde_json::json!({ "requestId": "abc", "type": "screenshot" }), &[0x89]);
+        assert!(!is_msgpack_frame(&screenshot));
+        assert!(matches!(encode_message(WireFormat::Json, &value).unwrap().message, Message::Text(_)));
+    }
+}
@@ -1,21 +1,108 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 
-#[derive(Debug, Clone)]
+/// Everything cached for one tab. Each field is its own locked slot, so an update
+/// replaces just that field instead of copying the whole tab.
+#[derive(Debug)]
 pub struct TabData {
     pub tab_id: u32,
-    pub page_content: Option<Arc<PageContent>>,
-    pub dom_snapshot: Option<Arc<DomSnapshot>>,
-    pub console_logs: Option<Arc<parking_lot::RwLock<VecDeque<ConsoleMessage>>>>,
-    pub network_data: Option<Arc<parking_lot::RwLock<VecDeque<NetworkRequest>>>>,
-    pub performance_metrics: Option<Arc<PerformanceMetrics>>,
-    pub accessibility_tree: Option<Arc<AccessibilityTree>>,
-    pub screenshot_data: Option<Arc<ScreenshotData>>,
+    pub page_content: parking_lot::RwLock<Option<Arc<PageContent>>>,
+    pub dom_snapshot: parking_lot::RwLock<Option<Arc<DomSnapshot>>>,
+    pub console_logs: parking_lot::RwLock<VecDeque<ConsoleMessage>>,
+    pub network_data: parking_lot::RwLock<VecDeque<NetworkRequest>>,
+    pub performance_metrics: parking_lot::RwLock<Option<Arc<PerformanceMetrics>>>,
+    pub accessibility_tree: parking_lot::RwLock<Option<Arc<AccessibilityTree>>>,
+    pub screenshot_data: parking_lot::RwLock<Option<Arc<ScreenshotData>>>,
+    /// Tool calls made against the tab, oldest first; empty unless activity logging is on.
+    pub activity_log: parking_lot::RwLock<VecDeque<ToolInvocation>>,
+    pub debugger_attached: AtomicBool,
+    /// JavaScript is stopped through the debugger (`pause_execution`).
+    pub execution_paused: AtomicBool,
+    last_updated: parking_lot::Mutex<SystemTime>,
+}
+
+impl TabData {
+    pub fn new(tab_id: u32) -> Self {
+        Self {
+            tab_id,
+            page_content: parking_lot::RwLock::new(None),
+            dom_snapshot: parking_lot::RwLock::new(None),
+            console_logs: parking_lot::RwLock::new(VecDeque::new()),
+            network_data: parking_lot::RwLock::new(VecDeque::new()),
+            performance_metrics: parking_lot::RwLock::new(None),
+            accessibility_tree: parking_lot::RwLock::new(None),
+            screenshot_data: parking_lot::RwLock::new(None),
+            activity_log: parking_lot::RwLock::new(VecDeque::new()),
+            debugger_attached: AtomicBool::new(false),
+            execution_paused: AtomicBool::new(false),
+            last_updated: parking_lot::Mutex::new(SystemTime::now()),
+        }
+    }
+
+    pub fn last_updated(&self) -> SystemTime {
+        *self.last_updated.lock()
+    }
+
+    /// Mark the tab as just updated, which keeps it from being evicted as stale.
+    pub fn touch(&self) {
+        *self.last_updated.lock() = SystemTime::now();
+    }
+
+    pub fn is_debugger_attached(&self) -> bool {
+        self.debugger_attached.load(Ordering::Relaxed)
+    }
+
+    pub fn summary(&self) -> TabSummary {
+        TabSummary {
+            tab_id: self.tab_id,
+            has_page_content: self.page_content.read().is_some(),
+            has_dom_snapshot: self.dom_snapshot.read().is_some(),
+            console_count: self.console_logs.read().len(),
+            network_count: self.network_data.read().len(),
+            has_screenshot: self.screenshot_data.read().is_some(),
+            debugger_attached: self.is_debugger_attached(),
+            intercept_rules: Vec::new(),
+            extra_headers: Vec::new(),
+            last_updated: self.last_updated().into(),
+        }
+    }
+}
+
+/// One tool call made against a tab: the agent's actions, as opposed to the page's console.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolInvocation {
+    pub tool: String,
+    /// Names of the arguments the call was made with; their values are never kept.
+    pub arguments: Vec<String>,
+    pub timestamp: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which cached data a tab has, so callers can skip tools that have nothing to return.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabSummary {
+    pub tab_id: u32,
+    pub has_page_content: bool,
+    pub has_dom_snapshot: bool,
+    pub console_count: usize,
+    pub network_count: usize,
+    pub has_screenshot: bool,
     pub debugger_attached: bool,
-    pub last_updated: SystemTime,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intercept_rules: Vec<InterceptRule>,
+    /// Names of the headers the extension adds to every request the tab makes. Values
+    /// are left out, since they are usually credentials.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_headers: Vec<String>,
+    pub last_updated: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +116,30 @@ pub struct PageContent {
 }
 
 impl PageContent {
+    /// Build content from the extension's `getPageContent` reply or a content-script
+    /// `pageContent` notification. Missing fields are empty; metadata keeps the string values.
+    pub fn from_extension(data: &serde_json::Value) -> Self {
+        let field = |name: &str| data.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let metadata = data
+            .get("metadata")
+            .and_then(|v| v.as_object())
+            .map(|metadata| {
+                metadata
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            url: field("url"),
+            title: field("title"),
+            text: field("text"),
+            html: field("html"),
+            metadata,
+            last_updated: SystemTime::now(),
+        }
+    }
+
     pub fn is_fresh(&self, max_age: std::time::Duration) -> bool {
         SystemTime::now()
             .duration_since(self.last_updated)
@@ -47,14 +158,86 @@ pub struct DomSnapshot {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DomNode {
-    pub node_type: String,
-    pub tag_name: Option<String>,
+    // Node types, tag names, attribute names and CSS property names repeat across
+    // every snapshot, so they are interned by the cache
+    pub node_type: Arc<str>,
+    pub tag_name: Option<Arc<str>>,
     pub text_content: Option<String>,
-    pub attributes: HashMap<String, String>,
-    pub computed_styles: Option<HashMap<String, String>>,
+    pub attributes: HashMap<Arc<str>, String>,
+    pub computed_styles: Option<HashMap<Arc<str>, String>>,
     pub children: Vec<DomNode>,
     pub xpath: Option<String>,
     pub selector: Option<String>,
+    /// Set on the node standing in for an open shadow root; its children are the
+    /// shadow tree. Only present in snapshots taken with `pierce`.
+    #[serde(default, alias = "shadowRoot")]
+    pub shadow_root: bool,
+}
+
+impl DomSnapshot {
+    /// Build a snapshot from the extension's `getDOMSnapshot` reply, whose tree sits
+    /// under `structure` with camelCase node fields. `None` if it has no tree.
+    pub fn from_extension(data: &serde_json::Value, max_depth: usize, include_styles: bool) -> Option<Self> {
+        let root = data.get("structure").or_else(|| data.get("root"))?;
+        let root = dom_node_from_extension(root)?;
+        let node_count = data
+            .get("nodeCount")
+            .and_then(|v| v.as_u64())
+            .map_or_else(|| count_dom_nodes(&root), |count| count as usize);
+        Some(Self {
+            root,
+            node_count,
+            max_depth,
+            include_styles,
+            timestamp: SystemTime::now(),
+        })
+    }
+}
+
+fn dom_node_from_extension(node: &serde_json::Value) -> Option<DomNode> {
+    let node = node.as_object()?;
+    let string_map = |key: &str| -> Option<HashMap<Arc<str>, String>> {
+        let map = node.get(key)?.as_object()?;
+        Some(
+            map.iter()
+                .map(|(name, value)| {
+                    let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+                    (Arc::from(name.as_str()), value)
+                })
+                .collect(),
+        )
+    };
+    let text = |key: &str| node.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    Some(DomNode {
+        node_type: Arc::from(node.get("nodeType").and_then(|v| v.as_str()).unwrap_or("element")),
+        tag_name: node.get("tagName").and_then(|v| v.as_str()).map(Arc::from),
+        text_content: text("textContent"),
+        attributes: string_map("attributes").unwrap_or_default(),
+        computed_styles: string_map("computedStyles"),
+        children: node
+            .get("children")
+            .and_then(|v| v.as_array())
+            .map(|children| children.iter().filter_map(dom_node_from_extension).collect())
+            .unwrap_or_default(),
+        xpath: text("xpath"),
+        selector: text("selector"),
+        shadow_root: node.get("shadowRoot").and_then(|v| v.as_bool()).unwrap_or(false),
+    })
+}
+
+fn count_dom_nodes(node: &DomNode) -> usize {
+    1 + node.children.iter().map(count_dom_nodes).sum::<usize>()
+}
+
+/// Result of an XPath expression, shaped by the XPath result type. Matched nodes
+/// come back without children.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum XPathResult {
+    Nodes(Vec<DomNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +262,12 @@ pub struct NetworkRequest {
     pub response_headers: Option<HashMap<String, String>>,
     pub request_body: Option<String>,
     pub response_body: Option<String>,
+    /// Set when the cache cut a body down to `cache.max_retained_body_bytes`.
+    #[serde(default)]
+    pub body_truncated: bool,
+    /// Set when the request body in particular was cut, so it can't be replayed as sent.
+    #[serde(default)]
+    pub request_body_truncated: bool,
     pub timestamp: DateTime<Utc>,
     pub duration_ms: Option<f64>,
     pub failed: bool,
@@ -86,6 +275,25 @@ pub struct NetworkRequest {
     pub resource_type: String,
 }
 
+impl NetworkRequest {
+    /// Fold a later notification for the same request into this one. Fields the update
+    /// carries win (a response arriving after the request started); fields it lacks keep
+    /// what was already known, so a late duplicate can't erase the response.
+    pub fn merge(&mut self, update: NetworkRequest) {
+        self.status_code = update.status_code.or(self.status_code);
+        self.status_text = update.status_text.or(self.status_text.take());
+        self.request_headers.extend(update.request_headers);
+        self.response_headers = update.response_headers.or(self.response_headers.take());
+        self.request_body = update.request_body.or(self.request_body.take());
+        self.response_body = update.response_body.or(self.response_body.take());
+        self.body_truncated |= update.body_truncated;
+        self.request_body_truncated |= update.request_body_truncated;
+        self.duration_ms = update.duration_ms.or(self.duration_ms);
+        self.failed |= update.failed;
+        self.from_cache |= update.from_cache;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
     pub navigation_timing: NavigationTiming,
@@ -95,6 +303,64 @@ pub struct PerformanceMetrics {
     pub timestamp: DateTime<Utc>,
 }
 
+impl PerformanceMetrics {
+    /// Build metrics from the extension's `getPerformanceMetrics` reply: camelCase
+    /// `timing`, `resources`, `webVitals` and `memory`. Anything the page didn't
+    /// report is zero or unset. `None` if the reply has no timing section.
+    pub fn from_extension(data: &serde_json::Value) -> Option<Self> {
+        let timing = data.get("timing")?.as_object()?;
+        let timing_ms = |key: &str| timing.get(key).and_then(|v| v.as_f64());
+        let vital = |key: &str| data.get("webVitals").and_then(|vitals| vitals.get(key)).and_then(|v| v.as_f64());
+        let memory = |key: &str| data.get("memory").and_then(|memory| memory.get(key)).and_then(|v| v.as_u64()).unwrap_or(0);
+
+        let resource_timing = data
+            .get("resources")
+            .and_then(|v| v.as_array())
+            .map(|resources| resources.iter().filter_map(resource_timing_from_extension).collect())
+            .unwrap_or_default();
+
+        Some(Self {
+            navigation_timing: NavigationTiming {
+                dns_lookup: timing_ms("dnsLookup").unwrap_or(0.0),
+                tcp_connect: timing_ms("tcpConnect").unwrap_or(0.0),
+                ssl_handshake: timing_ms("sslHandshake").unwrap_or(0.0),
+                request: timing_ms("request").unwrap_or(0.0),
+                response: timing_ms("response").unwrap_or(0.0),
+                dom_processing: timing_ms("domProcessing").unwrap_or(0.0),
+                load_complete: timing_ms("loadTime").unwrap_or(0.0),
+            },
+            resource_timing,
+            core_web_vitals: CoreWebVitals {
+                largest_contentful_paint: vital("largestContentfulPaint"),
+                first_input_delay: vital("firstInputDelay"),
+                cumulative_layout_shift: vital("cumulativeLayoutShift"),
+                first_contentful_paint: timing_ms("firstContentfulPaint"),
+                time_to_interactive: timing_ms("domInteractive"),
+            },
+            memory_usage: MemoryUsage {
+                used_js_heap_size: memory("usedJSHeapSize"),
+                total_js_heap_size: memory("totalJSHeapSize"),
+                js_heap_size_limit: memory("jsHeapSizeLimit"),
+            },
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+fn resource_timing_from_extension(resource: &serde_json::Value) -> Option<ResourceTiming> {
+    let number = |key: &str| resource.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let size = |key: &str| resource.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    Some(ResourceTiming {
+        name: resource.get("name")?.as_str()?.to_string(),
+        entry_type: resource.get("type").and_then(|v| v.as_str()).unwrap_or("resource").to_string(),
+        start_time: number("startTime"),
+        duration: number("duration"),
+        transfer_size: size("size"),
+        encoded_body_size: size("encodedBodySize"),
+        decoded_body_size: size("decodedBodySize"),
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NavigationTiming {
     pub dns_lookup: f64,
@@ -126,6 +392,173 @@ pub struct CoreWebVitals {
     pub time_to_interactive: Option<f64>,
 }
 
+// Good / poor boundaries from web.dev; values up to the first are good, above the second poor
+const LCP_THRESHOLDS_MS: (f64, f64) = (2500.0, 4000.0);
+const FID_THRESHOLDS_MS: (f64, f64) = (100.0, 300.0);
+const CLS_THRESHOLDS: (f64, f64) = (0.1, 0.25);
+
+impl CoreWebVitals {
+    pub fn report(&self) -> WebVitalsReport {
+        let score = |value: Option<f64>, thresholds: (f64, f64)| {
+            value.map(|value| VitalScore {
+                value,
+                rating: VitalRating::classify(value, thresholds),
+            })
+        };
+        let largest_contentful_paint = score(self.largest_contentful_paint, LCP_THRESHOLDS_MS);
+        let first_input_delay = score(self.first_input_delay, FID_THRESHOLDS_MS);
+        let cumulative_layout_shift = score(self.cumulative_layout_shift, CLS_THRESHOLDS);
+
+        let verdict = [&largest_contentful_paint, &first_input_delay, &cumulative_layout_shift]
+            .into_iter()
+            .flatten()
+            .map(|score| score.rating)
+            .max();
+
+        WebVitalsReport {
+            verdict,
+            passed: verdict == Some(VitalRating::Good),
+            largest_contentful_paint,
+            first_input_delay,
+            cumulative_layout_shift,
+        }
+    }
+}
+
+/// Ordered from best to worst, so the overall verdict is the maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VitalRating {
+    Good,
+    NeedsImprovement,
+    Poor,
+}
+
+impl VitalRating {
+    fn classify(value: f64, (good, poor): (f64, f64)) -> Self {
+        if value <= good {
+            Self::Good
+        } else if value <= poor {
+            Self::NeedsImprovement
+        } else {
+            Self::Poor
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VitalScore {
+    pub value: f64,
+    pub rating: VitalRating,
+}
+
+/// Core Web Vitals classified against the standard thresholds. `verdict` is the
+/// worst rating among the vitals that were measured, or `None` if none were.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebVitalsReport {
+    pub verdict: Option<VitalRating>,
+    pub passed: bool,
+    pub largest_contentful_paint: Option<VitalScore>,
+    pub first_input_delay: Option<VitalScore>,
+    pub cumulative_layout_shift: Option<VitalScore>,
+}
+
+/// Requests of one resource type and the bytes they cost.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTypeTotals {
+    pub resource_type: String,
+    pub count: usize,
+    pub transfer_size: u64,
+    pub decoded_size: u64,
+}
+
+/// A tab's cached network requests totalled by resource type, heaviest transfer first.
+/// Sizes come from the Resource Timing entry for the same URL; a request without one
+/// still counts but adds no bytes, and timings with no captured request are typed by
+/// their URL's extension.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceBreakdown {
+    pub by_type: Vec<ResourceTypeTotals>,
+    pub total_count: usize,
+    pub total_transfer_size: u64,
+    pub total_decoded_size: u64,
+    /// Requests no timing entry matched, so their sizes are unknown.
+    pub unsized_count: usize,
+}
+
+impl ResourceBreakdown {
+    pub fn new(requests: &[NetworkRequest], timings: &[ResourceTiming]) -> Self {
+        // A URL fetched more than once has one timing entry per fetch; pair them in order
+        let mut timings_by_url: HashMap<&str, VecDeque<&ResourceTiming>> = HashMap::new();
+        for timing in timings {
+            timings_by_url.entry(timing.name.as_str()).or_default().push_back(timing);
+        }
+
+        let mut totals: HashMap<String, ResourceTypeTotals> = HashMap::new();
+        let mut add = |resource_type: String, timing: Option<&ResourceTiming>| {
+            let entry = totals.entry(resource_type.clone()).or_insert_with(|| ResourceTypeTotals {
+                resource_type,
+                count: 0,
+                transfer_size: 0,
+                decoded_size: 0,
+            });
+            entry.count += 1;
+            if let Some(timing) = timing {
+                entry.transfer_size += timing.transfer_size;
+                entry.decoded_size += timing.decoded_body_size;
+            }
+        };
+
+        let mut unsized_count = 0;
+        for request in requests {
+            let timing = timings_by_url.get_mut(request.url.as_str()).and_then(|queue| queue.pop_front());
+            if timing.is_none() {
+                unsized_count += 1;
+            }
+            let resource_type = match request.resource_type.trim() {
+                "" => resource_type_from_url(&request.url).to_string(),
+                resource_type => resource_type.to_ascii_lowercase(),
+            };
+            add(resource_type, timing);
+        }
+        for timing in timings_by_url.into_values().flatten() {
+            add(resource_type_from_url(&timing.name).to_string(), Some(timing));
+        }
+
+        let mut by_type: Vec<ResourceTypeTotals> = totals.into_values().collect();
+        by_type.sort_by(|a, b| {
+            b.transfer_size
+                .cmp(&a.transfer_size)
+                .then_with(|| a.resource_type.cmp(&b.resource_type))
+        });
+        Self {
+            total_count: by_type.iter().map(|totals| totals.count).sum(),
+            total_transfer_size: by_type.iter().map(|totals| totals.transfer_size).sum(),
+            total_decoded_size: by_type.iter().map(|totals| totals.decoded_size).sum(),
+            unsized_count,
+            by_type,
+        }
+    }
+}
+
+/// Best guess at a resource's type from its URL's extension, for timing entries that
+/// have no captured request to say.
+fn resource_type_from_url(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("js" | "mjs") => "script",
+        Some("css") => "stylesheet",
+        Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico") => "image",
+        Some("woff" | "woff2" | "ttf" | "otf") => "font",
+        Some("mp4" | "webm" | "mp3" | "ogg") => "media",
+        _ => "other",
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryUsage {
     pub used_js_heap_size: u64,
@@ -133,6 +566,141 @@ pub struct MemoryUsage {
     pub js_heap_size_limit: u64,
 }
 
+/// Compact resource snapshot for polling loops, cheaper than a full `PerformanceMetrics`.
+/// Field names match what the extension reports; CPU time is only present while the
+/// debugger is attached.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    #[serde(rename = "usedJSHeapSize", default)]
+    pub used_js_heap_size: u64,
+    #[serde(rename = "totalJSHeapSize", default)]
+    pub total_js_heap_size: u64,
+    #[serde(rename = "jsHeapSizeLimit", default)]
+    pub js_heap_size_limit: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dom_node_count: Option<u64>,
+}
+
+/// A request interception rule applied by the extension through the debugger's
+/// Fetch domain. `url_pattern` uses Fetch wildcards: `*` matches any run of
+/// characters, `?` a single character and a backslash escapes either.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterceptRule {
+    pub url_pattern: String,
+    #[serde(flatten)]
+    pub action: InterceptAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum InterceptAction {
+    Block,
+    Delay {
+        delay_ms: u64,
+    },
+    RespondWith {
+        #[serde(default = "default_intercept_status")]
+        status: u16,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        #[serde(default)]
+        body: String,
+    },
+}
+
+fn default_intercept_status() -> u16 {
+    200
+}
+
+/// An `<a href>` on the page, with the href resolved to an absolute URL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageLink {
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rel: Option<String>,
+}
+
+/// One change seen by `observe_mutations`, in the order the page made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutationRecord {
+    #[serde(rename = "type")]
+    pub mutation_type: MutationType,
+    /// XPath of the node that changed (the parent, for child list changes).
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_value: Option<String>,
+    /// Short descriptions of the nodes involved, like `div#main.card` or `#text`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added_nodes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_nodes: Vec<String>,
+    /// Milliseconds after observation started.
+    pub offset_ms: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MutationType {
+    Attributes,
+    ChildList,
+    CharacterData,
+}
+
+/// One entry in a tab's back/forward history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavigationEntry {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// A tab's session history; `current_index` points at the page being shown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavigationHistory {
+    pub current_index: usize,
+    pub entries: Vec<NavigationEntry>,
+}
+
+/// Scroll position and viewport geometry of a tab, in CSS pixels, from the debugger's
+/// `Page.getLayoutMetrics`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutMetrics {
+    pub scroll_x: f64,
+    pub scroll_y: f64,
+    /// Size of the whole scrollable document.
+    pub scroll_width: f64,
+    pub scroll_height: f64,
+    /// Size of the visible area, after any pinch zoom.
+    pub viewport_width: f64,
+    pub viewport_height: f64,
+    pub device_pixel_ratio: f64,
+}
+
+/// Whether the extension can use a browser feature, with the reason when it can't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capability {
+    pub available: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// What the extension is currently allowed to do in a tab.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabCapabilities {
+    pub debugger: Capability,
+    pub screenshots: Capability,
+    pub cookies: Capability,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccessibilityTree {
     pub root: AccessibilityNode,
@@ -151,6 +719,127 @@ pub struct AccessibilityNode {
     pub bounds: Option<BoundingBox>,
 }
 
+impl AccessibilityTree {
+    /// Build a tree from the extension's `getAccessibilityTree` reply, whose nodes carry
+    /// `ariaAttributes` and `semanticInfo`. Those become the node's properties, with
+    /// `hidden` and heading `level` filled in where the rules look for them.
+    pub fn from_extension(data: &serde_json::Value) -> Option<Self> {
+        let root = accessibility_node_from_extension(data.get("tree")?)?;
+        let node_count = data
+            .pointer("/summary/totalNodes")
+            .and_then(|v| v.as_u64())
+            .map_or_else(|| count_accessibility_nodes(&root), |count| count as usize);
+        Some(Self {
+            root,
+            node_count,
+            timestamp: SystemTime::now(),
+        })
+    }
+}
+
+fn accessibility_node_from_extension(node: &serde_json::Value) -> Option<AccessibilityNode> {
+    let node = node.as_object()?;
+    let mut properties = HashMap::new();
+    for key in ["semanticInfo", "ariaAttributes"] {
+        if let Some(map) = node.get(key).and_then(|v| v.as_object()) {
+            properties.extend(map.iter().map(|(name, value)| (name.clone(), value.clone())));
+        }
+    }
+    for key in ["focusable", "tagName"] {
+        if let Some(value) = node.get(key) {
+            properties.insert(key.to_string(), value.clone());
+        }
+    }
+    let aria_hidden = properties.get("aria-hidden").and_then(|v| v.as_str()) == Some("true");
+    let visible = node.get("visible").and_then(|v| v.as_bool()).unwrap_or(true);
+    properties.insert("hidden".to_string(), (aria_hidden || !visible).into());
+
+    // The extension reports headings by tag name; the rules expect the ARIA role
+    let mut role = node.get("role").and_then(|v| v.as_str()).unwrap_or("generic").to_string();
+    if let Some(level) = properties.get("headingLevel").cloned() {
+        if role.len() == 2 && role.starts_with('h') {
+            role = "heading".to_string();
+        }
+        properties.insert("level".to_string(), level);
+    }
+
+    Some(AccessibilityNode {
+        role,
+        name: node.get("name").and_then(|v| v.as_str()).filter(|name| !name.is_empty()).map(str::to_string),
+        description: None,
+        value: None,
+        properties,
+        children: node
+            .get("children")
+            .and_then(|v| v.as_array())
+            .map(|children| children.iter().filter_map(accessibility_node_from_extension).collect())
+            .unwrap_or_default(),
+        bounds: None,
+    })
+}
+
+fn count_accessibility_nodes(node: &AccessibilityNode) -> usize {
+    1 + node.children.iter().map(count_accessibility_nodes).sum::<usize>()
+}
+
+/// Ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueSeverity {
+    Minor,
+    Moderate,
+    Serious,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessibilityRule {
+    MissingAltText,
+    LowContrast,
+    MissingFormLabel,
+    HeadingOrder,
+}
+
+/// A rule violation found in a cached DOM snapshot or accessibility tree. `node` is the
+/// element's selector or XPath when the snapshot recorded one, otherwise a path from
+/// the root such as `/html/body/img[2]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessibilityIssue {
+    pub rule: AccessibilityRule,
+    pub severity: IssueSeverity,
+    pub node: String,
+    pub message: String,
+}
+
+/// Machine-readable markup found in a page: JSON-LD blocks and `<meta>` tags, in
+/// document order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuredData {
+    pub json_ld: Vec<serde_json::Value>,
+    /// Parse errors for `application/ld+json` blocks that aren't valid JSON.
+    pub invalid_json_ld: Vec<String>,
+    pub meta: Vec<MetaTag>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaTag {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Open Graph and similar vocabularies use `property` instead of `name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub property: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_equiv: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BoundingBox {
     pub x: f64,
@@ -159,6 +848,17 @@ pub struct BoundingBox {
     pub height: f64,
 }
 
+/// Where an element is drawn, in CSS pixels relative to the viewport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElementRect {
+    pub bounding_box: BoundingBox,
+    /// Rendered with a non-zero size and not hidden by `display`, `visibility` or `opacity`.
+    pub is_visible: bool,
+    /// At least partly inside the viewport, so it can be clicked without scrolling.
+    pub is_in_viewport: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScreenshotData {
     pub data: Vec<u8>,
@@ -166,6 +866,10 @@ pub struct ScreenshotData {
     pub width: u32,
     pub height: u32,
     pub timestamp: SystemTime,
+    /// Page region the capture was clipped to, in CSS pixels from the top left of the
+    /// page. `None` for the whole viewport.
+    #[serde(default)]
+    pub clip: Option<BoundingBox>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -174,10 +878,59 @@ pub struct BrowserTab {
     pub title: String,
     pub url: String,
     pub active: bool,
+    // The extension reports tabs with chrome.tabs' camelCase names and leaves some out
+    #[serde(default)]
     pub loading: bool,
+    #[serde(default, alias = "favIconUrl")]
     pub favicon_url: Option<String>,
+    #[serde(default, alias = "windowId")]
     pub window_id: Option<u32>,
     pub index: u32,
+    /// The tab's window is the one the user last focused.
+    #[serde(default, alias = "windowFocused")]
+    pub window_focused: bool,
+}
+
+/// Script the debugger runs in every new document of a tab before the page's own scripts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitScript {
+    /// Identifier `Page.addScriptToEvaluateOnNewDocument` returned, used to remove it.
+    pub script_id: String,
+    pub source: String,
+}
+
+/// User agent a tab reports instead of the browser's own, set through the debugger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserAgentOverride {
+    pub user_agent: String,
+    /// `Accept-Language` header and `navigator.language` to go with it, if overridden.
+    pub accept_language: Option<String>,
+}
+
+/// CSS media type and media features a tab is forced to match, e.g. print media or
+/// `prefers-color-scheme: dark`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmulatedMedia {
+    /// `print` or `screen`; `None` leaves the media type alone.
+    pub media: Option<String>,
+    pub features: Vec<MediaFeature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaFeature {
+    pub name: String,
+    pub value: String,
+}
+
+/// A browser window and its tabs, in tab strip order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub window_id: Option<u32>,
+    pub focused: bool,
+    pub tabs: Vec<BrowserTab>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -190,17 +943,126 @@ pub struct JavaScriptExecutionResult {
 
 impl Default for TabData {
     fn default() -> Self {
-        Self {
-            tab_id: 0,
-            page_content: None,
-            dom_snapshot: None,
-            console_logs: None,
-            network_data: None,
-            performance_metrics: None,
-            accessibility_tree: None,
-            screenshot_data: None,
-            debugger_attached: false,
-            last_updated: SystemTime::now(),
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vitals(lcp: Option<f64>, fid: Option<f64>, cls: Option<f64>) -> CoreWebVitals {
+        CoreWebVitals {
+            largest_contentful_paint: lcp,
+            first_input_delay: fid,
+            cumulative_layout_shift: cls,
+            first_contentful_paint: None,
+            time_to_interactive: None,
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_web_vitals_thresholds() {
+        let rating = |score: Option<VitalScore>| score.unwrap().rating;
+
+        let at_good = vitals(Some(2500.0), Some(100.0), Some(0.1)).report();
+        assert_eq!(rating(at_good.largest_contentful_paint), VitalRating::Good);
+        assert_eq!(rating(at_good.first_input_delay), VitalRating::Good);
+        assert_eq!(rating(at_good.cumulative_layout_shift), VitalRating::Good);
+        assert!(at_good.passed);
+
+        let above_good = vitals(Some(2501.0), Some(101.0), Some(0.11)).report();
+        assert_eq!(rating(above_good.largest_contentful_paint), VitalRating::NeedsImprovement);
+        assert_eq!(rating(above_good.first_input_delay), VitalRating::NeedsImprovement);
+        assert_eq!(rating(above_good.cumulative_layout_shift), VitalRating::NeedsImprovement);
+
+        let at_poor = vitals(Some(4000.0), Some(300.0), Some(0.25)).report();
+        assert_eq!(at_poor.verdict, Some(VitalRating::NeedsImprovement));
+
+        let above_poor = vitals(Some(4001.0), Some(301.0), Some(0.26)).report();
+        assert_eq!(rating(above_poor.largest_contentful_paint), VitalRating::Poor);
+        assert_eq!(rating(above_poor.first_input_delay), VitalRating::Poor);
+        assert_eq!(rating(above_poor.cumulative_layout_shift), VitalRating::Poor);
+        assert!(!above_poor.passed);
+    }
+
+    #[test]
+    fn test_resource_breakdown_totals_by_type() {
+        let request = |url: &str, resource_type: &str| NetworkRequest {
+            request_id: url.to_string(),
+            url: url.to_string(),
+            method: "GET".to_string(),
+            status_code: Some(200),
+            status_text: None,
+            request_headers: HashMap::new(),
+            response_headers: None,
+            request_body: None,
+            response_body: None,
+            body_truncated: false,
+            request_body_truncated: false,
+            timestamp: Utc::now(),
+            duration_ms: None,
+            failed: false,
+            from_cache: false,
+            resource_type: resource_type.to_string(),
+        };
+        let timing = |url: &str, transfer_size: u64, decoded_body_size: u64| ResourceTiming {
+            name: url.to_string(),
+            entry_type: "resource".to_string(),
+            start_time: 0.0,
+            duration: 10.0,
+            transfer_size,
+            encoded_body_size: transfer_size,
+            decoded_body_size,
+        };
+
+        let requests = [
+            request("https://example.com/app.js", "Script"),
+            request("https://example.com/vendor.js", "script"),
+            request("https://example.com/hero.png", "Image"),
+            request("https://example.com/site.css", "Stylesheet"),
+            request("https://example.com/api/cart", "Fetch"),
+        ];
+        let timings = [
+            timing("https://example.com/app.js", 30_000, 90_000),
+            timing("https://example.com/vendor.js", 70_000, 210_000),
+            timing("https://example.com/hero.png", 120_000, 120_000),
+            timing("https://example.com/site.css", 5_000, 20_000),
+            // Loaded before network capture started
+            timing("https://fonts.example.com/inter.woff2?v=3", 40_000, 40_000),
+        ];
+
+        let breakdown = ResourceBreakdown::new(&requests, &timings);
+        let types: Vec<(&str, usize, u64, u64)> = breakdown
+            .by_type
+            .iter()
+            .map(|t| (t.resource_type.as_str(), t.count, t.transfer_size, t.decoded_size))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("image", 1, 120_000, 120_000),
+                ("script", 2, 100_000, 300_000),
+                ("font", 1, 40_000, 40_000),
+                ("stylesheet", 1, 5_000, 20_000),
+                ("fetch", 1, 0, 0),
+            ]
+        );
+        assert_eq!(breakdown.total_count, 6);
+        assert_eq!(breakdown.total_transfer_size, 265_000);
+        assert_eq!(breakdown.total_decoded_size, 480_000);
+        assert_eq!(breakdown.unsized_count, 1);
+    }
+
+    #[test]
+    fn test_web_vitals_verdict_is_worst_measured() {
+        let report = vitals(Some(1200.0), None, Some(0.3)).report();
+        assert_eq!(report.verdict, Some(VitalRating::Poor));
+        assert!(report.first_input_delay.is_none());
+
+        let unmeasured = vitals(None, None, None).report();
+        assert_eq!(unmeasured.verdict, None);
+        assert!(!unmeasured.passed);
+        assert_eq!(serde_json::to_value(VitalRating::NeedsImprovement).unwrap(), "needs-improvement");
+    }
+}
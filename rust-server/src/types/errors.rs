@@ -25,6 +25,10 @@ pub enum BrowserMcpError {
     #[error("JSON serialization error: {message}")]
     JsonError { message: String },
 
+    /// A server-side value that couldn't be turned into JSON, named so the bug can be found.
+    #[error("JSON serialization error: failed to serialize {type_name}: {message}")]
+    SerializationFailed { type_name: String, message: String },
+
     #[error("WebSocket error: {message}")]
     WebSocketError { message: String },
 
@@ -52,6 +56,10 @@ pub enum BrowserMcpError {
     #[error("Permission denied: {message}")]
     PermissionDenied { message: String },
 
+    /// The tab is in a state the operation can't run in, e.g. paused in the debugger.
+    #[error("Precondition failed: {message}")]
+    PreconditionFailed { message: String },
+
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
 
@@ -59,6 +67,74 @@ pub enum BrowserMcpError {
     ServiceUnavailable { message: String },
 }
 
+/// Start of each variant's message, for recovering the category from tool call errors
+/// that wrap it ("Failed to get page content: Request timeout after 30s").
+const CATEGORY_MESSAGE_PREFIXES: &[(&str, &str)] = &[
+    ("Connection not available for tab", "connection_not_available"),
+    ("Request timeout after", "request_timeout"),
+    ("Connection closed unexpectedly", "connection_closed"),
+    ("Invalid request format:", "invalid_request"),
+    ("Browser extension error:", "browser_extension_error"),
+    ("JSON serialization error:", "json_error"),
+    ("WebSocket error:", "websocket_error"),
+    ("Cache error:", "cache_error"),
+    ("Configuration error:", "config_error"),
+    ("Network error:", "network_error"),
+    ("Internal server error:", "internal_error"),
+    ("Resource not found:", "resource_not_found"),
+    ("Method not implemented:", "method_not_implemented"),
+    ("Invalid parameters:", "invalid_parameters"),
+    ("Permission denied:", "permission_denied"),
+    ("Precondition failed:", "precondition_failed"),
+    ("Rate limit exceeded", "rate_limit_exceeded"),
+    ("Service unavailable:", "service_unavailable"),
+];
+
+impl BrowserMcpError {
+    /// Stable label for per-category error counts and metrics.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::ConnectionNotAvailable { .. } => "connection_not_available",
+            Self::RequestTimeout { .. } => "request_timeout",
+            Self::ConnectionClosed => "connection_closed",
+            Self::InvalidRequest { .. } => "invalid_request",
+            Self::BrowserExtensionError { .. } => "browser_extension_error",
+            Self::TabNotFound { .. } => "tab_not_found",
+            Self::JsonError { .. } | Self::SerializationFailed { .. } => "json_error",
+            Self::WebSocketError { .. } => "websocket_error",
+            Self::CacheError { .. } => "cache_error",
+            Self::ConfigError { .. } => "config_error",
+            Self::NetworkError { .. } => "network_error",
+            Self::InternalError { .. } => "internal_error",
+            Self::ResourceNotFound { .. } => "resource_not_found",
+            Self::MethodNotImplemented { .. } => "method_not_implemented",
+            Self::InvalidParameters { .. } => "invalid_parameters",
+            Self::PermissionDenied { .. } => "permission_denied",
+            Self::PreconditionFailed { .. } => "precondition_failed",
+            Self::RateLimitExceeded => "rate_limit_exceeded",
+            Self::ServiceUnavailable { .. } => "service_unavailable",
+        }
+    }
+
+    /// Category of a tool call error message. Messages that embed no error are the tool
+    /// dispatcher's own argument checks ("tabId is required for ...") and count as
+    /// invalid parameters.
+    pub fn category_of_message(message: &str) -> &'static str {
+        if let Some((_, category)) = CATEGORY_MESSAGE_PREFIXES
+            .iter()
+            .filter_map(|(prefix, category)| message.find(prefix).map(|at| (at, *category)))
+            .min_by_key(|(at, _)| *at)
+        {
+            return category;
+        }
+        // "Tab {id} not found" has the id in the middle
+        if message.contains("Tab ") && message.contains(" not found") {
+            return "tab_not_found";
+        }
+        "invalid_parameters"
+    }
+}
+
 impl From<serde_json::Error> for BrowserMcpError {
     fn from(err: serde_json::Error) -> Self {
         BrowserMcpError::JsonError {
@@ -55,9 +55,32 @@ pub struct HealthStatus {
     pub active_connections: usize,
     pub cached_tabs: usize,
     pub memory_usage_mb: f64,
+    #[serde(default)]
+    pub in_flight_requests: usize,
+    #[serde(default)]
+    pub interned_strings: usize,
+    #[serde(default)]
+    pub interner_cache_hits: usize,
+    /// Most recent browser connection error and how long ago it happened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_connection_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_connection_error_age_secs: Option<u64>,
+    /// Bytes sent to browsers over what they would have been uncompressed.
+    #[serde(default = "default_compression_ratio")]
+    pub compression_ratio: f64,
+    #[serde(default)]
+    pub bytes_saved: u64,
+    /// Browser WebSocket messages sent and received per second, averaged over the last minute.
+    #[serde(default)]
+    pub messages_per_second: f64,
     pub performance_stats: PerformanceStats,
 }
 
+fn default_compression_ratio() -> f64 {
+    1.0
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceStats {
     pub requests_per_second: f64,
@@ -65,6 +88,16 @@ pub struct PerformanceStats {
     pub cache_hit_rate: f64,
     pub error_rate: f64,
     pub active_websocket_connections: usize,
+    /// Failures by `BrowserMcpError` category, to tell a slow browser from bad requests.
+    #[serde(default)]
+    pub errors_by_category: std::collections::BTreeMap<String, ErrorCategoryStats>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ErrorCategoryStats {
+    pub count: u64,
+    /// Share of all requests that failed this way.
+    pub rate: f64,
 }
 
 impl McpContent {
@@ -74,10 +107,13 @@ impl McpContent {
         }
     }
 
-    pub fn json(value: &serde_json::Value) -> Self {
-        Self::Text {
-            text: serde_json::to_string_pretty(value).unwrap_or_default(),
-        }
+    pub fn json(value: &serde_json::Value, pretty: bool) -> Result<Self, serde_json::Error> {
+        let text = if pretty {
+            serde_json::to_string_pretty(value)?
+        } else {
+            serde_json::to_string(value)?
+        };
+        Ok(Self::Text { text })
     }
 
     pub fn image(data: Vec<u8>, mime_type: &str) -> Self {
@@ -108,7 +144,10 @@ impl McpToolResult {
         Self::success(vec![McpContent::text(text)])
     }
 
-    pub fn json(value: &serde_json::Value) -> Self {
-        Self::success(vec![McpContent::json(value)])
+    pub fn json(value: &serde_json::Value, pretty: bool) -> Self {
+        match McpContent::json(value, pretty) {
+            Ok(content) => Self::success(vec![content]),
+            Err(e) => Self::error(&format!("Failed to serialize result: {}", e)),
+        }
     }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use crate::types::browser::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +14,7 @@ pub enum BrowserMessage {
     #[serde(rename = "response")]
     Response {
         request_id: Uuid,
+        #[serde(deserialize_with = "deserialize_response_result")]
         result: Result<BrowserResponse, String>,
     },
     #[serde(rename = "notification")]
@@ -22,6 +23,28 @@ pub enum BrowserMessage {
     Heartbeat { timestamp: chrono::DateTime<chrono::Utc> },
 }
 
+/// `#[serde(other)]` only matches tags without content, so a response that fails to
+/// parse is retried with just its tag to tell unknown types from malformed ones.
+fn deserialize_response_result<'de, D>(deserializer: D) -> Result<Result<BrowserResponse, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = match Result::<serde_json::Value, String>::deserialize(deserializer)? {
+        Ok(value) => value,
+        Err(error) => return Ok(Err(error)),
+    };
+    match serde_json::from_value::<BrowserResponse>(value.clone()) {
+        Ok(response) => Ok(Ok(response)),
+        Err(error) => {
+            let tag_only = serde_json::json!({ "type": value.get("type") });
+            match serde_json::from_value::<BrowserResponse>(tag_only) {
+                Ok(BrowserResponse::Unknown) => Ok(Ok(BrowserResponse::Unknown)),
+                _ => Err(serde::de::Error::custom(error)),
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "action", content = "params")]
 pub enum BrowserRequest {
@@ -32,14 +55,31 @@ pub enum BrowserRequest {
     GetDomSnapshot {
         max_depth: usize,
         include_styles: bool,
+        /// Descend into open shadow roots.
+        #[serde(default)]
+        pierce: bool,
     },
 
     #[serde(rename = "execute_javascript")]
     ExecuteJavaScript {
         code: String,
         return_by_value: bool,
+        context_id: Option<String>,
+        /// Resolve a returned Promise through the debugger and return its value instead.
+        #[serde(default)]
+        await_promise: bool,
     },
 
+    /// Replace the whole document with `html`; scripts in it run.
+    #[serde(rename = "set_page_content")]
+    SetPageContent { html: String, tab_id: u32 },
+
+    #[serde(rename = "create_js_context")]
+    CreateJsContext,
+
+    #[serde(rename = "dispose_js_context")]
+    DisposeJsContext { context_id: String },
+
     #[serde(rename = "get_console_messages")]
     GetConsoleMessages {
         level_filter: Option<String>,
@@ -52,19 +92,181 @@ pub enum BrowserRequest {
         limit: Option<usize>,
     },
 
+    #[serde(rename = "get_network_request_body")]
+    GetNetworkRequestBody {
+        request_id: String,
+    },
+
+    /// Fetch a URL from inside the tab so its cookies and auth apply. The extension
+    /// gives up on bodies over `max_bytes`.
+    #[serde(rename = "download_resource")]
+    DownloadResource {
+        url: String,
+        tab_id: u32,
+        max_bytes: usize,
+    },
+
+    /// Re-send a captured request with `fetch` from inside the tab. The extension cuts
+    /// response bodies longer than `max_bytes`.
+    #[serde(rename = "replay_request")]
+    ReplayRequest {
+        method: String,
+        url: String,
+        headers: std::collections::HashMap<String, String>,
+        body: Option<String>,
+        tab_id: u32,
+        max_bytes: usize,
+    },
+
     #[serde(rename = "capture_screenshot")]
     CaptureScreenshot {
         format: String,
         quality: Option<f32>,
         clip: Option<BoundingBox>,
+        /// Clip to this element's bounding box, resolved by the extension. Overrides `clip`.
+        #[serde(default)]
+        capture_selector: Option<String>,
     },
 
     #[serde(rename = "get_performance_metrics")]
     GetPerformanceMetrics,
 
+    #[serde(rename = "get_resource_usage")]
+    GetResourceUsage,
+
+    #[serde(rename = "wait_for_network_idle")]
+    WaitForNetworkIdle {
+        idle_ms: u64,
+        timeout_ms: u64,
+        max_inflight: usize,
+    },
+
+    /// Resolve with the first console error (or uncaught exception) whose text matches
+    /// `pattern`, any error if unset, logged within `timeout_ms`.
+    #[serde(rename = "wait_for_console_error")]
+    WaitForConsoleError {
+        timeout_ms: u64,
+        pattern: Option<String>,
+    },
+
+    #[serde(rename = "get_computed_style")]
+    GetComputedStyle {
+        selector: String,
+        properties: Option<Vec<String>>,
+    },
+
+    #[serde(rename = "get_links")]
+    GetLinks {
+        same_origin_only: bool,
+        include_text: bool,
+    },
+
+    #[serde(rename = "dispatch_key")]
+    DispatchKey {
+        selector: Option<String>,
+        key: String,
+        modifiers: Vec<String>,
+    },
+
+    /// Set several form fields in one round trip, keyed by CSS selector. Values are
+    /// strings, numbers, or booleans for checkboxes and radio buttons.
+    #[serde(rename = "fill_form")]
+    FillForm { fields: std::collections::HashMap<String, serde_json::Value> },
+
+    #[serde(rename = "evaluate_xpath")]
+    EvaluateXPath { expression: String },
+
+    /// Bounding box and visibility of the first element matching `selector`.
+    #[serde(rename = "get_element_rect")]
+    GetElementRect { selector: String },
+
+    /// Record DOM mutations under `selector` (the whole document if unset) for `duration_ms`.
+    #[serde(rename = "observe_mutations")]
+    ObserveMutations {
+        selector: Option<String>,
+        duration_ms: u64,
+        attributes: bool,
+        child_list: bool,
+        subtree: bool,
+    },
+
+    #[serde(rename = "get_navigation_history")]
+    GetNavigationHistory { tab_id: u32 },
+
+    /// Move through the tab's history: negative deltas go back, positive go forward.
+    #[serde(rename = "navigate_history")]
+    NavigateHistory { delta: i32 },
+
+    #[serde(rename = "set_intercept_rules")]
+    SetInterceptRules { rules: Vec<InterceptRule> },
+
+    /// Clear the selected kinds of stored data through the debugger. Local storage is
+    /// cleared for the tab's origin only; cache and cookies are browser-wide.
+    #[serde(rename = "clear_browsing_data")]
+    ClearBrowsingData {
+        cache: bool,
+        cookies: bool,
+        local_storage: bool,
+        tab_id: u32,
+    },
+
+    /// Serialize the page and its subresources as one MHTML document through the debugger.
+    #[serde(rename = "capture_mhtml")]
+    CaptureMhtml { tab_id: u32 },
+
+    /// Read scroll position, document size and viewport size through the debugger.
+    #[serde(rename = "get_layout_metrics")]
+    GetLayoutMetrics { tab_id: u32 },
+
+    /// Ask which permission-gated features the extension can use in the tab.
+    #[serde(rename = "get_capabilities")]
+    GetCapabilities { tab_id: u32 },
+
+    /// Replace the headers added to the tab's requests; an empty map removes them.
+    #[serde(rename = "set_extra_headers")]
+    SetExtraHeaders {
+        headers: std::collections::HashMap<String, String>,
+        tab_id: u32,
+    },
+
+    /// Report the user agent and language the tab's pages see.
+    #[serde(rename = "get_user_agent")]
+    GetUserAgent { tab_id: u32 },
+
+    /// Override the tab's user agent (and optionally its languages) with `Network.setUserAgentOverride`.
+    #[serde(rename = "set_user_agent")]
+    SetUserAgent {
+        user_agent: String,
+        accept_language: Option<String>,
+        tab_id: u32,
+    },
+
+    /// Stop the JavaScript the tab is running with `Runtime.terminateExecution`.
+    #[serde(rename = "terminate_execution")]
+    TerminateExecution,
+
+    /// Force CSS media and media features with `Emulation.setEmulatedMedia`. No media
+    /// and no features clears the emulation.
+    #[serde(rename = "set_emulated_media")]
+    SetEmulatedMedia {
+        media: Option<String>,
+        features: Vec<MediaFeature>,
+        tab_id: u32,
+    },
+
+    #[serde(rename = "add_init_script")]
+    AddInitScript { source: String, tab_id: u32 },
+
+    #[serde(rename = "remove_init_script")]
+    RemoveInitScript { script_id: String, tab_id: u32 },
+
     #[serde(rename = "get_accessibility_tree")]
     GetAccessibilityTree { max_depth: Option<usize> },
 
+    /// Role, name and state of the first element matching `selector`.
+    #[serde(rename = "get_accessibility_node")]
+    GetAccessibilityNode { selector: String },
+
     #[serde(rename = "get_browser_tabs")]
     GetBrowserTabs,
 
@@ -73,6 +275,73 @@ pub enum BrowserRequest {
 
     #[serde(rename = "detach_debugger")]
     DetachDebugger,
+
+    /// Stop the page's JavaScript with `Debugger.pause`.
+    #[serde(rename = "pause_execution")]
+    PauseExecution,
+
+    /// Let paused JavaScript continue with `Debugger.resume`.
+    #[serde(rename = "resume_execution")]
+    ResumeExecution,
+}
+
+impl BrowserRequest {
+    /// The snake_case name of the request, which is also the name of the tool that sends it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GetPageContent { .. } => "get_page_content",
+            Self::GetDomSnapshot { .. } => "get_dom_snapshot",
+            Self::ExecuteJavaScript { .. } => "execute_javascript",
+            Self::SetPageContent { .. } => "set_page_content",
+            Self::CreateJsContext => "create_js_context",
+            Self::DisposeJsContext { .. } => "dispose_js_context",
+            Self::GetConsoleMessages { .. } => "get_console_messages",
+            Self::GetNetworkRequests { .. } => "get_network_requests",
+            Self::GetNetworkRequestBody { .. } => "get_network_request_body",
+            Self::DownloadResource { .. } => "download_resource",
+            Self::ReplayRequest { .. } => "replay_request",
+            Self::CaptureScreenshot { .. } => "capture_screenshot",
+            Self::GetPerformanceMetrics => "get_performance_metrics",
+            Self::GetResourceUsage => "get_resource_usage",
+            Self::WaitForNetworkIdle { .. } => "wait_for_network_idle",
+            Self::WaitForConsoleError { .. } => "wait_for_console_error",
+            Self::GetComputedStyle { .. } => "get_computed_style",
+            Self::GetLinks { .. } => "get_links",
+            Self::DispatchKey { .. } => "press_key",
+            Self::FillForm { .. } => "fill_form",
+            Self::EvaluateXPath { .. } => "evaluate_xpath",
+            Self::GetElementRect { .. } => "get_element_rect",
+            Self::ObserveMutations { .. } => "observe_mutations",
+            Self::GetNavigationHistory { .. } => "get_navigation_history",
+            Self::NavigateHistory { .. } => "go_back_forward",
+            Self::SetInterceptRules { .. } => "set_intercept_rules",
+            Self::ClearBrowsingData { .. } => "clear_browsing_data",
+            Self::CaptureMhtml { .. } => "capture_mhtml",
+            Self::GetLayoutMetrics { .. } => "get_layout_metrics",
+            Self::GetCapabilities { .. } => "get_capabilities",
+            Self::SetExtraHeaders { .. } => "set_extra_headers",
+            Self::GetUserAgent { .. } => "get_user_agent",
+            Self::TerminateExecution => "terminate_execution",
+            Self::SetUserAgent { .. } => "set_user_agent",
+            Self::SetEmulatedMedia { .. } => "set_emulated_media",
+            Self::AddInitScript { .. } => "add_init_script",
+            Self::RemoveInitScript { .. } => "remove_init_script",
+            Self::GetAccessibilityTree { .. } => "get_accessibility_tree",
+            Self::GetAccessibilityNode { .. } => "get_accessibility_node",
+            Self::GetBrowserTabs => "get_browser_tabs",
+            Self::AttachDebugger => "attach_debugger",
+            Self::DetachDebugger => "detach_debugger",
+            Self::PauseExecution => "pause_execution",
+            Self::ResumeExecution => "resume_execution",
+        }
+    }
+
+    /// Requests that interrupt work already running in the tab, and attaching the
+    /// debugger they need. They skip the per-tab queue, since queueing behind the work
+    /// they are meant to stop would defeat them.
+    pub fn bypasses_tab_queue(&self) -> bool {
+        matches!(self, Self::TerminateExecution | Self::AttachDebugger)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,9 +368,43 @@ pub enum BrowserResponse {
     #[serde(rename = "performance_metrics")]
     PerformanceMetrics(PerformanceMetrics),
 
+    #[serde(rename = "resource_usage")]
+    ResourceUsage(ResourceUsage),
+
+    #[serde(rename = "computed_style")]
+    ComputedStyle(std::collections::HashMap<String, String>),
+
+    #[serde(rename = "links")]
+    Links(Vec<PageLink>),
+
+    #[serde(rename = "xpath_result")]
+    XPathResult(XPathResult),
+
+    #[serde(rename = "element_rect")]
+    ElementRect(ElementRect),
+
+    #[serde(rename = "mutation_records")]
+    MutationRecords(Vec<MutationRecord>),
+
+    #[serde(rename = "navigation_history")]
+    NavigationHistory(NavigationHistory),
+
+    #[serde(rename = "layout_metrics")]
+    LayoutMetrics(LayoutMetrics),
+
+    /// MHTML text from `Page.captureSnapshot`.
+    #[serde(rename = "mhtml")]
+    Mhtml { data: String },
+
+    #[serde(rename = "capabilities")]
+    Capabilities(TabCapabilities),
+
     #[serde(rename = "accessibility_tree")]
     AccessibilityTree(AccessibilityTree),
 
+    #[serde(rename = "accessibility_node")]
+    AccessibilityNode(AccessibilityNode),
+
     #[serde(rename = "browser_tabs")]
     BrowserTabs(Vec<BrowserTab>),
 
@@ -111,11 +414,21 @@ pub enum BrowserResponse {
     #[serde(rename = "debugger_detached")]
     DebuggerDetached { success: bool },
 
+    #[serde(rename = "js_context_created")]
+    JsContextCreated { context_id: String },
+
+    #[serde(rename = "js_context_disposed")]
+    JsContextDisposed { success: bool },
+
     #[serde(rename = "error")]
     Error { message: String },
 
     #[serde(rename = "raw_json")]
     RawJson(serde_json::Value),
+
+    /// A response type this server doesn't know, from a newer extension. Its data is dropped.
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -145,8 +458,17 @@ pub enum BrowserEvent {
         request: NetworkRequest,
     },
 
+    /// `label` is a stable name for the tab (e.g. "checkout-page") that tools can use
+    /// instead of its numeric ID, which changes between browser sessions.
     #[serde(rename = "connection_established")]
-    ConnectionEstablished { tab_id: u32 },
+    ConnectionEstablished {
+        tab_id: u32,
+        #[serde(default)]
+        label: Option<String>,
+        /// Token from a previous connection's `resume_token` reply.
+        #[serde(default)]
+        resume_token: Option<String>,
+    },
 
     #[serde(rename = "connection_lost")]
     ConnectionLost { tab_id: u32 },
@@ -157,6 +479,10 @@ pub struct DataUpdateEvent {
     pub tab_id: u32,
     pub update_type: DataUpdateType,
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The added item itself, for update types subscribers want to stream (console
+    /// messages). Only filled in while someone is subscribed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -165,6 +491,7 @@ pub enum DataUpdateType {
     DomSnapshotUpdated,
     ConsoleMessageAdded,
     NetworkRequestAdded,
+    NetworkRequestUpdated,
     PerformanceMetricsUpdated,
     AccessibilityTreeUpdated,
     ScreenshotCaptured,
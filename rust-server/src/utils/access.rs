@@ -0,0 +1,101 @@
+use crate::config::SecuritySettings;
+use crate::types::errors::*;
+use regex::Regex;
+
+/// URL-based access control for tools, compiled from `[security]`.
+#[derive(Debug, Clone, Default)]
+pub struct UrlAccessPolicy {
+    blocked: Vec<Regex>,
+    allowed: Vec<Regex>,
+}
+
+impl UrlAccessPolicy {
+    pub fn from_settings(settings: &SecuritySettings) -> Result<Self> {
+        Ok(Self {
+            blocked: compile_patterns(&settings.blocked_url_patterns)?,
+            allowed: compile_patterns(&settings.allowed_url_patterns)?,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.blocked.is_empty() || !self.allowed.is_empty()
+    }
+
+    /// Check whether tools may act on a tab currently at `url`. Blocked patterns always
+    /// win; with an allowlist configured the URL must match it, so a tab whose URL is
+    /// not known yet is refused.
+    pub fn check(&self, tab_id: u32, url: Option<&str>) -> Result<()> {
+        if let Some(url) = url {
+            if let Some(pattern) = self.blocked.iter().find(|re| re.is_match(url)) {
+                return Err(BrowserMcpError::PermissionDenied {
+                    message: format!("Tab {} URL {} matches blocked pattern '{}'", tab_id, url, pattern),
+                });
+            }
+        }
+
+        if self.allowed.is_empty() {
+            return Ok(());
+        }
+        match url {
+            Some(url) if self.allowed.iter().any(|re| re.is_match(url)) => Ok(()),
+            Some(url) => Err(BrowserMcpError::PermissionDenied {
+                message: format!("Tab {} URL {} is not in the allowed URL patterns", tab_id, url),
+            }),
+            None => Err(BrowserMcpError::PermissionDenied {
+                message: format!("Tab {} URL is unknown and an allowlist is configured", tab_id),
+            }),
+        }
+    }
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| BrowserMcpError::ConfigError {
+                message: format!("Invalid URL pattern '{}': {}", pattern, e),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(blocked: &[&str], allowed: &[&str]) -> UrlAccessPolicy {
+        UrlAccessPolicy::from_settings(&SecuritySettings {
+            blocked_url_patterns: blocked.iter().map(|p| p.to_string()).collect(),
+            allowed_url_patterns: allowed.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blocklist_and_allowlist() {
+        let blocklist = policy(&[r"^https://([a-z]+\.)?mybank\.com/"], &[]);
+        assert!(matches!(
+            blocklist.check(1, Some("https://online.mybank.com/accounts")),
+            Err(BrowserMcpError::PermissionDenied { .. })
+        ));
+        assert!(blocklist.check(1, Some("https://example.com/")).is_ok());
+        assert!(blocklist.check(1, None).is_ok());
+
+        let allowlist = policy(&[r"/admin"], &[r"^https://localhost(:\d+)?/"]);
+        assert!(allowlist.check(2, Some("https://localhost:3000/app")).is_ok());
+        assert!(allowlist.check(2, Some("https://localhost:3000/admin")).is_err());
+        assert!(allowlist.check(2, Some("https://example.com/")).is_err());
+        assert!(allowlist.check(2, None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_config_error() {
+        let result = UrlAccessPolicy::from_settings(&SecuritySettings {
+            blocked_url_patterns: vec!["(".to_string()],
+            allowed_url_patterns: Vec::new(),
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(BrowserMcpError::ConfigError { .. })));
+    }
+}
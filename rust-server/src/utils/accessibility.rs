@@ -0,0 +1,581 @@
+use crate::types::browser::{
+    AccessibilityIssue, AccessibilityNode, AccessibilityRule, DomNode, IssueSeverity,
+};
+use std::collections::HashSet;
+
+/// WCAG 2.1 AA minimum contrast for body text and for large text.
+const MIN_CONTRAST: f64 = 4.5;
+const MIN_CONTRAST_LARGE_TEXT: f64 = 3.0;
+
+const UNLABELLED_INPUT_TYPES: &[&str] = &["hidden", "submit", "reset", "button", "image"];
+const FORM_CONTROL_ROLES: &[&str] = &[
+    "textbox", "searchbox", "combobox", "listbox", "checkbox", "radio", "spinbutton", "slider", "switch",
+];
+
+/// Run the accessibility rules over whatever is cached for a tab. Alt text, form label
+/// and contrast checks read the DOM snapshot, falling back to the accessibility tree's
+/// computed names when no snapshot is cached; contrast needs the snapshot's computed
+/// styles. Heading order prefers the accessibility tree. Issues come back most severe
+/// first, in document order within a severity.
+pub fn find_accessibility_issues(
+    dom: Option<&DomNode>,
+    tree: Option<&AccessibilityNode>,
+) -> Vec<AccessibilityIssue> {
+    let mut issues = Vec::new();
+    let mut headings = Vec::new();
+
+    if let Some(root) = dom {
+        let mut labelled_ids = HashSet::new();
+        collect_label_targets(root, &mut labelled_ids);
+        let mut walk = DomWalk {
+            labelled_ids,
+            issues: &mut issues,
+            headings: &mut headings,
+        };
+        walk.visit(root, &format!("/{}", tag(root)), Rgba::WHITE, false);
+    } else if let Some(root) = tree {
+        check_accessibility_node(root, &format!("/{}", root.role), &mut issues);
+    }
+
+    if let Some(root) = tree {
+        headings.clear();
+        collect_tree_headings(root, &format!("/{}", root.role), &mut headings);
+    }
+    check_heading_order(&headings, &mut issues);
+
+    issues.sort_by_key(|issue| std::cmp::Reverse(issue.severity));
+    issues
+}
+
+fn issue(rule: AccessibilityRule, severity: IssueSeverity, node: &str, message: String) -> AccessibilityIssue {
+    AccessibilityIssue {
+        rule,
+        severity,
+        node: node.to_string(),
+        message,
+    }
+}
+
+fn tag(node: &DomNode) -> String {
+    node.tag_name.as_deref().unwrap_or_default().to_ascii_lowercase()
+}
+
+fn attribute<'a>(node: &'a DomNode, name: &str) -> Option<&'a str> {
+    node.attributes
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn style<'a>(node: &'a DomNode, name: &str) -> Option<&'a str> {
+    node.computed_styles.as_ref()?.get(name).map(String::as_str)
+}
+
+fn has_accessible_name(node: &DomNode) -> bool {
+    ["aria-label", "aria-labelledby", "title"]
+        .iter()
+        .any(|name| attribute(node, name).is_some_and(|value| !value.trim().is_empty()))
+}
+
+fn is_hidden(node: &DomNode) -> bool {
+    attribute(node, "aria-hidden") == Some("true")
+        || style(node, "display") == Some("none")
+        || style(node, "visibility") == Some("hidden")
+}
+
+fn is_text(node: &DomNode) -> bool {
+    matches!(&*node.node_type, "text" | "#text")
+}
+
+fn collect_label_targets<'a>(node: &'a DomNode, ids: &mut HashSet<&'a str>) {
+    if tag(node) == "label" {
+        if let Some(target) = attribute(node, "for") {
+            ids.insert(target);
+        }
+    }
+    for child in &node.children {
+        collect_label_targets(child, ids);
+    }
+}
+
+struct DomWalk<'a, 'b> {
+    labelled_ids: HashSet<&'a str>,
+    issues: &'b mut Vec<AccessibilityIssue>,
+    headings: &'b mut Vec<(u8, String)>,
+}
+
+impl<'a> DomWalk<'a, '_> {
+    fn visit(&mut self, node: &'a DomNode, path: &str, background: Rgba, in_label: bool) {
+        if is_text(node) || is_hidden(node) {
+            return;
+        }
+        let tag = tag(node);
+        let reference = node.selector.as_deref().or(node.xpath.as_deref()).unwrap_or(path);
+        let role = attribute(node, "role");
+
+        match tag.as_str() {
+            "img" if attribute(node, "alt").is_none()
+                && !matches!(role, Some("presentation" | "none"))
+                && !has_accessible_name(node) =>
+            {
+                let src = attribute(node, "src").unwrap_or("(no src)");
+                self.issues.push(issue(
+                    AccessibilityRule::MissingAltText,
+                    IssueSeverity::Critical,
+                    reference,
+                    format!("Image {} has no alt text; use alt=\"\" if it is decorative", src),
+                ));
+            }
+            "input" | "select" | "textarea" => {
+                let exempt = tag == "input"
+                    && attribute(node, "type").is_some_and(|kind| {
+                        UNLABELLED_INPUT_TYPES.contains(&kind.to_ascii_lowercase().as_str())
+                    });
+                let labelled = in_label
+                    || has_accessible_name(node)
+                    || attribute(node, "id").is_some_and(|id| self.labelled_ids.contains(id));
+                if !exempt && !labelled {
+                    self.issues.push(issue(
+                        AccessibilityRule::MissingFormLabel,
+                        IssueSeverity::Critical,
+                        reference,
+                        format!("<{}> has no associated <label>, aria-label or aria-labelledby", tag),
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(level) = heading_level(&tag) {
+            self.headings.push((level, reference.to_string()));
+        }
+
+        let background = style(node, "background-color")
+            .and_then(Rgba::parse)
+            .map_or(background, |color| color.over(background));
+        if let Some(contrast) = self.low_contrast(node, background) {
+            self.issues.push(issue(
+                AccessibilityRule::LowContrast,
+                IssueSeverity::Serious,
+                reference,
+                contrast,
+            ));
+        }
+
+        let in_label = in_label || tag == "label";
+        for (child, child_path) in child_paths(node, path) {
+            self.visit(child, &child_path, background, in_label);
+        }
+    }
+
+    /// Describe the shortfall if the element's own text is below the required contrast.
+    fn low_contrast(&self, node: &DomNode, background: Rgba) -> Option<String> {
+        if !has_own_text(node) {
+            return None;
+        }
+        let foreground = style(node, "color").and_then(Rgba::parse)?.over(background);
+        let ratio = foreground.contrast(background);
+        let required = if is_large_text(node) { MIN_CONTRAST_LARGE_TEXT } else { MIN_CONTRAST };
+        (ratio < required).then(|| {
+            format!(
+                "Text contrast {:.2}:1 ({} on {}) is below the required {}:1",
+                ratio,
+                foreground.hex(),
+                background.hex(),
+                required
+            )
+        })
+    }
+}
+
+/// Children paired with XPath-style paths; same-tag siblings get a 1-based index.
+fn child_paths<'n>(node: &'n DomNode, path: &str) -> Vec<(&'n DomNode, String)> {
+    let elements: Vec<_> = node.children.iter().filter(|child| !is_text(child)).collect();
+    elements
+        .iter()
+        .map(|child| {
+            let tag = tag(child);
+            let same_tag: Vec<_> = elements.iter().filter(|sibling| self::tag(sibling) == tag).collect();
+            let child_path = if same_tag.len() > 1 {
+                let index = same_tag.iter().position(|sibling| std::ptr::eq(**sibling, *child)).unwrap_or(0) + 1;
+                format!("{}/{}[{}]", path, tag, index)
+            } else {
+                format!("{}/{}", path, tag)
+            };
+            (*child, child_path)
+        })
+        .collect()
+}
+
+fn has_own_text(node: &DomNode) -> bool {
+    let has_text = |text: &Option<String>| text.as_deref().is_some_and(|text| !text.trim().is_empty());
+    if node.children.iter().any(|child| is_text(child) && has_text(&child.text_content)) {
+        return true;
+    }
+    !node.children.iter().any(|child| !is_text(child)) && has_text(&node.text_content)
+}
+
+/// 24px, or 18.66px (14pt) when bold.
+fn is_large_text(node: &DomNode) -> bool {
+    let size = style(node, "font-size")
+        .and_then(|size| size.trim().trim_end_matches("px").parse::<f64>().ok())
+        .unwrap_or(16.0);
+    let bold = style(node, "font-weight").is_some_and(|weight| {
+        matches!(weight, "bold" | "bolder") || weight.parse::<u32>().is_ok_and(|weight| weight >= 700)
+    });
+    size >= 24.0 || (bold && size >= 18.66)
+}
+
+fn heading_level(tag: &str) -> Option<u8> {
+    match tag.as_bytes() {
+        [b'h', level @ b'1'..=b'6'] => Some(level - b'0'),
+        _ => None,
+    }
+}
+
+fn check_accessibility_node(node: &AccessibilityNode, path: &str, issues: &mut Vec<AccessibilityIssue>) {
+    if node.properties.get("hidden").and_then(|v| v.as_bool()) == Some(true) {
+        return;
+    }
+    let unnamed = node.name.as_deref().is_none_or(|name| name.trim().is_empty());
+    if unnamed && matches!(node.role.as_str(), "img" | "image") {
+        issues.push(issue(
+            AccessibilityRule::MissingAltText,
+            IssueSeverity::Critical,
+            path,
+            "Image has no accessible name".to_string(),
+        ));
+    } else if unnamed && FORM_CONTROL_ROLES.contains(&node.role.as_str()) {
+        issues.push(issue(
+            AccessibilityRule::MissingFormLabel,
+            IssueSeverity::Critical,
+            path,
+            format!("Form control with role {} has no accessible name", node.role),
+        ));
+    }
+    for (child, child_path) in tree_child_paths(node, path) {
+        check_accessibility_node(child, &child_path, issues);
+    }
+}
+
+fn collect_tree_headings(node: &AccessibilityNode, path: &str, headings: &mut Vec<(u8, String)>) {
+    if node.role == "heading" {
+        let level = node.properties.get("level").and_then(|level| {
+            level.as_u64().or_else(|| level.as_str().and_then(|level| level.parse().ok()))
+        });
+        if let Some(level) = level {
+            headings.push((level.min(6) as u8, path.to_string()));
+        }
+    }
+    for (child, child_path) in tree_child_paths(node, path) {
+        collect_tree_headings(child, &child_path, headings);
+    }
+}
+
+fn tree_child_paths<'n>(node: &'n AccessibilityNode, path: &str) -> Vec<(&'n AccessibilityNode, String)> {
+    node.children
+        .iter()
+        .map(|child| {
+            let same_role: Vec<_> = node.children.iter().filter(|sibling| sibling.role == child.role).collect();
+            let child_path = if same_role.len() > 1 {
+                let index = same_role.iter().position(|sibling| std::ptr::eq(*sibling, child)).unwrap_or(0) + 1;
+                format!("{}/{}[{}]", path, child.role, index)
+            } else {
+                format!("{}/{}", path, child.role)
+            };
+            (child, child_path)
+        })
+        .collect()
+}
+
+/// Headings may go back up any number of levels but only down one at a time.
+fn check_heading_order(headings: &[(u8, String)], issues: &mut Vec<AccessibilityIssue>) {
+    for pair in headings.windows(2) {
+        let ((previous, _), (level, reference)) = (&pair[0], &pair[1]);
+        if *level > previous + 1 {
+            issues.push(issue(
+                AccessibilityRule::HeadingOrder,
+                IssueSeverity::Moderate,
+                reference,
+                format!("Heading level jumps from h{} to h{}", previous, level),
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rgba {
+    r: f64,
+    g: f64,
+    b: f64,
+    a: f64,
+}
+
+impl Rgba {
+    const WHITE: Self = Self { r: 255.0, g: 255.0, b: 255.0, a: 1.0 };
+
+    /// Parse the color forms computed styles use (`rgb()`, `rgba()`, hex and a few keywords).
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "transparent" => return Some(Self { a: 0.0, ..Self::WHITE }),
+            "white" => return Some(Self::WHITE),
+            "black" => return Some(Self { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }),
+            _ => {}
+        }
+        if let Some(hex) = value.strip_prefix('#') {
+            let digits: Vec<f64> = match hex.len() {
+                3 | 4 => hex
+                    .chars()
+                    .map(|c| c.to_digit(16).map(|d| (d * 17) as f64))
+                    .collect::<Option<_>>()?,
+                6 | 8 => (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok().map(f64::from))
+                    .collect::<Option<_>>()?,
+                _ => return None,
+            };
+            let a = digits.get(3).map_or(1.0, |a| a / 255.0);
+            return Some(Self { r: digits[0], g: digits[1], b: digits[2], a });
+        }
+        let args = value
+            .strip_prefix("rgba(")
+            .or_else(|| value.strip_prefix("rgb("))?
+            .strip_suffix(')')?;
+        let parts: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .collect();
+        let number = |part: &str| -> Option<f64> {
+            match part.strip_suffix('%') {
+                Some(percent) => percent.parse::<f64>().ok().map(|p| p / 100.0),
+                None => part.parse().ok(),
+            }
+        };
+        let channel = |part: &str| -> Option<f64> {
+            let value = number(part)?;
+            Some(if part.ends_with('%') { value * 255.0 } else { value })
+        };
+        if !(3..=4).contains(&parts.len()) {
+            return None;
+        }
+        Some(Self {
+            r: channel(parts[0])?,
+            g: channel(parts[1])?,
+            b: channel(parts[2])?,
+            a: parts.get(3).map_or(Some(1.0), |a| number(a))?,
+        })
+    }
+
+    /// Composite this color over an opaque background.
+    fn over(self, background: Self) -> Self {
+        let blend = |fg: f64, bg: f64| self.a * fg + (1.0 - self.a) * bg;
+        Self {
+            r: blend(self.r, background.r),
+            g: blend(self.g, background.g),
+            b: blend(self.b, background.b),
+            a: 1.0,
+        }
+    }
+
+    fn luminance(self) -> f64 {
+        let linear = |channel: f64| {
+            let c = channel / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    fn contrast(self, other: Self) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r.round() as u8, self.g.round() as u8, self.b.round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, sync::Arc};
+
+    fn element(tag: &str, attributes: &[(&str, &str)], children: Vec<DomNode>) -> DomNode {
+        DomNode {
+            node_type: Arc::from("element"),
+            tag_name: Some(Arc::from(tag)),
+            text_content: None,
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| (Arc::from(*name), value.to_string()))
+                .collect(),
+            computed_styles: None,
+            children,
+            xpath: None,
+            selector: None,
+            shadow_root: false,
+        }
+    }
+
+    fn text(content: &str) -> DomNode {
+        DomNode {
+            node_type: Arc::from("text"),
+            tag_name: None,
+            text_content: Some(content.to_string()),
+            ..element("", &[], vec![])
+        }
+    }
+
+    fn styled(mut node: DomNode, styles: &[(&str, &str)]) -> DomNode {
+        node.computed_styles = Some(
+            styles
+                .iter()
+                .map(|(name, value)| (Arc::from(*name), value.to_string()))
+                .collect(),
+        );
+        node
+    }
+
+    fn page(body: Vec<DomNode>) -> DomNode {
+        element("html", &[], vec![element("body", &[], body)])
+    }
+
+    fn ax(role: &str, name: Option<&str>, properties: &[(&str, serde_json::Value)], children: Vec<AccessibilityNode>) -> AccessibilityNode {
+        AccessibilityNode {
+            role: role.to_string(),
+            name: name.map(str::to_string),
+            description: None,
+            value: None,
+            properties: properties
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+            children,
+            bounds: None,
+        }
+    }
+
+    fn rules(issues: &[AccessibilityIssue]) -> Vec<(AccessibilityRule, &str)> {
+        issues.iter().map(|issue| (issue.rule, issue.node.as_str())).collect()
+    }
+
+    #[test]
+    fn test_images_need_alt_text() {
+        let dom = page(vec![
+            element("img", &[("src", "/logo.png")], vec![]),
+            element("img", &[("src", "/divider.png"), ("alt", "")], vec![]),
+            element("img", &[("src", "/chart.png"), ("aria-label", "Sales chart")], vec![]),
+            element("img", &[("src", "/spacer.gif"), ("role", "presentation")], vec![]),
+        ]);
+
+        let issues = find_accessibility_issues(Some(&dom), None);
+        assert_eq!(rules(&issues), vec![(AccessibilityRule::MissingAltText, "/html/body/img[1]")]);
+        assert_eq!(issues[0].severity, IssueSeverity::Critical);
+        assert!(issues[0].message.contains("/logo.png"));
+
+        // Without a DOM snapshot, unnamed images in the accessibility tree are flagged
+        let tree = ax("document", None, &[], vec![ax("img", None, &[], vec![]), ax("img", Some("Logo"), &[], vec![])]);
+        let issues = find_accessibility_issues(None, Some(&tree));
+        assert_eq!(rules(&issues), vec![(AccessibilityRule::MissingAltText, "/document/img[1]")]);
+    }
+
+    #[test]
+    fn test_form_controls_need_labels() {
+        let mut email = element("input", &[("type", "email"), ("id", "email")], vec![]);
+        email.selector = Some("#email".to_string());
+        let dom = page(vec![element(
+            "form",
+            &[],
+            vec![
+                element("label", &[("for", "name")], vec![text("Name")]),
+                element("input", &[("id", "name")], vec![]),
+                email,
+                element("label", &[], vec![text("Remember me"), element("input", &[("type", "checkbox")], vec![])]),
+                element("textarea", &[("aria-label", "Comments")], vec![]),
+                element("select", &[], vec![]),
+                element("input", &[("type", "submit")], vec![]),
+                element("input", &[("type", "hidden")], vec![]),
+            ],
+        )]);
+
+        let issues = find_accessibility_issues(Some(&dom), None);
+        assert_eq!(
+            rules(&issues),
+            vec![
+                (AccessibilityRule::MissingFormLabel, "#email"),
+                (AccessibilityRule::MissingFormLabel, "/html/body/form/select"),
+            ]
+        );
+
+        let tree = ax("document", None, &[], vec![ax("textbox", Some(""), &[], vec![]), ax("checkbox", Some("Agree"), &[], vec![])]);
+        let issues = find_accessibility_issues(None, Some(&tree));
+        assert_eq!(rules(&issues), vec![(AccessibilityRule::MissingFormLabel, "/document/textbox")]);
+    }
+
+    #[test]
+    fn test_low_contrast_text_is_flagged() {
+        let dom = page(vec![styled(
+            element(
+                "main",
+                &[],
+                vec![
+                    styled(element("p", &[], vec![text("Readable")]), &[("color", "rgb(33, 33, 33)")]),
+                    styled(element("p", &[], vec![text("Faint")]), &[("color", "rgb(170, 170, 170)")]),
+                    styled(
+                        element("h1", &[], vec![text("Large heading")]),
+                        &[("color", "#949494"), ("font-size", "32px")],
+                    ),
+                    styled(element("span", &[], vec![]), &[("color", "#eeeeee")]),
+                    styled(
+                        element("p", &[], vec![text("Translucent")]),
+                        &[("color", "rgba(0, 0, 0, 0.3)")],
+                    ),
+                ],
+            ),
+            &[("background-color", "rgb(255, 255, 255)")],
+        )]);
+
+        let issues = find_accessibility_issues(Some(&dom), None);
+        assert_eq!(
+            rules(&issues),
+            vec![
+                (AccessibilityRule::LowContrast, "/html/body/main/p[2]"),
+                (AccessibilityRule::LowContrast, "/html/body/main/p[3]"),
+            ]
+        );
+        assert_eq!(issues[0].severity, IssueSeverity::Serious);
+        assert!(issues[0].message.contains("#aaaaaa on #ffffff"), "{}", issues[0].message);
+
+        // Text on a dark background inherited from an ancestor
+        let dark = page(vec![styled(
+            element("div", &[], vec![styled(element("p", &[], vec![text("Dim")]), &[("color", "#333333")])]),
+            &[("background-color", "#000000")],
+        )]);
+        assert_eq!(rules(&find_accessibility_issues(Some(&dark), None)).len(), 1);
+    }
+
+    #[test]
+    fn test_heading_levels_must_not_skip() {
+        let dom = page(vec![
+            element("h1", &[], vec![text("Title")]),
+            element("h2", &[], vec![text("Section")]),
+            element("h4", &[], vec![text("Skipped")]),
+            element("h2", &[], vec![text("Back up")]),
+        ]);
+        let issues = find_accessibility_issues(Some(&dom), None);
+        assert_eq!(rules(&issues), vec![(AccessibilityRule::HeadingOrder, "/html/body/h4")]);
+        assert_eq!(issues[0].severity, IssueSeverity::Moderate);
+        assert_eq!(issues[0].message, "Heading level jumps from h2 to h4");
+
+        // The accessibility tree's heading levels take precedence when cached
+        let heading = |level: serde_json::Value| ax("heading", Some("Heading"), &[("level", level)], vec![]);
+        let tree = ax("document", None, &[], vec![heading(serde_json::json!(1)), heading(serde_json::json!("3"))]);
+        let issues = find_accessibility_issues(Some(&page(vec![])), Some(&tree));
+        assert_eq!(rules(&issues), vec![(AccessibilityRule::HeadingOrder, "/document/heading[2]")]);
+    }
+}
@@ -143,6 +143,28 @@ pub fn filter_dom_by_selector(node: &Value, selector: &str) -> Option<Value> {
     None
 }
 
+/// Flatten a DOM tree into a pre-order array. Each node loses its `children` and gains
+/// its own `node_index` and its parent's `parent_index` (null for the root), so clients
+/// can address nodes by index.
+pub fn flatten_dom_tree(root: &Value) -> Vec<Value> {
+    let mut nodes = Vec::new();
+    let mut pending = vec![(root, None::<usize>)];
+    while let Some((node, parent_index)) = pending.pop() {
+        let node_index = nodes.len();
+        let mut flat = node.as_object().cloned().unwrap_or_default();
+        flat.remove("children");
+        flat.insert("node_index".to_string(), node_index.into());
+        flat.insert("parent_index".to_string(), parent_index.into());
+        nodes.push(Value::Object(flat));
+
+        // Pushed in reverse so they pop in document order
+        if let Some(children) = node.get("children").and_then(|v| v.as_array()) {
+            pending.extend(children.iter().rev().map(|child| (child, Some(node_index))));
+        }
+    }
+    nodes
+}
+
 /// Remove styles and computedStyles fields from DOM tree recursively.
 pub fn remove_styles_from_dom_tree(node: &mut Value) {
     if let Some(obj) = node.as_object_mut() {
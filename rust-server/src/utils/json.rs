@@ -0,0 +1,54 @@
+use crate::types::errors::{BrowserMcpError, Result};
+use serde::Serialize;
+
+/// `serde_json::to_value` whose error names the type that failed. Use this instead of
+/// falling back to `Null` or an empty string, which hides the failure from the caller.
+pub fn to_json_value<T: Serialize + ?Sized>(value: &T) -> Result<serde_json::Value> {
+    serde_json::to_value(value).map_err(serialization_failed::<T>)
+}
+
+/// `serde_json::to_string_pretty` whose error names the type that failed.
+pub fn to_json_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).map_err(serialization_failed::<T>)
+}
+
+/// Serializes to a pretty or compact string, as chosen by `monitoring.pretty_json`.
+pub fn to_json_string<T: Serialize + ?Sized>(value: &T, pretty: bool) -> Result<String> {
+    if pretty {
+        to_json_pretty(value)
+    } else {
+        serde_json::to_string(value).map_err(serialization_failed::<T>)
+    }
+}
+
+fn serialization_failed<T: ?Sized>(error: serde_json::Error) -> BrowserMcpError {
+    BrowserMcpError::SerializationFailed {
+        type_name: std::any::type_name::<T>().to_string(),
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_serialization_failure_names_the_type() {
+        // JSON object keys must be strings
+        let mut by_position = HashMap::new();
+        by_position.insert((3, 4), "cell");
+
+        let error = to_json_value(&by_position).unwrap_err();
+        assert_eq!(error.category(), "json_error");
+        let message = error.to_string();
+        assert!(message.starts_with("JSON serialization error: failed to serialize std::collections::hash"), "{}", message);
+        assert!(message.contains("(i32, i32)"), "{}", message);
+        assert!(message.ends_with("key must be a string"), "{}", message);
+
+        assert!(to_json_pretty(&by_position).is_err());
+        assert_eq!(to_json_pretty(&["ok"]).unwrap(), "[\n  \"ok\"\n]");
+        assert_eq!(to_json_string(&["ok"], false).unwrap(), "[\"ok\"]");
+        assert!(to_json_string(&by_position, false).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Target the default filter and `logging/setLevel` apply to.
+pub const LOG_TARGET: &str = "browser_mcp_rust_server";
+
+/// Handle to the process's log filter, so MCP clients can change verbosity without a restart.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilter {
+    /// Wrap `initial` in a reloadable layer. Install the layer on the subscriber and keep
+    /// the `LogFilter` to change it later.
+    pub fn new(initial: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let (layer, handle) = reload::Layer::new(initial);
+        (layer, Self { handle })
+    }
+
+    pub fn set_level(&self, level: tracing::Level) -> Result<(), String> {
+        let filter = EnvFilter::new(format!("{}={}", LOG_TARGET, level.as_str().to_ascii_lowercase()));
+        self.handle
+            .reload(filter)
+            .map_err(|e| format!("Failed to change log level: {}", e))
+    }
+
+    /// The active filter directives, e.g. `browser_mcp_rust_server=debug`.
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Map an MCP log level (RFC 5424 severities) to the nearest tracing level.
+pub fn mcp_log_level(level: &str) -> Option<tracing::Level> {
+    match level {
+        "debug" => Some(tracing::Level::DEBUG),
+        "info" | "notice" => Some(tracing::Level::INFO),
+        "warning" => Some(tracing::Level::WARN),
+        "error" | "critical" | "alert" | "emergency" => Some(tracing::Level::ERROR),
+        _ => None,
+    }
+}
@@ -1,9 +1,21 @@
+pub mod access;
+pub mod accessibility;
 pub mod truncation;
 pub mod dom;
+pub mod json;
 pub mod filtering;
+pub mod screenshot;
+pub mod logging;
 pub mod pagination;
+pub mod structured_data;
 
+pub use access::*;
+pub use accessibility::*;
 pub use truncation::*;
 pub use dom::*;
+pub use json::*;
 pub use filtering::*;
+pub use screenshot::*;
+pub use logging::*;
 pub use pagination::*;
+pub use structured_data::*;
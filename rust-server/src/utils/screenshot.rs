@@ -0,0 +1,155 @@
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder},
+    imageops::FilterType,
+    DynamicImage, ExtendedColorType, ImageEncoder, Rgba, RgbaImage,
+};
+
+/// Colour differing pixels are painted in the diff image.
+const DIFF_HIGHLIGHT: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// Result of comparing two screenshots pixel by pixel.
+#[derive(Debug, Clone)]
+pub struct ScreenshotDiff {
+    pub width: u32,
+    pub height: u32,
+    pub differing_pixels: u64,
+    pub difference_percent: f64,
+    /// PNG with differing pixels in red over a faded copy of the current image.
+    pub diff_image: Option<Vec<u8>>,
+}
+
+/// Read the dimensions from an encoded image's header without decoding it.
+pub fn image_dimensions(data: &[u8]) -> Result<(u32, u32), String> {
+    image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .into_dimensions()
+        .map_err(|e| format!("Failed to read image: {}", e))
+}
+
+/// A screenshot shrunk to fit size limits, re-encoded in its original format.
+#[derive(Debug, Clone)]
+pub struct ScaledImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// New size over old, the same for both axes.
+    pub scale: f64,
+}
+
+/// Shrink an encoded `width` x `height` image to fit within `max_width` x `max_height`,
+/// keeping its aspect ratio. A limit of 0 means none. Returns `None` when it already fits.
+pub fn downscale_image(
+    data: &[u8],
+    format: &str,
+    (width, height): (u32, u32),
+    (max_width, max_height): (u32, u32),
+    jpeg_quality: u8,
+) -> Result<Option<ScaledImage>, String> {
+    let limit = |max: u32, size: u32| if max == 0 || size <= max { 1.0 } else { f64::from(max) / f64::from(size) };
+    let scale = limit(max_width, width).min(limit(max_height, height));
+    if scale >= 1.0 {
+        return Ok(None);
+    }
+
+    let scaled_size = |size: u32| ((f64::from(size) * scale).round() as u32).max(1);
+    let (new_width, new_height) = (scaled_size(width), scaled_size(height));
+    let resized = image::load_from_memory(data)
+        .map_err(|e| format!("Failed to decode screenshot: {}", e))?
+        .resize_exact(new_width, new_height, FilterType::Triangle);
+
+    Ok(Some(ScaledImage {
+        data: encode_image(&resized, format, jpeg_quality)?,
+        width: new_width,
+        height: new_height,
+        scale,
+    }))
+}
+
+/// Re-encode an image as `format` ("png", "jpeg" or "jpg"), for when the extension
+/// captured in a different format than the client asked for.
+pub fn convert_image(data: &[u8], format: &str, jpeg_quality: u8) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(data).map_err(|e| format!("Failed to decode screenshot: {}", e))?;
+    encode_image(&image, format, jpeg_quality)
+}
+
+/// Compare two encoded images (PNG or JPEG) of the same size. A pixel differs when
+/// any of its RGBA channels do.
+pub fn diff_screenshots(baseline: &[u8], current: &[u8], with_image: bool) -> Result<ScreenshotDiff, String> {
+    let decode = |data: &[u8], which: &str| {
+        image::load_from_memory(data)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| format!("Failed to decode {} screenshot: {}", which, e))
+    };
+    let baseline = decode(baseline, "baseline")?;
+    let current = decode(current, "current")?;
+
+    if baseline.dimensions() != current.dimensions() {
+        return Err(format!(
+            "Screenshot is {}x{} but the baseline is {}x{}",
+            current.width(),
+            current.height(),
+            baseline.width(),
+            baseline.height()
+        ));
+    }
+
+    let (width, height) = current.dimensions();
+    let mut diff_image = with_image.then(|| RgbaImage::new(width, height));
+    let mut differing_pixels = 0u64;
+    for (x, y, pixel) in current.enumerate_pixels() {
+        let differs = baseline.get_pixel(x, y) != pixel;
+        if differs {
+            differing_pixels += 1;
+        }
+        if let Some(diff) = diff_image.as_mut() {
+            let faded = if differs { DIFF_HIGHLIGHT } else { fade(pixel) };
+            diff.put_pixel(x, y, faded);
+        }
+    }
+
+    let total = u64::from(width) * u64::from(height);
+    let difference_percent = if total == 0 {
+        0.0
+    } else {
+        differing_pixels as f64 * 100.0 / total as f64
+    };
+    let diff_image = diff_image.map(|diff| encode_png(&diff)).transpose()?;
+
+    Ok(ScreenshotDiff {
+        width,
+        height,
+        differing_pixels,
+        difference_percent,
+        diff_image,
+    })
+}
+
+/// Grey, washed-out version of an unchanged pixel so the red highlights stand out.
+fn fade(pixel: &Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, _] = pixel.0;
+    let luma = (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000;
+    let washed = (255 - (255 - luma) / 3) as u8;
+    Rgba([washed, washed, washed, 255])
+}
+
+fn encode_image(image: &DynamicImage, format: &str, jpeg_quality: u8) -> Result<Vec<u8>, String> {
+    if !matches!(format, "jpeg" | "jpg") {
+        return encode_png(&image.to_rgba8());
+    }
+    // JPEG has no alpha channel
+    let rgb = image.to_rgb8();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, jpeg_quality)
+        .write_image(rgb.as_raw(), rgb.width(), rgb.height(), ExtendedColorType::Rgb8)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(jpeg)
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(png)
+}
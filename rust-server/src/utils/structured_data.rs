@@ -0,0 +1,134 @@
+use crate::types::browser::{MetaTag, StructuredData};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static SCRIPT_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<script\b([^>]*)>(.*?)</script\s*>").unwrap());
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<meta\b([^>]*)>").unwrap());
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([^\s"'<>/=]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#).unwrap()
+});
+
+/// Pull JSON-LD blocks and `<meta>` tags out of a page's HTML. This is a tag scanner
+/// rather than a full parser, which is enough for markup that lives in well-formed
+/// `<head>`s and `<script>` blocks; commented-out markup is ignored.
+pub fn extract_structured_data(html: &str) -> StructuredData {
+    let html = COMMENT.replace_all(html, "");
+    let mut data = StructuredData::default();
+
+    for script in SCRIPT_TAG.captures_iter(&html) {
+        let is_json_ld = attributes(&script[1])
+            .any(|(name, value)| name == "type" && value.trim().eq_ignore_ascii_case("application/ld+json"));
+        if !is_json_ld {
+            continue;
+        }
+        // Some CMSes wrap the block in CDATA markers
+        let body = script[2].trim().trim_start_matches("<![CDATA[").trim_end_matches("]]>").trim();
+        match serde_json::from_str(body) {
+            Ok(value) => data.json_ld.push(value),
+            Err(e) => data.invalid_json_ld.push(e.to_string()),
+        }
+    }
+
+    // Meta tags inside scripts are just strings
+    let outside_scripts = SCRIPT_TAG.replace_all(&html, "");
+    for meta in META_TAG.captures_iter(&outside_scripts) {
+        let mut tag = MetaTag::default();
+        for (name, value) in attributes(&meta[1]) {
+            let slot = match name.as_str() {
+                "name" => &mut tag.name,
+                "property" => &mut tag.property,
+                "http-equiv" => &mut tag.http_equiv,
+                "charset" => &mut tag.charset,
+                "content" => &mut tag.content,
+                _ => continue,
+            };
+            slot.get_or_insert(value);
+        }
+        data.meta.push(tag);
+    }
+    data
+}
+
+/// Attribute names (lowercased) and their decoded values; bare attributes have an empty value.
+fn attributes(source: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    ATTRIBUTE.captures_iter(source).map(|attribute| {
+        let value = attribute
+            .get(2)
+            .or_else(|| attribute.get(3))
+            .or_else(|| attribute.get(4))
+            .map_or(String::new(), |value| decode_entities(value.as_str()));
+        (attribute[1].to_ascii_lowercase(), value)
+    })
+}
+
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <meta name="description" content="Hand-thrown mugs &amp; bowls">
+  <meta property='og:title' content='Stoneware Mug'/>
+  <meta http-equiv="refresh" content="30">
+  <meta name=robots content=noindex>
+  <!-- <meta name="author" content="draft"> -->
+  <script type="application/ld+json">
+    {
+      "@context": "https://schema.org",
+      "@type": "Product",
+      "name": "Stoneware Mug",
+      "offers": { "@type": "Offer", "price": "24.00", "priceCurrency": "USD" }
+    }
+  </script>
+  <script type="application/ld+json">
+    [{ "@context": "https://schema.org", "@type": "BreadcrumbList", "itemListElement": [] }]
+  </script>
+  <script TYPE="Application/LD+JSON">{ "@type": "Organization", </script>
+  <script>document.write('<meta name="injected" content="x">');</script>
+</head>
+<body></body>
+</html>"#;
+
+    #[test]
+    fn test_extracts_json_ld_blocks() {
+        let data = extract_structured_data(PAGE);
+        assert_eq!(data.json_ld.len(), 2);
+        assert_eq!(data.json_ld[0]["@type"], "Product");
+        assert_eq!(data.json_ld[0]["offers"]["price"], "24.00");
+        assert_eq!(data.json_ld[1][0]["@type"], "BreadcrumbList");
+        assert_eq!(data.invalid_json_ld.len(), 1);
+    }
+
+    #[test]
+    fn test_extracts_meta_tags() {
+        let data = extract_structured_data(PAGE);
+        let meta = |name: &str| data.meta.iter().find(|m| m.name.as_deref() == Some(name)).cloned();
+
+        assert_eq!(data.meta.len(), 5);
+        assert_eq!(data.meta[0].charset.as_deref(), Some("utf-8"));
+        assert_eq!(meta("description").unwrap().content.as_deref(), Some("Hand-thrown mugs & bowls"));
+        assert_eq!(meta("robots").unwrap().content.as_deref(), Some("noindex"));
+        assert_eq!(data.meta[2].property.as_deref(), Some("og:title"));
+        assert_eq!(data.meta[3].http_equiv.as_deref(), Some("refresh"));
+        // Commented-out and script-generated tags aren't part of the page
+        assert!(meta("author").is_none());
+        assert!(meta("injected").is_none());
+    }
+}